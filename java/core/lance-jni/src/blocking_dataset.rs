@@ -108,7 +108,8 @@ pub extern "system" fn Java_com_lancedb_lance_Dataset_getFragmentsIds<'a>(
         let dataset =
             unsafe { env.get_rust_field::<_, _, BlockingDataset>(jdataset, NATIVE_DATASET) }
                 .expect("Failed to get native dataset handle");
-        dataset.inner.get_fragments()
+        RT.block_on(dataset.inner.get_fragments())
+            .expect("Failed to load fragments")
     };
 
     let array_list = env
@@ -37,7 +37,7 @@ use crate::{
 };
 
 fn fragment_count_rows(dataset: &BlockingDataset, fragment_id: jlong) -> Result<jint> {
-    let Some(fragment) = dataset.inner.get_fragment(fragment_id as usize) else {
+    let Some(fragment) = RT.block_on(dataset.inner.get_fragment(fragment_id as usize))? else {
         return Err(Error::InvalidArgument {
             message: format!("Fragment not found: {}", fragment_id),
         });
@@ -54,6 +54,7 @@ impl FragmentScanner {
         let fragment = dataset
             .inner
             .get_fragment(fragment_id)
+            .await?
             .ok_or_else(|| Error::IO {
                 message: format!("Fragment not found: {}", fragment_id),
                 location: location!(),
@@ -288,10 +289,18 @@ pub extern "system" fn Java_com_lancedb_lance_ipc_FragmentScanner_openStream(
                 .expect("Dataset handle not set");
         dataset.clone()
     };
-    let Some(fragment) = dataset.inner.get_fragment(fragment_id as usize) else {
-        env.throw("Fragment not found")
-            .expect("Throw exception failed");
-        return;
+    let fragment = match RT.block_on(dataset.inner.get_fragment(fragment_id as usize)) {
+        Ok(Some(fragment)) => fragment,
+        Ok(None) => {
+            env.throw("Fragment not found")
+                .expect("Throw exception failed");
+            return;
+        }
+        Err(e) => {
+            env.throw(format!("Loading fragments: {}", e))
+                .expect("Throw exception failed");
+            return;
+        }
     };
     let mut scanner: Scanner = fragment.scan();
     if let Some(cols) = columns {
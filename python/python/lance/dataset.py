@@ -83,7 +83,8 @@ class MergeInsertBuilder(_MergeInsertBuilder):
     def execute(self, data_obj: ReaderLike, *, schema: Optional[pa.Schema] = None):
         """Executes the merge insert operation
 
-        There is no return value but the original dataset will be updated.
+        The original dataset will be updated.  Returns a dictionary with the number
+        of inserted, updated, deleted and deduplicated rows.
 
         Parameters
         ----------
@@ -97,9 +98,9 @@ class MergeInsertBuilder(_MergeInsertBuilder):
             source is some kind of generator.
         """
         reader = _coerce_reader(data_obj, schema)
-        super(MergeInsertBuilder, self).execute(reader)
+        return super(MergeInsertBuilder, self).execute(reader)
 
-    # These next three overrides exist only to document the methods
+    # These next overrides exist only to document the methods
 
     def when_matched_update_all(self, condition: Optional[str] = None):
         """
@@ -144,6 +145,20 @@ class MergeInsertBuilder(_MergeInsertBuilder):
         """
         return super(MergeInsertBuilder, self).when_not_matched_by_source_delete(expr)
 
+    def source_dedupe(self, behavior: str = "fail", order_by: Optional[str] = None):
+        """
+        Configure how source rows with the same key are handled
+
+        By default the operation fails if more than one source row has the same
+        value of the `on` columns.  The `behavior` can be one of:
+
+        - ``"fail"``: raise an error when a key appears more than once.
+        - ``"first_seen"``: keep the first source row seen for each key.
+        - ``"last_by"``: keep the source row with the largest value in the
+          `order_by` column.
+        """
+        return super(MergeInsertBuilder, self).source_dedupe(behavior, order_by)
+
 
 class LanceDataset(pa.dataset.Dataset):
     """A dataset in Lance format where the data is stored at the given uri."""
@@ -2233,6 +2248,7 @@ class DatasetOptimizer:
         materialize_deletions: bool = True,
         materialize_deletions_threshold: float = 0.1,
         num_threads: Optional[int] = None,
+        max_data_files_per_fragment: Optional[int] = None,
     ) -> CompactionMetrics:
         """Compacts small files in the dataset, reducing total number of files.
 
@@ -2240,6 +2256,7 @@ class DatasetOptimizer:
          * Removes deleted rows from fragments
          * Removes dropped columns from fragments
          * Merges small fragments into larger ones
+         * Consolidates the data files of fragments that have too many of them
 
         This method preserves the insertion order of the dataset. This may mean
         it leaves small fragments in the dataset if they are not adjacent to
@@ -2265,6 +2282,12 @@ class DatasetOptimizer:
         num_threads: int, optional
             The number of threads to use when performing compaction. If not
             specified, defaults to the number of cores on the machine.
+        max_data_files_per_fragment: int, optional
+            The max number of data files per fragment. Each call to
+            ``add_columns`` or ``merge`` adds a data file to every fragment.
+            Fragments with more data files than this are rewritten into a single
+            data file, keeping the addresses of their rows. If not specified,
+            fragments are not consolidated.
 
         Returns
         -------
@@ -2281,6 +2304,7 @@ class DatasetOptimizer:
             materialize_deletions=materialize_deletions,
             materialize_deletions_threshold=materialize_deletions_threshold,
             num_threads=num_threads,
+            max_data_files_per_fragment=max_data_files_per_fragment,
         )
         return Compaction.execute(self._dataset, opts)
 
@@ -2350,6 +2374,7 @@ def write_dataset(
     progress: Optional[FragmentWriteProgress] = None,
     storage_options: Optional[Dict[str, str]] = None,
     use_experimental_writer: bool = False,
+    schema_evolution: str = "strict",
 ) -> LanceDataset:
     """Write a given data_obj to the given uri
 
@@ -2392,6 +2417,14 @@ def write_dataset(
     use_experimental_writer : optional, bool
         Use the Lance v2 writer to write Lance v2 files.  This is not recommended
         at this time as there are several known limitations in the v2 writer.
+    schema_evolution : str, default "strict"
+        How to append data whose schema differs from the schema of the dataset.
+        **strict** - the schemas must match.
+        **add_missing_columns** - new nullable columns are added to the dataset,
+        existing rows read them as null.
+        **widen_types** - also allows a column to have a type that can be
+        losslessly widened to or from the type in the dataset, e.g. int32 and
+        int64.  The dataset column is converted to the wider type if needed.
     """
     if _check_for_hugging_face(data_obj):
         # Huggingface datasets
@@ -2414,6 +2447,7 @@ def write_dataset(
         "progress": progress,
         "storage_options": storage_options,
         "use_experimental_writer": use_experimental_writer,
+        "schema_evolution": schema_evolution,
     }
 
     if commit_lock:
@@ -43,3 +43,9 @@ class CompactionOptions(TypedDict):
     The number of threads to use when performing compaction. If not
     specified, defaults to the number of cores on the machine.
     """
+    max_data_files_per_fragment: Optional[int]
+    """
+    The max number of data files per fragment. Fragments with more data files
+    than this are rewritten into a single data file, keeping the addresses of
+    their rows. If not specified, fragments are not consolidated.
+    """
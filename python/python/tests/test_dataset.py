@@ -1095,6 +1095,34 @@ def test_merge_insert_incompatible_schema(tmp_path: Path):
         dataset.merge_insert("a").when_matched_update_all().execute(new_table)
 
 
+def test_merge_insert_source_dedupe(tmp_path: Path):
+    table = pa.Table.from_pydict({"a": [1, 2], "b": [0, 0]})
+    dataset = lance.write_dataset(table, tmp_path / "dataset", mode="create")
+
+    new_table = pa.Table.from_pydict({"a": [1, 1, 3], "b": [5, 7, 1]})
+
+    with pytest.raises(OSError, match="duplicate"):
+        dataset.merge_insert("a").when_matched_update_all().execute(new_table)
+
+    with pytest.raises(ValueError):
+        dataset.merge_insert("a").source_dedupe("last_by")
+
+    stats = (
+        dataset.merge_insert("a")
+        .source_dedupe("last_by", order_by="b")
+        .when_matched_update_all()
+        .when_not_matched_insert_all()
+        .execute(new_table)
+    )
+    assert stats["num_deduplicated_rows"] == 1
+    assert stats["num_updated_rows"] == 1
+    assert stats["num_inserted_rows"] == 1
+    assert dataset.to_table().sort_by("a") == pa.Table.from_pydict({
+        "a": [1, 2, 3],
+        "b": [7, 0, 1],
+    })
+
+
 def test_merge_insert_vector_column(tmp_path: Path):
     table = pa.Table.from_pydict({
         "vec": pa.array([[1, 2, 3], [4, 5, 6]], pa.list_(pa.float32(), 3)),
@@ -33,8 +33,8 @@ use lance::dataset::{
     fragment::FileFragment as LanceFileFragment, progress::WriteFragmentProgress,
     scanner::Scanner as LanceScanner, transaction::Operation as LanceOperation,
     Dataset as LanceDataset, MergeInsertBuilder as LanceMergeInsertBuilder, ReadParams,
-    UpdateBuilder, Version, WhenMatched, WhenNotMatched, WhenNotMatchedBySource, WriteMode,
-    WriteParams,
+    SchemaEvolution, SourceDedupeBehavior, UpdateBuilder, Version, WhenMatched, WhenNotMatched,
+    WhenNotMatchedBySource, WriteMode, WriteParams,
 };
 use lance::dataset::{BatchInfo, BatchUDF, NewColumnTransform, UDFCheckpointStore};
 use lance::index::{scalar::ScalarIndexParams, vector::VectorIndexParams};
@@ -175,7 +175,38 @@ impl MergeInsertBuilder {
         Ok(slf)
     }
 
-    pub fn execute(&mut self, new_data: &PyAny) -> PyResult<()> {
+    pub fn source_dedupe<'a>(
+        mut slf: PyRefMut<'a, Self>,
+        behavior: &str,
+        order_by: Option<String>,
+    ) -> PyResult<PyRefMut<'a, Self>> {
+        let new_val = match (behavior, order_by) {
+            ("fail", None) => SourceDedupeBehavior::Fail,
+            ("first_seen", None) => SourceDedupeBehavior::FirstSeen,
+            ("last_by", Some(column)) => SourceDedupeBehavior::LastBy(column),
+            ("last_by", None) => {
+                return Err(PyValueError::new_err(
+                    "The last_by dedupe behavior requires an order_by column",
+                ))
+            }
+            ("fail" | "first_seen", Some(_)) => {
+                return Err(PyValueError::new_err(format!(
+                    "The {} dedupe behavior does not take an order_by column",
+                    behavior
+                )))
+            }
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                "Unknown source dedupe behavior '{}', expected one of fail, first_seen or last_by",
+                behavior
+            )))
+            }
+        };
+        slf.builder.source_dedupe_behavior(new_val);
+        Ok(slf)
+    }
+
+    pub fn execute(&mut self, new_data: &PyAny) -> PyResult<PyObject> {
         let py = new_data.py();
 
         let new_data: Box<dyn RecordBatchReader + Send> = if new_data.is_instance_of::<Scanner>() {
@@ -193,7 +224,7 @@ impl MergeInsertBuilder {
             .try_build()
             .map_err(|err| PyValueError::new_err(err.to_string()))?;
 
-        let new_self = RT
+        let (new_self, stats) = RT
             .spawn(Some(py), job.execute_reader(new_data))?
             .map_err(|err| PyIOError::new_err(err.to_string()))?;
 
@@ -201,7 +232,12 @@ impl MergeInsertBuilder {
 
         dataset.borrow_mut().ds = new_self;
 
-        Ok(())
+        let dict = PyDict::new(py);
+        dict.set_item("num_inserted_rows", stats.num_inserted_rows)?;
+        dict.set_item("num_updated_rows", stats.num_updated_rows)?;
+        dict.set_item("num_deleted_rows", stats.num_deleted_rows)?;
+        dict.set_item("num_deduplicated_rows", stats.num_deduplicated_rows)?;
+        Ok(dict.into())
     }
 }
 
@@ -776,7 +812,7 @@ impl Dataset {
             .build()
             .map_err(|err| PyValueError::new_err(err.to_string()))?;
 
-        let new_self = RT
+        let (new_self, _) = RT
             .block_on(None, operation.execute())?
             .map_err(|err| PyIOError::new_err(err.to_string()))?;
 
@@ -935,12 +971,14 @@ impl Dataset {
     }
 
     fn num_small_files(&self, max_rows_per_group: usize) -> PyResult<usize> {
-        RT.block_on(None, self.ds.num_small_files(max_rows_per_group))
+        RT.block_on(None, self.ds.num_small_files(max_rows_per_group))?
             .map_err(|err| PyIOError::new_err(err.to_string()))
     }
 
     fn get_fragments(self_: PyRef<'_, Self>) -> PyResult<Vec<FileFragment>> {
-        let core_fragments = self_.ds.get_fragments();
+        let core_fragments = RT
+            .block_on(None, self_.ds.get_fragments())?
+            .map_err(|err| PyIOError::new_err(err.to_string()))?;
 
         Python::with_gil(|_| {
             let fragments: Vec<FileFragment> = core_fragments
@@ -952,7 +990,10 @@ impl Dataset {
     }
 
     fn get_fragment(self_: PyRef<'_, Self>, fragment_id: usize) -> PyResult<Option<FileFragment>> {
-        if let Some(fragment) = self_.ds.get_fragment(fragment_id) {
+        let fragment = RT
+            .block_on(None, self_.ds.get_fragment(fragment_id))?
+            .map_err(|err| PyIOError::new_err(err.to_string()))?;
+        if let Some(fragment) = fragment {
             Ok(Some(FileFragment::new(fragment)))
         } else {
             Ok(None)
@@ -981,7 +1022,11 @@ impl Dataset {
         let ds = RT
             .block_on(commit_lock.map(|cl| cl.py()), async move {
                 let dataset = match DatasetBuilder::from_uri(dataset_uri).load().await {
-                    Ok(ds) => Some(ds),
+                    Ok(mut ds) => {
+                        // Validating the operation may look at the existing fragments
+                        ds.load_fragments().await?;
+                        Some(ds)
+                    }
                     Err(lance::Error::DatasetNotFound { .. }) => None,
                     Err(err) => return Err(err),
                 };
@@ -1149,6 +1194,11 @@ pub fn get_write_params(options: &PyDict) -> PyResult<Option<WriteParams>> {
         if let Some(use_experimental_writer) = options.get_item("use_experimental_writer")? {
             p.use_experimental_writer = use_experimental_writer.extract()?;
         }
+        if let Some(schema_evolution) = options.get_item("schema_evolution")? {
+            p.schema_evolution =
+                SchemaEvolution::try_from(schema_evolution.extract::<String>()?.as_str())
+                    .map_err(|err| PyValueError::new_err(err.to_string()))?;
+        }
         if let Some(progress) = options.get_item("progress")? {
             if !progress.is_none() {
                 p.progress = Arc::new(PyWriteProgress::new(progress.to_object(options.py())));
@@ -47,6 +47,9 @@ fn parse_compaction_options(options: &PyDict) -> PyResult<CompactionOptions> {
                     .extract::<Option<usize>>()?
                     .unwrap_or_else(num_cpus::get);
             }
+            "max_data_files_per_fragment" => {
+                opts.max_data_files_per_fragment = value.extract()?;
+            }
             _ => {
                 return Err(PyValueError::new_err(format!(
                     "Invalid compaction option: {}",
@@ -23,6 +23,7 @@ pub mod bfloat16;
 pub mod floats;
 pub use floats::*;
 pub mod cast;
+pub mod sparse;
 
 type Result<T> = std::result::Result<T, ArrowError>;
 
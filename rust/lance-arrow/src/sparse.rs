@@ -0,0 +1,266 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Sparse vector layout.
+//!
+//! A sparse vector column is stored as a struct of two parallel lists:
+//!
+//! ```text
+//! Struct {
+//!     indices: List<UInt32>,   // dimension ids, sorted ascending
+//!     values: List<Float32>,   // weight of each dimension
+//! }
+//! ```
+//!
+//! Both lists of a row must have the same length.
+
+use std::sync::Arc;
+
+use arrow_array::{
+    cast::AsArray, types::Float32Type, types::UInt32Type, Array, ArrayRef, Float32Array, ListArray,
+    StructArray, UInt32Array,
+};
+use arrow_buffer::{NullBuffer, OffsetBuffer};
+use arrow_schema::{ArrowError, DataType, Field, Fields};
+
+use crate::Result;
+
+/// Name of the child field holding the dimension ids of a sparse vector.
+pub const SPARSE_INDICES_FIELD: &str = "indices";
+/// Name of the child field holding the weights of a sparse vector.
+pub const SPARSE_VALUES_FIELD: &str = "values";
+
+fn sparse_vector_fields() -> Fields {
+    Fields::from(vec![
+        Field::new(
+            SPARSE_INDICES_FIELD,
+            DataType::List(Arc::new(Field::new("item", DataType::UInt32, true))),
+            true,
+        ),
+        Field::new(
+            SPARSE_VALUES_FIELD,
+            DataType::List(Arc::new(Field::new("item", DataType::Float32, true))),
+            true,
+        ),
+    ])
+}
+
+/// The [DataType] of a sparse vector column.
+pub fn sparse_vector_type() -> DataType {
+    DataType::Struct(sparse_vector_fields())
+}
+
+/// Returns true if the data type follows the sparse vector layout.
+///
+/// ```
+/// use lance_arrow::sparse::{is_sparse_vector, sparse_vector_type};
+/// use arrow_schema::DataType;
+///
+/// assert!(is_sparse_vector(&sparse_vector_type()));
+/// assert!(!is_sparse_vector(&DataType::Float32));
+/// ```
+pub fn is_sparse_vector(data_type: &DataType) -> bool {
+    let DataType::Struct(fields) = data_type else {
+        return false;
+    };
+    if fields.len() != 2 {
+        return false;
+    }
+    let is_list_of = |field: &Field, name: &str, item: &DataType| {
+        field.name() == name
+            && matches!(field.data_type(), DataType::List(f) if f.data_type() == item)
+    };
+    is_list_of(&fields[0], SPARSE_INDICES_FIELD, &DataType::UInt32)
+        && is_list_of(&fields[1], SPARSE_VALUES_FIELD, &DataType::Float32)
+}
+
+/// A typed view over an array of sparse vectors.
+#[derive(Debug, Clone)]
+pub struct SparseVectorArray {
+    indices: ListArray,
+    values: ListArray,
+    nulls: Option<NullBuffer>,
+}
+
+impl SparseVectorArray {
+    /// Create a [SparseVectorArray] from flattened dimension ids and weights.
+    ///
+    /// `offsets` has one more element than the number of vectors; vector `i`
+    /// owns `indices[offsets[i]..offsets[i + 1]]`.
+    pub fn try_new(
+        offsets: OffsetBuffer<i32>,
+        indices: UInt32Array,
+        values: Float32Array,
+        nulls: Option<NullBuffer>,
+    ) -> Result<Self> {
+        if indices.len() != values.len() {
+            return Err(ArrowError::InvalidArgumentError(format!(
+                "Sparse vector indices and values must have the same length: {} != {}",
+                indices.len(),
+                values.len()
+            )));
+        }
+        let indices = ListArray::try_new(
+            Arc::new(Field::new("item", DataType::UInt32, true)),
+            offsets.clone(),
+            Arc::new(indices),
+            nulls.clone(),
+        )?;
+        let values = ListArray::try_new(
+            Arc::new(Field::new("item", DataType::Float32, true)),
+            offsets,
+            Arc::new(values),
+            nulls.clone(),
+        )?;
+        Ok(Self {
+            indices,
+            values,
+            nulls,
+        })
+    }
+
+    /// Build a [SparseVectorArray] from `(indices, values)` pairs, sorting each
+    /// vector by dimension id.
+    pub fn from_pairs<'a>(
+        vectors: impl IntoIterator<Item = (&'a [u32], &'a [f32])>,
+    ) -> Result<Self> {
+        let mut offsets = vec![0_i32];
+        let mut all_indices = Vec::new();
+        let mut all_values = Vec::new();
+        for (indices, values) in vectors {
+            if indices.len() != values.len() {
+                return Err(ArrowError::InvalidArgumentError(format!(
+                    "Sparse vector indices and values must have the same length: {} != {}",
+                    indices.len(),
+                    values.len()
+                )));
+            }
+            let mut pairs = indices
+                .iter()
+                .copied()
+                .zip(values.iter().copied())
+                .collect::<Vec<_>>();
+            pairs.sort_by_key(|(idx, _)| *idx);
+            all_indices.extend(pairs.iter().map(|(idx, _)| *idx));
+            all_values.extend(pairs.iter().map(|(_, val)| *val));
+            offsets.push(all_indices.len() as i32);
+        }
+        Self::try_new(
+            OffsetBuffer::new(offsets.into()),
+            UInt32Array::from(all_indices),
+            Float32Array::from(all_values),
+            None,
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    pub fn is_null(&self, i: usize) -> bool {
+        self.nulls.as_ref().map(|n| n.is_null(i)).unwrap_or(false)
+    }
+
+    /// The dimension ids and weights of the `i`-th vector.
+    pub fn value(&self, i: usize) -> (&[u32], &[f32]) {
+        let offsets = self.indices.value_offsets();
+        let start = offsets[i] as usize;
+        let end = offsets[i + 1] as usize;
+        let indices = self.indices.values().as_primitive::<UInt32Type>().values();
+        let values = self.values.values().as_primitive::<Float32Type>().values();
+        (&indices[start..end], &values[start..end])
+    }
+
+    /// Iterate over all vectors. Null vectors are yielded as `None`.
+    pub fn iter(&self) -> impl Iterator<Item = Option<(&[u32], &[f32])>> + '_ {
+        (0..self.len()).map(|i| {
+            if self.is_null(i) {
+                None
+            } else {
+                Some(self.value(i))
+            }
+        })
+    }
+
+    pub fn nulls(&self) -> Option<&NullBuffer> {
+        self.nulls.as_ref()
+    }
+
+    /// Convert into the on-disk [StructArray] layout.
+    pub fn into_struct_array(self) -> StructArray {
+        StructArray::new(
+            sparse_vector_fields(),
+            vec![
+                Arc::new(self.indices) as ArrayRef,
+                Arc::new(self.values) as ArrayRef,
+            ],
+            self.nulls,
+        )
+    }
+}
+
+impl TryFrom<&dyn Array> for SparseVectorArray {
+    type Error = ArrowError;
+
+    fn try_from(array: &dyn Array) -> Result<Self> {
+        if !is_sparse_vector(array.data_type()) {
+            return Err(ArrowError::InvalidArgumentError(format!(
+                "Expect a sparse vector array, got {}",
+                array.data_type()
+            )));
+        }
+        let array = array.as_struct();
+        let indices = array.column(0).as_list::<i32>().clone();
+        let values = array.column(1).as_list::<i32>().clone();
+        if indices.value_offsets() != values.value_offsets() {
+            return Err(ArrowError::InvalidArgumentError(
+                "Sparse vector indices and values have different offsets".to_string(),
+            ));
+        }
+        let nulls = NullBuffer::union(array.nulls(), indices.nulls());
+        Ok(Self {
+            indices,
+            values,
+            nulls,
+        })
+    }
+}
+
+impl From<SparseVectorArray> for StructArray {
+    fn from(array: SparseVectorArray) -> Self {
+        array.into_struct_array()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sparse_vector_roundtrip() {
+        let arr = SparseVectorArray::from_pairs([
+            (&[5_u32, 1, 3][..], &[0.5_f32, 0.1, 0.3][..]),
+            (&[][..], &[][..]),
+            (&[7][..], &[7.0][..]),
+        ])
+        .unwrap();
+        assert_eq!(arr.len(), 3);
+        assert_eq!(arr.value(0), (&[1_u32, 3, 5][..], &[0.1_f32, 0.3, 0.5][..]));
+        assert_eq!(arr.value(1), (&[][..], &[][..]));
+
+        let struct_arr = arr.into_struct_array();
+        assert!(is_sparse_vector(struct_arr.data_type()));
+        let arr = SparseVectorArray::try_from(&struct_arr as &dyn Array).unwrap();
+        assert_eq!(arr.value(2), (&[7_u32][..], &[7.0_f32][..]));
+    }
+
+    #[test]
+    fn test_sparse_vector_length_mismatch() {
+        assert!(SparseVectorArray::from_pairs([(&[1_u32, 2][..], &[1.0_f32][..])]).is_err());
+        assert!(SparseVectorArray::try_from(&Float32Array::from(vec![1.0]) as &dyn Array).is_err());
+    }
+}
@@ -22,6 +22,25 @@ pub use field::Field;
 pub use field::SchemaCompareOptions;
 pub use schema::Schema;
 
+/// Field metadata key of the default value of a column, as a constant SQL expression
+///
+/// The default value is used for the column when appended data omits it.
+pub const DEFAULT_VALUE_META_KEY: &str = "lance:default";
+
+/// Field metadata key of a CHECK constraint on a column, as a SQL boolean expression
+///
+/// Written rows must not make the expression false.  Like in SQL, rows for which the
+/// expression is null satisfy the constraint.
+pub const CHECK_CONSTRAINT_META_KEY: &str = "lance:check";
+
+/// Schema metadata key of the primary key of a dataset, as a comma-separated list of columns
+pub const PRIMARY_KEY_META_KEY: &str = "lance:primary_key";
+
+/// Schema metadata key set to `"true"` when the uniqueness of the primary key is enforced
+///
+/// Appends that would create duplicate keys are then rejected.
+pub const PRIMARY_KEY_ENFORCED_META_KEY: &str = "lance:primary_key_enforced";
+
 /// LogicalType is a string presentation of arrow type.
 /// to be serialized into protobuf.
 #[derive(Debug, Clone, PartialEq)]
@@ -21,7 +21,7 @@ use arrow_schema::{DataType, Field as ArrowField};
 use lance_arrow::{bfloat16::ARROW_EXT_NAME_KEY, *};
 use snafu::{location, Location};
 
-use super::{Dictionary, LogicalType};
+use super::{Dictionary, LogicalType, CHECK_CONSTRAINT_META_KEY, DEFAULT_VALUE_META_KEY};
 use crate::{Error, Result};
 
 #[derive(Default)]
@@ -234,6 +234,20 @@ impl Field {
         self.metadata.get(ARROW_EXT_NAME_KEY).map(String::as_str)
     }
 
+    /// The default value of the column, see [`DEFAULT_VALUE_META_KEY`]
+    pub fn default_value(&self) -> Option<&str> {
+        self.metadata
+            .get(DEFAULT_VALUE_META_KEY)
+            .map(String::as_str)
+    }
+
+    /// The CHECK constraint on the column, see [`CHECK_CONSTRAINT_META_KEY`]
+    pub fn check_constraint(&self) -> Option<&str> {
+        self.metadata
+            .get(CHECK_CONSTRAINT_META_KEY)
+            .map(String::as_str)
+    }
+
     pub fn child(&self, name: &str) -> Option<&Self> {
         self.children.iter().find(|f| f.name == name)
     }
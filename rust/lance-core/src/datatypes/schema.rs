@@ -14,6 +14,7 @@ use lance_arrow::*;
 use snafu::{location, Location};
 
 use super::field::{Field, SchemaCompareOptions};
+use super::{PRIMARY_KEY_ENFORCED_META_KEY, PRIMARY_KEY_META_KEY};
 use crate::{Error, Result};
 
 /// Lance Schema.
@@ -335,6 +336,31 @@ impl Schema {
             .and_then(|c| c.sub_field(&split[1..]))
     }
 
+    /// The columns of the primary key, see [`PRIMARY_KEY_META_KEY`]
+    ///
+    /// Empty if the schema has no primary key.
+    pub fn primary_key(&self) -> Vec<&str> {
+        self.metadata
+            .get(PRIMARY_KEY_META_KEY)
+            .map(|key| {
+                key.split(',')
+                    .map(str::trim)
+                    .filter(|c| !c.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// True if the uniqueness of the primary key is enforced, see
+    /// [`PRIMARY_KEY_ENFORCED_META_KEY`]
+    pub fn primary_key_enforced(&self) -> bool {
+        self.metadata
+            .get(PRIMARY_KEY_ENFORCED_META_KEY)
+            .map(|v| v == "true")
+            .unwrap_or(false)
+            && !self.primary_key().is_empty()
+    }
+
     // TODO: This is not a public API, change to pub(crate) after refactor is done.
     pub fn field_id(&self, column: &str) -> Result<i32> {
         self.field(column)
@@ -271,6 +271,16 @@ impl From<Error> for datafusion_common::DataFusionError {
 impl From<datafusion_common::DataFusionError> for Error {
     #[track_caller]
     fn from(e: datafusion_common::DataFusionError) -> Self {
+        let e = match e {
+            // A lance error raised inside a DataFusion stream keeps its kind
+            datafusion_common::DataFusionError::External(source) => {
+                match source.downcast::<Self>() {
+                    Ok(err) => return *err,
+                    Err(source) => datafusion_common::DataFusionError::External(source),
+                }
+            }
+            e => e,
+        };
         Self::IO {
             message: e.to_string(),
             location: std::panic::Location::caller().to_snafu_location(),
@@ -91,6 +91,43 @@ impl FileReader {
         &self.metadata
     }
 
+    /// Check every page buffer against the checksum recorded by the writer.
+    ///
+    /// Pages written without checksums are skipped. Returns an error naming the
+    /// first damaged buffer.
+    pub async fn verify_checksums(&self) -> Result<()> {
+        let file_scheduler = &self.scheduler.0;
+        for (column_idx, column) in self.metadata.column_metadatas.iter().enumerate() {
+            for (page_idx, page) in column.pages.iter().enumerate() {
+                if page.buffer_checksums.is_empty() {
+                    continue;
+                }
+                let ranges = page
+                    .buffer_offsets
+                    .iter()
+                    .zip(&page.buffer_sizes)
+                    .map(|(offset, size)| *offset..(*offset + *size))
+                    .collect::<Vec<_>>();
+                let buffers = file_scheduler.submit_request(ranges).await?;
+                for (buffer_idx, (buffer, checksum)) in
+                    buffers.iter().zip(&page.buffer_checksums).enumerate()
+                {
+                    if crc32c::crc32c(buffer) != *checksum {
+                        return Err(Error::corrupt_file(
+                            file_scheduler.reader().path().clone(),
+                            format!(
+                                "checksum mismatch in buffer {} of page {} of column {}",
+                                buffer_idx, page_idx, column_idx
+                            ),
+                            location!(),
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     async fn read_tail(scheduler: &FileScheduler) -> Result<(Bytes, u64)> {
         let file_size = scheduler.reader().size().await? as u64;
         let begin = if file_size < scheduler.reader().block_size() as u64 {
@@ -617,4 +654,56 @@ mod tests {
             debug_assert_eq!(total_remaining, 0);
         }
     }
+
+    #[tokio::test]
+    async fn test_verify_checksums() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let file_path = tmp_dir.path().join("some_file.lance");
+        let tmp_path = Path::parse(file_path.to_str().unwrap()).unwrap();
+        let obj_store = Arc::new(ObjectStore::local());
+        let scheduler = StoreScheduler::new(obj_store.clone(), 8);
+
+        let reader = gen()
+            .col(Some("score".to_string()), array::rand::<Float64Type>())
+            .into_reader_rows(RowCount::from(1000), BatchCount::from(10));
+        let schema = reader.schema();
+        let lance_schema = lance_core::datatypes::Schema::try_from(schema.as_ref()).unwrap();
+        let writer = obj_store.create(&tmp_path).await.unwrap();
+        let mut file_writer = FileWriter::try_new(
+            writer,
+            tmp_path.to_string(),
+            lance_schema,
+            FileWriterOptions::default(),
+        )
+        .unwrap();
+        for batch in reader {
+            file_writer.write_batch(&batch.unwrap()).await.unwrap();
+        }
+        file_writer.finish().await.unwrap();
+        let bytes = std::fs::read(&file_path).unwrap();
+        assert_eq!(file_writer.checksum(), crc32c::crc32c(&bytes));
+
+        let file_scheduler = scheduler.open_file(&tmp_path).await.unwrap();
+        let file_reader = FileReader::try_open(file_scheduler, (*schema).clone())
+            .await
+            .unwrap();
+        let page = &file_reader.metadata().column_metadatas[0].pages[0];
+        assert_eq!(page.buffer_checksums.len(), page.buffer_offsets.len());
+        file_reader.verify_checksums().await.unwrap();
+
+        // Flip a bit in the first page
+        let mut corrupted = bytes.clone();
+        corrupted[page.buffer_offsets[0] as usize] ^= 1;
+        std::fs::write(&file_path, corrupted).unwrap();
+        let file_scheduler = scheduler.open_file(&tmp_path).await.unwrap();
+        let file_reader = FileReader::try_open(file_scheduler, (*schema).clone())
+            .await
+            .unwrap();
+        let err = file_reader.verify_checksums().await.unwrap_err();
+        assert!(
+            err.to_string().contains("buffer 0 of page 0 of column 0"),
+            "{}",
+            err
+        );
+    }
 }
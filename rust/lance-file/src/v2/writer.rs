@@ -90,6 +90,7 @@ impl FileWriter {
     async fn write_page(&mut self, encoded_page: EncodedPage) -> Result<()> {
         let mut buffer_offsets = Vec::with_capacity(encoded_page.array.buffers.len());
         let mut buffer_sizes = Vec::with_capacity(encoded_page.array.buffers.len());
+        let mut buffer_checksums = Vec::with_capacity(encoded_page.array.buffers.len());
         for buffer in encoded_page.array.buffers {
             buffer_offsets.push(self.writer.tell().await? as u64);
             buffer_sizes.push(
@@ -103,9 +104,12 @@ impl FileWriter {
             // write_vectored_all and object_store doesn't support it anyways and
             // buffers won't normally be in *too* many parts so its unlikely to
             // have much benefit in most cases.
+            let mut checksum = 0;
             for part in &buffer.parts {
                 self.writer.write_all(part).await?;
+                checksum = crc32c::crc32c_append(checksum, part);
             }
+            buffer_checksums.push(checksum);
         }
         let encoded_encoding = Any::from_msg(&encoded_page.array.encoding)?;
         let page = pbfile::column_metadata::Page {
@@ -117,6 +121,7 @@ impl FileWriter {
                 })),
             }),
             length: encoded_page.num_rows,
+            buffer_checksums,
         };
         self.column_metadata[encoded_page.column_idx as usize]
             .pages
@@ -317,6 +322,11 @@ impl FileWriter {
     pub fn path(&self) -> &str {
         &self.path
     }
+
+    /// CRC32C of the whole file, final once [`Self::finish`] returns.
+    pub fn checksum(&self) -> u32 {
+        self.writer.checksum()
+    }
 }
 
 #[cfg(test)]
@@ -249,6 +249,11 @@ impl<M: ManifestProvider + Send + Sync> FileWriter<M> {
         &self.object_writer.multipart_id
     }
 
+    /// CRC32C of the whole file, final once [`Self::finish`] returns.
+    pub fn checksum(&self) -> u32 {
+        self.object_writer.checksum()
+    }
+
     /// Return the id of the next batch to be written.
     pub fn next_batch_id(&self) -> i32 {
         self.batch_id
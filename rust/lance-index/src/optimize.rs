@@ -17,12 +17,51 @@ pub struct OptimizeOptions {
     /// A common usage pattern will be that, the caller can keep a large snapshot of the index of the base version,
     /// and accumulate a few delta indices, then merge them into the snapshot.
     pub num_indices_to_merge: usize,
+
+    /// Rebalance the partitions of IVF_PQ indices. Default: `None`.
+    ///
+    /// When set, `num_indices_to_merge` is ignored for IVF_PQ indices: the delta updates
+    /// and all the existing indices of the column are merged into one single index, whose
+    /// oversized partitions are split and empty partitions are removed.
+    pub repartition: Option<RepartitionOptions>,
 }
 
 impl Default for OptimizeOptions {
     fn default() -> Self {
         Self {
             num_indices_to_merge: 1,
+            repartition: None,
+        }
+    }
+}
+
+/// Options for rebalancing IVF partitions that drifted after appends.
+///
+/// Partition sizes are compared against the median partition size, or the average
+/// size if most partitions are empty. Only the rows of the split or merged partitions
+/// are re-assigned.
+#[derive(Debug, Clone)]
+pub struct RepartitionOptions {
+    /// Split a partition when it has more than `split_factor` times the median
+    /// number of rows. It is split into `ceil(size / median)` partitions with k-means.
+    /// Must be greater than 1. Default: 4.0.
+    pub split_factor: f32,
+
+    /// Merge a partition into its neighbors when it has at most `merge_factor` times the
+    /// median number of rows. Must be in `[0, 1)`. Default: 0.0, which only removes
+    /// empty partitions.
+    pub merge_factor: f32,
+
+    /// Max number of k-means iterations to split one partition. Default: 50.
+    pub max_iters: u32,
+}
+
+impl Default for RepartitionOptions {
+    fn default() -> Self {
+        Self {
+            split_factor: 4.0,
+            merge_factor: 0.0,
+            max_iters: 50,
         }
     }
 }
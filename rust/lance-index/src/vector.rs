@@ -16,6 +16,7 @@ pub mod kmeans;
 pub mod pq;
 pub mod quantizer;
 pub mod residual;
+pub mod sparse;
 pub mod sq;
 pub mod transform;
 pub mod utils;
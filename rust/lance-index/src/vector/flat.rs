@@ -6,8 +6,10 @@
 
 use std::sync::Arc;
 
+use arrow::buffer::NullBuffer;
 use arrow_array::{
-    cast::AsArray, make_array, Array, ArrayRef, FixedSizeListArray, RecordBatch, StructArray,
+    cast::AsArray, make_array, Array, ArrayRef, FixedSizeListArray, Float32Array, RecordBatch,
+    StructArray,
 };
 use arrow_ord::sort::sort_to_indices;
 use arrow_schema::{DataType, Field as ArrowField, SchemaRef, SortOptions};
@@ -16,10 +18,10 @@ use futures::{
     future,
     stream::{repeat_with, StreamExt, TryStreamExt},
 };
-use lance_arrow::*;
+use lance_arrow::{sparse::is_sparse_vector, *};
 use lance_core::{Error, Result, ROW_ID};
 use lance_io::stream::RecordBatchStream;
-use lance_linalg::distance::DistanceType;
+use lance_linalg::distance::{sparse_dot_distance_arrow_batch, DistanceType};
 use snafu::{location, Location};
 use tracing::instrument;
 
@@ -82,6 +84,31 @@ async fn flat_search_batch(
             location: location!(),
         })?;
 
+    if is_sparse_vector(vectors.data_type()) {
+        if mt != DistanceType::Dot {
+            return Err(Error::InvalidInput {
+                source: format!(
+                    "Sparse vector search only supports dot distance, got {}",
+                    mt
+                )
+                .into(),
+                location: location!(),
+            });
+        }
+        let vectors = vectors.clone();
+        let row_id_nulls = batch
+            .column_by_name(ROW_ID)
+            .and_then(|rowids| rowids.nulls().cloned());
+        return tokio::task::spawn_blocking(move || {
+            let distances = sparse_dot_distance_arrow_batch(key.as_ref(), vectors.as_ref())?;
+            let nulls = NullBuffer::union(distances.nulls(), row_id_nulls.as_ref());
+            let distances = Arc::new(Float32Array::new(distances.values().clone(), nulls));
+            select_top_k(batch, distances, k)
+        })
+        .await
+        .unwrap();
+    }
+
     // A selection vector may have been applied to _rowid column, so we need to
     // push that onto vectors if possible.
     let vectors = as_fixed_size_list_array(vectors.as_ref()).clone();
@@ -101,25 +128,29 @@ async fn flat_search_batch(
 
     tokio::task::spawn_blocking(move || {
         let distances = mt.arrow_batch_func()(key.as_ref(), &vectors)? as ArrayRef;
-
-        // We don't want any nulls in result, so limit to k or the number of valid values.
-        let k = std::cmp::min(k, distances.len() - distances.null_count());
-
-        let sort_options = SortOptions {
-            nulls_first: false,
-            ..Default::default()
-        };
-        let indices = sort_to_indices(&distances, Some(sort_options), Some(k))?;
-
-        let batch_with_distance = batch.try_with_column(distance_field(), distances)?;
-        let struct_arr = StructArray::from(batch_with_distance);
-        let selected_arr = take(&struct_arr, &indices, None)?;
-        Ok::<RecordBatch, Error>(selected_arr.as_struct().into())
+        select_top_k(batch, distances, k)
     })
     .await
     .unwrap()
 }
 
+/// Attach `distances` to `batch` and keep the `k` closest non-null rows.
+fn select_top_k(batch: RecordBatch, distances: ArrayRef, k: usize) -> Result<RecordBatch> {
+    // We don't want any nulls in result, so limit to k or the number of valid values.
+    let k = std::cmp::min(k, distances.len() - distances.null_count());
+
+    let sort_options = SortOptions {
+        nulls_first: false,
+        ..Default::default()
+    };
+    let indices = sort_to_indices(&distances, Some(sort_options), Some(k))?;
+
+    let batch_with_distance = batch.try_with_column(distance_field(), distances)?;
+    let struct_arr = StructArray::from(batch_with_distance);
+    let selected_arr = take(&struct_arr, &indices, None)?;
+    Ok(selected_arr.as_struct().into())
+}
+
 fn concat_batches<'a>(
     schema: &SchemaRef,
     input_batches: impl IntoIterator<Item = &'a RecordBatch>,
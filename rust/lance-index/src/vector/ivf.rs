@@ -26,7 +26,7 @@ use lance_linalg::{
 use crate::vector::ivf::transform::IvfTransformer;
 use crate::vector::{
     pq::{transform::PQTransformer, ProductQuantizer},
-    residual::ResidualTransform,
+    residual::{compute_residual_mixed, ResidualTransform},
     transform::Transformer,
 };
 
@@ -250,6 +250,8 @@ pub trait Ivf: Send + Sync + std::fmt::Debug + Transformer {
     /// -------
     /// A 1-D array of partition id for each vector.
     ///
+    /// Vectors stored as `int8`, `uint8` or `bfloat16` are compared to the centroids in place.
+    ///
     /// Raises [Error] if the input data type does not match with the IVF model.
     ///
     async fn compute_partitions(&self, data: &FixedSizeListArray) -> Result<UInt32Array>;
@@ -413,6 +415,9 @@ impl<T: ArrowFloatType + Dot + L2 + ArrowPrimitiveType> IvfImpl<T> {
 #[async_trait]
 impl<T: ArrowFloatType + Dot + L2 + ArrowPrimitiveType> Ivf for IvfImpl<T> {
     async fn compute_partitions(&self, data: &FixedSizeListArray) -> Result<UInt32Array> {
+        if data.value_type() != T::DATA_TYPE {
+            return self.ivf_transform.compute_partitions_mixed(data);
+        }
         let array = data
             .values()
             .as_any()
@@ -434,6 +439,15 @@ impl<T: ArrowFloatType + Dot + L2 + ArrowPrimitiveType> Ivf for IvfImpl<T> {
         original: &FixedSizeListArray,
         partitions: Option<&UInt32Array>,
     ) -> Result<FixedSizeListArray> {
+        let part_ids = if let Some(part_ids) = partitions {
+            part_ids.clone()
+        } else {
+            self.compute_partitions(original).await?
+        };
+        if original.value_type() != T::DATA_TYPE {
+            return compute_residual_mixed(&self.centroids, original, part_ids.values());
+        }
+
         let flatten_arr = original
             .values()
             .as_any()
@@ -447,11 +461,6 @@ impl<T: ArrowFloatType + Dot + L2 + ArrowPrimitiveType> Ivf for IvfImpl<T> {
                 location: Default::default(),
             })?;
 
-        let part_ids = if let Some(part_ids) = partitions {
-            part_ids.clone()
-        } else {
-            self.compute_partitions(original).await?
-        };
         let dim = original.value_length() as usize;
         let residual_arr = flatten_arr
             .as_slice()
@@ -17,7 +17,7 @@ use std::sync::Arc;
 use arrow::compute::sort_to_indices;
 use arrow_array::{cast::AsArray, types::UInt64Type, Array, RecordBatch, UInt32Array};
 use arrow_schema::Field;
-use futures::stream::repeat_with;
+use futures::stream::{repeat_with, BoxStream};
 use futures::{stream, Stream, StreamExt, TryStreamExt};
 use lance_arrow::RecordBatchExt;
 use lance_core::datatypes::Schema;
@@ -89,78 +89,7 @@ pub async fn shuffle_dataset(
     } else {
         let mut shuffler = IvfShuffler::try_new(num_partitions, num_sub_vectors, None)?;
 
-        let column = column.to_owned();
-        let precomputed_partitions = precomputed_partitions.map(Arc::new);
-        let stream = data
-            .zip(repeat_with(move || ivf.clone()))
-            .map(move |(b, ivf)| {
-                // If precomputed_partitions map is provided, use it
-                // for fast partitions.
-                let partition_map = precomputed_partitions
-                    .as_ref()
-                    .cloned()
-                    .unwrap_or(Arc::new(HashMap::new()));
-                let nan_filter = KeepFiniteVectors::new(&column);
-
-                tokio::task::spawn(async move {
-                    let mut batch = b?;
-
-                    if !partition_map.is_empty() {
-                        let row_ids = batch.column_by_name(ROW_ID).ok_or(Error::Index {
-                            message: "column does not exist".to_string(),
-                            location: location!(),
-                        })?;
-                        let part_ids = UInt32Array::from_iter(
-                            row_ids
-                                .as_primitive::<UInt64Type>()
-                                .values()
-                                .iter()
-                                .map(|row_id| partition_map.get(row_id).copied()),
-                        );
-                        let part_ids = UInt32Array::from(part_ids);
-                        batch = batch
-                            .try_with_column(
-                                Field::new(PART_ID_COLUMN, part_ids.data_type().clone(), true),
-                                Arc::new(part_ids.clone()),
-                            )
-                            .expect("failed to add part id column");
-
-                        if part_ids.null_count() > 0 {
-                            info!(
-                                "Filter out rows without valid partition IDs: null_count={}",
-                                part_ids.null_count()
-                            );
-                            let indices = UInt32Array::from_iter(
-                                part_ids
-                                    .iter()
-                                    .enumerate()
-                                    .filter_map(|(idx, v)| v.map(|_| idx as u32)),
-                            );
-                            assert_eq!(indices.len(), batch.num_rows() - part_ids.null_count());
-                            batch = batch.take(&indices)?;
-                        }
-                    }
-
-                    // Filter out NaNs/Infs
-                    batch = nan_filter.transform(&batch).await?;
-
-                    ivf.transform(&batch).await
-                })
-            })
-            .buffer_unordered(num_cpus::get())
-            .map(|res| match res {
-                Ok(Ok(batch)) => Ok(batch),
-                Ok(Err(err)) => Err(Error::IO {
-                    message: err.to_string(),
-                    location: location!(),
-                }),
-                Err(err) => Err(Error::IO {
-                    message: err.to_string(),
-                    location: location!(),
-                }),
-            })
-            .boxed();
-
+        let stream = transform_stream(data, column, ivf, precomputed_partitions);
         let start = std::time::Instant::now();
         shuffler.write_unsorted_stream(stream).await?;
         info!("wrote unstored stream in {:?}", start.elapsed());
@@ -183,6 +112,109 @@ pub async fn shuffle_dataset(
     Ok(stream)
 }
 
+/// Assign partitions and apply the sub-quantizer to each batch of the input data,
+/// dropping vectors with non-finite values.
+fn transform_stream(
+    data: impl RecordBatchStream + Unpin + 'static,
+    column: &str,
+    ivf: Arc<dyn crate::vector::ivf::Ivf>,
+    precomputed_partitions: Option<HashMap<u64, u32>>,
+) -> BoxStream<'static, Result<RecordBatch>> {
+    let column = column.to_owned();
+    let precomputed_partitions = precomputed_partitions.map(Arc::new);
+    data.zip(repeat_with(move || ivf.clone()))
+        .map(move |(b, ivf)| {
+            // If precomputed_partitions map is provided, use it
+            // for fast partitions.
+            let partition_map = precomputed_partitions
+                .as_ref()
+                .cloned()
+                .unwrap_or(Arc::new(HashMap::new()));
+            let nan_filter = KeepFiniteVectors::new(&column);
+
+            tokio::task::spawn(async move {
+                let mut batch = b?;
+
+                if !partition_map.is_empty() {
+                    let row_ids = batch.column_by_name(ROW_ID).ok_or(Error::Index {
+                        message: "column does not exist".to_string(),
+                        location: location!(),
+                    })?;
+                    let part_ids = UInt32Array::from_iter(
+                        row_ids
+                            .as_primitive::<UInt64Type>()
+                            .values()
+                            .iter()
+                            .map(|row_id| partition_map.get(row_id).copied()),
+                    );
+                    let part_ids = UInt32Array::from(part_ids);
+                    batch = batch
+                        .try_with_column(
+                            Field::new(PART_ID_COLUMN, part_ids.data_type().clone(), true),
+                            Arc::new(part_ids.clone()),
+                        )
+                        .expect("failed to add part id column");
+
+                    if part_ids.null_count() > 0 {
+                        info!(
+                            "Filter out rows without valid partition IDs: null_count={}",
+                            part_ids.null_count()
+                        );
+                        let indices = UInt32Array::from_iter(
+                            part_ids
+                                .iter()
+                                .enumerate()
+                                .filter_map(|(idx, v)| v.map(|_| idx as u32)),
+                        );
+                        assert_eq!(indices.len(), batch.num_rows() - part_ids.null_count());
+                        batch = batch.take(&indices)?;
+                    }
+                }
+
+                // Filter out NaNs/Infs
+                batch = nan_filter.transform(&batch).await?;
+
+                ivf.transform(&batch).await
+            })
+        })
+        .buffer_unordered(num_cpus::get())
+        .map(|res| match res {
+            Ok(Ok(batch)) => Ok(batch),
+            Ok(Err(err)) => Err(Error::IO {
+                message: err.to_string(),
+                location: location!(),
+            }),
+            Err(err) => Err(Error::IO {
+                message: err.to_string(),
+                location: location!(),
+            }),
+        })
+        .boxed()
+}
+
+/// Write the transformed input data into an unsorted buffer named `buffer_name`
+/// under `output_dir`.
+///
+/// This is the first half of [shuffle_dataset]. Independent workers can each run it
+/// over a disjoint subset of the data, with distinct buffer names in a shared
+/// directory, and pass all the buffers to [shuffle_dataset] as
+/// `precomputed_shuffle_buffers` to finish the shuffle.
+pub async fn write_unsorted_buffer(
+    data: impl RecordBatchStream + Unpin + 'static,
+    column: &str,
+    ivf: Arc<dyn crate::vector::ivf::Ivf>,
+    num_partitions: u32,
+    num_sub_vectors: usize,
+    output_dir: Path,
+    buffer_name: &str,
+) -> Result<()> {
+    let mut shuffler = IvfShuffler::try_new(num_partitions, num_sub_vectors, Some(output_dir))?;
+    let stream = transform_stream(data, column, ivf, None);
+    shuffler
+        .write_named_unsorted_stream(stream, buffer_name)
+        .await
+}
+
 pub struct IvfShuffler {
     unsorted_buffers: Vec<String>,
 
@@ -231,9 +263,19 @@ impl IvfShuffler {
     pub async fn write_unsorted_stream(
         &mut self,
         data: impl Stream<Item = Result<RecordBatch>>,
+    ) -> Result<()> {
+        self.write_named_unsorted_stream(data, UNSORTED_BUFFER)
+            .await
+    }
+
+    /// Write the stream to an unsorted buffer named `name` in the output directory.
+    pub async fn write_named_unsorted_stream(
+        &mut self,
+        data: impl Stream<Item = Result<RecordBatch>>,
+        name: &str,
     ) -> Result<()> {
         let object_store = ObjectStore::local();
-        let path = self.output_dir.child(UNSORTED_BUFFER);
+        let path = self.output_dir.child(name);
         let writer = object_store.create(&path).await?;
 
         let mut data = Box::pin(data.peekable());
@@ -277,7 +319,7 @@ impl IvfShuffler {
         file_writer.finish().await?;
 
         unsafe {
-            self.set_unsorted_buffers(&[UNSORTED_BUFFER]);
+            self.set_unsorted_buffers(&[name]);
         }
 
         Ok(())
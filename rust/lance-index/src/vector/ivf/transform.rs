@@ -6,15 +6,17 @@
 use std::ops::Range;
 use std::sync::Arc;
 
-use arrow_array::types::UInt32Type;
-use arrow_array::{cast::AsArray, Array, ArrowPrimitiveType, RecordBatch, UInt32Array};
-use arrow_schema::Field;
+use arrow_array::types::{Float16Type, Int8Type, UInt32Type, UInt8Type};
+use arrow_array::{
+    cast::AsArray, Array, ArrowPrimitiveType, FixedSizeListArray, RecordBatch, UInt32Array,
+};
+use arrow_schema::{DataType, Field};
 use futures::{stream, StreamExt};
 use log::info;
 use snafu::{location, Location};
 use tracing::{instrument, Instrument};
 
-use lance_arrow::{ArrowFloatType, RecordBatchExt};
+use lance_arrow::{bfloat16::BFloat16Array, ArrowFloatType, FloatArray, RecordBatchExt};
 use lance_core::Result;
 use lance_linalg::distance::{Dot, MetricType, L2};
 use lance_linalg::MatrixView;
@@ -106,6 +108,59 @@ impl<T: ArrowFloatType + L2 + Dot> IvfTransformer<T> {
 
         UInt32Array::from_iter(result.iter().flatten().copied())
     }
+
+    /// Compute the partition for each vector stored in a different element type than
+    /// the centroids, i.e., `int8`, `uint8` or `bfloat16` vectors.
+    ///
+    /// The stored vectors are read in place, without being converted to the centroid type.
+    pub(super) fn compute_partitions_mixed(
+        &self,
+        data: &FixedSizeListArray,
+    ) -> Result<UInt32Array> {
+        use lance_linalg::kmeans::compute_partitions_mixed;
+
+        let centroids = self.centroids.data();
+        let centroids = centroids.as_slice();
+        let dimension = data.value_length() as usize;
+        let values = data.values();
+        let partitions = match values.data_type() {
+            DataType::Int8 => compute_partitions_mixed(
+                centroids,
+                values.as_primitive::<Int8Type>().values(),
+                dimension,
+                self.metric_type,
+            ),
+            DataType::UInt8 => compute_partitions_mixed(
+                centroids,
+                values.as_primitive::<UInt8Type>().values(),
+                dimension,
+                self.metric_type,
+            ),
+            DataType::Float16 => compute_partitions_mixed(
+                centroids,
+                values.as_primitive::<Float16Type>().values(),
+                dimension,
+                self.metric_type,
+            ),
+            DataType::FixedSizeBinary(2) => compute_partitions_mixed(
+                centroids,
+                BFloat16Array::try_from(values.as_fixed_size_binary().clone())?.as_slice(),
+                dimension,
+                self.metric_type,
+            ),
+            _ => {
+                return Err(lance_core::Error::Index {
+                    message: format!(
+                        "IvfTransformer: unsupported vector type {} for {} centroids",
+                        data.value_type(),
+                        T::FLOAT_TYPE
+                    ),
+                    location: location!(),
+                })
+            }
+        }?;
+        Ok(UInt32Array::from(partitions))
+    }
 }
 
 #[async_trait::async_trait]
@@ -136,8 +191,12 @@ impl<T: ArrowFloatType + L2 + Dot + ArrowPrimitiveType> Transformer for IvfTrans
                 location: location!(),
             })?;
 
-        let mat = MatrixView::<T>::try_from(fsl)?;
-        let part_ids = self.compute_partitions(&mat).await;
+        let part_ids = if fsl.value_type() == T::DATA_TYPE {
+            let mat = MatrixView::<T>::try_from(fsl)?;
+            self.compute_partitions(&mat).await
+        } else {
+            self.compute_partitions_mixed(fsl)?
+        };
         let field = Field::new(PART_ID_COLUMN, part_ids.data_type().clone(), true);
         Ok(batch.try_with_column(field, Arc::new(part_ids))?)
     }
@@ -22,11 +22,11 @@ pub mod storage;
 pub mod transform;
 pub(crate) mod utils;
 
-use self::distance::{build_distance_table_l2, compute_l2_distance};
-pub use self::utils::num_centroids;
+use self::distance::{build_distance_table_l2, compute_distances_4bit, compute_l2_distance};
+pub use self::utils::{code_length, num_centroids};
 use super::pb;
 pub use builder::PQBuildParams;
-use utils::get_sub_vector_centroids;
+use utils::{get_sub_vector_centroids, get_sub_vector_code};
 
 /// Product Quantization
 #[async_trait::async_trait]
@@ -44,6 +44,11 @@ pub trait ProductQuantizer: Send + Sync + std::fmt::Debug {
 
     fn num_bits(&self) -> u32;
 
+    /// Number of bytes of the PQ code of one vector.
+    fn code_length(&self) -> usize {
+        code_length(self.num_bits(), self.num_sub_vectors())
+    }
+
     fn dimension(&self) -> usize;
 
     // TODO: move to pub(crate) once the refactor of lance::index to lance-index is done.
@@ -61,7 +66,8 @@ pub trait ProductQuantizer: Send + Sync + std::fmt::Debug {
 pub struct ProductQuantizerImpl<T: ArrowFloatType + Dot + L2> {
     /// Number of bits for the centroids.
     ///
-    /// Only support 8, as one of `u8` byte now.
+    /// Support 8, one code per `u8` byte, or 4, with two codes packed in one
+    /// byte. The code of the even sub-vector is in the low nibble.
     pub num_bits: u32,
 
     /// Number of sub-vectors.
@@ -107,7 +113,7 @@ impl<T: ArrowFloatType + Dot + L2> ProductQuantizerImpl<T> {
             MetricType::Cosine,
             "Product quantization does not support cosine, use normalized L2 instead"
         );
-        assert_eq!(nbits, 8, "nbits can only be 8");
+        assert!(nbits == 4 || nbits == 8, "nbits can only be 4 or 8");
         Self {
             num_bits: nbits,
             num_sub_vectors: m,
@@ -140,14 +146,13 @@ impl<T: ArrowFloatType + Dot + L2> ProductQuantizerImpl<T> {
     }
 
     /// Reconstruct a vector from its PQ code.
-    ///
-    /// It only supports U8 PQ code for now.
     #[allow(dead_code)]
     pub(crate) fn reconstruct(&self, code: &[u8]) -> Arc<T::ArrayType> {
-        assert_eq!(code.len(), self.num_sub_vectors);
+        assert_eq!(code.len(), code_length(self.num_bits, self.num_sub_vectors));
         let mut builder = Vec::with_capacity(self.dimension);
         let sub_vector_dim = self.dimension / self.num_sub_vectors;
-        for (i, sub_code) in code.iter().enumerate() {
+        for i in 0..self.num_sub_vectors {
+            let sub_code = &get_sub_vector_code(code, self.num_bits, i);
             let centroids = self.centroids(i);
             builder.extend_from_slice(
                 &centroids[*sub_code as usize * sub_vector_dim
@@ -249,6 +254,14 @@ impl<T: ArrowFloatType + Dot + L2> ProductQuantizerImpl<T> {
     fn l2_distances(&self, key: &dyn Array, code: &UInt8Array) -> Result<Float32Array> {
         let distance_table = self.build_l2_distance_table(key)?;
 
+        if self.num_bits == 4 {
+            return Ok(Float32Array::from(compute_distances_4bit(
+                &distance_table,
+                self.num_sub_vectors,
+                code.values(),
+            )));
+        }
+
         #[cfg(target_feature = "avx512f")]
         {
             Ok(self.compute_l2_distance::<16, 64>(&distance_table, code.values()))
@@ -287,13 +300,22 @@ impl<T: ArrowFloatType + Dot + L2> ProductQuantizerImpl<T> {
                 distance_table.extend(distances);
             });
 
+        if self.num_bits == 4 {
+            return Ok(Float32Array::from(compute_distances_4bit(
+                &distance_table,
+                self.num_sub_vectors,
+                code.values(),
+            )));
+        }
+
         // Compute distance from the pre-compute table.
+        let num_centroids = num_centroids(self.num_bits);
         Ok(Float32Array::from_iter_values(
             code.values().chunks_exact(self.num_sub_vectors).map(|c| {
                 c.iter()
                     .enumerate()
                     .map(|(sub_vec_idx, centroid)| {
-                        distance_table[sub_vec_idx * 256 + *centroid as usize]
+                        distance_table[sub_vec_idx * num_centroids + *centroid as usize]
                     })
                     .sum::<f32>()
             }),
@@ -326,6 +348,7 @@ impl<T: ArrowFloatType + Dot + L2 + 'static> ProductQuantizer for ProductQuantiz
         let codebook = self.codebook.clone();
 
         let metric_type = self.metric_type;
+        let code_length = self.code_length();
         let values = tokio::task::spawn_blocking(move || {
             let all_centroids = (0..num_sub_vectors)
                 .map(|idx| {
@@ -351,7 +374,7 @@ impl<T: ArrowFloatType + Dot + L2 + 'static> ProductQuantizer for ProductQuantiz
                     })?;
 
             let flatten_values = flatten_data.as_slice();
-            let capacity = num_sub_vectors * num_rows;
+            let capacity = code_length * num_rows;
             let mut builder: Vec<u8> = vec![0; capacity];
             // Dimension of each sub-vector.
             let sub_dim = dim / num_sub_vectors;
@@ -376,7 +399,11 @@ impl<T: ArrowFloatType + Dot + L2 + 'static> ProductQuantizer for ProductQuantiz
                         ),
                         location: location!(),
                     })? as u8;
-                    builder[i * num_sub_vectors + sub_idx] = code as u8;
+                    if num_bits == 4 {
+                        builder[i * code_length + sub_idx / 2] |= code << ((sub_idx % 2) * 4);
+                    } else {
+                        builder[i * code_length + sub_idx] = code;
+                    }
                 }
             }
             Ok::<UInt8Array, Error>(UInt8Array::from(builder))
@@ -385,7 +412,7 @@ impl<T: ArrowFloatType + Dot + L2 + 'static> ProductQuantizer for ProductQuantiz
 
         Ok(Arc::new(FixedSizeListArray::try_new_from_values(
             values,
-            self.num_sub_vectors as i32,
+            self.code_length() as i32,
         )?))
     }
 
@@ -529,4 +556,61 @@ mod tests {
                 assert_relative_eq!(*v, *e, epsilon = 1e-4);
             });
     }
+
+    #[tokio::test]
+    async fn test_4bit_pq_distance() {
+        const DIM: usize = 32;
+        const NUM_SUB_VECTORS: usize = 8;
+        const TOTAL: usize = 100;
+        let codebook = Arc::new(generate_random_array(16 * DIM));
+        let pq = ProductQuantizerImpl::<Float32Type>::new(
+            NUM_SUB_VECTORS,
+            4,
+            DIM,
+            codebook,
+            MetricType::L2,
+        );
+        assert_eq!(pq.code_length(), NUM_SUB_VECTORS / 2);
+
+        let data = generate_random_array(TOTAL * DIM);
+        let fsl = FixedSizeListArray::try_new_from_values(data, DIM as i32).unwrap();
+        let code = pq.transform(&fsl).await.unwrap();
+        let code = code.as_fixed_size_list();
+        assert_eq!(code.value_length(), (NUM_SUB_VECTORS / 2) as i32);
+        let code = code
+            .values()
+            .as_primitive::<arrow_array::types::UInt8Type>();
+
+        let query = generate_random_array(DIM);
+        let dists = pq.compute_distances(&query, code).unwrap();
+        assert_eq!(dists.len(), TOTAL);
+
+        // Compare with the distances to the reconstructed vectors.
+        let table = pq.build_l2_distance_table(&query).unwrap();
+        let max_range = table
+            .chunks_exact(16)
+            .map(|t| {
+                let (lo, hi) = t
+                    .iter()
+                    .fold((f32::MAX, f32::MIN), |(lo, hi), &d| (lo.min(d), hi.max(d)));
+                hi - lo
+            })
+            .fold(0.0, f32::max);
+        let tolerance = NUM_SUB_VECTORS as f32 * max_range / 255.0 / 2.0 + 1e-4;
+        for (c, dist) in code
+            .values()
+            .chunks_exact(pq.code_length())
+            .zip(dists.values())
+        {
+            let reconstructed = pq.reconstruct(c);
+            let expected =
+                l2_distance_batch(query.values(), reconstructed.values(), DIM).sum::<f32>();
+            assert!(
+                (dist - expected).abs() <= tolerance,
+                "dist={}, expected={}",
+                dist,
+                expected
+            );
+        }
+    }
 }
@@ -32,7 +32,9 @@ pub struct PQBuildParams {
     /// Number of sub-vectors to build PQ code
     pub num_sub_vectors: usize,
 
-    /// The number of bits to present one PQ centroid.
+    /// The number of bits to present one PQ centroid, either 4 or 8.
+    ///
+    /// 4-bit codes are packed two per byte and scanned with the fast-scan kernel.
     pub num_bits: usize,
 
     /// Train as optimized product quantization.
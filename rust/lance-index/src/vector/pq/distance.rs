@@ -115,3 +115,270 @@ pub(super) fn compute_l2_distance<const C: usize, const V: usize>(
     });
     distances.chain(remainder).collect()
 }
+
+/// Number of PQ codes scored at once by the 4-bit fast-scan kernel.
+const FAST_SCAN_BATCH: usize = 32;
+
+/// Distance table quantized to `u8`, used by 4-bit PQ fast-scan.
+///
+/// Each sub-vector table is shifted by its minimum and all tables share one
+/// scale, so the distance of a code is `bias + scale * sum(quantized)`.
+#[derive(Debug)]
+pub(super) struct QuantizedDistanceTable {
+    /// Flatten `[num_padded_sub_vectors, 16]` table. The row of the padding
+    /// sub-vector, if any, is all zeros.
+    table: Vec<u8>,
+    scale: f32,
+    bias: f32,
+}
+
+impl QuantizedDistanceTable {
+    /// Quantize a `[num_sub_vectors, 16]` f32 distance table.
+    pub(super) fn new(distance_table: &[f32], num_sub_vectors: usize) -> Self {
+        const NUM_CENTROIDS: usize = 16;
+        debug_assert_eq!(distance_table.len(), num_sub_vectors * NUM_CENTROIDS);
+
+        let bounds = distance_table
+            .chunks_exact(NUM_CENTROIDS)
+            .map(|t| {
+                t.iter()
+                    .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &d| {
+                        (lo.min(d), hi.max(d))
+                    })
+            })
+            .collect::<Vec<_>>();
+        let max_range = bounds.iter().map(|(lo, hi)| hi - lo).fold(0.0, f32::max);
+        let scale = if max_range > 0.0 {
+            max_range / u8::MAX as f32
+        } else {
+            1.0
+        };
+        let bias = bounds.iter().map(|(lo, _)| lo).sum();
+
+        let num_padded_sub_vectors = num_sub_vectors.div_ceil(2) * 2;
+        let mut table = vec![0_u8; num_padded_sub_vectors * NUM_CENTROIDS];
+        for (sub_table, (t, (lo, _))) in table
+            .chunks_exact_mut(NUM_CENTROIDS)
+            .zip(distance_table.chunks_exact(NUM_CENTROIDS).zip(bounds))
+        {
+            for (q, d) in sub_table.iter_mut().zip(t) {
+                *q = ((d - lo) / scale).round().min(u8::MAX as f32) as u8;
+            }
+        }
+        Self { table, scale, bias }
+    }
+
+    #[inline]
+    fn distance(&self, sum: u32) -> f32 {
+        self.bias + self.scale * sum as f32
+    }
+
+    /// Sum of the quantized distances of one packed code.
+    #[inline]
+    fn scan_one(&self, code: &[u8]) -> u32 {
+        code.iter()
+            .enumerate()
+            .map(|(i, c)| {
+                self.table[i * 32 + (c & 0x0F) as usize] as u32
+                    + self.table[i * 32 + 16 + (c >> 4) as usize] as u32
+            })
+            .sum()
+    }
+}
+
+/// Compute the distances from the query to 4-bit PQ codes, using fast-scan.
+///
+/// Parameters
+/// ----------
+/// - distance_table: the pre-computed f32 distance table, a flatten array of
+///   `[num_sub_vectors, 16]`.
+/// - num_sub_vectors: the number of sub-vectors.
+/// - code: the PQ codes, two sub-vector codes packed per byte, low nibble first.
+///
+/// Returns
+/// -------
+///  The approximated distances. The error of each distance is bounded by
+///  `num_sub_vectors * scale / 2`, where `scale` is the quantization step of
+///  the distance table.
+pub(super) fn compute_distances_4bit(
+    distance_table: &[f32],
+    num_sub_vectors: usize,
+    code: &[u8],
+) -> Vec<f32> {
+    let table = QuantizedDistanceTable::new(distance_table, num_sub_vectors);
+    let code_length = num_sub_vectors.div_ceil(2);
+
+    let mut distances = Vec::with_capacity(code.len() / code_length);
+    let iter = code.chunks_exact(code_length * FAST_SCAN_BATCH);
+    let remainder = iter.remainder();
+    // Sums are accumulated in u16 lanes: 255 * 256 sub-vectors still fits.
+    if num_sub_vectors <= 256 {
+        let mut sums = [0_u16; FAST_SCAN_BATCH];
+        for batch in iter {
+            fast_scan_batch(&table.table, batch, code_length, &mut sums);
+            distances.extend(sums.iter().map(|s| table.distance(*s as u32)));
+        }
+    } else {
+        distances.extend(
+            iter.flat_map(|batch| batch.chunks_exact(code_length))
+                .map(|c| table.distance(table.scan_one(c))),
+        );
+    }
+    distances.extend(
+        remainder
+            .chunks_exact(code_length)
+            .map(|c| table.distance(table.scan_one(c))),
+    );
+    distances
+}
+
+/// Transpose column `j` of a batch of 32 codes into one register-sized array.
+#[inline]
+fn gather_column(codes: &[u8], code_length: usize, j: usize) -> [u8; FAST_SCAN_BATCH] {
+    let mut column = [0_u8; FAST_SCAN_BATCH];
+    for (v, c) in column.iter_mut().enumerate() {
+        *c = codes[v * code_length + j];
+    }
+    column
+}
+
+/// Score 32 codes at once with in-register table lookups.
+#[cfg(all(target_arch = "x86_64", target_feature = "avx2"))]
+#[inline]
+fn fast_scan_batch(table: &[u8], codes: &[u8], code_length: usize, sums: &mut [u16; 32]) {
+    use std::arch::x86_64::*;
+
+    unsafe {
+        let mask = _mm256_set1_epi8(0x0F);
+        let mut acc_lo = _mm256_setzero_si256();
+        let mut acc_hi = _mm256_setzero_si256();
+        for j in 0..code_length {
+            let column = gather_column(codes, code_length, j);
+            let c = _mm256_loadu_si256(column.as_ptr() as *const __m256i);
+            // The 16-entry tables are broadcast to both 128-bit lanes, since
+            // the shuffle only looks up within a lane.
+            let lut_even = _mm256_broadcastsi128_si256(_mm_loadu_si128(
+                table[j * 32..].as_ptr() as *const __m128i
+            ));
+            let lut_odd = _mm256_broadcastsi128_si256(_mm_loadu_si128(
+                table[j * 32 + 16..].as_ptr() as *const __m128i,
+            ));
+            let d_even = _mm256_shuffle_epi8(lut_even, _mm256_and_si256(c, mask));
+            let d_odd =
+                _mm256_shuffle_epi8(lut_odd, _mm256_and_si256(_mm256_srli_epi16(c, 4), mask));
+
+            // Widen to u16 to accumulate.
+            acc_lo = _mm256_add_epi16(acc_lo, _mm256_cvtepu8_epi16(_mm256_castsi256_si128(d_even)));
+            acc_lo = _mm256_add_epi16(acc_lo, _mm256_cvtepu8_epi16(_mm256_castsi256_si128(d_odd)));
+            acc_hi = _mm256_add_epi16(
+                acc_hi,
+                _mm256_cvtepu8_epi16(_mm256_extracti128_si256(d_even, 1)),
+            );
+            acc_hi = _mm256_add_epi16(
+                acc_hi,
+                _mm256_cvtepu8_epi16(_mm256_extracti128_si256(d_odd, 1)),
+            );
+        }
+        _mm256_storeu_si256(sums.as_mut_ptr() as *mut __m256i, acc_lo);
+        _mm256_storeu_si256(sums[16..].as_mut_ptr() as *mut __m256i, acc_hi);
+    }
+}
+
+/// Score 32 codes at once with in-register table lookups.
+#[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+#[inline]
+fn fast_scan_batch(table: &[u8], codes: &[u8], code_length: usize, sums: &mut [u16; 32]) {
+    use std::arch::aarch64::*;
+
+    unsafe {
+        let mask = vdupq_n_u8(0x0F);
+        let mut acc = [vdupq_n_u16(0); 4];
+        for j in 0..code_length {
+            let column = gather_column(codes, code_length, j);
+            let lut_even = vld1q_u8(table[j * 32..].as_ptr());
+            let lut_odd = vld1q_u8(table[j * 32 + 16..].as_ptr());
+            for half in 0..2 {
+                let c = vld1q_u8(column[half * 16..].as_ptr());
+                let d_even = vqtbl1q_u8(lut_even, vandq_u8(c, mask));
+                let d_odd = vqtbl1q_u8(lut_odd, vshrq_n_u8(c, 4));
+                for d in [d_even, d_odd] {
+                    acc[half * 2] = vaddw_u8(acc[half * 2], vget_low_u8(d));
+                    acc[half * 2 + 1] = vaddw_high_u8(acc[half * 2 + 1], d);
+                }
+            }
+        }
+        for (i, a) in acc.iter().enumerate() {
+            vst1q_u16(sums[i * 8..].as_mut_ptr(), *a);
+        }
+    }
+}
+
+/// Score 32 codes at once, portable fallback.
+#[cfg(not(any(
+    all(target_arch = "x86_64", target_feature = "avx2"),
+    all(target_arch = "aarch64", target_feature = "neon")
+)))]
+#[inline]
+fn fast_scan_batch(table: &[u8], codes: &[u8], code_length: usize, sums: &mut [u16; 32]) {
+    sums.fill(0);
+    for j in 0..code_length {
+        let column = gather_column(codes, code_length, j);
+        for (sum, c) in sums.iter_mut().zip(column) {
+            *sum += table[j * 32 + (c & 0x0F) as usize] as u16
+                + table[j * 32 + 16 + (c >> 4) as usize] as u16;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    #[test]
+    fn test_fast_scan_matches_scalar() {
+        const NUM_SUB_VECTORS: usize = 7; // Odd to cover the padding nibble.
+        const TOTAL: usize = 32 * 3 + 5;
+        let mut rng = StdRng::seed_from_u64(42);
+        let distance_table = (0..NUM_SUB_VECTORS * 16)
+            .map(|_| rng.gen_range(-5.0..10.0))
+            .collect::<Vec<f32>>();
+        let code_length = NUM_SUB_VECTORS.div_ceil(2);
+        let codes = (0..TOTAL * code_length)
+            .map(|i| {
+                let b: u8 = rng.gen();
+                // The padding nibble of the last byte is always zero.
+                if i % code_length == code_length - 1 {
+                    b & 0x0F
+                } else {
+                    b
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let distances = compute_distances_4bit(&distance_table, NUM_SUB_VECTORS, &codes);
+        assert_eq!(distances.len(), TOTAL);
+
+        let table = QuantizedDistanceTable::new(&distance_table, NUM_SUB_VECTORS);
+        let tolerance = NUM_SUB_VECTORS as f32 * table.scale / 2.0 + 1e-4;
+        for (code, dist) in codes.chunks_exact(code_length).zip(distances) {
+            // Same result as the scalar scan over the quantized table.
+            assert_eq!(dist, table.distance(table.scan_one(code)));
+
+            let expected = (0..NUM_SUB_VECTORS)
+                .map(|i| {
+                    let c = (code[i / 2] >> ((i % 2) * 4)) & 0x0F;
+                    distance_table[i * 16 + c as usize]
+                })
+                .sum::<f32>();
+            assert!(
+                (dist - expected).abs() <= tolerance,
+                "dist={}, expected={}, tolerance={}",
+                dist,
+                expected,
+                tolerance
+            );
+        }
+    }
+}
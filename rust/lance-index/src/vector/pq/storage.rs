@@ -27,7 +27,10 @@ use object_store::path::Path;
 use serde::{Deserialize, Serialize};
 use snafu::{location, Location};
 
-use super::{distance::build_distance_table_l2, num_centroids, ProductQuantizerImpl};
+use super::{
+    code_length, distance::build_distance_table_l2, num_centroids, utils::get_sub_vector_code,
+    ProductQuantizerImpl,
+};
 use crate::{
     pb,
     vector::{
@@ -426,6 +429,7 @@ impl VectorStorage for ProductQuantizationStorage {
 struct PQDistCalculator {
     distance_table: Vec<f32>,
     pq_code: Arc<UInt8Array>,
+    num_bits: u32,
     num_sub_vectors: usize,
     num_centroids: usize,
 }
@@ -446,6 +450,7 @@ impl PQDistCalculator {
         };
         Self {
             distance_table,
+            num_bits,
             num_sub_vectors,
             pq_code,
             num_centroids: num_centroids(num_bits),
@@ -453,8 +458,9 @@ impl PQDistCalculator {
     }
 
     fn get_pq_code(&self, id: u32) -> &[u8] {
-        let start = id as usize * self.num_sub_vectors;
-        let end = start + self.num_sub_vectors;
+        let code_length = code_length(self.num_bits, self.num_sub_vectors);
+        let start = id as usize * code_length;
+        let end = start + code_length;
         &self.pq_code.values()[start..end]
     }
 }
@@ -462,10 +468,11 @@ impl PQDistCalculator {
 impl DistCalculator for PQDistCalculator {
     fn distance(&self, id: u32) -> f32 {
         let pq_code = self.get_pq_code(id);
-        pq_code
-            .iter()
-            .enumerate()
-            .map(|(i, &c)| self.distance_table[i * self.num_centroids + c as usize])
+        (0..self.num_sub_vectors)
+            .map(|i| {
+                let c = get_sub_vector_code(pq_code, self.num_bits, i);
+                self.distance_table[i * self.num_centroids + c as usize]
+            })
             .sum()
     }
 }
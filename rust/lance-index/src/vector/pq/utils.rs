@@ -42,6 +42,24 @@ pub fn num_centroids(num_bits: impl Into<u32>) -> usize {
     2_usize.pow(num_bits.into())
 }
 
+/// Number of bytes of the PQ code of one vector.
+///
+/// With 4-bit PQ, two sub-vector codes are packed into one byte.
+pub fn code_length(num_bits: impl Into<u32>, num_sub_vectors: usize) -> usize {
+    (num_sub_vectors * num_bits.into() as usize).div_ceil(8)
+}
+
+/// Get the centroid id of the `sub_vector_idx`-th sub-vector from the PQ code
+/// of one vector.
+#[inline]
+pub fn get_sub_vector_code(code: &[u8], num_bits: u32, sub_vector_idx: usize) -> u8 {
+    if num_bits == 4 {
+        (code[sub_vector_idx / 2] >> ((sub_vector_idx % 2) * 4)) & 0x0F
+    } else {
+        code[sub_vector_idx]
+    }
+}
+
 pub fn get_sub_vector_centroids<T: FloatToArrayType>(
     codebook: &[T],
     dimension: usize,
@@ -66,7 +66,7 @@ pub enum Quantizer {
 impl Quantizer {
     pub fn code_dim(&self) -> usize {
         match self {
-            Self::Product(pq) => pq.num_sub_vectors(),
+            Self::Product(pq) => pq.code_length(),
             Self::Scalar(sq) => sq.dim,
         }
     }
@@ -157,7 +157,7 @@ impl Quantization for dyn ProductQuantizer {
     type Storage = ProductQuantizationStorage;
 
     fn code_dim(&self) -> usize {
-        self.num_sub_vectors()
+        self.code_length()
     }
 
     fn column(&self) -> &'static str {
@@ -194,7 +194,7 @@ impl<T: ArrowFloatType + Dot + L2 + 'static> Quantization for ProductQuantizerIm
     type Storage = ProductQuantizationStorage;
 
     fn code_dim(&self) -> usize {
-        self.num_sub_vectors()
+        self.code_length()
     }
 
     fn column(&self) -> &'static str {
@@ -1,13 +1,16 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
-use arrow_array::types::UInt32Type;
-use arrow_array::{cast::AsArray, Array, FixedSizeListArray, RecordBatch};
-use arrow_schema::Field;
+use arrow_array::types::{Float16Type, Int8Type, UInt32Type, UInt8Type};
+use arrow_array::{cast::AsArray, Array, FixedSizeListArray, Float32Array, RecordBatch};
+use arrow_schema::{DataType, Field};
 use async_trait::async_trait;
-use lance_arrow::{ArrowFloatType, FixedSizeListArrayExt, FloatArray, RecordBatchExt};
+use lance_arrow::{
+    bfloat16::BFloat16Array, ArrowFloatType, FixedSizeListArrayExt, FloatArray, RecordBatchExt,
+};
 use lance_core::{Error, Result};
 use lance_linalg::MatrixView;
+use num_traits::AsPrimitive;
 use snafu::{location, Location};
 use std::sync::Arc;
 
@@ -15,6 +18,78 @@ use super::transform::Transformer;
 
 pub const RESIDUAL_COLUMN: &str = "__residual_vector";
 
+fn residual_to_f32<T: ArrowFloatType, X: AsPrimitive<f32>>(
+    centroids: &MatrixView<T>,
+    vectors: &[X],
+    dim: usize,
+    part_ids: &[u32],
+) -> Vec<f32> {
+    vectors
+        .chunks_exact(dim)
+        .zip(part_ids.iter())
+        .flat_map(|(vector, &part_id)| {
+            let centroid = centroids.row(part_id as usize).unwrap();
+            vector
+                .iter()
+                .zip(centroid.iter())
+                .map(|(v, &c)| v.as_() - AsPrimitive::<f32>::as_(c))
+        })
+        .collect()
+}
+
+/// Compute the residual vectors of vectors stored in a different element type than
+/// the centroids, i.e., `int8`, `uint8` or `bfloat16` vectors.
+///
+/// The residuals are always `f32`. The stored vectors are read in place.
+pub(crate) fn compute_residual_mixed<T: ArrowFloatType>(
+    centroids: &MatrixView<T>,
+    vectors: &FixedSizeListArray,
+    part_ids: &[u32],
+) -> Result<FixedSizeListArray> {
+    let dim = vectors.value_length() as usize;
+    let values = vectors.values();
+    let residual = match values.data_type() {
+        DataType::Int8 => residual_to_f32(
+            centroids,
+            values.as_primitive::<Int8Type>().values(),
+            dim,
+            part_ids,
+        ),
+        DataType::UInt8 => residual_to_f32(
+            centroids,
+            values.as_primitive::<UInt8Type>().values(),
+            dim,
+            part_ids,
+        ),
+        DataType::Float16 => residual_to_f32(
+            centroids,
+            values.as_primitive::<Float16Type>().values(),
+            dim,
+            part_ids,
+        ),
+        DataType::FixedSizeBinary(2) => residual_to_f32(
+            centroids,
+            BFloat16Array::try_from(values.as_fixed_size_binary().clone())?.as_slice(),
+            dim,
+            part_ids,
+        ),
+        _ => {
+            return Err(Error::Index {
+                message: format!(
+                    "Compute residual vector: unsupported vector type {} for {} centroids",
+                    vectors.value_type(),
+                    T::FLOAT_TYPE
+                ),
+                location: location!(),
+            })
+        }
+    };
+    Ok(FixedSizeListArray::try_new_from_values(
+        Float32Array::from(residual),
+        dim as i32,
+    )?)
+}
+
 /// Compute the residual vector of a Vector Matrix to their centroids.
 ///
 /// The residual vector is the difference between the original vector and the centroid.
@@ -75,39 +150,36 @@ impl<T: ArrowFloatType> Transformer for ResidualTransform<T> {
             location: location!(),
         })?;
 
+        let part_ids = part_ids.as_primitive::<UInt32Type>().values();
         // BFloat16Array is not supported via `as_primitive()` cast yet, so we have to do
-        // `downcast_ref()` for now.
-        let flatten_data = original_vectors
+        // `downcast_ref()` for now. Vectors stored in another type than the centroids
+        // take the mixed path.
+        let residual_arr = match original_vectors
             .values()
             .as_any()
             .downcast_ref::<T::ArrayType>()
-            .ok_or(Error::Index {
-                message: format!(
-                    "Compute residual vector: original vector column {} is not expected type: expect: {}, got {}",
-                    self.vec_col,
-                    T::FLOAT_TYPE,
-                    original_vectors.value_type(),
-                ),
-                location: location!(),
-            })?;
-        let dim = original_vectors.value_length();
-        let mut residual_arr: Vec<T::Native> = Vec::with_capacity(flatten_data.len());
-        flatten_data
-            .as_slice()
-            .chunks_exact(dim as usize)
-            .zip(part_ids.as_primitive::<UInt32Type>().values().iter())
-            .for_each(|(vector, &part_id)| {
-                let centroid = self.centroids.row(part_id as usize).unwrap();
-                // TODO: SIMD
-                residual_arr.extend(
-                    vector
-                        .iter()
-                        .zip(centroid.iter())
-                        .map(|(v, cent)| *v - *cent),
-                );
-            });
-        let residual_arr =
-            FixedSizeListArray::try_new_from_values(T::ArrayType::from(residual_arr), dim)?;
+        {
+            Some(flatten_data) => {
+                let dim = original_vectors.value_length();
+                let mut residual_arr: Vec<T::Native> = Vec::with_capacity(flatten_data.len());
+                flatten_data
+                    .as_slice()
+                    .chunks_exact(dim as usize)
+                    .zip(part_ids.iter())
+                    .for_each(|(vector, &part_id)| {
+                        let centroid = self.centroids.row(part_id as usize).unwrap();
+                        // TODO: SIMD
+                        residual_arr.extend(
+                            vector
+                                .iter()
+                                .zip(centroid.iter())
+                                .map(|(v, cent)| *v - *cent),
+                        );
+                    });
+                FixedSizeListArray::try_new_from_values(T::ArrayType::from(residual_arr), dim)?
+            }
+            None => compute_residual_mixed(&self.centroids, original_vectors, part_ids)?,
+        };
 
         // Replace original column with residual column.
         let batch = batch.drop_column(&self.vec_col)?;
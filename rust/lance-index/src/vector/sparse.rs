@@ -0,0 +1,528 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Inverted index for sparse vectors.
+//!
+//! For each dimension, the index keeps a posting list of `(row_id, value)`
+//! sorted by row id. Queries are scored with the dot product, using
+//! Block-Max WAND to skip rows that can not make it into the top-k.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::Arc;
+
+use arrow_array::{
+    cast::AsArray,
+    types::{Float32Type, UInt32Type, UInt64Type},
+    Array, ArrayRef, Float32Array, ListArray, RecordBatch, UInt32Array, UInt64Array,
+};
+use arrow_schema::{DataType, Field, Schema};
+use lance_arrow::sparse::SparseVectorArray;
+use lance_core::{Error, Result};
+use snafu::{location, Location};
+
+use super::graph::OrderedFloat;
+
+/// Index type name of the sparse inverted index, stored in the index metadata.
+pub const SPARSE_INVERTED_INDEX_TYPE: &str = "SPARSE_INVERTED";
+/// Schema metadata key holding the block size used for block-max pruning.
+pub const SPARSE_BLOCK_SIZE_KEY: &str = "lance:sparse:block_size";
+
+const DIM_COLUMN: &str = "dim";
+const ROW_IDS_COLUMN: &str = "row_ids";
+const VALUES_COLUMN: &str = "values";
+
+/// Parameters to build a sparse inverted index.
+#[derive(Debug, Clone)]
+pub struct SparseIndexParams {
+    /// Number of postings summarized by one block-max entry.
+    ///
+    /// Smaller blocks give tighter upper bounds at the cost of more metadata.
+    pub block_size: usize,
+}
+
+impl Default for SparseIndexParams {
+    fn default() -> Self {
+        Self { block_size: 128 }
+    }
+}
+
+/// Posting list of one dimension, sorted by row id.
+#[derive(Debug, Clone, Default)]
+struct PostingList {
+    row_ids: Vec<u64>,
+    values: Vec<f32>,
+    /// Max absolute value of each block of `block_size` postings.
+    block_max: Vec<f32>,
+    /// Max absolute value of the whole list.
+    max_abs: f32,
+}
+
+impl PostingList {
+    fn new(mut entries: Vec<(u64, f32)>, block_size: usize) -> Self {
+        entries.sort_unstable_by_key(|(row_id, _)| *row_id);
+        let row_ids = entries.iter().map(|(r, _)| *r).collect::<Vec<_>>();
+        let values = entries.iter().map(|(_, v)| *v).collect::<Vec<_>>();
+        let block_max = values
+            .chunks(block_size)
+            .map(|block| block.iter().fold(0.0_f32, |acc, v| acc.max(v.abs())))
+            .collect::<Vec<_>>();
+        let max_abs = block_max.iter().copied().fold(0.0_f32, f32::max);
+        Self {
+            row_ids,
+            values,
+            block_max,
+            max_abs,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.row_ids.len()
+    }
+}
+
+/// Iterates over a posting list during a query.
+struct Cursor<'a> {
+    posting: &'a PostingList,
+    block_size: usize,
+    /// The query weight of this dimension.
+    weight: f32,
+    /// Upper bound of `weight * value` over the whole list.
+    upper_bound: f32,
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(posting: &'a PostingList, block_size: usize, weight: f32) -> Self {
+        Self {
+            posting,
+            block_size,
+            weight,
+            upper_bound: weight.abs() * posting.max_abs,
+            pos: 0,
+        }
+    }
+
+    fn row_id(&self) -> Option<u64> {
+        self.posting.row_ids.get(self.pos).copied()
+    }
+
+    fn score(&self) -> f32 {
+        self.weight * self.posting.values[self.pos]
+    }
+
+    /// Upper bound of the score within the current block.
+    fn block_upper_bound(&self) -> f32 {
+        self.weight.abs() * self.posting.block_max[self.pos / self.block_size]
+    }
+
+    /// Move to the first posting whose row id is `>= target`.
+    fn seek(&mut self, target: u64) {
+        let row_ids = &self.posting.row_ids;
+        // Skip whole blocks first, by checking the last row id of each block.
+        let mut block_end = (self.pos / self.block_size + 1) * self.block_size;
+        while block_end < row_ids.len() && row_ids[block_end - 1] < target {
+            self.pos = block_end;
+            block_end += self.block_size;
+        }
+        self.pos += row_ids[self.pos..].partition_point(|&r| r < target);
+    }
+}
+
+/// In-memory sparse inverted index.
+#[derive(Debug, Clone)]
+pub struct SparseInvertedIndex {
+    block_size: usize,
+    postings: HashMap<u32, PostingList>,
+}
+
+impl SparseInvertedIndex {
+    /// Build the index from `(row_id, indices, values)` triples.
+    pub fn builder(params: &SparseIndexParams) -> SparseIndexBuilder {
+        SparseIndexBuilder {
+            block_size: params.block_size.max(1),
+            postings: HashMap::new(),
+        }
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Number of dimensions that have at least one posting.
+    pub fn num_dimensions(&self) -> usize {
+        self.postings.len()
+    }
+
+    /// Total number of postings across all dimensions.
+    pub fn num_postings(&self) -> usize {
+        self.postings.values().map(|p| p.len()).sum()
+    }
+
+    /// All row ids in the index, possibly with duplicates.
+    pub fn row_ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.postings
+            .values()
+            .flat_map(|p| p.row_ids.iter().copied())
+    }
+
+    /// Search for the `k` rows with the largest dot product with the query.
+    ///
+    /// Only rows that share at least one dimension with the query are
+    /// returned. `filter` is called on candidate row ids and should return
+    /// false for rows to exclude.
+    ///
+    /// Returns `(row_id, dot_product)` pairs, best first.
+    pub fn search(
+        &self,
+        query_indices: &[u32],
+        query_values: &[f32],
+        k: usize,
+        filter: impl Fn(u64) -> bool,
+    ) -> Vec<(u64, f32)> {
+        if k == 0 {
+            return vec![];
+        }
+        let mut cursors = query_indices
+            .iter()
+            .zip(query_values.iter())
+            .filter(|(_, &w)| w != 0.0)
+            .filter_map(|(dim, &w)| {
+                self.postings
+                    .get(dim)
+                    .map(|posting| Cursor::new(posting, self.block_size, w))
+            })
+            .collect::<Vec<_>>();
+
+        // Min-heap of the current top-k, keyed by score.
+        let mut heap: BinaryHeap<Reverse<(OrderedFloat, u64)>> = BinaryHeap::with_capacity(k);
+        loop {
+            cursors.retain(|c| c.row_id().is_some());
+            if cursors.is_empty() {
+                break;
+            }
+            cursors.sort_unstable_by_key(|c| c.row_id());
+
+            let threshold = if heap.len() < k {
+                f32::NEG_INFINITY
+            } else {
+                heap.peek().unwrap().0 .0 .0
+            };
+
+            // Find the pivot: the first cursor where the accumulated upper bound
+            // can beat the current threshold.
+            let mut acc = 0.0;
+            let Some(pivot) = cursors.iter().position(|c| {
+                acc += c.upper_bound;
+                acc > threshold
+            }) else {
+                break;
+            };
+            let pivot_row = cursors[pivot].row_id().unwrap();
+
+            if cursors[0].row_id() == Some(pivot_row) {
+                let num_matched = cursors
+                    .iter()
+                    .take_while(|c| c.row_id() == Some(pivot_row))
+                    .count();
+                let block_bound: f32 = cursors[..num_matched]
+                    .iter()
+                    .map(|c| c.block_upper_bound())
+                    .sum();
+                if (heap.len() < k || block_bound > threshold) && filter(pivot_row) {
+                    let score: f32 = cursors[..num_matched].iter().map(|c| c.score()).sum();
+                    if heap.len() < k {
+                        heap.push(Reverse((OrderedFloat(score), pivot_row)));
+                    } else if score > threshold {
+                        heap.pop();
+                        heap.push(Reverse((OrderedFloat(score), pivot_row)));
+                    }
+                }
+                for c in cursors[..num_matched].iter_mut() {
+                    c.pos += 1;
+                }
+            } else {
+                // None of the rows before the pivot can make it into the top-k.
+                for c in cursors[..pivot].iter_mut() {
+                    c.seek(pivot_row);
+                }
+            }
+        }
+
+        let mut results = heap
+            .into_iter()
+            .map(|Reverse((score, row_id))| (row_id, score.0))
+            .collect::<Vec<_>>();
+        results.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        results
+    }
+
+    /// Brute-force search, used to verify the pruning.
+    #[cfg(test)]
+    fn search_exhaustive(
+        &self,
+        query_indices: &[u32],
+        query_values: &[f32],
+        k: usize,
+    ) -> Vec<(u64, f32)> {
+        let mut scores: HashMap<u64, f32> = HashMap::new();
+        for (dim, w) in query_indices.iter().zip(query_values.iter()) {
+            if let Some(posting) = self.postings.get(dim) {
+                for (row_id, v) in posting.row_ids.iter().zip(posting.values.iter()) {
+                    *scores.entry(*row_id).or_default() += w * v;
+                }
+            }
+        }
+        let mut results = scores.into_iter().collect::<Vec<_>>();
+        results.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        results.truncate(k);
+        results
+    }
+
+    /// Remap the row ids in the index.
+    ///
+    /// Row ids mapped to `None` are removed. Row ids not in the mapping are kept.
+    pub fn remap(&self, mapping: &HashMap<u64, Option<u64>>) -> Self {
+        let mut builder = SparseIndexBuilder {
+            block_size: self.block_size,
+            postings: HashMap::with_capacity(self.postings.len()),
+        };
+        for (dim, posting) in self.postings.iter() {
+            let entries = posting
+                .row_ids
+                .iter()
+                .zip(posting.values.iter())
+                .filter_map(|(row_id, v)| match mapping.get(row_id) {
+                    Some(Some(new_id)) => Some((*new_id, *v)),
+                    Some(None) => None,
+                    None => Some((*row_id, *v)),
+                });
+            builder.postings.entry(*dim).or_default().extend(entries);
+        }
+        builder.build()
+    }
+
+    /// Schema of the serialized index.
+    pub fn schema(&self) -> Schema {
+        Schema::new(vec![
+            Field::new(DIM_COLUMN, DataType::UInt32, false),
+            Field::new(
+                ROW_IDS_COLUMN,
+                DataType::List(Arc::new(Field::new("item", DataType::UInt64, true))),
+                false,
+            ),
+            Field::new(
+                VALUES_COLUMN,
+                DataType::List(Arc::new(Field::new("item", DataType::Float32, true))),
+                false,
+            ),
+        ])
+        .with_metadata(HashMap::from([(
+            SPARSE_BLOCK_SIZE_KEY.to_string(),
+            self.block_size.to_string(),
+        )]))
+    }
+
+    /// Serialize the index into one row per dimension.
+    pub fn to_batch(&self) -> Result<RecordBatch> {
+        let mut dims = self.postings.keys().copied().collect::<Vec<_>>();
+        dims.sort_unstable();
+        let mut offsets = vec![0_i32];
+        let mut row_ids = Vec::with_capacity(self.num_postings());
+        let mut values = Vec::with_capacity(self.num_postings());
+        for dim in dims.iter() {
+            let posting = &self.postings[dim];
+            row_ids.extend_from_slice(&posting.row_ids);
+            values.extend_from_slice(&posting.values);
+            offsets.push(row_ids.len() as i32);
+        }
+        let offsets = arrow::buffer::OffsetBuffer::new(offsets.into());
+        let row_ids = ListArray::try_new(
+            Arc::new(Field::new("item", DataType::UInt64, true)),
+            offsets.clone(),
+            Arc::new(UInt64Array::from(row_ids)),
+            None,
+        )?;
+        let values = ListArray::try_new(
+            Arc::new(Field::new("item", DataType::Float32, true)),
+            offsets,
+            Arc::new(Float32Array::from(values)),
+            None,
+        )?;
+        Ok(RecordBatch::try_new(
+            Arc::new(self.schema()),
+            vec![
+                Arc::new(UInt32Array::from(dims)) as ArrayRef,
+                Arc::new(row_ids),
+                Arc::new(values),
+            ],
+        )?)
+    }
+
+    /// Load the index from batches written by [Self::to_batch].
+    pub fn try_from_batches(batches: &[RecordBatch], block_size: usize) -> Result<Self> {
+        let mut builder = SparseIndexBuilder {
+            block_size: block_size.max(1),
+            postings: HashMap::new(),
+        };
+        for batch in batches {
+            let column = |name: &str| {
+                batch.column_by_name(name).ok_or_else(|| Error::Index {
+                    message: format!("Sparse index: missing column {}", name),
+                    location: location!(),
+                })
+            };
+            let dims = column(DIM_COLUMN)?.as_primitive::<UInt32Type>();
+            let row_ids = column(ROW_IDS_COLUMN)?.as_list::<i32>();
+            let values = column(VALUES_COLUMN)?.as_list::<i32>();
+            for (i, dim) in dims.values().iter().enumerate() {
+                let row_ids = row_ids.value(i);
+                let values = values.value(i);
+                builder.postings.entry(*dim).or_default().extend(
+                    row_ids
+                        .as_primitive::<UInt64Type>()
+                        .values()
+                        .iter()
+                        .copied()
+                        .zip(
+                            values
+                                .as_primitive::<Float32Type>()
+                                .values()
+                                .iter()
+                                .copied(),
+                        ),
+                );
+            }
+        }
+        Ok(builder.build())
+    }
+}
+
+/// Accumulates postings before building a [SparseInvertedIndex].
+#[derive(Debug)]
+pub struct SparseIndexBuilder {
+    block_size: usize,
+    postings: HashMap<u32, Vec<(u64, f32)>>,
+}
+
+impl SparseIndexBuilder {
+    /// Add one sparse vector.
+    pub fn add(&mut self, row_id: u64, indices: &[u32], values: &[f32]) {
+        for (dim, v) in indices.iter().zip(values.iter()) {
+            if *v != 0.0 {
+                self.postings.entry(*dim).or_default().push((row_id, *v));
+            }
+        }
+    }
+
+    /// Add a batch of sparse vectors. Null vectors are skipped.
+    pub fn add_batch(&mut self, row_ids: &UInt64Array, vectors: &dyn Array) -> Result<()> {
+        let vectors = SparseVectorArray::try_from(vectors)?;
+        if vectors.len() != row_ids.len() {
+            return Err(Error::Index {
+                message: format!(
+                    "Sparse index: {} row ids for {} vectors",
+                    row_ids.len(),
+                    vectors.len()
+                ),
+                location: location!(),
+            });
+        }
+        for (row_id, vector) in row_ids.values().iter().zip(vectors.iter()) {
+            if let Some((indices, values)) = vector {
+                self.add(*row_id, indices, values);
+            }
+        }
+        Ok(())
+    }
+
+    /// Add all postings of an existing index.
+    pub fn add_index(&mut self, index: &SparseInvertedIndex) {
+        for (dim, posting) in index.postings.iter() {
+            self.postings.entry(*dim).or_default().extend(
+                posting
+                    .row_ids
+                    .iter()
+                    .copied()
+                    .zip(posting.values.iter().copied()),
+            );
+        }
+    }
+
+    pub fn build(self) -> SparseInvertedIndex {
+        let block_size = self.block_size;
+        SparseInvertedIndex {
+            block_size,
+            postings: self
+                .postings
+                .into_iter()
+                .map(|(dim, entries)| (dim, PostingList::new(entries, block_size)))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn random_index(
+        num_rows: u64,
+        num_dims: u32,
+        nnz: usize,
+        block_size: usize,
+    ) -> SparseInvertedIndex {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut builder = SparseInvertedIndex::builder(&SparseIndexParams { block_size });
+        for row_id in 0..num_rows {
+            let mut indices = (0..nnz)
+                .map(|_| rng.gen_range(0..num_dims))
+                .collect::<Vec<_>>();
+            indices.sort_unstable();
+            indices.dedup();
+            let values = indices.iter().map(|_| rng.gen::<f32>()).collect::<Vec<_>>();
+            builder.add(row_id, &indices, &values);
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn test_search_matches_exhaustive() {
+        let index = random_index(2000, 64, 8, 16);
+        let query_indices = [1, 5, 9, 17, 33, 60];
+        let query_values = [0.3, 1.0, 0.2, 0.8, 0.5, 0.1];
+        for k in [1, 10, 100] {
+            let results = index.search(&query_indices, &query_values, k, |_| true);
+            let expected = index.search_exhaustive(&query_indices, &query_values, k);
+            assert_eq!(results.len(), k);
+            for (r, e) in results.iter().zip(expected.iter()) {
+                assert!((r.1 - e.1).abs() < 1e-6, "{:?} vs {:?}", r, e);
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_with_filter() {
+        let index = random_index(500, 16, 4, 8);
+        let results = index.search(&[0, 1, 2, 3], &[1.0, 1.0, 1.0, 1.0], 10, |row_id| {
+            row_id % 2 == 0
+        });
+        assert!(!results.is_empty());
+        assert!(results.iter().all(|(row_id, _)| row_id % 2 == 0));
+    }
+
+    #[test]
+    fn test_serialize_and_remap() {
+        let index = random_index(100, 16, 4, 8);
+        let batch = index.to_batch().unwrap();
+        let loaded = SparseInvertedIndex::try_from_batches(&[batch], index.block_size()).unwrap();
+        assert_eq!(loaded.num_postings(), index.num_postings());
+        assert_eq!(loaded.num_dimensions(), index.num_dimensions());
+
+        let mapping = HashMap::from_iter((0..50).map(|r| (r, None)));
+        let remapped = loaded.remap(&mapping);
+        assert!(remapped.row_ids().all(|r| r >= 50));
+    }
+}
@@ -11,7 +11,7 @@ use arrow_array::types::{Float16Type, Float32Type, Float64Type};
 use arrow_array::{cast::AsArray, Array, ArrowPrimitiveType, RecordBatch, UInt32Array};
 use arrow_schema::{DataType, Field};
 use async_trait::async_trait;
-use lance_arrow::RecordBatchExt;
+use lance_arrow::{bfloat16::BFloat16Array, FloatArray, RecordBatchExt};
 use num_traits::Float;
 use snafu::{location, Location};
 
@@ -87,7 +87,7 @@ impl Transformer for NormalizeTransformer {
 
 /// Only keep the vectors that is finite number, filter out NaN and Inf.
 #[derive(Debug)]
-pub(crate) struct KeepFiniteVectors {
+pub struct KeepFiniteVectors {
     column: String,
 }
 
@@ -137,6 +137,12 @@ impl Transformer for KeepFiniteVectors {
                         DataType::Float16 => is_all_finite::<Float16Type>(&data),
                         DataType::Float32 => is_all_finite::<Float32Type>(&data),
                         DataType::Float64 => is_all_finite::<Float64Type>(&data),
+                        DataType::FixedSizeBinary(2) => {
+                            BFloat16Array::try_from(data.as_fixed_size_binary().clone())
+                                .map(|arr| arr.as_slice().iter().all(|v| v.is_finite()))
+                                .unwrap_or(false)
+                        }
+                        DataType::Int8 | DataType::UInt8 => true,
                         _ => false,
                     };
                     if is_valid {
@@ -1,12 +1,18 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
-use arrow_array::{Array, FixedSizeListArray};
+use arrow_array::types::{Float16Type, Float32Type, Float64Type, Int8Type, UInt8Type};
+use arrow_array::{
+    cast::AsArray, Array, ArrayRef, FixedSizeListArray, Float32Array, Int8Array, UInt8Array,
+};
 use arrow_schema::{DataType, Field};
-use lance_arrow::{ArrowFloatType, FloatType};
+use lance_arrow::bfloat16::{is_bfloat16_field, BFloat16Array};
+use lance_arrow::floats::coerce_float_vector;
+use lance_arrow::{ArrowFloatType, FloatArray, FloatType};
 use lance_core::{Error, Result};
 use lance_io::encodings::plain::bytes_to_array;
 use lance_linalg::MatrixView;
+use num_traits::AsPrimitive;
 use prost::bytes;
 use rand::distributions::Standard;
 use rand::prelude::*;
@@ -138,11 +144,59 @@ impl TryFrom<&pb::Tensor> for FixedSizeListArray {
     }
 }
 
+/// Returns true if vectors with this element field can be searched in their stored
+/// type: floats, bfloat16, int8 and uint8.
+pub fn is_vector_element(field: &Field) -> bool {
+    field.data_type().is_floating()
+        || is_bfloat16_field(field)
+        || matches!(field.data_type(), DataType::Int8 | DataType::UInt8)
+}
+
+/// Coerce a `f32` query vector to the element type of a vector column.
+///
+/// Integer elements are rounded and saturated to the range of the type.
+pub fn coerce_query_vector(query: &Float32Array, element: &Field) -> Result<ArrayRef> {
+    match element.data_type() {
+        DataType::Int8 => Ok(Arc::new(Int8Array::from_iter_values(
+            query.values().iter().map(|v| v.round() as i8),
+        ))),
+        DataType::UInt8 => Ok(Arc::new(UInt8Array::from_iter_values(
+            query.values().iter().map(|v| v.round() as u8),
+        ))),
+        _ => Ok(coerce_float_vector(query, FloatType::try_from(element)?)?.into()),
+    }
+}
+
+/// Widen `int8`, `uint8`, `float16` or `bfloat16` values to `f32`.
+///
+/// This is meant for query vectors and training samples, which meet `f32` centroids
+/// and codebooks. Stored vectors are searched in their own type.
+pub fn widen_to_float32(values: &dyn Array) -> Result<Float32Array> {
+    fn widen<X: AsPrimitive<f32>>(values: &[X]) -> Float32Array {
+        Float32Array::from_iter_values(values.iter().map(|v| v.as_()))
+    }
+
+    match values.data_type() {
+        DataType::Int8 => Ok(widen(values.as_primitive::<Int8Type>().values())),
+        DataType::UInt8 => Ok(widen(values.as_primitive::<UInt8Type>().values())),
+        DataType::Float16 => Ok(widen(values.as_primitive::<Float16Type>().values())),
+        DataType::Float32 => Ok(values.as_primitive::<Float32Type>().clone()),
+        DataType::Float64 => Ok(widen(values.as_primitive::<Float64Type>().values())),
+        DataType::FixedSizeBinary(2) => Ok(widen(
+            BFloat16Array::try_from(values.as_fixed_size_binary().clone())?.as_slice(),
+        )),
+        _ => Err(Error::Index {
+            message: format!("Cannot widen {} values to float32", values.data_type()),
+            location: location!(),
+        }),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use arrow_array::{types::*, Float16Array, Float32Array, Float64Array};
+    use arrow_array::{Float16Array, Float64Array};
     use half::f16;
     use lance_arrow::bfloat16::BFloat16Type;
     use lance_arrow::FixedSizeListArrayExt;
@@ -207,4 +261,28 @@ mod tests {
         assert_eq!(tensor.shape, vec![4, 5]);
         assert_eq!(tensor.data.len(), 20 * 8);
     }
+
+    #[test]
+    fn test_coerce_query_vector() {
+        let query = Float32Array::from(vec![-200.0, -1.4, 0.6, 300.0]);
+        let int8 = coerce_query_vector(&query, &Field::new("item", DataType::Int8, true)).unwrap();
+        assert_eq!(
+            int8.as_primitive::<Int8Type>().values(),
+            &[-128, -1, 1, 127]
+        );
+        let uint8 =
+            coerce_query_vector(&query, &Field::new("item", DataType::UInt8, true)).unwrap();
+        assert_eq!(uint8.as_primitive::<UInt8Type>().values(), &[0, 0, 1, 255]);
+
+        assert!(is_vector_element(&Field::new("item", DataType::Int8, true)));
+        assert!(!is_vector_element(&Field::new(
+            "item",
+            DataType::Int32,
+            true
+        )));
+        assert_eq!(
+            widen_to_float32(&int8).unwrap().values(),
+            &[-128.0, -1.0, 1.0, 127.0]
+        );
+    }
 }
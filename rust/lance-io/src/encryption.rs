@@ -0,0 +1,678 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Client-side envelope encryption of objects.
+//!
+//! Every encrypted object gets its own random AES-256 data key. The data key is
+//! wrapped by a [`KeyProvider`] and stored, together with the id of the key that
+//! wrapped it, in a header at the start of the object:
+//!
+//! ```text
+//! | magic (8) | version: u16 | header length: u32 | block size: u32 |
+//! | key id length: u16 | key id | wrapped key length: u16 | wrapped key |
+//! ```
+//!
+//! The header is followed by a sequence of independently sealed AES-256-GCM
+//! blocks. Each block holds `block size` bytes of plaintext (only the last one
+//! may be shorter) followed by a 16 byte authentication tag. Since blocks are
+//! sealed independently, any byte range can be read by fetching and decrypting
+//! only the blocks that overlap it. The nonce of a block is its index plus a flag
+//! marking the final block, so a truncated object fails to authenticate.
+
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use async_trait::async_trait;
+use bytes::Bytes;
+use object_store::path::Path;
+use snafu::{location, Location};
+use tokio::io::AsyncWrite;
+
+use lance_core::{Error, Result};
+
+use crate::traits::Reader;
+
+/// Magic bytes at the start of every encrypted object.
+pub const ENCRYPTION_MAGIC: &[u8; 8] = b"LANCEENC";
+/// Default number of plaintext bytes sealed together in one block.
+pub const DEFAULT_ENCRYPTION_BLOCK_SIZE: usize = 64 * 1024;
+
+const FORMAT_VERSION: u16 = 1;
+/// Magic, version and header length.
+const PREFIX_LEN: usize = ENCRYPTION_MAGIC.len() + 2 + 4;
+/// How much to read when sniffing an object, enough for any reasonable header.
+const SNIFF_LEN: usize = 1024;
+const TAG_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+
+/// Wraps and unwraps the per-object data keys.
+///
+/// Implementations typically delegate to a key management service, so that
+/// the master keys never leave it.
+#[async_trait]
+pub trait KeyProvider: std::fmt::Debug + Send + Sync {
+    /// Id of the master key used to wrap new data keys.
+    ///
+    /// The id is stored with every object (and in the table metadata) so
+    /// that objects stay readable after the current key is rotated.
+    fn key_id(&self) -> &str;
+
+    /// Wrap a data key with the master key returned by [`Self::key_id`].
+    async fn wrap_key(&self, data_key: &[u8]) -> Result<Vec<u8>>;
+
+    /// Unwrap a data key that was wrapped with the master key `key_id`.
+    async fn unwrap_key(&self, key_id: &str, wrapped_key: &[u8]) -> Result<Vec<u8>>;
+}
+
+fn block_nonce(index: u64, is_final: bool) -> [u8; NONCE_LEN] {
+    let mut nonce = [0; NONCE_LEN];
+    nonce[..8].copy_from_slice(&index.to_le_bytes());
+    nonce[8] = is_final as u8;
+    nonce
+}
+
+fn new_cipher(key: &[u8]) -> Result<Aes256Gcm> {
+    Aes256Gcm::new_from_slice(key).map_err(|_| Error::IO {
+        message: format!(
+            "invalid data key length: expected {} bytes, got {}",
+            KEY_LEN,
+            key.len()
+        ),
+        location: location!(),
+    })
+}
+
+fn encode_header(block_size: usize, key_id: &str, wrapped_key: &[u8]) -> Result<Vec<u8>> {
+    let (Ok(key_id_len), Ok(wrapped_key_len)) = (
+        u16::try_from(key_id.len()),
+        u16::try_from(wrapped_key.len()),
+    ) else {
+        return Err(Error::invalid_input(
+            "key id and wrapped key must be shorter than 64KiB",
+            location!(),
+        ));
+    };
+    let header_len = PREFIX_LEN + 4 + 2 + key_id.len() + 2 + wrapped_key.len();
+    let mut header = Vec::with_capacity(header_len);
+    header.extend_from_slice(ENCRYPTION_MAGIC);
+    header.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    header.extend_from_slice(&(header_len as u32).to_le_bytes());
+    header.extend_from_slice(&(block_size as u32).to_le_bytes());
+    header.extend_from_slice(&key_id_len.to_le_bytes());
+    header.extend_from_slice(key_id.as_bytes());
+    header.extend_from_slice(&wrapped_key_len.to_le_bytes());
+    header.extend_from_slice(wrapped_key);
+    Ok(header)
+}
+
+/// Seals plaintext into encrypted blocks as it is written.
+///
+/// The sealed bytes, starting with the object header, are buffered until they
+/// are drained into the underlying writer with [`Self::poll_drain`].
+pub struct BlockEncryptor {
+    cipher: Aes256Gcm,
+    block_size: usize,
+    next_block: u64,
+    plaintext: Vec<u8>,
+    sealed: Vec<u8>,
+    sealed_offset: usize,
+    finished: bool,
+}
+
+impl BlockEncryptor {
+    pub async fn try_new(key_provider: &dyn KeyProvider) -> Result<Self> {
+        Self::try_new_with_block_size(key_provider, DEFAULT_ENCRYPTION_BLOCK_SIZE).await
+    }
+
+    pub async fn try_new_with_block_size(
+        key_provider: &dyn KeyProvider,
+        block_size: usize,
+    ) -> Result<Self> {
+        if block_size == 0 || block_size > u32::MAX as usize {
+            return Err(Error::invalid_input(
+                format!("invalid encryption block size: {}", block_size),
+                location!(),
+            ));
+        }
+        let data_key = Aes256Gcm::generate_key(OsRng);
+        let wrapped_key = key_provider.wrap_key(&data_key).await?;
+        let header = encode_header(block_size, key_provider.key_id(), &wrapped_key)?;
+        Ok(Self {
+            cipher: Aes256Gcm::new(&data_key),
+            block_size,
+            next_block: 0,
+            plaintext: Vec::with_capacity(block_size),
+            sealed: header,
+            sealed_offset: 0,
+            finished: false,
+        })
+    }
+
+    /// Buffer (part of) `buf`, returning the number of bytes accepted.
+    ///
+    /// A full block is only sealed once more data arrives, since we can't know
+    /// whether it is the final block before that.
+    pub fn buffer(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.finished {
+            return Err(std::io::Error::other(
+                "cannot write to a finished encrypted object",
+            ));
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.plaintext.len() == self.block_size {
+            self.seal(false)?;
+        }
+        let len = buf.len().min(self.block_size - self.plaintext.len());
+        self.plaintext.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    /// Seal the remaining plaintext as the final block.
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        if !self.finished {
+            self.seal(true)?;
+            self.finished = true;
+        }
+        Ok(())
+    }
+
+    fn seal(&mut self, is_final: bool) -> std::io::Result<()> {
+        let nonce = block_nonce(self.next_block, is_final);
+        let sealed = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), self.plaintext.as_slice())
+            .map_err(|_| std::io::Error::other("failed to encrypt block"))?;
+        self.sealed.extend_from_slice(&sealed);
+        self.plaintext.clear();
+        self.next_block += 1;
+        Ok(())
+    }
+
+    /// Write all sealed bytes to `writer`.
+    pub fn poll_drain<W: AsyncWrite + ?Sized>(
+        &mut self,
+        mut writer: Pin<&mut W>,
+        cx: &mut Context<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        while self.sealed_offset < self.sealed.len() {
+            let written = ready!(writer
+                .as_mut()
+                .poll_write(cx, &self.sealed[self.sealed_offset..]))?;
+            if written == 0 {
+                return Poll::Ready(Err(ErrorKind::WriteZero.into()));
+            }
+            self.sealed_offset += written;
+        }
+        self.sealed.clear();
+        self.sealed_offset = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Splits `len` bytes off the front of `buf`.
+fn take<'a>(buf: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if buf.len() < len {
+        return None;
+    }
+    let (head, tail) = buf.split_at(len);
+    *buf = tail;
+    Some(head)
+}
+
+fn take_u16(buf: &mut &[u8]) -> Option<u16> {
+    take(buf, 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn take_u32(buf: &mut &[u8]) -> Option<u32> {
+    take(buf, 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// [Reader] that decrypts an object written through a [BlockEncryptor].
+pub struct EncryptedReader {
+    inner: Box<dyn Reader>,
+    cipher: Aes256Gcm,
+    key_id: String,
+    header_len: usize,
+    block_size: usize,
+    num_blocks: usize,
+    object_size: usize,
+    size: usize,
+}
+
+impl std::fmt::Debug for EncryptedReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncryptedReader")
+            .field("inner", &self.inner)
+            .field("key_id", &self.key_id)
+            .field("block_size", &self.block_size)
+            .field("size", &self.size)
+            .finish()
+    }
+}
+
+impl EncryptedReader {
+    /// Open `inner` for decryption.
+    ///
+    /// `expected_key_id` is the key id recorded for the object in the table
+    /// metadata, if any.  Objects without an encryption header are returned
+    /// unchanged when no key id is recorded, so that files written before
+    /// encryption was enabled stay readable.  If a key id is recorded then a
+    /// missing header is an error, the object must have been replaced.
+    pub async fn open(
+        inner: Box<dyn Reader>,
+        key_provider: &dyn KeyProvider,
+        expected_key_id: Option<&str>,
+    ) -> Result<Box<dyn Reader>> {
+        let path = inner.path().clone();
+        let corrupt = |message: &str| Error::corrupt_file(path.clone(), message, location!());
+        let plaintext = |inner: Box<dyn Reader>| match expected_key_id {
+            Some(key_id) => Err(corrupt(&format!(
+                "object is recorded as encrypted with key '{}' but has no encryption header",
+                key_id
+            ))),
+            None => Ok(inner),
+        };
+
+        let object_size = inner.size().await?;
+        if object_size < PREFIX_LEN {
+            return plaintext(inner);
+        }
+        let head = inner.get_range(0..object_size.min(SNIFF_LEN)).await?;
+        if &head[..ENCRYPTION_MAGIC.len()] != ENCRYPTION_MAGIC {
+            return plaintext(inner);
+        }
+
+        let mut prefix = &head[ENCRYPTION_MAGIC.len()..PREFIX_LEN];
+        let version = take_u16(&mut prefix).unwrap();
+        if version != FORMAT_VERSION {
+            return Err(Error::NotSupported {
+                source: format!("unsupported encryption format version {}", version).into(),
+                location: location!(),
+            });
+        }
+        let header_len = take_u32(&mut prefix).unwrap() as usize;
+        if header_len > object_size {
+            return Err(corrupt("encryption header is larger than the object"));
+        }
+        let header = if header_len <= head.len() {
+            head.slice(..header_len)
+        } else {
+            inner.get_range(0..header_len).await?
+        };
+
+        let mut buf = &header[PREFIX_LEN..];
+        let block_size = take_u32(&mut buf).unwrap_or(0) as usize;
+        let key_id = take_u16(&mut buf)
+            .and_then(|len| take(&mut buf, len as usize))
+            .and_then(|key_id| std::str::from_utf8(key_id).ok());
+        let wrapped_key = take_u16(&mut buf).and_then(|len| take(&mut buf, len as usize));
+        let (Some(key_id), Some(wrapped_key)) = (key_id, wrapped_key) else {
+            return Err(corrupt("invalid encryption header"));
+        };
+        if block_size == 0 {
+            return Err(corrupt("invalid encryption block size"));
+        }
+
+        let body_len = object_size - header_len;
+        let sealed_block_size = block_size + TAG_LEN;
+        let num_blocks = body_len.div_ceil(sealed_block_size);
+        if num_blocks == 0 || body_len - (num_blocks - 1) * sealed_block_size < TAG_LEN {
+            return Err(corrupt("encrypted object is truncated"));
+        }
+
+        let data_key = key_provider.unwrap_key(key_id, wrapped_key).await?;
+        Ok(Box::new(Self {
+            cipher: new_cipher(&data_key)?,
+            key_id: key_id.to_string(),
+            header_len,
+            block_size,
+            num_blocks,
+            object_size,
+            size: body_len - num_blocks * TAG_LEN,
+            inner,
+        }))
+    }
+
+    /// Id of the master key that wrapped this object's data key.
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+}
+
+#[async_trait]
+impl Reader for EncryptedReader {
+    fn path(&self) -> &Path {
+        self.inner.path()
+    }
+
+    fn block_size(&self) -> usize {
+        self.inner.block_size()
+    }
+
+    async fn size(&self) -> Result<usize> {
+        Ok(self.size)
+    }
+
+    async fn get_range(&self, range: Range<usize>) -> Result<Bytes> {
+        if range.start > range.end || range.end > self.size {
+            return Err(Error::IO {
+                message: format!(
+                    "range {:?} is out of bounds for {} (size {})",
+                    range,
+                    self.path(),
+                    self.size
+                ),
+                location: location!(),
+            });
+        }
+        if range.is_empty() {
+            return Ok(Bytes::new());
+        }
+
+        let first_block = range.start / self.block_size;
+        let last_block = (range.end - 1) / self.block_size;
+        let sealed_block_size = self.block_size + TAG_LEN;
+        let sealed_start = self.header_len + first_block * sealed_block_size;
+        let sealed_end =
+            (self.header_len + (last_block + 1) * sealed_block_size).min(self.object_size);
+        let sealed = self.inner.get_range(sealed_start..sealed_end).await?;
+
+        let mut plaintext = Vec::with_capacity((last_block - first_block + 1) * self.block_size);
+        for (block, chunk) in (first_block..).zip(sealed.chunks(sealed_block_size)) {
+            let nonce = block_nonce(block as u64, block + 1 == self.num_blocks);
+            let decrypted = self
+                .cipher
+                .decrypt(Nonce::from_slice(&nonce), chunk)
+                .map_err(|_| {
+                    Error::corrupt_file(
+                        self.path().clone(),
+                        format!("failed to authenticate encrypted block {}", block),
+                        location!(),
+                    )
+                })?;
+            plaintext.extend_from_slice(&decrypted);
+        }
+
+        let offset = first_block * self.block_size;
+        Ok(Bytes::from(plaintext).slice(range.start - offset..range.end - offset))
+    }
+}
+
+/// A [KeyProvider] that keeps master keys in a local directory.
+///
+/// Each master key is stored as 32 raw bytes in `{dir}/{key_id}.key`. This is
+/// meant for tests and local development, production deployments should use a
+/// provider backed by a key management service.
+#[derive(Debug, Clone)]
+pub struct LocalFileKeyProvider {
+    dir: PathBuf,
+    key_id: String,
+}
+
+impl LocalFileKeyProvider {
+    /// Use the master key `key_id` in `dir`, generating it if it doesn't exist yet.
+    ///
+    /// Keys that were used before stay in the directory, so switching to a new
+    /// `key_id` rotates the key without losing access to existing objects.
+    pub fn try_new(dir: impl Into<PathBuf>, key_id: impl Into<String>) -> Result<Self> {
+        let provider = Self {
+            dir: dir.into(),
+            key_id: key_id.into(),
+        };
+        let path = provider.key_path(&provider.key_id)?;
+        std::fs::create_dir_all(&provider.dir)?;
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(&Aes256Gcm::generate_key(OsRng))?;
+                file.sync_all()?;
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e.into()),
+        }
+        Ok(provider)
+    }
+
+    fn key_path(&self, key_id: &str) -> Result<PathBuf> {
+        let valid = !key_id.is_empty()
+            && key_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(Error::invalid_input(
+                format!(
+                    "invalid key id '{}': only ASCII letters, digits, '-' and '_' are allowed",
+                    key_id
+                ),
+                location!(),
+            ));
+        }
+        Ok(self.dir.join(format!("{}.key", key_id)))
+    }
+
+    fn master_key(&self, key_id: &str) -> Result<Aes256Gcm> {
+        let path = self.key_path(key_id)?;
+        let key = std::fs::read(&path).map_err(|e| Error::IO {
+            message: format!("failed to read master key {}: {}", path.display(), e),
+            location: location!(),
+        })?;
+        new_cipher(&key)
+    }
+}
+
+#[async_trait]
+impl KeyProvider for LocalFileKeyProvider {
+    fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    async fn wrap_key(&self, data_key: &[u8]) -> Result<Vec<u8>> {
+        let master_key = self.master_key(&self.key_id)?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: data_key,
+            aad: self.key_id.as_bytes(),
+        };
+        let sealed = master_key.encrypt(&nonce, payload).map_err(|_| Error::IO {
+            message: format!("failed to wrap data key with key {}", self.key_id),
+            location: location!(),
+        })?;
+        let mut wrapped = nonce.to_vec();
+        wrapped.extend_from_slice(&sealed);
+        Ok(wrapped)
+    }
+
+    async fn unwrap_key(&self, key_id: &str, wrapped_key: &[u8]) -> Result<Vec<u8>> {
+        let master_key = self.master_key(key_id)?;
+        let error = || Error::IO {
+            message: format!("failed to unwrap data key with key {}", key_id),
+            location: location!(),
+        };
+        if wrapped_key.len() < NONCE_LEN {
+            return Err(error());
+        }
+        let (nonce, sealed) = wrapped_key.split_at(NONCE_LEN);
+        let payload = Payload {
+            msg: sealed,
+            aad: key_id.as_bytes(),
+        };
+        master_key
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+    use crate::object_store::ObjectStore;
+    use crate::traits::Writer;
+
+    fn encrypted_store(dir: &std::path::Path, key_id: &str) -> ObjectStore {
+        let provider = LocalFileKeyProvider::try_new(dir.join("keys"), key_id).unwrap();
+        let mut store = ObjectStore::memory();
+        store.set_key_provider(Some(Arc::new(provider)));
+        store
+    }
+
+    #[tokio::test]
+    async fn test_random_access() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = encrypted_store(dir.path(), "key-1");
+        let path = Path::from("foo");
+
+        let data: Vec<u8> = (0..200_000).map(|i| (i % 251) as u8).collect();
+        let mut writer = store.create(&path).await.unwrap();
+        // Write in odd sized pieces so they straddle the block boundaries.
+        for chunk in data.chunks(10_007) {
+            writer.write_all(chunk).await.unwrap();
+        }
+        assert_eq!(writer.tell().await.unwrap(), data.len());
+        writer.shutdown().await.unwrap();
+
+        // The stored object holds neither the plaintext nor its size.
+        let raw = store.inner.get(&path).await.unwrap().bytes().await.unwrap();
+        assert!(raw.starts_with(ENCRYPTION_MAGIC));
+        assert_ne!(&raw[raw.len() - 100..], &data[data.len() - 100..]);
+
+        let reader = store.open(&path).await.unwrap();
+        assert_eq!(reader.size().await.unwrap(), data.len());
+        for range in [
+            0..1,
+            0..data.len(),
+            65_530..65_540,
+            131_072..131_073,
+            150_000..200_000,
+            199_999..200_000,
+            42..42,
+        ] {
+            let bytes = reader.get_range(range.clone()).await.unwrap();
+            assert_eq!(bytes.as_ref(), &data[range]);
+        }
+        assert!(reader.get_range(0..data.len() + 1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_block_boundaries() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = encrypted_store(dir.path(), "key-1");
+
+        for len in [
+            0,
+            1,
+            DEFAULT_ENCRYPTION_BLOCK_SIZE,
+            2 * DEFAULT_ENCRYPTION_BLOCK_SIZE + 1,
+        ] {
+            let path = Path::from(format!("file-{}", len));
+            let data = vec![7u8; len];
+            store.put(&path, &data).await.unwrap();
+            let read = store.read_object(&path).await.unwrap();
+            assert_eq!(read.as_ref(), data.as_slice());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tampering_is_detected() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = encrypted_store(dir.path(), "key-1");
+        let path = Path::from("foo");
+        let data = vec![1u8; 3 * DEFAULT_ENCRYPTION_BLOCK_SIZE];
+        store.put(&path, &data).await.unwrap();
+        let raw = store.inner.get(&path).await.unwrap().bytes().await.unwrap();
+
+        // Truncating at a block boundary drops the final block.
+        let sealed_block_size = DEFAULT_ENCRYPTION_BLOCK_SIZE + TAG_LEN;
+        let truncated = raw.slice(..raw.len() - sealed_block_size);
+        store.inner.put(&path, truncated).await.unwrap();
+        let reader = store.open(&path).await.unwrap();
+        let size = reader.size().await.unwrap();
+        assert!(matches!(
+            reader.get_range(size - 1..size).await,
+            Err(Error::CorruptFile { .. })
+        ));
+
+        let mut flipped = raw.to_vec();
+        let last = flipped.len() - 1;
+        flipped[last] ^= 1;
+        store.inner.put(&path, flipped.into()).await.unwrap();
+        let reader = store.open(&path).await.unwrap();
+        assert!(reader.get_range(0..10).await.is_ok());
+        assert!(matches!(
+            reader.get_range(data.len() - 10..data.len()).await,
+            Err(Error::CorruptFile { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_key_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = encrypted_store(dir.path(), "key-1");
+        let old_path = Path::from("old");
+        store.put(&old_path, b"written with key 1").await.unwrap();
+
+        let provider = LocalFileKeyProvider::try_new(dir.path().join("keys"), "key-2").unwrap();
+        store.set_key_provider(Some(Arc::new(provider)));
+        let new_path = Path::from("new");
+        store.put(&new_path, b"written with key 2").await.unwrap();
+
+        assert_eq!(
+            store.read_object(&old_path).await.unwrap().as_ref(),
+            b"written with key 1"
+        );
+        assert_eq!(
+            store.read_object(&new_path).await.unwrap().as_ref(),
+            b"written with key 2"
+        );
+
+        // Losing the master key makes the object unreadable.
+        std::fs::remove_file(dir.path().join("keys").join("key-1.key")).unwrap();
+        assert!(store.open(&old_path).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_plaintext_objects() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = encrypted_store(dir.path(), "key-1");
+        let path = Path::from("plain");
+        store
+            .inner
+            .put(&path, Bytes::from_static(b"not encrypted"))
+            .await
+            .unwrap();
+        assert_eq!(
+            store.read_object(&path).await.unwrap().as_ref(),
+            b"not encrypted"
+        );
+
+        // Unless the metadata says the object is encrypted
+        assert!(matches!(
+            store.read_object_with_key_id(&path, Some("key-1")).await,
+            Err(Error::CorruptFile { .. })
+        ));
+        let empty = Path::from("empty");
+        store.inner.put(&empty, Bytes::new()).await.unwrap();
+        assert!(matches!(
+            store.open_with_key_id(&empty, Some("key-1")).await,
+            Err(Error::CorruptFile { .. })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_key_id() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(LocalFileKeyProvider::try_new(dir.path(), "../escape").is_err());
+        assert!(LocalFileKeyProvider::try_new(dir.path(), "").is_err());
+    }
+}
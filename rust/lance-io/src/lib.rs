@@ -9,6 +9,7 @@ use snafu::{location, Location};
 use lance_core::{Error, Result};
 
 pub mod encodings;
+pub mod encryption;
 pub mod ffi;
 pub mod local;
 pub mod object_reader;
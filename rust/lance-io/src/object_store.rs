@@ -12,6 +12,7 @@ use std::time::{Duration, SystemTime};
 use async_trait::async_trait;
 use aws_config::default_provider::credentials::DefaultCredentialsChain;
 use aws_credential_types::provider::ProvideCredentials;
+use bytes::Bytes;
 use chrono::{DateTime, Utc};
 use futures::{future, stream::BoxStream, StreamExt, TryStreamExt};
 use object_store::aws::{
@@ -32,9 +33,16 @@ use url::Url;
 
 use super::local::LocalObjectReader;
 
+pub mod cache;
 mod tracing;
+use self::cache::{DiskCache, DiskCacheConfig};
 use self::tracing::ObjectStoreTracingExt;
-use crate::{object_reader::CloudObjectReader, object_writer::ObjectWriter, traits::Reader};
+use crate::encryption::{EncryptedReader, KeyProvider};
+use crate::{
+    object_reader::CloudObjectReader,
+    object_writer::{ChecksumRecorder, ObjectWriter},
+    traits::Reader,
+};
 use lance_core::{Error, Result};
 
 #[async_trait]
@@ -85,6 +93,10 @@ pub struct ObjectStore {
     scheme: String,
     base_path: Path,
     block_size: usize,
+    // Encrypts files created through, and decrypts files opened through, this store.
+    key_provider: Option<Arc<dyn KeyProvider>>,
+    // Receives the checksums of files written through this store.
+    checksum_recorder: Option<Arc<ChecksumRecorder>>,
 }
 
 impl std::fmt::Display for ObjectStore {
@@ -298,6 +310,8 @@ pub struct ObjectStoreParams {
     pub aws_credentials: Option<AwsCredentialProvider>,
     pub object_store_wrapper: Option<Arc<dyn WrappingObjectStore>>,
     pub storage_options: Option<HashMap<String, String>>,
+    /// Encrypt data, deletion and index files with keys wrapped by this provider.
+    pub key_provider: Option<Arc<dyn KeyProvider>>,
 }
 
 impl Default for ObjectStoreParams {
@@ -309,6 +323,7 @@ impl Default for ObjectStoreParams {
             aws_credentials: None,
             object_store_wrapper: None,
             storage_options: None,
+            key_provider: None,
         }
     }
 }
@@ -356,13 +371,27 @@ impl ObjectStore {
             Err(_) => Self::from_path(uri),
         }?;
 
+        // Caching local files on local disk would only add overhead
+        let disk_cache = match params.storage_options.as_ref() {
+            Some(options) if !object_store.is_local() => {
+                DiskCacheConfig::from_storage_options(options)?
+                    .map(DiskCache::open)
+                    .transpose()?
+            }
+            _ => None,
+        };
+        let inner = disk_cache
+            .map(|cache| cache.wrap(object_store.inner.clone()))
+            .unwrap_or(object_store.inner.clone());
+
         Ok((
             Self {
                 inner: params
                     .object_store_wrapper
                     .as_ref()
-                    .map(|w| w.wrap(object_store.inner.clone()))
-                    .unwrap_or(object_store.inner),
+                    .map(|w| w.wrap(inner.clone()))
+                    .unwrap_or(inner),
+                key_provider: params.key_provider.clone(),
                 ..object_store
             },
             base_path,
@@ -385,6 +414,8 @@ impl ObjectStore {
                 scheme: String::from("file"),
                 base_path: Path::from_absolute_path(&expanded_path)?,
                 block_size: 4 * 1024, // 4KB block size
+                key_provider: None,
+                checksum_recorder: None,
             },
             Path::from_filesystem_path(&expanded_path)?,
         ))
@@ -400,6 +431,8 @@ impl ObjectStore {
             scheme: String::from("file"),
             base_path: Path::from("/"),
             block_size: 4 * 1024, // 4KB block size
+            key_provider: None,
+            checksum_recorder: None,
         }
     }
 
@@ -410,6 +443,8 @@ impl ObjectStore {
             scheme: String::from("memory"),
             base_path: Path::from("/"),
             block_size: 64 * 1024,
+            key_provider: None,
+            checksum_recorder: None,
         }
     }
 
@@ -430,18 +465,80 @@ impl ObjectStore {
         &self.base_path
     }
 
+    pub fn key_provider(&self) -> Option<&Arc<dyn KeyProvider>> {
+        self.key_provider.as_ref()
+    }
+
+    pub fn set_key_provider(&mut self, key_provider: Option<Arc<dyn KeyProvider>>) {
+        self.key_provider = key_provider;
+    }
+
+    /// A copy of this store that records the checksum of every file written through it.
+    ///
+    /// The checksums are computed while the files are written, so they don't
+    /// need to be read back.
+    pub fn with_checksum_recorder(&self, recorder: Arc<ChecksumRecorder>) -> Self {
+        Self {
+            checksum_recorder: Some(recorder),
+            ..self.clone()
+        }
+    }
+
+    /// Id of the master key new files are encrypted with, if encryption is enabled.
+    pub fn encryption_key_id(&self) -> Option<String> {
+        self.key_provider
+            .as_ref()
+            .map(|provider| provider.key_id().to_string())
+    }
+
+    /// Check that a file encrypted with `key_id` (if any) can be decrypted by this store.
+    pub fn check_can_decrypt(&self, path: &Path, key_id: Option<&str>) -> Result<()> {
+        match (key_id, &self.key_provider) {
+            (Some(key_id), None) => Err(Error::invalid_input(
+                format!(
+                    "{} is encrypted with key '{}' but no key provider is configured",
+                    path, key_id
+                ),
+                location!(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
     /// Open a file for path.
     ///
     /// Parameters
     /// - ``path``: Absolute path to the file.
+    ///
+    /// When a key provider is configured, encrypted files are decrypted
+    /// transparently. Files without an encryption header are read as is.
     pub async fn open(&self, path: &Path) -> Result<Box<dyn Reader>> {
-        match self.scheme.as_str() {
-            "file" => LocalObjectReader::open(path, self.block_size).await,
-            _ => Ok(Box::new(CloudObjectReader::new(
+        self.open_with_key_id(path, None).await
+    }
+
+    /// Open a file whose metadata records the master key it was encrypted with.
+    ///
+    /// If `key_id` is set the file must be encrypted, a file without an
+    /// encryption header is an error instead of being read as plaintext.
+    pub async fn open_with_key_id(
+        &self,
+        path: &Path,
+        key_id: Option<&str>,
+    ) -> Result<Box<dyn Reader>> {
+        self.check_can_decrypt(path, key_id)?;
+        let reader = match self.scheme.as_str() {
+            "file" => LocalObjectReader::open(path, self.block_size).await?,
+            _ => Box::new(CloudObjectReader::new(
                 self.inner.clone(),
                 path.clone(),
                 self.block_size,
-            )?)),
+            )?),
+        };
+        match &self.key_provider {
+            Some(key_provider) => {
+                EncryptedReader::open(reader, key_provider.as_ref(), key_id).await
+            }
+            None => Ok(reader),
         }
     }
 
@@ -460,8 +557,17 @@ impl ObjectStore {
     }
 
     /// Create a new file.
+    ///
+    /// The file is encrypted if a key provider is configured.
     pub async fn create(&self, path: &Path) -> Result<ObjectWriter> {
-        ObjectWriter::new(self.inner.as_ref(), path).await
+        let writer = match &self.key_provider {
+            Some(key_provider) => {
+                ObjectWriter::new_encrypted(self.inner.as_ref(), path, key_provider.as_ref())
+                    .await?
+            }
+            None => ObjectWriter::new(self.inner.as_ref(), path).await?,
+        };
+        Ok(writer.with_checksum_recorder(self.checksum_recorder.clone()))
     }
 
     /// A helper function to create a file and write content to it.
@@ -472,6 +578,44 @@ impl ObjectStore {
         writer.shutdown().await
     }
 
+    /// Write a small file in a single request, encrypting it if a key provider is configured.
+    ///
+    /// Returns the CRC32C of the stored bytes.
+    pub async fn write_object(&self, path: &Path, content: Bytes) -> Result<u32> {
+        if self.key_provider.is_some() {
+            let mut writer = self.create(path).await?;
+            writer.write_all(&content).await?;
+            writer.shutdown().await?;
+            Ok(writer.checksum())
+        } else {
+            let checksum = crc32c::crc32c(&content);
+            self.inner.put(path, content).await?;
+            if let Some(recorder) = &self.checksum_recorder {
+                recorder.record(path, checksum);
+            }
+            Ok(checksum)
+        }
+    }
+
+    /// Read a whole file, decrypting it if a key provider is configured.
+    pub async fn read_object(&self, path: &Path) -> Result<Bytes> {
+        self.read_object_with_key_id(path, None).await
+    }
+
+    /// Read a whole file, see [Self::open_with_key_id].
+    pub async fn read_object_with_key_id(
+        &self,
+        path: &Path,
+        key_id: Option<&str>,
+    ) -> Result<Bytes> {
+        if self.key_provider.is_some() || key_id.is_some() {
+            let reader = self.open_with_key_id(path, key_id).await?;
+            reader.get_range(0..reader.size().await?).await
+        } else {
+            Ok(self.inner.get(path).await?.bytes().await?)
+        }
+    }
+
     pub async fn delete(&self, path: &Path) -> Result<()> {
         self.inner.delete(path).await?;
         Ok(())
@@ -549,6 +693,16 @@ impl ObjectStore {
     pub async fn size(&self, path: &Path) -> Result<usize> {
         Ok(self.inner.head(path).await?.size)
     }
+
+    /// Compute the CRC32C of the stored bytes of a file, streaming its content.
+    pub async fn checksum(&self, path: &Path) -> Result<u32> {
+        let mut stream = self.inner.get(path).await?.into_stream();
+        let mut checksum = 0;
+        while let Some(chunk) = stream.next().await {
+            checksum = crc32c::crc32c_append(checksum, &chunk?);
+        }
+        Ok(checksum)
+    }
 }
 #[derive(Clone, Debug, Default)]
 pub struct StorageOptions(pub HashMap<String, String>);
@@ -710,6 +864,8 @@ async fn configure_store(url: &str, options: ObjectStoreParams) -> Result<Object
                 scheme: String::from(url.scheme()),
                 base_path: Path::from(url.path()),
                 block_size: 64 * 1024,
+                key_provider: None,
+                checksum_recorder: None,
             })
         }
 
@@ -722,6 +878,8 @@ async fn configure_store(url: &str, options: ObjectStoreParams) -> Result<Object
                 scheme: String::from("gs"),
                 base_path: Path::from(url.path()),
                 block_size: 64 * 1024,
+                key_provider: None,
+                checksum_recorder: None,
             })
         }
         "az" => {
@@ -735,6 +893,8 @@ async fn configure_store(url: &str, options: ObjectStoreParams) -> Result<Object
                 scheme: String::from("az"),
                 base_path: Path::from(url.path()),
                 block_size: 64 * 1024,
+                key_provider: None,
+                checksum_recorder: None,
             })
         }
         "file" => Ok(ObjectStore::from_path(url.path())?.0),
@@ -743,6 +903,8 @@ async fn configure_store(url: &str, options: ObjectStoreParams) -> Result<Object
             scheme: String::from("memory"),
             base_path: Path::from(url.path()),
             block_size: 64 * 1024,
+            key_provider: None,
+            checksum_recorder: None,
         }),
         s => Err(Error::IO {
             message: format!("Unsupported URI scheme: {}", s),
@@ -771,6 +933,8 @@ impl ObjectStore {
             scheme: scheme.into(),
             base_path: location.path().into(),
             block_size,
+            key_provider: None,
+            checksum_recorder: None,
         }
     }
 }
@@ -0,0 +1,988 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! A read-through cache that keeps blocks of remote objects on local disk
+//!
+//! Objects are split into fixed-size blocks.  Each block is stored as its own
+//! file in the cache directory, prefixed with a small header that records the
+//! object it came from (store, path, size and ETag).  Blocks are evicted in
+//! least-recently-used order once the total size exceeds the configured capacity.
+//!
+//! Every cached block is validated against the latest size and ETag seen for
+//! the object (from `head`, `get`, or a ranged read that misses the cache).  If
+//! no version has been seen yet in this process the object is checked with a
+//! `head` request before any block is served.  Writes,
+//! copies, renames and deletes that go through the cache invalidate the
+//! affected entries.  Most Lance files are immutable, but `_latest.manifest` is rewritten
+//! in place, so only manifests in the `_versions` directory are cached.
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+use bytes::{Bytes, BytesMut};
+use futures::stream::BoxStream;
+use lazy_static::lazy_static;
+use object_store::path::Path;
+use object_store::{
+    GetOptions, GetResult, ListResult, MultipartId, ObjectMeta, ObjectStore as OSObjectStore,
+    PutOptions, PutResult, Result as OSResult,
+};
+use snafu::{location, Location};
+use tokio::io::AsyncWrite;
+
+use super::WrappingObjectStore;
+use lance_core::{Error, Result};
+
+/// Storage option that enables the disk cache, pointing at the cache directory
+pub const DISK_CACHE_PATH_KEY: &str = "lance_disk_cache_path";
+/// Storage option for the maximum size of the disk cache, in bytes
+pub const DISK_CACHE_CAPACITY_KEY: &str = "lance_disk_cache_capacity";
+/// Storage option for the size of the blocks stored in the disk cache, in bytes
+pub const DISK_CACHE_BLOCK_SIZE_KEY: &str = "lance_disk_cache_block_size";
+
+/// The default maximum size of the disk cache (10GiB)
+pub const DEFAULT_DISK_CACHE_CAPACITY: u64 = 10 * 1024 * 1024 * 1024;
+/// The default size of a cached block (1MiB)
+pub const DEFAULT_DISK_CACHE_BLOCK_SIZE: u64 = 1024 * 1024;
+
+const TMP_SUFFIX: &str = ".tmp";
+
+/// Configuration for a [`DiskCache`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiskCacheConfig {
+    /// The directory that cached blocks are stored in
+    pub path: PathBuf,
+    /// The maximum number of bytes to keep on disk
+    pub capacity: u64,
+    /// The size of each cached block
+    pub block_size: u64,
+}
+
+impl DiskCacheConfig {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            capacity: DEFAULT_DISK_CACHE_CAPACITY,
+            block_size: DEFAULT_DISK_CACHE_BLOCK_SIZE,
+        }
+    }
+
+    /// Parse the cache configuration from storage options
+    ///
+    /// Returns None if [`DISK_CACHE_PATH_KEY`] is not set.
+    pub fn from_storage_options(options: &HashMap<String, String>) -> Result<Option<Self>> {
+        let Some(path) = options.get(DISK_CACHE_PATH_KEY) else {
+            return Ok(None);
+        };
+        let parse = |key: &str, default: u64| -> Result<u64> {
+            options
+                .get(key)
+                .map(|value| {
+                    value.parse::<u64>().map_err(|_| {
+                        Error::invalid_input(
+                            format!("Invalid value for storage option {}: {}", key, value),
+                            location!(),
+                        )
+                    })
+                })
+                .unwrap_or(Ok(default))
+        };
+        let config = Self {
+            path: PathBuf::from(shellexpand::tilde(path).as_ref()),
+            capacity: parse(DISK_CACHE_CAPACITY_KEY, DEFAULT_DISK_CACHE_CAPACITY)?,
+            block_size: parse(DISK_CACHE_BLOCK_SIZE_KEY, DEFAULT_DISK_CACHE_BLOCK_SIZE)?,
+        };
+        if config.block_size == 0 {
+            return Err(Error::invalid_input(
+                format!("{} must be greater than 0", DISK_CACHE_BLOCK_SIZE_KEY),
+                location!(),
+            ));
+        }
+        Ok(Some(config))
+    }
+}
+
+/// Hit / miss counters for a [`DiskCache`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiskCacheStats {
+    /// Number of blocks served from disk
+    pub hits: u64,
+    /// Number of blocks that had to be fetched from the object store
+    pub misses: u64,
+    /// Number of blocks removed to stay within capacity or because they were stale
+    pub evictions: u64,
+    /// Bytes served from disk
+    pub bytes_from_cache: u64,
+    /// Bytes fetched from the object store
+    pub bytes_fetched: u64,
+    /// Number of bytes currently stored on disk
+    pub size_bytes: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    bytes_from_cache: AtomicU64,
+    bytes_fetched: AtomicU64,
+}
+
+// The identity of an object version.  Blocks are only served if they were
+// read from the same version of the object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ObjectVersion {
+    size: usize,
+    e_tag: Option<String>,
+}
+
+impl From<&ObjectMeta> for ObjectVersion {
+    fn from(meta: &ObjectMeta) -> Self {
+        Self {
+            size: meta.size,
+            e_tag: meta.e_tag.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    // file name -> (last access tick, file size)
+    entries: HashMap<String, (u64, u64)>,
+    // last access tick -> file name
+    lru: BTreeMap<u64, String>,
+    size_bytes: u64,
+    tick: u64,
+    // The latest version seen for each object (keyed by store and path).  None
+    // means the object was modified through the cache and its new version is
+    // not known yet.
+    versions: HashMap<String, Option<ObjectVersion>>,
+}
+
+impl CacheState {
+    fn touch(&mut self, name: &str) -> bool {
+        let tick = self.tick;
+        let Some((last_access, _)) = self.entries.get_mut(name) else {
+            return false;
+        };
+        self.lru.remove(last_access);
+        *last_access = tick;
+        self.lru.insert(tick, name.to_string());
+        self.tick += 1;
+        true
+    }
+
+    fn insert(&mut self, name: String, size: u64) {
+        self.remove(&name);
+        let tick = self.tick;
+        self.tick += 1;
+        self.lru.insert(tick, name.clone());
+        self.entries.insert(name, (tick, size));
+        self.size_bytes += size;
+    }
+
+    fn remove(&mut self, name: &str) -> bool {
+        if let Some((last_access, size)) = self.entries.remove(name) {
+            self.lru.remove(&last_access);
+            self.size_bytes -= size;
+            true
+        } else {
+            false
+        }
+    }
+
+    // Removes least recently used entries until the cache is within capacity
+    fn evict(&mut self, capacity: u64) -> Vec<String> {
+        let mut evicted = Vec::new();
+        while self.size_bytes > capacity {
+            let Some((_, name)) = self.lru.pop_first() else {
+                break;
+            };
+            let (_, size) = self.entries.remove(&name).unwrap();
+            self.size_bytes -= size;
+            evicted.push(name);
+        }
+        evicted
+    }
+}
+
+lazy_static! {
+    // Caches are shared by every store configured with the same directory so
+    // that the size accounting stays correct.
+    static ref DISK_CACHES: Mutex<HashMap<PathBuf, Weak<DiskCache>>> = Mutex::new(HashMap::new());
+}
+
+/// A local disk cache of blocks of remote objects
+///
+/// This implements [`WrappingObjectStore`] so it can be passed as
+/// `ObjectStoreParams::object_store_wrapper`.  It can also be enabled by
+/// setting [`DISK_CACHE_PATH_KEY`] in the storage options.
+#[derive(Debug)]
+pub struct DiskCache {
+    // Used to hand out references to ourselves when wrapping stores
+    self_ref: Weak<Self>,
+    config: DiskCacheConfig,
+    state: Mutex<CacheState>,
+    counters: Counters,
+}
+
+impl DiskCache {
+    /// Open the cache in the configured directory
+    ///
+    /// Blocks left in the directory by a previous process are reused.  If the
+    /// directory is already in use by this process then the existing cache is
+    /// returned (and must have the same block size).
+    pub fn open(config: DiskCacheConfig) -> Result<Arc<Self>> {
+        std::fs::create_dir_all(&config.path)?;
+        let dir = config.path.canonicalize()?;
+        let mut caches = DISK_CACHES.lock().unwrap();
+        if let Some(cache) = caches.get(&dir).and_then(|cache| cache.upgrade()) {
+            if cache.config.block_size != config.block_size {
+                return Err(Error::invalid_input(
+                    format!(
+                        "Disk cache at {} is already open with block size {}",
+                        dir.display(),
+                        cache.config.block_size
+                    ),
+                    location!(),
+                ));
+            }
+            return Ok(cache);
+        }
+
+        let state = Self::load_state(&dir)?;
+        let cache = Arc::new_cyclic(|self_ref| Self {
+            self_ref: self_ref.clone(),
+            state: Mutex::new(state),
+            config: DiskCacheConfig {
+                path: dir.clone(),
+                ..config
+            },
+            counters: Counters::default(),
+        });
+        let evicted = cache.state.lock().unwrap().evict(cache.config.capacity);
+        for name in evicted {
+            let _ = std::fs::remove_file(cache.config.path.join(name));
+        }
+        caches.insert(dir, Arc::downgrade(&cache));
+        Ok(cache)
+    }
+
+    // Rebuild the LRU from the files on disk, oldest modification first
+    fn load_state(dir: &std::path::Path) -> Result<CacheState> {
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let metadata = entry.metadata()?;
+            if !metadata.is_file() {
+                continue;
+            }
+            if name.ends_with(TMP_SUFFIX) {
+                // Left over from an interrupted write
+                let _ = std::fs::remove_file(entry.path());
+                continue;
+            }
+            files.push((metadata.modified().ok(), name, metadata.len()));
+        }
+        files.sort();
+        let mut state = CacheState::default();
+        for (_, name, size) in files {
+            state.insert(name, size);
+        }
+        Ok(state)
+    }
+
+    /// Current hit / miss counts and size of the cache
+    pub fn stats(&self) -> DiskCacheStats {
+        DiskCacheStats {
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            evictions: self.counters.evictions.load(Ordering::Relaxed),
+            bytes_from_cache: self.counters.bytes_from_cache.load(Ordering::Relaxed),
+            bytes_fetched: self.counters.bytes_fetched.load(Ordering::Relaxed),
+            size_bytes: self.state.lock().unwrap().size_bytes,
+        }
+    }
+
+    pub fn config(&self) -> &DiskCacheConfig {
+        &self.config
+    }
+
+    fn block_file_name(object_key: &str, block: u64) -> String {
+        // The header stores the full key so a hash collision is just a miss
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        object_key.hash(&mut hasher);
+        block.hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+
+    // Record the latest version of an object.  Blocks from other versions
+    // will be treated as misses.
+    fn observe_version(&self, object_key: &str, version: ObjectVersion) {
+        self.state
+            .lock()
+            .unwrap()
+            .versions
+            .insert(object_key.to_string(), Some(version));
+    }
+
+    // The latest version seen for an object, if it is known
+    fn known_version(&self, object_key: &str) -> Option<ObjectVersion> {
+        self.state
+            .lock()
+            .unwrap()
+            .versions
+            .get(object_key)
+            .cloned()
+            .flatten()
+    }
+
+    // Called before an object is modified.  Cached blocks of the object will
+    // not be served until the new version has been seen.
+    fn invalidate_object(&self, object_key: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .versions
+            .insert(object_key.to_string(), None);
+    }
+
+    async fn remove_files(&self, names: Vec<String>) {
+        self.counters
+            .evictions
+            .fetch_add(names.len() as u64, Ordering::Relaxed);
+        for name in names {
+            let _ = tokio::fs::remove_file(self.config.path.join(name)).await;
+        }
+    }
+
+    // Read a block, if it was cached from `version` of the object
+    async fn read_block(
+        &self,
+        object_key: &str,
+        version: &ObjectVersion,
+        block: u64,
+    ) -> Option<Bytes> {
+        let name = Self::block_file_name(object_key, block);
+        if !self.state.lock().unwrap().entries.contains_key(&name) {
+            return None;
+        }
+        let contents = match tokio::fs::read(self.config.path.join(&name)).await {
+            Ok(contents) => Bytes::from(contents),
+            Err(_) => {
+                self.state.lock().unwrap().remove(&name);
+                return None;
+            }
+        };
+        let valid = match BlockHeader::parse(&contents) {
+            Some((header, data_offset))
+                if header.object_key == object_key && &header.version == version =>
+            {
+                self.state.lock().unwrap().touch(&name);
+                Some(contents.slice(data_offset..))
+            }
+            _ => None,
+        };
+        if valid.is_none() {
+            let removed = self.state.lock().unwrap().remove(&name);
+            if removed {
+                self.remove_files(vec![name]).await;
+            }
+        }
+        valid
+    }
+
+    async fn write_block(
+        &self,
+        object_key: &str,
+        version: &ObjectVersion,
+        block: u64,
+        data: &[u8],
+    ) {
+        let name = Self::block_file_name(object_key, block);
+        let header = BlockHeader {
+            object_key: object_key.to_string(),
+            version: version.clone(),
+        };
+        let mut contents = header.encode();
+        contents.extend_from_slice(data);
+        let size = contents.len() as u64;
+        if size > self.config.capacity {
+            return;
+        }
+
+        // Write to a temporary file first so readers never see a partial block
+        let path = self.config.path.join(&name);
+        let tmp_path = self
+            .config
+            .path
+            .join(format!("{}.{}{}", name, uuid_suffix(), TMP_SUFFIX));
+        let written = async {
+            tokio::fs::write(&tmp_path, &contents).await?;
+            tokio::fs::rename(&tmp_path, &path).await
+        }
+        .await;
+        if let Err(err) = written {
+            ::tracing::warn!("Failed to write block to disk cache: {}", err);
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+            return;
+        }
+
+        let evicted = {
+            let mut state = self.state.lock().unwrap();
+            state.insert(name.clone(), size);
+            // The new block is the most recently used and fits in the cache
+            // so it will not be evicted
+            state.evict(self.config.capacity)
+        };
+        if !evicted.is_empty() {
+            self.remove_files(evicted).await;
+        }
+    }
+}
+
+impl WrappingObjectStore for DiskCache {
+    fn wrap(&self, original: Arc<dyn OSObjectStore>) -> Arc<dyn OSObjectStore> {
+        Arc::new(DiskCachedObjectStore {
+            store_key: original.to_string(),
+            target: original,
+            // We are always created inside an Arc, see DiskCache::open
+            cache: self.self_ref.upgrade().unwrap(),
+        })
+    }
+}
+
+fn uuid_suffix() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    format!(
+        "{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+// Header stored at the start of each block file:
+//
+// | header length (u32 LE) | object size (u64 LE) | etag length (u32 LE) | etag | object key |
+//
+// An etag length of u32::MAX means the object has no etag.
+struct BlockHeader {
+    object_key: String,
+    version: ObjectVersion,
+}
+
+impl BlockHeader {
+    fn encode(&self) -> Vec<u8> {
+        let e_tag = self.version.e_tag.as_deref().unwrap_or_default().as_bytes();
+        let e_tag_len = if self.version.e_tag.is_some() {
+            e_tag.len() as u32
+        } else {
+            u32::MAX
+        };
+        let header_len = 4 + 8 + 4 + e_tag.len() + self.object_key.len();
+        let mut buf = Vec::with_capacity(header_len);
+        buf.extend_from_slice(&(header_len as u32).to_le_bytes());
+        buf.extend_from_slice(&(self.version.size as u64).to_le_bytes());
+        buf.extend_from_slice(&e_tag_len.to_le_bytes());
+        buf.extend_from_slice(e_tag);
+        buf.extend_from_slice(self.object_key.as_bytes());
+        buf
+    }
+
+    // Returns the header and the offset of the block data
+    fn parse(buf: &[u8]) -> Option<(Self, usize)> {
+        let read_u32 = |offset: usize| -> Option<u32> {
+            Some(u32::from_le_bytes(
+                buf.get(offset..offset + 4)?.try_into().ok()?,
+            ))
+        };
+        let header_len = read_u32(0)? as usize;
+        let size = u64::from_le_bytes(buf.get(4..12)?.try_into().ok()?) as usize;
+        let e_tag_len = read_u32(12)?;
+        let (e_tag, key_offset) = if e_tag_len == u32::MAX {
+            (None, 16)
+        } else {
+            let end = 16 + e_tag_len as usize;
+            (
+                Some(String::from_utf8(buf.get(16..end)?.to_vec()).ok()?),
+                end,
+            )
+        };
+        let object_key = String::from_utf8(buf.get(key_offset..header_len)?.to_vec()).ok()?;
+        Some((
+            Self {
+                object_key,
+                version: ObjectVersion { size, e_tag },
+            },
+            header_len,
+        ))
+    }
+}
+
+/// Whether reads of this path can be served from the cache
+///
+/// Everything Lance writes is immutable except for `_latest.manifest`, so
+/// manifests are only cached when they are addressed by version.
+fn is_cacheable(path: &Path) -> bool {
+    match path.extension() {
+        Some("manifest") => {
+            let parts = path.parts().collect::<Vec<_>>();
+            parts.len() >= 2 && parts[parts.len() - 2].as_ref() == "_versions"
+        }
+        _ => true,
+    }
+}
+
+/// An object store whose ranged reads are served from a [`DiskCache`]
+#[derive(Debug)]
+struct DiskCachedObjectStore {
+    target: Arc<dyn OSObjectStore>,
+    // Distinguishes objects with the same path in different stores
+    store_key: String,
+    cache: Arc<DiskCache>,
+}
+
+impl std::fmt::Display for DiskCachedObjectStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DiskCachedObjectStore({})", self.target)
+    }
+}
+
+impl DiskCachedObjectStore {
+    fn object_key(&self, location: &Path) -> String {
+        format!("{}/{}", self.store_key, location)
+    }
+
+    // The current version of an object, from a `head` request if it has not
+    // been seen yet.  Blocks are only trusted if they match this version.
+    async fn current_version(&self, location: &Path, object_key: &str) -> OSResult<ObjectVersion> {
+        if let Some(version) = self.cache.known_version(object_key) {
+            return Ok(version);
+        }
+        let version = ObjectVersion::from(&self.target.head(location).await?);
+        self.cache.observe_version(object_key, version.clone());
+        Ok(version)
+    }
+
+    // Fetch blocks [first, last) from the object store and add them to the cache
+    //
+    // Returns the version of the object the blocks were read from
+    async fn fetch_blocks(
+        &self,
+        location: &Path,
+        object_key: &str,
+        blocks: Range<u64>,
+    ) -> OSResult<(ObjectVersion, Vec<Bytes>)> {
+        let block_size = self.cache.config.block_size;
+        let start = (blocks.start * block_size) as usize;
+        let end = (blocks.end * block_size) as usize;
+        // A bounded range that extends past the end of the object returns
+        // the rest of the object
+        let result = self
+            .target
+            .get_opts(
+                location,
+                GetOptions {
+                    range: Some((start..end).into()),
+                    ..Default::default()
+                },
+            )
+            .await?;
+        let version = ObjectVersion::from(&result.meta);
+        self.cache.observe_version(object_key, version.clone());
+        let data = result.bytes().await?;
+        self.cache
+            .counters
+            .misses
+            .fetch_add(blocks.end - blocks.start, Ordering::Relaxed);
+        self.cache
+            .counters
+            .bytes_fetched
+            .fetch_add(data.len() as u64, Ordering::Relaxed);
+
+        let mut fetched = Vec::with_capacity((blocks.end - blocks.start) as usize);
+        for (idx, block) in blocks.enumerate() {
+            let block_start = idx * block_size as usize;
+            if block_start >= data.len() {
+                break;
+            }
+            let block_end = (block_start + block_size as usize).min(data.len());
+            let block_data = data.slice(block_start..block_end);
+            self.cache
+                .write_block(object_key, &version, block, &block_data)
+                .await;
+            fetched.push(block_data);
+        }
+        Ok((version, fetched))
+    }
+
+    async fn cached_get_range(&self, location: &Path, range: Range<usize>) -> OSResult<Bytes> {
+        let block_size = self.cache.config.block_size;
+        let object_key = self.object_key(location);
+        let first_block = range.start as u64 / block_size;
+        let last_block = (range.end as u64).div_ceil(block_size);
+        let mut version = self.current_version(location, &object_key).await?;
+
+        let mut blocks = Vec::with_capacity((last_block - first_block) as usize);
+        let mut block = first_block;
+        while block < last_block {
+            if let Some(data) = self.cache.read_block(&object_key, &version, block).await {
+                self.cache.counters.hits.fetch_add(1, Ordering::Relaxed);
+                self.cache
+                    .counters
+                    .bytes_from_cache
+                    .fetch_add(data.len() as u64, Ordering::Relaxed);
+                blocks.push(data);
+                block += 1;
+                continue;
+            }
+            // Fetch the run of missing blocks with a single request
+            let mut run_end = block + 1;
+            while run_end < last_block
+                && !self
+                    .cache
+                    .state
+                    .lock()
+                    .unwrap()
+                    .entries
+                    .contains_key(&DiskCache::block_file_name(&object_key, run_end))
+            {
+                run_end += 1;
+            }
+            let (fetched_version, fetched) = self
+                .fetch_blocks(location, &object_key, block..run_end)
+                .await?;
+            version = fetched_version;
+            let num_fetched = fetched.len() as u64;
+            blocks.extend(fetched);
+            if num_fetched < run_end - block {
+                // Reached the end of the object
+                break;
+            }
+            block = run_end;
+        }
+
+        let available = blocks.iter().map(|b| b.len()).sum::<usize>();
+        let offset = range.start - (first_block * block_size) as usize;
+        if offset + range.len() > available {
+            return Err(object_store::Error::Generic {
+                store: "DiskCache",
+                source: format!(
+                    "Range {:?} is out of bounds for object {} of size {}",
+                    range,
+                    location,
+                    (first_block * block_size) as usize + available
+                )
+                .into(),
+            });
+        }
+        if blocks.len() == 1 {
+            return Ok(blocks[0].slice(offset..offset + range.len()));
+        }
+        let mut buf = BytesMut::with_capacity(range.len());
+        let mut skip = offset;
+        let mut remaining = range.len();
+        for block in blocks {
+            if remaining == 0 {
+                break;
+            }
+            if skip >= block.len() {
+                skip -= block.len();
+                continue;
+            }
+            let take = (block.len() - skip).min(remaining);
+            buf.extend_from_slice(&block[skip..skip + take]);
+            remaining -= take;
+            skip = 0;
+        }
+        Ok(buf.freeze())
+    }
+}
+
+#[async_trait::async_trait]
+impl OSObjectStore for DiskCachedObjectStore {
+    async fn put(&self, location: &Path, bytes: Bytes) -> OSResult<PutResult> {
+        self.cache.invalidate_object(&self.object_key(location));
+        self.target.put(location, bytes).await
+    }
+
+    async fn put_opts(
+        &self,
+        location: &Path,
+        bytes: Bytes,
+        opts: PutOptions,
+    ) -> OSResult<PutResult> {
+        self.cache.invalidate_object(&self.object_key(location));
+        self.target.put_opts(location, bytes, opts).await
+    }
+
+    async fn put_multipart(
+        &self,
+        location: &Path,
+    ) -> OSResult<(MultipartId, Box<dyn AsyncWrite + Unpin + Send>)> {
+        self.cache.invalidate_object(&self.object_key(location));
+        self.target.put_multipart(location).await
+    }
+
+    async fn abort_multipart(&self, location: &Path, multipart_id: &MultipartId) -> OSResult<()> {
+        self.target.abort_multipart(location, multipart_id).await
+    }
+
+    async fn get_opts(&self, location: &Path, options: GetOptions) -> OSResult<GetResult> {
+        let result = self.target.get_opts(location, options).await?;
+        self.cache
+            .observe_version(&self.object_key(location), (&result.meta).into());
+        Ok(result)
+    }
+
+    async fn get_range(&self, location: &Path, range: Range<usize>) -> OSResult<Bytes> {
+        if range.is_empty() || !is_cacheable(location) {
+            return self.target.get_range(location, range).await;
+        }
+        self.cached_get_range(location, range).await
+    }
+
+    async fn head(&self, location: &Path) -> OSResult<ObjectMeta> {
+        let meta = self.target.head(location).await?;
+        self.cache
+            .observe_version(&self.object_key(location), (&meta).into());
+        Ok(meta)
+    }
+
+    async fn delete(&self, location: &Path) -> OSResult<()> {
+        self.cache.invalidate_object(&self.object_key(location));
+        self.target.delete(location).await
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, OSResult<ObjectMeta>> {
+        self.target.list(prefix)
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> OSResult<ListResult> {
+        self.target.list_with_delimiter(prefix).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> OSResult<()> {
+        self.cache.invalidate_object(&self.object_key(to));
+        self.target.copy(from, to).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> OSResult<()> {
+        self.cache.invalidate_object(&self.object_key(from));
+        self.cache.invalidate_object(&self.object_key(to));
+        self.target.rename(from, to).await
+    }
+
+    async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> OSResult<()> {
+        self.cache.invalidate_object(&self.object_key(to));
+        self.target.copy_if_not_exists(from, to).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::memory::InMemory;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::object_store::{ObjectStore, ObjectStoreParams};
+
+    fn test_data(len: usize) -> Bytes {
+        Bytes::from((0..len).map(|i| i as u8).collect::<Vec<_>>())
+    }
+
+    fn open_cache(dir: &std::path::Path, capacity: u64) -> Arc<DiskCache> {
+        DiskCache::open(DiskCacheConfig {
+            path: dir.to_path_buf(),
+            capacity,
+            block_size: 16,
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_read_through() {
+        let dir = tempdir().unwrap();
+        let cache = open_cache(dir.path(), 1024 * 1024);
+        let store = cache.wrap(Arc::new(InMemory::new()));
+        let path = Path::from("data/foo.lance");
+        let data = test_data(100);
+        store.put(&path, data.clone()).await.unwrap();
+
+        // Blocks 0, 1 and 2 are fetched with a single request
+        let bytes = store.get_range(&path, 10..40).await.unwrap();
+        assert_eq!(bytes, data.slice(10..40));
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (0, 3));
+        assert_eq!(stats.bytes_fetched, 48);
+
+        let bytes = store.get_range(&path, 12..33).await.unwrap();
+        assert_eq!(bytes, data.slice(12..33));
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (3, 3));
+        assert_eq!(stats.bytes_from_cache, 48);
+
+        // The last block is shorter than the block size
+        let bytes = store.get_range(&path, 90..100).await.unwrap();
+        assert_eq!(bytes, data.slice(90..100));
+        let bytes = store.get_range(&path, 95..100).await.unwrap();
+        assert_eq!(bytes, data.slice(95..100));
+        assert_eq!(cache.stats().hits, 5);
+
+        assert!(store.get_range(&path, 90..120).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lru_eviction() {
+        let dir = tempdir().unwrap();
+        let cache = open_cache(dir.path(), 1024 * 1024);
+        let store = cache.wrap(Arc::new(InMemory::new()));
+        let path = Path::from("foo.lance");
+        let data = test_data(64);
+        store.put(&path, data.clone()).await.unwrap();
+        store.get_range(&path, 0..1).await.unwrap();
+        let block_file_size = cache.stats().size_bytes;
+        drop(store);
+        drop(cache);
+
+        // Room for two blocks
+        let dir = tempdir().unwrap();
+        let cache = open_cache(dir.path(), 2 * block_file_size);
+        let store = cache.wrap(Arc::new(InMemory::new()));
+        store.put(&path, data.clone()).await.unwrap();
+
+        store.get_range(&path, 0..1).await.unwrap();
+        store.get_range(&path, 16..17).await.unwrap();
+        // Touch block 0 so block 1 is the least recently used
+        store.get_range(&path, 0..1).await.unwrap();
+        store.get_range(&path, 32..33).await.unwrap();
+
+        let stats = cache.stats();
+        assert_eq!(stats.evictions, 1);
+        assert_eq!(stats.size_bytes, 2 * block_file_size);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+
+        store.get_range(&path, 0..1).await.unwrap();
+        assert_eq!(cache.stats().misses, 3);
+        store.get_range(&path, 16..17).await.unwrap();
+        assert_eq!(cache.stats().misses, 4);
+    }
+
+    #[tokio::test]
+    async fn test_validate_version() {
+        let dir = tempdir().unwrap();
+        let cache = open_cache(dir.path(), 1024 * 1024);
+        let inner = Arc::new(InMemory::new());
+        let store = cache.wrap(inner.clone());
+        let path = Path::from("foo.lance");
+        store.put(&path, test_data(32)).await.unwrap();
+        store.get_range(&path, 0..32).await.unwrap();
+
+        // Modified behind the cache's back, noticed through head
+        let new_data = Bytes::from(vec![7u8; 40]);
+        inner.put(&path, new_data.clone()).await.unwrap();
+        assert_eq!(store.head(&path).await.unwrap().size, 40);
+        let bytes = store.get_range(&path, 0..40).await.unwrap();
+        assert_eq!(bytes, new_data);
+        assert_eq!(cache.stats().hits, 0);
+
+        // Modified through the cache
+        let newer_data = Bytes::from(vec![9u8; 40]);
+        store.put(&path, newer_data.clone()).await.unwrap();
+        let bytes = store.get_range(&path, 0..40).await.unwrap();
+        assert_eq!(bytes, newer_data);
+        assert_eq!(cache.stats().hits, 0);
+        let bytes = store.get_range(&path, 0..40).await.unwrap();
+        assert_eq!(bytes, newer_data);
+        assert_eq!(cache.stats().hits, 3);
+    }
+
+    #[test]
+    fn test_is_cacheable() {
+        assert!(is_cacheable(&Path::from("ds/data/abc.lance")));
+        assert!(is_cacheable(&Path::from("ds/_versions/3.manifest")));
+        assert!(is_cacheable(&Path::from("ds/_indices/abc/index.idx")));
+        assert!(!is_cacheable(&Path::from("ds/_latest.manifest")));
+    }
+
+    #[tokio::test]
+    async fn test_reopen() {
+        let dir = tempdir().unwrap();
+        let inner = Arc::new(InMemory::new());
+        let path = Path::from("foo.lance");
+        let data = test_data(48);
+        inner.put(&path, data.clone()).await.unwrap();
+
+        let cache = open_cache(dir.path(), 1024 * 1024);
+        let store = cache.wrap(inner.clone());
+        store.get_range(&path, 0..48).await.unwrap();
+        let size = cache.stats().size_bytes;
+        // Opening the same directory again shares the cache
+        assert!(Arc::ptr_eq(&cache, &open_cache(dir.path(), 1024 * 1024)));
+        drop(store);
+        drop(cache);
+
+        let cache = open_cache(dir.path(), 1024 * 1024);
+        assert_eq!(cache.stats().size_bytes, size);
+        let store = cache.wrap(inner.clone());
+        let bytes = store.get_range(&path, 5..45).await.unwrap();
+        assert_eq!(bytes, data.slice(5..45));
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (3, 0));
+        drop(store);
+        drop(cache);
+
+        // Blocks left by a previous process are checked against the object
+        // before they are served
+        let new_data = Bytes::from(vec![7u8; 48]);
+        inner.put(&path, new_data.clone()).await.unwrap();
+        let cache = open_cache(dir.path(), 1024 * 1024);
+        let store = cache.wrap(inner);
+        let bytes = store.get_range(&path, 0..48).await.unwrap();
+        assert_eq!(bytes, new_data);
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (0, 3));
+    }
+
+    #[tokio::test]
+    async fn test_configure_from_storage_options() {
+        let dir = tempdir().unwrap();
+        let options = HashMap::from([
+            (
+                DISK_CACHE_PATH_KEY.to_string(),
+                dir.path().to_str().unwrap().to_string(),
+            ),
+            (DISK_CACHE_BLOCK_SIZE_KEY.to_string(), "16".to_string()),
+        ]);
+        let params = ObjectStoreParams {
+            storage_options: Some(options.clone()),
+            ..Default::default()
+        };
+        let (store, base) = ObjectStore::from_uri_and_params("memory:///foo", &params)
+            .await
+            .unwrap();
+        let path = base.child("data.lance");
+        store.put(&path, &test_data(64)).await.unwrap();
+        let reader = store.open(&path).await.unwrap();
+        reader.get_range(0..20).await.unwrap();
+        reader.get_range(0..20).await.unwrap();
+
+        let config = DiskCacheConfig::from_storage_options(&options)
+            .unwrap()
+            .unwrap();
+        assert_eq!(config.capacity, DEFAULT_DISK_CACHE_CAPACITY);
+        let stats = DiskCache::open(config).unwrap().stats();
+        assert_eq!((stats.hits, stats.misses), (2, 2));
+
+        let bad_options = HashMap::from([
+            (DISK_CACHE_PATH_KEY.to_string(), "/tmp".to_string()),
+            (DISK_CACHE_CAPACITY_KEY.to_string(), "lots".to_string()),
+        ]);
+        assert!(DiskCacheConfig::from_storage_options(&bad_options).is_err());
+    }
+}
@@ -1,8 +1,10 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
+use std::collections::HashMap;
 use std::pin::Pin;
-use std::task::{Context, Poll};
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
 
 use async_trait::async_trait;
 use object_store::{path::Path, MultipartId, ObjectStore};
@@ -12,6 +14,7 @@ use tokio::io::{AsyncWrite, AsyncWriteExt};
 
 use lance_core::{Error, Result};
 
+use crate::encryption::{BlockEncryptor, KeyProvider};
 use crate::traits::Writer;
 
 /// AsyncWrite with the capability to tell the position the data is written.
@@ -20,13 +23,50 @@ use crate::traits::Writer;
 pub struct ObjectWriter {
     // TODO: wrap writer with a BufWriter.
     #[pin]
-    writer: Box<dyn AsyncWrite + Send + Unpin>,
+    writer: ChecksumWriter,
 
     // TODO: pub(crate)
     pub multipart_id: MultipartId,
     path: Path,
 
     cursor: usize,
+
+    // Set when the object is encrypted, `cursor` counts plaintext bytes then.
+    encryptor: Option<BlockEncryptor>,
+
+    // Receives the checksum of the object once it is written.
+    checksum_recorder: Option<Arc<ChecksumRecorder>>,
+}
+
+/// Collects the CRC32C of the objects written through the writers it is attached to.
+///
+/// See [ObjectStore::with_checksum_recorder](crate::object_store::ObjectStore::with_checksum_recorder).
+#[derive(Debug, Default)]
+pub struct ChecksumRecorder {
+    checksums: Mutex<HashMap<Path, u32>>,
+}
+
+impl ChecksumRecorder {
+    pub fn record(&self, path: &Path, checksum: u32) {
+        self.checksums
+            .lock()
+            .unwrap()
+            .insert(path.clone(), checksum);
+    }
+
+    /// Checksums of the objects written under `dir`, keyed by their path relative to `dir`.
+    pub fn checksums_under(&self, dir: &Path) -> HashMap<String, u32> {
+        let prefix = format!("{}/", dir);
+        self.checksums
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(path, checksum)| {
+                let name = path.as_ref().strip_prefix(&prefix)?;
+                Some((name.to_string(), *checksum))
+            })
+            .collect()
+    }
 }
 
 impl ObjectWriter {
@@ -41,22 +81,80 @@ impl ObjectWriter {
                 })?;
 
         Ok(Self {
-            writer,
+            writer: ChecksumWriter {
+                inner: writer,
+                checksum: 0,
+            },
             multipart_id,
             cursor: 0,
             path: path.clone(),
+            encryptor: None,
+            checksum_recorder: None,
         })
     }
 
+    /// Record the checksum of the object in `recorder` once it is written.
+    pub fn with_checksum_recorder(mut self, recorder: Option<Arc<ChecksumRecorder>>) -> Self {
+        self.checksum_recorder = recorder;
+        self
+    }
+
+    /// Create a writer that encrypts the object with a new data key wrapped by `key_provider`.
+    pub async fn new_encrypted(
+        object_store: &dyn ObjectStore,
+        path: &Path,
+        key_provider: &dyn KeyProvider,
+    ) -> Result<Self> {
+        // Wrap the data key before starting the upload, so a failure doesn't leave it dangling.
+        let encryptor = BlockEncryptor::try_new(key_provider).await?;
+        let mut writer = Self::new(object_store, path).await?;
+        writer.encryptor = Some(encryptor);
+        Ok(writer)
+    }
+
+    /// CRC32C of the bytes stored so far.
+    ///
+    /// For encrypted objects this covers the ciphertext, so stored objects can be
+    /// checked without their keys. The value is final once the writer is shut down.
+    pub fn checksum(&self) -> u32 {
+        self.writer.checksum
+    }
+
     pub async fn shutdown(&mut self) -> Result<()> {
-        self.writer
-            .as_mut()
-            .shutdown()
-            .await
-            .map_err(|e| Error::IO {
-                message: format!("failed to shutdown object writer for {}: {}", self.path, e),
-                location: location!(),
-            })
+        AsyncWriteExt::shutdown(self).await.map_err(|e| Error::IO {
+            message: format!("failed to shutdown object writer for {}: {}", self.path, e),
+            location: location!(),
+        })
+    }
+}
+
+/// Computes the CRC32C of everything handed to the inner writer.
+#[pin_project]
+struct ChecksumWriter {
+    #[pin]
+    inner: Box<dyn AsyncWrite + Send + Unpin>,
+    checksum: u32,
+}
+
+impl AsyncWrite for ChecksumWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.project();
+        this.inner.poll_write(cx, buf).map_ok(|n| {
+            *this.checksum = crc32c::crc32c_append(*this.checksum, &buf[..n]);
+            n
+        })
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.project().inner.poll_shutdown(cx)
     }
 }
 
@@ -73,6 +171,12 @@ impl AsyncWrite for ObjectWriter {
         buf: &[u8],
     ) -> Poll<std::io::Result<usize>> {
         let mut this = self.project();
+        if let Some(encryptor) = this.encryptor.as_mut() {
+            ready!(encryptor.poll_drain(this.writer.as_mut(), cx))?;
+            let n = encryptor.buffer(buf)?;
+            *this.cursor += n;
+            return Poll::Ready(Ok(n));
+        }
         this.writer.as_mut().poll_write(cx, buf).map_ok(|n| {
             *this.cursor += n;
             n
@@ -80,11 +184,24 @@ impl AsyncWrite for ObjectWriter {
     }
 
     fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
-        self.project().writer.as_mut().poll_flush(cx)
+        let mut this = self.project();
+        if let Some(encryptor) = this.encryptor.as_mut() {
+            ready!(encryptor.poll_drain(this.writer.as_mut(), cx))?;
+        }
+        this.writer.as_mut().poll_flush(cx)
     }
 
     fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
-        self.project().writer.as_mut().poll_shutdown(cx)
+        let mut this = self.project();
+        if let Some(encryptor) = this.encryptor.as_mut() {
+            encryptor.finish()?;
+            ready!(encryptor.poll_drain(this.writer.as_mut(), cx))?;
+        }
+        ready!(this.writer.as_mut().poll_shutdown(cx))?;
+        if let Some(recorder) = this.checksum_recorder.as_ref() {
+            recorder.record(this.path, this.writer.checksum);
+        }
+        Poll::Ready(Ok(()))
     }
 }
 
@@ -115,5 +232,29 @@ mod tests {
         assert_eq!(object_writer.tell().await.unwrap(), 256 * 3);
 
         object_writer.shutdown().await.unwrap();
+        assert_eq!(object_writer.checksum(), crc32c::crc32c(&[0; 256 * 3]));
+    }
+
+    #[tokio::test]
+    async fn test_checksum_recorder() {
+        let store = InMemory::new();
+        let recorder = Arc::new(ChecksumRecorder::default());
+        for path in ["dir/a", "dir/sub/b", "other/c"] {
+            let mut object_writer = ObjectWriter::new(&store, &Path::from(path))
+                .await
+                .unwrap()
+                .with_checksum_recorder(Some(recorder.clone()));
+            object_writer.write_all(path.as_bytes()).await.unwrap();
+            object_writer.shutdown().await.unwrap();
+        }
+
+        let checksums = recorder.checksums_under(&Path::from("dir"));
+        assert_eq!(
+            checksums,
+            HashMap::from([
+                ("a".to_string(), crc32c::crc32c(b"dir/a")),
+                ("sub/b".to_string(), crc32c::crc32c(b"dir/sub/b")),
+            ])
+        );
     }
 }
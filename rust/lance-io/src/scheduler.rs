@@ -3,20 +3,25 @@
 
 use bytes::Bytes;
 use futures::channel::oneshot;
-use futures::{FutureExt, StreamExt, TryFutureExt};
+use futures::{Future, TryFutureExt};
 use object_store::path::Path;
 use snafu::{location, Location};
-use std::future::Future;
+use std::collections::{BTreeMap, HashMap};
 use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use tokio::sync::mpsc;
-use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio::sync::Notify;
 
 use lance_core::{Error, Result};
 
 use crate::object_store::ObjectStore;
 use crate::traits::Reader;
 
+/// The default number of bytes that may be loaded, but not yet consumed, at any one time
+pub const DEFAULT_IO_BUFFER_SIZE: u64 = 2 * 1024 * 1024 * 1024;
+/// The default upper bound on the size of a single coalesced read
+pub const DEFAULT_MAX_COALESCED_BYTES: u64 = 16 * 1024 * 1024;
+
 // There is one instance of MutableBatch shared by all the I/O operations
 // that make up a single request.  When all the I/O operations complete
 // then the MutableBatch goes out of scope and the batch request is considered
@@ -79,13 +84,21 @@ impl<F: FnOnce(Result<Vec<Bytes>>) + Send> DataSink for MutableBatch<F> {
     }
 }
 
+/// A single read against the object store
+///
+/// A read may satisfy several of the ranges in a request if they were coalesced
 struct IoTask {
+    request_id: u64,
     reader: Arc<dyn Reader>,
     to_read: Range<u64>,
     when_done: Box<dyn FnOnce(Result<Bytes>) + Send>,
 }
 
 impl IoTask {
+    fn num_bytes(&self) -> u64 {
+        self.to_read.end - self.to_read.start
+    }
+
     async fn run(self) {
         let bytes = self
             .reader
@@ -95,28 +108,294 @@ impl IoTask {
     }
 }
 
+// Book-keeping for a single call to submit_request
+struct RequestState {
+    priority: u64,
+    // Bytes that have been (or are being) loaded and that will be released
+    // once the request is consumed
+    bytes_launched: u64,
+    launched: bool,
+    num_pending: usize,
+}
+
+struct IoQueueState {
+    // The number of additional IOPS we can launch
+    iops_avail: u32,
+    // The number of additional bytes we can load.  This can go negative if a
+    // request larger than the buffer is allowed through.
+    bytes_avail: i64,
+    // Reads that have not been launched yet, ordered by (priority, submission order)
+    pending: BTreeMap<(u64, u64), IoTask>,
+    requests: HashMap<u64, RequestState>,
+    // The priorities of requests holding loaded but unconsumed bytes (with counts)
+    priorities_in_flight: BTreeMap<u64, usize>,
+    next_id: u64,
+    closed: bool,
+}
+
+impl IoQueueState {
+    fn new(io_parallelism: u32, io_buffer_size: u64) -> Self {
+        Self {
+            iops_avail: io_parallelism,
+            bytes_avail: io_buffer_size as i64,
+            pending: BTreeMap::new(),
+            requests: HashMap::new(),
+            priorities_in_flight: BTreeMap::new(),
+            next_id: 0,
+            closed: false,
+        }
+    }
+
+    fn next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    fn push_request(&mut self, request_id: u64, priority: u64, tasks: Vec<IoTask>) {
+        self.requests.insert(
+            request_id,
+            RequestState {
+                priority,
+                bytes_launched: 0,
+                launched: false,
+                num_pending: tasks.len(),
+            },
+        );
+        for task in tasks {
+            let task_id = self.next_id();
+            self.pending.insert((priority, task_id), task);
+        }
+    }
+
+    // Pops the highest priority read if there is capacity to run it
+    //
+    // A read is always allowed through (even if it exceeds the buffer) when it
+    // belongs to the highest priority request that has loaded data.  Otherwise
+    // the consumer could end up waiting on a request that is itself waiting for
+    // lower priority data to be consumed.
+    fn next_task(&mut self) -> Option<IoTask> {
+        if self.iops_avail == 0 {
+            return None;
+        }
+        let (&(priority, _), task) = self.pending.first_key_value()?;
+        let is_highest_priority = self
+            .priorities_in_flight
+            .first_key_value()
+            .map(|(in_flight, _)| priority <= *in_flight)
+            .unwrap_or(true);
+        if task.num_bytes() as i64 > self.bytes_avail && !is_highest_priority {
+            return None;
+        }
+        let (_, task) = self.pending.pop_first().unwrap();
+        let num_bytes = task.num_bytes();
+        self.iops_avail -= 1;
+        self.bytes_avail -= num_bytes as i64;
+        let request = self
+            .requests
+            .get_mut(&task.request_id)
+            .expect("pending I/O for an unknown request");
+        request.num_pending -= 1;
+        request.bytes_launched += num_bytes;
+        if !request.launched {
+            request.launched = true;
+            *self.priorities_in_flight.entry(priority).or_default() += 1;
+        }
+        Some(task)
+    }
+
+    // Called when a request has been consumed (or abandoned)
+    fn release_request(&mut self, request_id: u64) {
+        let Some(request) = self.requests.remove(&request_id) else {
+            return;
+        };
+        self.bytes_avail += request.bytes_launched as i64;
+        if request.launched {
+            let count = self
+                .priorities_in_flight
+                .get_mut(&request.priority)
+                .expect("in-flight request without a priority");
+            *count -= 1;
+            if *count == 0 {
+                self.priorities_in_flight.remove(&request.priority);
+            }
+        }
+        if request.num_pending > 0 {
+            // The request was dropped before all of its I/O was launched
+            self.pending.retain(|_, task| task.request_id != request_id);
+        }
+    }
+}
+
+// The queue of reads shared between the scheduler, the I/O loop, and the
+// futures handed out to callers
+struct IoQueue {
+    state: Mutex<IoQueueState>,
+    notify: Notify,
+}
+
+impl IoQueue {
+    fn new(io_parallelism: u32, io_buffer_size: u64) -> Self {
+        Self {
+            state: Mutex::new(IoQueueState::new(io_parallelism, io_buffer_size)),
+            notify: Notify::new(),
+        }
+    }
+
+    fn push_request(&self, request_id: u64, priority: u64, tasks: Vec<IoTask>) {
+        self.state
+            .lock()
+            .unwrap()
+            .push_request(request_id, priority, tasks);
+        self.notify.notify_one();
+    }
+
+    fn next_request_id(&self) -> u64 {
+        self.state.lock().unwrap().next_id()
+    }
+
+    // Waits for the next read that is allowed to run.  Returns None once the
+    // queue is closed and drained.
+    async fn pop(&self) -> Option<IoTask> {
+        loop {
+            {
+                let mut state = self.state.lock().unwrap();
+                if let Some(task) = state.next_task() {
+                    return Some(task);
+                }
+                if state.closed && state.pending.is_empty() {
+                    return None;
+                }
+            }
+            self.notify.notified().await;
+        }
+    }
+
+    fn on_iop_complete(&self) {
+        self.state.lock().unwrap().iops_avail += 1;
+        self.notify.notify_one();
+    }
+
+    fn on_request_consumed(&self, request_id: u64) {
+        self.state.lock().unwrap().release_request(request_id);
+        self.notify.notify_one();
+    }
+
+    fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.notify.notify_one();
+    }
+}
+
+// Held by the future returned from submit_request.  Once the caller has received
+// the data (or given up on it) the bytes no longer count against the buffer.
+struct RequestPermit {
+    queue: Arc<IoQueue>,
+    request_id: u64,
+}
+
+impl Drop for RequestPermit {
+    fn drop(&mut self) {
+        self.queue.on_request_consumed(self.request_id);
+    }
+}
+
 // Every time a scheduler starts up it launches a task to run the I/O loop.  This loop
-// repeats endlessly until the scheduler is destroyed.
-async fn run_io_loop(tasks: mpsc::UnboundedReceiver<IoTask>, io_capacity: u32) {
-    let io_stream = UnboundedReceiverStream::new(tasks);
-    let tokio_task_stream = io_stream.map(|task| tokio::spawn(task.run()));
-    let mut tokio_task_stream = tokio_task_stream.buffer_unordered(io_capacity as usize);
-    while tokio_task_stream.next().await.is_some() {
-        // We don't actually do anything with the results here, they are sent
-        // via the io tasks's when_done.  Instead we just keep chugging away
-        // indefinitely until the tasks receiver returns none (scheduler has
-        // been shut down)
+// repeats until the scheduler is destroyed and all queued I/O has been launched.
+async fn run_io_loop(queue: Arc<IoQueue>) {
+    while let Some(task) = queue.pop().await {
+        let queue = queue.clone();
+        // The results are sent via the io task's when_done
+        tokio::spawn(async move {
+            task.run().await;
+            queue.on_iop_complete();
+        });
+    }
+}
+
+/// Merges nearby ranges into larger reads
+///
+/// Ranges are merged if the gap between them is at most `max_gap` and the
+/// merged read would be no larger than `max_size`.  Returns each read along
+/// with the indices (into `ranges`) of the ranges it satisfies.
+fn coalesce_ranges(
+    ranges: &[Range<u64>],
+    max_gap: u64,
+    max_size: u64,
+) -> Vec<(Range<u64>, Vec<usize>)> {
+    let mut order = (0..ranges.len()).collect::<Vec<_>>();
+    order.sort_by_key(|idx| (ranges[*idx].start, ranges[*idx].end));
+
+    let mut reads: Vec<(Range<u64>, Vec<usize>)> = Vec::with_capacity(ranges.len());
+    for idx in order {
+        let range = &ranges[idx];
+        if let Some((read, members)) = reads.last_mut() {
+            let merged_end = read.end.max(range.end);
+            if range.start <= read.end.saturating_add(max_gap)
+                && merged_end - read.start <= max_size
+            {
+                read.end = merged_end;
+                members.push(idx);
+                continue;
+            }
+        }
+        reads.push((range.clone(), vec![idx]));
+    }
+    reads
+}
+
+/// Configuration for a [`StoreScheduler`]
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    /// The maximum number of parallel requests that will be allowed
+    pub io_parallelism: u32,
+    /// The maximum number of bytes that may be loaded but not yet consumed
+    ///
+    /// Once this is reached, new I/O is paused until earlier requests are
+    /// received by their callers.  The highest priority request is always
+    /// allowed to proceed, even if it is larger than the buffer.
+    pub io_buffer_size: u64,
+    /// Ranges within a single request that are separated by at most this
+    /// many bytes are merged into a single read.
+    ///
+    /// If not set, the block size of the object store is used.
+    pub coalesce_gap: Option<u64>,
+    /// Coalesced reads will not grow beyond this many bytes
+    pub max_coalesced_bytes: u64,
+}
+
+impl SchedulerConfig {
+    /// Creates a configuration with the given parallelism and default settings otherwise
+    pub fn new(io_parallelism: u32) -> Self {
+        Self {
+            io_parallelism,
+            io_buffer_size: DEFAULT_IO_BUFFER_SIZE,
+            coalesce_gap: None,
+            max_coalesced_bytes: DEFAULT_MAX_COALESCED_BYTES,
+        }
     }
 }
 
-/// An I/O scheduler which wraps an ObjectStore and throttles the amount of\
+/// An I/O scheduler which wraps an ObjectStore and throttles the amount of
 /// parallel I/O that can be run.
 ///
-/// TODO: This will also add coalescing
-#[derive(Debug)]
+/// Nearby ranges in a request are coalesced into a single read, the number of
+/// bytes loaded but not yet consumed is bounded, and requests are served in
+/// priority order (by default, the order in which they were submitted).
 pub struct StoreScheduler {
     object_store: Arc<ObjectStore>,
-    io_submitter: mpsc::UnboundedSender<IoTask>,
+    config: SchedulerConfig,
+    queue: Arc<IoQueue>,
+    next_priority: AtomicU64,
+}
+
+impl std::fmt::Debug for StoreScheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StoreScheduler")
+            .field("object_store", &self.object_store)
+            .field("config", &self.config)
+            .finish()
+    }
 }
 
 impl StoreScheduler {
@@ -127,38 +406,52 @@ impl StoreScheduler {
     /// * object_store - the store to wrap
     /// * io_capacity - the maximum number of parallel requests that will be allowed
     pub fn new(object_store: Arc<ObjectStore>, io_capacity: u32) -> Arc<Self> {
-        // TODO: we don't have any backpressure in place if the compute thread falls
-        // behind.  The scheduler thread will schedule ALL of the I/O and then the
-        // loaded data will eventually pile up.
-        //
-        // We could bound this channel but that wouldn't help.  If the decode thread
-        // was paused then the I/O loop would keep running and reading from this channel.
-        //
-        // Once the reader is finished we should revisit.  We will probably want to convert
-        // from `when_done` futures to delivering data into a queue.  That queue should fill
-        // up, causing the I/O loop to pause.
-        let (reg_tx, reg_rx) = mpsc::unbounded_channel();
+        Self::new_with_config(object_store, SchedulerConfig::new(io_capacity))
+    }
+
+    /// Create a new scheduler with the given configuration
+    pub fn new_with_config(object_store: Arc<ObjectStore>, config: SchedulerConfig) -> Arc<Self> {
+        let queue = Arc::new(IoQueue::new(config.io_parallelism, config.io_buffer_size));
         let scheduler = Self {
             object_store,
-            io_submitter: reg_tx,
+            config,
+            queue: queue.clone(),
+            next_priority: AtomicU64::new(0),
         };
-        tokio::task::spawn(async move { run_io_loop(reg_rx, io_capacity).await });
+        tokio::task::spawn(async move { run_io_loop(queue).await });
         Arc::new(scheduler)
     }
 
     /// Open a file for reading
     pub async fn open_file(self: &Arc<Self>, path: &Path) -> Result<FileScheduler> {
-        let reader = self.object_store.open(path).await?;
+        self.open_file_with_key_id(path, None).await
+    }
+
+    /// Open a file for reading that must be encrypted with `key_id`, if set
+    ///
+    /// See [ObjectStore::open_with_key_id]
+    pub async fn open_file_with_key_id(
+        self: &Arc<Self>,
+        path: &Path,
+        key_id: Option<&str>,
+    ) -> Result<FileScheduler> {
+        let reader = self.object_store.open_with_key_id(path, key_id).await?;
         Ok(FileScheduler {
             reader: reader.into(),
             root: self.clone(),
         })
     }
 
+    fn next_priority(&self) -> u64 {
+        self.next_priority.fetch_add(1, Ordering::Relaxed)
+    }
+
     fn do_submit_request(
         &self,
         reader: Arc<dyn Reader>,
         request: Vec<Range<u64>>,
+        request_id: u64,
+        priority: u64,
         tx: oneshot::Sender<Result<Vec<Bytes>>>,
     ) {
         let num_iops = request.len() as u32;
@@ -173,34 +466,86 @@ impl StoreScheduler {
             num_iops,
         ))));
 
-        for (task_idx, iop) in request.into_iter().enumerate() {
-            let dest = dest.clone();
-            let task = IoTask {
-                reader: reader.clone(),
-                to_read: iop,
-                when_done: Box::new(move |bytes| {
-                    let mut dest = dest.lock().unwrap();
-                    dest.deliver_data(bytes.map(|bytes| (task_idx, bytes)));
-                }),
-            };
-            if self.io_submitter.send(task).is_err() {
-                panic!("unable to submit I/O because the I/O thread has panic'd");
-            }
-        }
+        let max_gap = self
+            .config
+            .coalesce_gap
+            .unwrap_or(reader.block_size() as u64);
+        let reads = coalesce_ranges(&request, max_gap, self.config.max_coalesced_bytes);
+
+        let tasks = reads
+            .into_iter()
+            .map(|(read, members)| {
+                let dest = dest.clone();
+                let pieces = members
+                    .into_iter()
+                    .map(|idx| {
+                        let range = &request[idx];
+                        (
+                            idx,
+                            (range.start - read.start) as usize..(range.end - read.start) as usize,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                IoTask {
+                    request_id,
+                    reader: reader.clone(),
+                    to_read: read.clone(),
+                    when_done: Box::new(move |bytes: Result<Bytes>| {
+                        let mut dest = dest.lock().unwrap();
+                        match bytes {
+                            Ok(bytes) if bytes.len() as u64 == read.end - read.start => {
+                                // Split the coalesced read back out into the requested ranges
+                                for (idx, piece) in pieces {
+                                    dest.deliver_data(Ok((idx, bytes.slice(piece))));
+                                }
+                            }
+                            Ok(bytes) => dest.deliver_data(Err(Error::IO {
+                                message: format!(
+                                    "Expected {} bytes when reading range {:?} but received {}",
+                                    read.end - read.start,
+                                    read,
+                                    bytes.len()
+                                ),
+                                location: location!(),
+                            })),
+                            Err(err) => dest.deliver_data(Err(err)),
+                        }
+                    }),
+                }
+            })
+            .collect::<Vec<_>>();
+        self.queue.push_request(request_id, priority, tasks);
     }
 
     fn submit_request(
         &self,
         reader: Arc<dyn Reader>,
         request: Vec<Range<u64>>,
+        priority: u64,
     ) -> impl Future<Output = Result<Vec<Bytes>>> + Send {
         let (tx, rx) = oneshot::channel::<Result<Vec<Bytes>>>();
 
-        self.do_submit_request(reader, request, tx);
+        let request_id = self.queue.next_request_id();
+        let permit = RequestPermit {
+            queue: self.queue.clone(),
+            request_id,
+        };
+        self.do_submit_request(reader, request, request_id, priority, tx);
+
+        async move {
+            // Right now, it isn't possible for I/O to be cancelled so a cancel error should
+            // not occur
+            let data = rx.await.unwrap();
+            // The data now belongs to the caller and no longer counts against the buffer
+            drop(permit);
+            data
+        }
+    }
+}
 
-        // Right now, it isn't possible for I/O to be cancelled so a cancel error should
-        // not occur
-        rx.map(|wrapped_err| wrapped_err.unwrap())
+impl Drop for StoreScheduler {
+    fn drop(&mut self) {
+        self.queue.close();
     }
 }
 
@@ -214,13 +559,30 @@ pub struct FileScheduler {
 impl FileScheduler {
     /// Submit a batch of I/O requests to the reader
     ///
-    /// The requests will be queued in a FIFO manner and, when all requests
-    /// have been fulfilled, the returned future will be completed.
+    /// Requests are prioritized in the order they are submitted and, when all
+    /// ranges have been fulfilled, the returned future will be completed.
+    /// Nearby ranges may be fetched with a single read.
     pub fn submit_request(
         &self,
         request: Vec<Range<u64>>,
     ) -> impl Future<Output = Result<Vec<Bytes>>> + Send {
-        self.root.submit_request(self.reader.clone(), request)
+        let priority = self.root.next_priority();
+        self.root
+            .submit_request(self.reader.clone(), request, priority)
+    }
+
+    /// Submit a batch of I/O requests with an explicit priority
+    ///
+    /// Requests with a lower priority value are run first.  Note that
+    /// [`Self::submit_request`] uses the number of previously submitted requests
+    /// as its priority.
+    pub fn submit_request_with_priority(
+        &self,
+        request: Vec<Range<u64>>,
+        priority: u64,
+    ) -> impl Future<Output = Result<Vec<Bytes>>> + Send {
+        self.root
+            .submit_request(self.reader.clone(), request, priority)
     }
 
     /// Submit a single IOP to the reader
@@ -295,4 +657,164 @@ mod tests {
             offset += READ_SIZE;
         }
     }
+
+    #[test]
+    fn test_coalesce_ranges() {
+        let ranges = vec![100..200, 0..10, 12..20, 150..160, 1000..1010];
+        let reads = coalesce_ranges(&ranges, 4, 1024);
+        assert_eq!(
+            reads,
+            vec![
+                (0..20, vec![1, 2]),
+                (100..200, vec![0, 3]),
+                (1000..1010, vec![4])
+            ]
+        );
+
+        // Reads are not allowed to grow past the maximum size
+        let reads = coalesce_ranges(&ranges, 4, 16);
+        assert_eq!(reads.len(), 5);
+
+        // Without a gap only adjacent ranges are merged
+        let reads = coalesce_ranges(&[0..10, 10..20, 21..30], 0, 1024);
+        assert_eq!(reads, vec![(0..20, vec![0, 1]), (21..30, vec![2])]);
+    }
+
+    async fn write_random_file(obj_store: &ObjectStore, path: &Path, size: usize) -> Vec<u8> {
+        let mut some_data = vec![0; size];
+        rand::thread_rng().fill_bytes(&mut some_data);
+        obj_store.put(path, &some_data).await.unwrap();
+        some_data
+    }
+
+    #[tokio::test]
+    async fn test_coalesced_request() {
+        let tmpdir = tempdir().unwrap();
+        let tmp_path = Path::parse(tmpdir.path().to_str().unwrap()).unwrap();
+        let tmp_file = tmp_path.child("foo.file");
+
+        let obj_store = Arc::new(ObjectStore::local());
+        let some_data = write_random_file(&obj_store, &tmp_file, 64 * 1024).await;
+
+        let scheduler = StoreScheduler::new(obj_store, 4);
+        let file_scheduler = scheduler.open_file(&tmp_file).await.unwrap();
+
+        // Out of order, overlapping, adjacent, nearby, empty and far apart ranges
+        let request = vec![
+            5000..6000,
+            0..100,
+            100..200,
+            150..400,
+            300..300,
+            1000..1024,
+            60000..65536,
+        ];
+        let data = file_scheduler
+            .submit_request(request.clone())
+            .await
+            .unwrap();
+        assert_eq!(data.len(), request.len());
+        for (range, bytes) in request.iter().zip(data.iter()) {
+            assert_eq!(
+                &some_data[range.start as usize..range.end as usize],
+                bytes.as_ref()
+            );
+        }
+
+        // A read past the end of the file is an error
+        let err = file_scheduler
+            .submit_request(vec![0..10, 65000..70000])
+            .await;
+        assert!(err.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_backpressure() {
+        let tmpdir = tempdir().unwrap();
+        let tmp_path = Path::parse(tmpdir.path().to_str().unwrap()).unwrap();
+        let tmp_file = tmp_path.child("foo.file");
+
+        let obj_store = Arc::new(ObjectStore::local());
+        const READ_SIZE: u64 = 4 * 1024;
+        let some_data = write_random_file(&obj_store, &tmp_file, 8 * READ_SIZE as usize).await;
+
+        let config = SchedulerConfig {
+            io_buffer_size: 2 * READ_SIZE,
+            ..SchedulerConfig::new(16)
+        };
+        let scheduler = StoreScheduler::new_with_config(obj_store, config);
+        let file_scheduler = scheduler.open_file(&tmp_file).await.unwrap();
+
+        // Submit everything up front but don't consume anything yet
+        let reqs = (0..8)
+            .map(|idx| {
+                #[allow(clippy::single_range_in_vec_init)]
+                file_scheduler.submit_request(vec![idx * READ_SIZE..(idx + 1) * READ_SIZE])
+            })
+            .collect::<Vec<_>>();
+
+        // Wait for the reads that fit in the buffer to finish
+        loop {
+            {
+                let state = scheduler.queue.state.lock().unwrap();
+                if state.iops_avail == 16 && state.pending.len() == 6 {
+                    assert!(state.bytes_avail <= 0);
+                    break;
+                }
+            }
+            tokio::task::yield_now().await;
+        }
+
+        // Consuming the requests, in order, lets the rest of the I/O through
+        for (idx, req) in reqs.into_iter().enumerate() {
+            let data = req.await.unwrap();
+            let start = idx * READ_SIZE as usize;
+            assert_eq!(
+                &some_data[start..start + READ_SIZE as usize],
+                data[0].as_ref()
+            );
+        }
+        let state = scheduler.queue.state.lock().unwrap();
+        assert_eq!(state.bytes_avail, 2 * READ_SIZE as i64);
+        assert!(state.requests.is_empty());
+        assert!(state.priorities_in_flight.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_priority_order() {
+        let tmpdir = tempdir().unwrap();
+        let tmp_path = Path::parse(tmpdir.path().to_str().unwrap()).unwrap();
+        let tmp_file = tmp_path.child("foo.file");
+
+        let obj_store = ObjectStore::local();
+        write_random_file(&obj_store, &tmp_file, 1024).await;
+        let reader: Arc<dyn Reader> = obj_store.open(&tmp_file).await.unwrap().into();
+
+        let make_task = |request_id: u64, range: Range<u64>| IoTask {
+            request_id,
+            reader: reader.clone(),
+            to_read: range,
+            when_done: Box::new(|_| {}),
+        };
+
+        let mut state = IoQueueState::new(1, 1024);
+        state.push_request(0, 10, vec![make_task(0, 0..10), make_task(0, 20..30)]);
+        state.push_request(1, 2, vec![make_task(1, 100..110)]);
+
+        let task = state.next_task().unwrap();
+        assert_eq!(task.to_read, 100..110);
+        // Only one IOP may run at a time
+        assert!(state.next_task().is_none());
+        state.iops_avail += 1;
+        let task = state.next_task().unwrap();
+        assert_eq!(task.to_read, 0..10);
+
+        // Dropping a request removes its queued I/O and returns its bytes
+        state.iops_avail += 1;
+        state.release_request(0);
+        assert!(state.next_task().is_none());
+        state.release_request(1);
+        assert_eq!(state.bytes_avail, 1024);
+        assert!(state.pending.is_empty());
+    }
 }
@@ -6,26 +6,31 @@
 //! This module provides distance metrics for vectors.
 //!
 //! - `bf16, f16, f32, f64` types are supported.
+//! - `int8` and `uint8` vectors use integer kernels, without converting to floats.
 //! - SIMD is used when available, on `x86_64` and `aarch64` architectures.
 
 use std::sync::Arc;
 
-use arrow_array::{Array, FixedSizeListArray, Float32Array};
-use arrow_schema::ArrowError;
+use arrow_array::{cast::AsArray, Array, ArrowPrimitiveType, FixedSizeListArray, Float32Array};
+use arrow_schema::{ArrowError, DataType};
+use half::bf16;
+use lance_arrow::{bfloat16::BFloat16Array, FloatArray};
 
 pub mod cosine;
 pub mod dot;
 pub mod hamming;
 pub mod l2;
 pub mod norm_l2;
+pub mod sparse;
 
 pub use cosine::*;
 pub use dot::*;
 pub use l2::*;
 use lance_arrow::FloatToArrayType;
 pub use norm_l2::*;
+pub use sparse::*;
 
-use crate::Result;
+use crate::{Error, Result};
 
 /// Distance metrics type.
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -67,6 +72,73 @@ impl DistanceType {
     }
 }
 
+/// Compute the distance from one primitive vector to a batch of vectors of the same
+/// element type, using `distance` on the native values.
+///
+/// Null buffer of `to` is propagated to the returned array.
+pub(crate) fn primitive_distance_arrow_batch<T: ArrowPrimitiveType>(
+    from: &dyn Array,
+    to: &FixedSizeListArray,
+    distance: impl Fn(&[T::Native], &[T::Native]) -> f32,
+) -> Result<Arc<Float32Array>> {
+    let dimension = to.value_length() as usize;
+    debug_assert_eq!(from.len(), dimension);
+
+    let to_values = to
+        .values()
+        .as_primitive_opt::<T>()
+        .ok_or(Error::InvalidArgumentError(format!(
+            "Invalid type: expect {:?} got {:?}",
+            from.data_type(),
+            to.value_type()
+        )))?;
+    let from = from.as_primitive::<T>().values();
+    let dists = to_values
+        .values()
+        .chunks_exact(dimension)
+        .map(|v| distance(from, v));
+
+    Ok(Arc::new(Float32Array::new(
+        dists.collect(),
+        to.nulls().cloned(),
+    )))
+}
+
+/// Compute the distance from one bfloat16 vector to a batch of bfloat16 vectors,
+/// both stored as `FixedSizeBinary(2)`.
+///
+/// The buffers are viewed as [bf16] slices, not copied. Null buffer of `to` is
+/// propagated to the returned array.
+pub(crate) fn bfloat16_distance_arrow_batch(
+    from: &dyn Array,
+    to: &FixedSizeListArray,
+    distance: impl Fn(&[bf16], &[bf16]) -> f32,
+) -> Result<Arc<Float32Array>> {
+    let dimension = to.value_length() as usize;
+    debug_assert_eq!(from.len(), dimension);
+
+    let (DataType::FixedSizeBinary(2), DataType::FixedSizeBinary(2)) =
+        (from.data_type(), to.value_type())
+    else {
+        return Err(Error::InvalidArgumentError(format!(
+            "Invalid type: expect bfloat16 got {:?} and {:?}",
+            from.data_type(),
+            to.value_type()
+        )));
+    };
+    let from = BFloat16Array::try_from(from.as_fixed_size_binary().clone())?;
+    let to_values = BFloat16Array::try_from(to.values().as_fixed_size_binary().clone())?;
+    let dists = to_values
+        .as_slice()
+        .chunks_exact(dimension)
+        .map(|v| distance(from.as_slice(), v));
+
+    Ok(Arc::new(Float32Array::new(
+        dists.collect(),
+        to.nulls().cloned(),
+    )))
+}
+
 impl std::fmt::Display for DistanceType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
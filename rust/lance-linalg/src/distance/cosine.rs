@@ -5,17 +5,17 @@
 //!
 //! <https://en.wikipedia.org/wiki/Cosine_similarity>
 //!
-//! `bf16, f16, f32, f64` types are supported.
+//! `bf16, f16, f32, f64` types are supported, as well as `int8` and `uint8`.
 
 use std::sync::Arc;
 
 use arrow_array::{
     cast::AsArray,
-    types::{Float16Type, Float32Type, Float64Type},
+    types::{Float16Type, Float32Type, Float64Type, Int8Type, UInt8Type},
     Array, FixedSizeListArray, Float32Array,
 };
 use arrow_schema::DataType;
-use half::f16;
+use half::{bf16, f16};
 use lance_arrow::bfloat16::BFloat16Type;
 use lance_arrow::{ArrowFloatType, FloatArray, FloatToArrayType};
 #[cfg(feature = "fp16kernels")]
@@ -24,7 +24,11 @@ use lance_core::utils::cpu::FP16_SIMD_SUPPORT;
 use num_traits::{AsPrimitive, FromPrimitive};
 
 use super::norm_l2::norm_l2;
-use super::{dot::dot, Normalize};
+use super::{
+    bfloat16_distance_arrow_batch,
+    dot::{dot, dot_int8, dot_uint8},
+    primitive_distance_arrow_batch, Normalize,
+};
 use crate::simd::{
     f32::{f32x16, f32x8},
     FloatSimd, SIMD,
@@ -269,6 +273,28 @@ where
     T::ArrowType::cosine(from, to)
 }
 
+/// Cosine distance between two int8 slices.
+///
+/// The dot products are accumulated in integers.
+#[inline]
+pub fn cosine_distance_int8(from: &[i8], to: &[i8]) -> f32 {
+    let xy = dot_int8(from, to) as f32;
+    let x_sq = dot_int8(from, from) as f32;
+    let y_sq = dot_int8(to, to) as f32;
+    1.0 - xy / (x_sq.sqrt() * y_sq.sqrt())
+}
+
+/// Cosine distance between two uint8 slices.
+///
+/// The dot products are accumulated in integers.
+#[inline]
+pub fn cosine_distance_uint8(from: &[u8], to: &[u8]) -> f32 {
+    let xy = dot_uint8(from, to) as f32;
+    let x_sq = dot_uint8(from, from) as f32;
+    let y_sq = dot_uint8(to, to) as f32;
+    1.0 - xy / (x_sq.sqrt() * y_sq.sqrt())
+}
+
 /// Cosine Distance
 ///
 /// <https://en.wikipedia.org/wiki/Cosine_similarity>
@@ -339,6 +365,15 @@ pub fn cosine_distance_arrow_batch(
         DataType::Float16 => do_cosine_distance_arrow_batch::<Float16Type>(from.as_primitive(), to),
         DataType::Float32 => do_cosine_distance_arrow_batch::<Float32Type>(from.as_primitive(), to),
         DataType::Float64 => do_cosine_distance_arrow_batch::<Float64Type>(from.as_primitive(), to),
+        DataType::FixedSizeBinary(2) => {
+            bfloat16_distance_arrow_batch(from, to, cosine_distance::<bf16>)
+        }
+        DataType::Int8 => {
+            primitive_distance_arrow_batch::<Int8Type>(from, to, cosine_distance_int8)
+        }
+        DataType::UInt8 => {
+            primitive_distance_arrow_batch::<UInt8Type>(from, to, cosine_distance_uint8)
+        }
         _ => Err(Error::InvalidArgumentError(format!(
             "Unsupported data type {:?}",
             from.data_type()
@@ -350,6 +385,8 @@ pub fn cosine_distance_arrow_batch(
 mod tests {
     use super::*;
 
+    use lance_arrow::FixedSizeListArrayExt;
+
     use crate::test_utils::{
         arbitrary_bf16, arbitrary_f16, arbitrary_f32, arbitrary_f64, arbitrary_vector_pair,
     };
@@ -471,4 +508,23 @@ mod tests {
             do_cosine_test(&x, &y)?;
         }
     }
+
+    #[test]
+    fn test_cosine_int8() {
+        let x = [1_i8, 2, 3, 4];
+        let y = [-4_i8, 3, -2, 1];
+        let expected = cosine_dist_brute_force(&[1.0, 2.0, 3.0, 4.0], &[-4.0, 3.0, -2.0, 1.0]);
+        assert_relative_eq!(cosine_distance_int8(&x, &y), expected);
+        assert_relative_eq!(cosine_distance_uint8(&[1, 2], &[2, 4]), 0.0);
+
+        let to = FixedSizeListArray::try_new_from_values(
+            arrow_array::UInt8Array::from(vec![1, 2, 2, 1]),
+            2,
+        )
+        .unwrap();
+        let from = arrow_array::UInt8Array::from(vec![1, 2]);
+        let dists = cosine_distance_arrow_batch(&from, &to).unwrap();
+        assert_relative_eq!(dists.value(0), 0.0);
+        assert_relative_eq!(dists.value(1), 0.2);
+    }
 }
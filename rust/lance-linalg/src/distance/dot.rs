@@ -8,7 +8,7 @@ use std::ops::{AddAssign, Neg};
 use std::sync::Arc;
 
 use crate::Error;
-use arrow_array::types::{Float16Type, Float64Type};
+use arrow_array::types::{Float16Type, Float64Type, Int8Type, UInt8Type};
 use arrow_array::{cast::AsArray, types::Float32Type, Array, FixedSizeListArray, Float32Array};
 use arrow_schema::DataType;
 use half::{bf16, f16};
@@ -26,6 +26,8 @@ use crate::simd::{
 };
 use crate::Result;
 
+use super::{bfloat16_distance_arrow_batch, primitive_distance_arrow_batch};
+
 /// Default implementation of dot product.
 ///
 // The following code has been tuned for auto-vectorization.
@@ -80,6 +82,47 @@ where
     T::ArrowType::dot(from, to).neg()
 }
 
+/// Dot product between two int8 slices, accumulated in `i32`.
+#[inline]
+pub fn dot_int8(from: &[i8], to: &[i8]) -> i32 {
+    from.iter()
+        .zip(to.iter())
+        .map(|(&x, &y)| x as i32 * y as i32)
+        .sum()
+}
+
+/// Dot product between two uint8 slices, accumulated in `u32`.
+#[inline]
+pub fn dot_uint8(from: &[u8], to: &[u8]) -> u32 {
+    from.iter()
+        .zip(to.iter())
+        .map(|(&x, &y)| x as u32 * y as u32)
+        .sum()
+}
+
+/// Negative dot distance between two int8 slices.
+#[inline]
+pub fn dot_distance_int8(from: &[i8], to: &[i8]) -> f32 {
+    -(dot_int8(from, to) as f32)
+}
+
+/// Negative dot distance between two uint8 slices.
+#[inline]
+pub fn dot_distance_uint8(from: &[u8], to: &[u8]) -> f32 {
+    -(dot_uint8(from, to) as f32)
+}
+
+/// Negative dot distance between two vectors of different element types,
+/// i.e., an `int8` vector to a `f32` centroid.
+#[inline]
+pub fn dot_distance_mixed<X: AsPrimitive<f32>, Y: AsPrimitive<f32>>(from: &[X], to: &[Y]) -> f32 {
+    -from
+        .iter()
+        .zip(to.iter())
+        .map(|(&x, &y)| x.as_() * y.as_())
+        .sum::<f32>()
+}
+
 /// Dot product
 pub trait Dot: ArrowFloatType {
     /// Dot product.
@@ -263,6 +306,13 @@ pub fn dot_distance_arrow_batch(
         DataType::Float16 => do_dot_distance_arrow_batch::<Float16Type>(from.as_primitive(), to),
         DataType::Float32 => do_dot_distance_arrow_batch::<Float32Type>(from.as_primitive(), to),
         DataType::Float64 => do_dot_distance_arrow_batch::<Float64Type>(from.as_primitive(), to),
+        DataType::FixedSizeBinary(2) => {
+            bfloat16_distance_arrow_batch(from, to, dot_distance::<bf16>)
+        }
+        DataType::Int8 => primitive_distance_arrow_batch::<Int8Type>(from, to, dot_distance_int8),
+        DataType::UInt8 => {
+            primitive_distance_arrow_batch::<UInt8Type>(from, to, dot_distance_uint8)
+        }
         _ => Err(Error::InvalidArgumentError(format!(
             "Unsupported data type: {:?}",
             from.data_type()
@@ -274,9 +324,11 @@ pub fn dot_distance_arrow_batch(
 mod tests {
 
     use super::*;
+
     use crate::test_utils::{
         arbitrary_bf16, arbitrary_f16, arbitrary_f32, arbitrary_f64, arbitrary_vector_pair,
     };
+    use lance_arrow::FixedSizeListArrayExt;
     use num_traits::{Float, FromPrimitive};
     use proptest::prelude::*;
 
@@ -362,4 +414,22 @@ mod tests {
             do_dot_test(&x, &y)?;
         }
     }
+
+    #[test]
+    fn test_dot_int8() {
+        let x = [1_i8, -2, 127, -128];
+        let y = [3_i8, 4, 127, -128];
+        assert_eq!(dot_int8(&x, &y), 3 - 8 + 127 * 127 + 128 * 128);
+        assert_eq!(dot_distance_uint8(&[1, 2], &[255, 255]), -765.0);
+        assert_eq!(dot_distance_mixed(&[1_u8, 2], &[0.5_f32, -1.0]), 1.5);
+
+        let to = FixedSizeListArray::try_new_from_values(
+            arrow_array::Int8Array::from(vec![1, 2, -3, 4]),
+            2,
+        )
+        .unwrap();
+        let from = arrow_array::Int8Array::from(vec![1, 1]);
+        let dists = dot_distance_arrow_batch(&from, &to).unwrap();
+        assert_eq!(dists.values(), &[-3.0, -1.0]);
+    }
 }
@@ -10,7 +10,7 @@ use std::sync::Arc;
 
 use arrow_array::{
     cast::AsArray,
-    types::{Float16Type, Float32Type, Float64Type},
+    types::{Float16Type, Float32Type, Float64Type, Int8Type, UInt8Type},
     Array, FixedSizeListArray, Float32Array,
 };
 use arrow_schema::DataType;
@@ -27,7 +27,7 @@ use crate::simd::{
 };
 use crate::{Error, Result};
 
-use super::Normalize;
+use super::{bfloat16_distance_arrow_batch, primitive_distance_arrow_batch, Normalize};
 
 /// Calculate the L2 distance between two vectors.
 ///
@@ -61,6 +61,30 @@ pub fn l2_distance_uint_scalar(key: &[u8], target: &[u8]) -> f32 {
         .sum::<u32>() as f32
 }
 
+/// Calculate L2 distance between two int8 slices.
+#[inline]
+pub fn l2_distance_int8(key: &[i8], target: &[i8]) -> f32 {
+    key.iter()
+        .zip(target.iter())
+        .map(|(&x, &y)| (x.abs_diff(y) as u32).pow(2))
+        .sum::<u32>() as f32
+}
+
+/// Calculate L2 distance between two vectors of different element types,
+/// i.e., an `int8` vector to a `f32` centroid.
+///
+/// Each element is widened to `f32` inside the loop, so neither side is copied.
+#[inline]
+pub fn l2_distance_mixed<X: AsPrimitive<f32>, Y: AsPrimitive<f32>>(from: &[X], to: &[Y]) -> f32 {
+    from.iter()
+        .zip(to.iter())
+        .map(|(&x, &y)| {
+            let diff = x.as_() - y.as_();
+            diff * diff
+        })
+        .sum()
+}
+
 /// Calculate the L2 distance between two vectors, using scalar operations.
 ///
 /// It relies on LLVM for auto-vectorization and unrolling.
@@ -279,6 +303,11 @@ pub fn l2_distance_arrow_batch(
         DataType::Float16 => do_l2_distance_arrow_batch::<Float16Type>(from.as_primitive(), to),
         DataType::Float32 => do_l2_distance_arrow_batch::<Float32Type>(from.as_primitive(), to),
         DataType::Float64 => do_l2_distance_arrow_batch::<Float64Type>(from.as_primitive(), to),
+        DataType::FixedSizeBinary(2) => bfloat16_distance_arrow_batch(from, to, l2::<bf16>),
+        DataType::Int8 => primitive_distance_arrow_batch::<Int8Type>(from, to, l2_distance_int8),
+        DataType::UInt8 => {
+            primitive_distance_arrow_batch::<UInt8Type>(from, to, l2_distance_uint_scalar)
+        }
         _ => Err(Error::ComputeError(format!(
             "Unsupported data type: {}",
             from.data_type()
@@ -290,6 +319,8 @@ pub fn l2_distance_arrow_batch(
 mod tests {
     use super::*;
 
+    use lance_arrow::FixedSizeListArrayExt;
+
     use approx::assert_relative_eq;
     use proptest::prelude::*;
 
@@ -468,4 +499,36 @@ mod tests {
             (255_u32.pow(2) * 2048) as f32
         );
     }
+
+    #[test]
+    fn test_l2_arrow_batch_native_types() {
+        let to = FixedSizeListArray::try_new_from_values(
+            arrow_array::Int8Array::from(vec![1, 2, -3, 4, -128, 127]),
+            2,
+        )
+        .unwrap();
+        let from = arrow_array::Int8Array::from(vec![1, 2]);
+        let dists = l2_distance_arrow_batch(&from, &to).unwrap();
+        assert_eq!(
+            dists.values(),
+            &[0.0, 20.0, 129.0_f32.powi(2) + 125.0_f32.powi(2)]
+        );
+
+        let to = FixedSizeListArray::try_new_from_values(
+            lance_arrow::bfloat16::BFloat16Array::from_iter_values(
+                [0.0_f32, 1.0, 2.0, 3.0].map(bf16::from_f32),
+            )
+            .into_inner(),
+            2,
+        )
+        .unwrap();
+        let from = lance_arrow::bfloat16::BFloat16Array::from_iter_values(
+            [0.0_f32, 1.0].map(bf16::from_f32),
+        )
+        .into_inner();
+        let dists = l2_distance_arrow_batch(&from, &to).unwrap();
+        assert_eq!(dists.values(), &[0.0, 8.0]);
+
+        assert_eq!(l2_distance_mixed(&[1_i8, -2], &[0.5_f32, 1.0]), 9.25);
+    }
 }
@@ -0,0 +1,131 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Dot product over sparse vectors.
+//!
+//! Sparse vectors are represented as a pair of `(indices, values)` slices,
+//! with `indices` sorted in ascending order. See [lance_arrow::sparse].
+
+use std::sync::Arc;
+
+use arrow_array::{Array, Float32Array};
+use lance_arrow::sparse::SparseVectorArray;
+
+use crate::{Error, Result};
+
+/// Dot product between two sparse vectors.
+///
+/// Both `x_indices` and `y_indices` must be sorted in ascending order.
+#[inline]
+pub fn sparse_dot(x_indices: &[u32], x_values: &[f32], y_indices: &[u32], y_values: &[f32]) -> f32 {
+    debug_assert_eq!(x_indices.len(), x_values.len());
+    debug_assert_eq!(y_indices.len(), y_values.len());
+
+    let mut sum = 0.0;
+    let (mut i, mut j) = (0, 0);
+    while i < x_indices.len() && j < y_indices.len() {
+        match x_indices[i].cmp(&y_indices[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                sum += x_values[i] * y_values[j];
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    sum
+}
+
+/// Negative dot distance between two sparse vectors.
+#[inline]
+pub fn sparse_dot_distance(
+    x_indices: &[u32],
+    x_values: &[f32],
+    y_indices: &[u32],
+    y_values: &[f32],
+) -> f32 {
+    -sparse_dot(x_indices, x_values, y_indices, y_values)
+}
+
+/// Dot product between a sparse vector and a dense vector.
+///
+/// Dimensions beyond the length of `dense` contribute nothing.
+#[inline]
+pub fn sparse_dense_dot(indices: &[u32], values: &[f32], dense: &[f32]) -> f32 {
+    indices
+        .iter()
+        .zip(values.iter())
+        .filter_map(|(&idx, &v)| dense.get(idx as usize).map(|d| d * v))
+        .sum()
+}
+
+/// Compute the negative dot distance from one sparse vector to a batch of
+/// sparse vectors.
+///
+/// `from` must be a sparse vector array of length 1. Null vectors in `to`
+/// produce null distances.
+pub fn sparse_dot_distance_arrow_batch(
+    from: &dyn Array,
+    to: &dyn Array,
+) -> Result<Arc<Float32Array>> {
+    let query = SparseVectorArray::try_from(from)?;
+    if query.len() != 1 || query.is_null(0) {
+        return Err(Error::InvalidArgumentError(format!(
+            "Sparse query must contain exactly one non-null vector, got {}",
+            query.len()
+        )));
+    }
+    let (q_indices, q_values) = query.value(0);
+    let vectors = SparseVectorArray::try_from(to)?;
+    Ok(Arc::new(Float32Array::from_iter(vectors.iter().map(|v| {
+        v.map(|(indices, values)| sparse_dot_distance(q_indices, q_values, indices, values))
+    }))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::StructArray;
+
+    #[test]
+    fn test_sparse_dot() {
+        let x_idx = [1, 3, 5, 9];
+        let x_val = [1.0, 2.0, 3.0, 4.0];
+        let y_idx = [0, 3, 4, 9, 10];
+        let y_val = [5.0, 6.0, 7.0, 8.0, 9.0];
+        assert_eq!(
+            sparse_dot(&x_idx, &x_val, &y_idx, &y_val),
+            2.0 * 6.0 + 4.0 * 8.0
+        );
+        assert_eq!(sparse_dot(&x_idx, &x_val, &[], &[]), 0.0);
+        assert_eq!(
+            sparse_dot_distance(&x_idx, &x_val, &y_idx, &y_val),
+            -(2.0 * 6.0 + 4.0 * 8.0)
+        );
+
+        let dense = [0.0, 1.0, 0.0, 1.0];
+        assert_eq!(sparse_dense_dot(&x_idx, &x_val, &dense), 3.0);
+    }
+
+    #[test]
+    fn test_sparse_dot_arrow_batch() {
+        let query: StructArray =
+            SparseVectorArray::from_pairs([(&[1_u32, 2][..], &[1.0_f32, 2.0][..])])
+                .unwrap()
+                .into();
+        let data: StructArray = SparseVectorArray::from_pairs([
+            (&[2_u32][..], &[3.0_f32][..]),
+            (&[0, 1, 2][..], &[1.0, 1.0, 1.0][..]),
+            (&[][..], &[][..]),
+        ])
+        .unwrap()
+        .into();
+        let dists = sparse_dot_distance_arrow_batch(&query, &data).unwrap();
+        assert_eq!(dists.values(), &[-6.0, -3.0, 0.0]);
+
+        // Query must be a single vector.
+        assert!(sparse_dot_distance_arrow_batch(&data, &data).is_err());
+    }
+}
@@ -24,8 +24,8 @@ use num_traits::{AsPrimitive, Float, FromPrimitive, Zero};
 use rand::prelude::*;
 use tracing::instrument;
 
-use crate::distance::dot_distance_batch;
 use crate::distance::norm_l2::Normalize;
+use crate::distance::{dot_distance_batch, dot_distance_mixed, l2_distance_mixed};
 use crate::kernels::{argmax, argmin_value_float};
 use crate::{
     distance::{
@@ -656,6 +656,40 @@ where
         .collect()
 }
 
+/// Compute partitions for vectors whose element type differs from the centroids,
+/// i.e., `int8`, `uint8` or `bfloat16` vectors against `f32` centroids.
+///
+/// Each element is widened inside the distance kernel, so the vectors are never
+/// materialized as a `f32` copy.
+pub fn compute_partitions_mixed<X: AsPrimitive<f32>, C: AsPrimitive<f32>>(
+    centroids: &[C],
+    vectors: &[X],
+    dimension: usize,
+    metric_type: MetricType,
+) -> Result<Vec<Option<u32>>> {
+    let distance = match metric_type {
+        MetricType::L2 => l2_distance_mixed::<X, C>,
+        MetricType::Dot => dot_distance_mixed::<X, C>,
+        MetricType::Cosine => {
+            return Err(Error::InvalidArgumentError(
+                "compute_partitions_mixed: cosine is not supported, use Normalized L2 instead"
+                    .to_string(),
+            ))
+        }
+    };
+    Ok(vectors
+        .chunks_exact(dimension)
+        .map(|row| {
+            argmin_value_float(
+                centroids
+                    .chunks_exact(dimension)
+                    .map(|centroid| distance(row, centroid)),
+            )
+            .map(|(cluster, _)| cluster)
+        })
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -738,4 +772,16 @@ mod tests {
             .iter()
             .for_each(|cd| assert!(cd.is_none()));
     }
+
+    #[test]
+    fn test_compute_partitions_mixed() {
+        let centroids = [0.0_f32, 0.0, 10.0, 10.0, -10.0, 10.0];
+        let vectors = [1_i8, -1, 9, 12, -8, 8, 100, 100];
+        let parts = compute_partitions_mixed(&centroids, &vectors, 2, MetricType::L2).unwrap();
+        assert_eq!(parts, vec![Some(0), Some(1), Some(2), Some(1)]);
+
+        let parts = compute_partitions_mixed(&centroids, &vectors, 2, MetricType::Dot).unwrap();
+        assert_eq!(parts[1], Some(1));
+        assert!(compute_partitions_mixed(&centroids, &vectors, 2, MetricType::Cosine).is_err());
+    }
 }
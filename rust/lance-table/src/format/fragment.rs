@@ -33,6 +33,16 @@ pub struct DataFile {
     /// The minor version of the file format used to write this file.
     #[serde(default)]
     pub file_minor_version: u32,
+    /// Id of the master key that wrapped this file's data key, if the file is encrypted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption_key_id: Option<String>,
+    /// CRC32C of the file as stored. None for files written before checksums were recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<u32>,
+    /// The ids of fields in `fields` whose values have been replaced by a later
+    /// data file of the same fragment. Their data in this file is ignored.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub replaced_fields: Vec<i32>,
 }
 
 impl DataFile {
@@ -49,9 +59,27 @@ impl DataFile {
             column_indices,
             file_major_version,
             file_minor_version,
+            encryption_key_id: None,
+            checksum: None,
+            replaced_fields: vec![],
         }
     }
 
+    pub fn with_checksum(mut self, checksum: Option<u32>) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    pub fn with_replaced_fields(mut self, replaced_fields: Vec<i32>) -> Self {
+        self.replaced_fields = replaced_fields;
+        self
+    }
+
+    pub fn with_encryption_key_id(mut self, encryption_key_id: Option<String>) -> Self {
+        self.encryption_key_id = encryption_key_id;
+        self
+    }
+
     pub fn new_legacy_from_fields(path: impl Into<String>, fields: Vec<i32>) -> Self {
         Self::new(path, fields, vec![], 0, 0)
     }
@@ -62,8 +90,22 @@ impl DataFile {
         Self::new(path, field_ids, vec![], 0, 0)
     }
 
+    /// The schema of the fields read from this file, which excludes the
+    /// fields that have been replaced by a later data file.
     pub fn schema(&self, full_schema: &Schema) -> Schema {
-        full_schema.project_by_ids(&self.fields)
+        if self.replaced_fields.is_empty() {
+            full_schema.project_by_ids(&self.fields)
+        } else {
+            full_schema.project_by_ids(&self.live_fields().collect::<Vec<_>>())
+        }
+    }
+
+    /// The ids of the fields whose values are read from this file.
+    pub fn live_fields(&self) -> impl Iterator<Item = i32> + '_ {
+        self.fields
+            .iter()
+            .copied()
+            .filter(|id| !self.replaced_fields.contains(id))
     }
 
     pub fn is_legacy_file(&self) -> bool {
@@ -98,6 +140,9 @@ impl From<&DataFile> for pb::DataFile {
             column_indices: df.column_indices.clone(),
             file_major_version: df.file_major_version,
             file_minor_version: df.file_minor_version,
+            encryption_key_id: df.encryption_key_id.clone().unwrap_or_default(),
+            checksum: df.checksum,
+            replaced_fields: df.replaced_fields.clone(),
         }
     }
 }
@@ -111,6 +156,11 @@ impl From<&pb::DataFile> for DataFile {
             proto.file_major_version,
             proto.file_minor_version,
         )
+        .with_encryption_key_id(
+            Some(proto.encryption_key_id.clone()).filter(|key_id| !key_id.is_empty()),
+        )
+        .with_checksum(proto.checksum)
+        .with_replaced_fields(proto.replaced_fields.clone())
     }
 }
 
@@ -138,6 +188,12 @@ pub struct DeletionFile {
     pub file_type: DeletionFileType,
     /// Number of deleted rows in this file. If None, this is unknown.
     pub num_deleted_rows: Option<usize>,
+    /// Id of the master key that wrapped this file's data key, if the file is encrypted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption_key_id: Option<String>,
+    /// CRC32C of the file as stored. None for files written before checksums were recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<u32>,
 }
 
 // TODO: should we convert this to TryFrom and surface the error?
@@ -159,6 +215,9 @@ impl From<&pb::DeletionFile> for DeletionFile {
             id: value.id,
             file_type,
             num_deleted_rows,
+            encryption_key_id: Some(value.encryption_key_id.clone())
+                .filter(|key_id| !key_id.is_empty()),
+            checksum: value.checksum,
         }
     }
 }
@@ -280,6 +339,8 @@ impl From<&Fragment> for pb::DataFragment {
                 id: f.id,
                 file_type: file_type.into(),
                 num_deleted_rows: f.num_deleted_rows.unwrap_or_default() as u64,
+                encryption_key_id: f.encryption_key_id.clone().unwrap_or_default(),
+                checksum: f.checksum,
             }
         });
         Self {
@@ -337,7 +398,12 @@ mod tests {
             id: 456,
             file_type: DeletionFileType::Array,
             num_deleted_rows: Some(10),
+            encryption_key_id: Some("key-1".to_string()),
+            checksum: Some(42),
         });
+        fragment.files[0].encryption_key_id = Some("key-1".to_string());
+        fragment.files[0].checksum = Some(7);
+        fragment.files[0].replaced_fields = vec![0];
 
         let proto = pb::DataFragment::from(&fragment);
         let fragment2 = Fragment::from(&proto);
@@ -359,6 +425,8 @@ mod tests {
             id: 456,
             file_type: DeletionFileType::Array,
             num_deleted_rows: Some(10),
+            encryption_key_id: None,
+            checksum: None,
         });
 
         let json = serde_json::to_string(&fragment).unwrap();
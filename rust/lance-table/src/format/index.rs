@@ -3,6 +3,8 @@
 
 //! Metadata for index
 
+use std::collections::HashMap;
+
 use roaring::RoaringBitmap;
 use snafu::{location, Location};
 use uuid::Uuid;
@@ -29,6 +31,12 @@ pub struct Index {
     ///
     /// If this is None, then this is unknown.
     pub fragment_bitmap: Option<RoaringBitmap>,
+
+    /// CRC32C of each file of the index, keyed by the file name relative to
+    /// the index directory.
+    ///
+    /// Empty for indices built before checksums were recorded.
+    pub file_checksums: HashMap<String, u32>,
 }
 
 impl TryFrom<&pb::IndexMetadata> for Index {
@@ -56,6 +64,7 @@ impl TryFrom<&pb::IndexMetadata> for Index {
             fields: proto.fields.clone(),
             dataset_version: proto.dataset_version,
             fragment_bitmap,
+            file_checksums: proto.file_checksums.clone(),
         })
     }
 }
@@ -78,6 +87,7 @@ impl From<&Index> for pb::IndexMetadata {
             fields: idx.fields.clone(),
             dataset_version: idx.dataset_version,
             fragment_bitmap,
+            file_checksums: idx.file_checksums.clone(),
         }
     }
 }
@@ -65,6 +65,12 @@ pub struct Manifest {
     /// The path to the transaction file, relative to the root of the dataset
     pub transaction_file: Option<String>,
 
+    /// Fragment list files, if the fragments are not stored inline.
+    pub fragment_lists: Option<FragmentLists>,
+
+    /// The path to the newest version index page, relative to the root of the dataset
+    pub version_index_file: Option<String>,
+
     /// Precomputed logic offset of each fragment
     /// accelerating the fragment search using offset ranges.
     fragment_offsets: Vec<usize>,
@@ -83,6 +89,46 @@ fn compute_fragment_offsets(fragments: &[Fragment]) -> Vec<usize> {
         .collect()
 }
 
+/// Reference to a file holding a run of the dataset's fragments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FragmentListRef {
+    /// Path to the file, relative to the root of the dataset
+    pub path: String,
+    /// Number of fragments in the file
+    pub num_fragments: u64,
+}
+
+impl From<&pb::FragmentListRef> for FragmentListRef {
+    fn from(p: &pb::FragmentListRef) -> Self {
+        Self {
+            path: p.path.clone(),
+            num_fragments: p.num_fragments,
+        }
+    }
+}
+
+impl From<&FragmentListRef> for pb::FragmentListRef {
+    fn from(r: &FragmentListRef) -> Self {
+        Self {
+            path: r.path.clone(),
+            num_fragments: r.num_fragments,
+        }
+    }
+}
+
+/// Fragment list files of a manifest
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FragmentLists {
+    /// The list files, in fragment order
+    pub lists: Vec<FragmentListRef>,
+
+    /// The fragments stored in `lists`.
+    ///
+    /// This is None until the lists have been loaded.  The lists are only
+    /// written to the manifest if these are still the manifest's fragments.
+    pub fragments: Option<Arc<Vec<Fragment>>>,
+}
+
 impl Manifest {
     pub fn new(schema: Schema, fragments: Arc<Vec<Fragment>>) -> Self {
         let fragment_offsets = compute_fragment_offsets(&fragments);
@@ -99,6 +145,8 @@ impl Manifest {
             writer_feature_flags: 0,
             max_fragment_id: 0,
             transaction_file: None,
+            fragment_lists: None,
+            version_index_file: None,
             fragment_offsets,
         }
     }
@@ -123,6 +171,9 @@ impl Manifest {
             writer_feature_flags: 0, // These will be set on commit
             max_fragment_id: previous.max_fragment_id,
             transaction_file: None,
+            // Keep the layout of the previous version so unchanged lists can be reused
+            fragment_lists: previous.fragment_lists.clone(),
+            version_index_file: None, // This will be set on commit
             fragment_offsets,
         }
     }
@@ -143,6 +194,47 @@ impl Manifest {
         self.timestamp_nanos = nanos;
     }
 
+    /// Returns true if the fragments still need to be loaded from fragment list files
+    pub fn needs_fragment_lists(&self) -> bool {
+        self.fragment_lists
+            .as_ref()
+            .map(|lists| lists.fragments.is_none())
+            .unwrap_or(false)
+    }
+
+    /// The number of fragments, which is known without loading the fragment list files
+    pub fn num_fragments(&self) -> usize {
+        if self.needs_fragment_lists() {
+            self.fragment_lists
+                .iter()
+                .flat_map(|lists| lists.lists.iter())
+                .map(|list| list.num_fragments as usize)
+                .sum()
+        } else {
+            self.fragments.len()
+        }
+    }
+
+    /// Set the fragments loaded from the fragment list files
+    pub fn set_loaded_fragments(&mut self, fragments: Arc<Vec<Fragment>>) {
+        self.fragment_offsets = compute_fragment_offsets(&fragments);
+        if let Some(lists) = self.fragment_lists.as_mut() {
+            lists.fragments = Some(fragments.clone());
+        }
+        self.fragments = fragments;
+    }
+
+    /// The fragment list files, if they are up to date with the fragments
+    fn current_fragment_lists(&self) -> Option<&[FragmentListRef]> {
+        self.fragment_lists.as_ref().and_then(|lists| {
+            lists
+                .fragments
+                .as_ref()
+                .filter(|fragments| Arc::ptr_eq(fragments, &self.fragments))
+                .map(|_| lists.lists.as_slice())
+        })
+    }
+
     /// Check the current fragment list and update the high water mark
     pub fn update_max_fragment_id(&mut self) {
         let max_fragment_id = self
@@ -343,6 +435,15 @@ impl From<pb::Manifest> for Manifest {
             _ => None,
         };
         let fragments = Arc::new(p.fragments.iter().map(Fragment::from).collect::<Vec<_>>());
+        // The fragments are loaded from the lists separately
+        let fragment_lists = if p.fragment_lists.is_empty() {
+            None
+        } else {
+            Some(FragmentLists {
+                lists: p.fragment_lists.iter().map(FragmentListRef::from).collect(),
+                fragments: None,
+            })
+        };
         let fragment_offsets = compute_fragment_offsets(fragments.as_slice());
         let fields_with_meta = FieldsWithMeta {
             fields: Fields(p.fields),
@@ -365,6 +466,12 @@ impl From<pb::Manifest> for Manifest {
             } else {
                 Some(p.transaction_file)
             },
+            fragment_lists,
+            version_index_file: if p.version_index_file.is_empty() {
+                None
+            } else {
+                Some(p.version_index_file)
+            },
             fragment_offsets,
         }
     }
@@ -383,6 +490,17 @@ impl From<&Manifest> for pb::Manifest {
             })
         };
         let fields_with_meta: FieldsWithMeta = (&m.schema).into();
+        // Fragments are stored inline unless up-to-date list files were written
+        let (fragments, fragment_lists) = match m.current_fragment_lists() {
+            Some(lists) => (
+                vec![],
+                lists.iter().map(pb::FragmentListRef::from).collect(),
+            ),
+            None => (
+                m.fragments.iter().map(pb::DataFragment::from).collect(),
+                vec![],
+            ),
+        };
         Self {
             fields: fields_with_meta.fields.0,
             version: m.version,
@@ -393,7 +511,7 @@ impl From<&Manifest> for pb::Manifest {
                     library: wv.library.clone(),
                     version: wv.version.clone(),
                 }),
-            fragments: m.fragments.iter().map(pb::DataFragment::from).collect(),
+            fragments,
             metadata: fields_with_meta.metadata,
             version_aux_data: m.version_aux_data as u64,
             index_section: m.index_section.map(|i| i as u64),
@@ -403,6 +521,8 @@ impl From<&Manifest> for pb::Manifest {
             writer_feature_flags: m.writer_feature_flags,
             max_fragment_id: m.max_fragment_id,
             transaction_file: m.transaction_file.clone().unwrap_or_default(),
+            fragment_lists,
+            version_index_file: m.version_index_file.clone().unwrap_or_default(),
         }
     }
 }
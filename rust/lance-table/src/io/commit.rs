@@ -16,7 +16,9 @@
 //! at the final path. This is an atomic operation in most object stores, but
 //! not in AWS S3. So for AWS S3, the default commit handler is
 //! [UnsafeCommitHandler], which writes the manifest to the final path without
-//! any checks.
+//! any checks. Stores that support conditional writes can instead use
+//! [ConditionalPutCommitHandler], which only creates the manifest if it does
+//! not already exist.
 //!
 //! When providing your own commit handler, most often you are implementing in
 //! terms of a lock. The trait [CommitLock] can be implemented as a simpler
@@ -31,13 +33,14 @@ use futures::{
     stream::BoxStream,
     StreamExt, TryStreamExt,
 };
-use object_store::{path::Path, Error as ObjectStoreError, ObjectStore};
+use object_store::{memory::InMemory, path::Path, Error as ObjectStoreError, ObjectStore, PutMode};
 use snafu::{location, Location};
 use url::Url;
 
 #[cfg(feature = "dynamodb")]
 pub mod dynamodb;
 pub mod external_manifest;
+pub mod file_external_manifest;
 
 use lance_core::{Error, Result};
 use lance_io::object_store::ObjectStoreExt;
@@ -315,8 +318,10 @@ pub async fn commit_handler_from_url(
     };
 
     match url.scheme() {
-        // TODO: for Cloudflare R2 and Minio, we can provide a PutIfNotExist commit handler
+        // S3-compatible stores such as Cloudflare R2 and Minio can be configured to
+        // support conditional puts.
         // See: https://docs.rs/object_store/latest/object_store/aws/enum.S3ConditionalPut.html#variant.ETagMatch
+        "s3" if has_conditional_put(options) => Ok(Arc::new(ConditionalPutCommitHandler)),
         "s3" => Ok(Arc::new(UnsafeCommitHandler)),
         #[cfg(not(feature = "dynamodb"))]
         "s3+ddb" => Err(Error::InvalidInput {
@@ -389,6 +394,35 @@ pub async fn commit_handler_from_url(
     }
 }
 
+/// Whether the S3 storage options enable conditional puts
+fn has_conditional_put(options: &Option<ObjectStoreParams>) -> bool {
+    options
+        .as_ref()
+        .and_then(|options| options.storage_options.as_ref())
+        .map(|storage_options| {
+            storage_options.iter().any(|(key, value)| {
+                let key = key.to_ascii_lowercase();
+                (key == "aws_conditional_put" || key == "conditional_put")
+                    && is_conditional_put_mode(value)
+            })
+        })
+        .unwrap_or(false)
+}
+
+/// Whether `value` names a conditional put mode of the S3 store
+///
+/// These are the values accepted by [object_store::aws::S3ConditionalPut]:
+/// `etag` or `dynamo:<TABLE_NAME>[:<TIMEOUT_MILLIS>]`.
+fn is_conditional_put_mode(value: &str) -> bool {
+    match value.trim() {
+        "etag" => true,
+        value => matches!(
+            value.split_once(':'),
+            Some(("dynamo", table)) if !table.trim().is_empty()
+        ),
+    }
+}
+
 #[cfg(feature = "dynamodb")]
 fn get_dynamodb_endpoint(storage_options: &StorageOptions) -> Option<String> {
     if let Some(endpoint) = storage_options.0.get("dynamodb_endpoint") {
@@ -622,14 +656,125 @@ impl Debug for RenameCommitHandler {
     }
 }
 
+/// A commit implementation that writes the manifest with a conditional put.
+///
+/// The manifest for a version is only created if no object exists at its path
+/// yet, so the object store itself decides which concurrent writer wins. This
+/// only works for object stores that support [PutMode::Create], such as GCS,
+/// local file systems, and S3-compatible stores with conditional put enabled.
+pub struct ConditionalPutCommitHandler;
+
+#[async_trait::async_trait]
+impl CommitHandler for ConditionalPutCommitHandler {
+    async fn commit(
+        &self,
+        manifest: &mut Manifest,
+        indices: Option<Vec<Index>>,
+        base_path: &Path,
+        object_store: &dyn ObjectStore,
+        manifest_writer: ManifestWriter,
+    ) -> std::result::Result<(), CommitError> {
+        let path = self
+            .resolve_version(base_path, manifest.version, object_store)
+            .await?;
+
+        // The manifest writer streams to the store, which can't be made
+        // conditional, so the manifest is first written to memory.
+        let staging = InMemory::new();
+        manifest_writer(&staging, manifest, indices, &path).await?;
+        let data = staging
+            .get(&path)
+            .await
+            .map_err(|err| CommitError::OtherError(err.into()))?
+            .bytes()
+            .await
+            .map_err(|err| CommitError::OtherError(err.into()))?;
+
+        match object_store
+            .put_opts(&path, data, PutMode::Create.into())
+            .await
+        {
+            Ok(_) => {}
+            Err(ObjectStoreError::AlreadyExists { .. })
+            | Err(ObjectStoreError::Precondition { .. }) => {
+                // Another transaction has already been committed
+                return Err(CommitError::CommitConflict);
+            }
+            Err(e) => {
+                // Something else went wrong
+                return Err(CommitError::OtherError(e.into()));
+            }
+        }
+
+        write_latest_manifest(&path, base_path, object_store).await?;
+
+        Ok(())
+    }
+}
+
+impl Debug for ConditionalPutCommitHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConditionalPutCommitHandler").finish()
+    }
+}
+
+/// How a commit is reconciled with transactions committed concurrently.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IsolationLevel {
+    /// Fail if a concurrent transaction modified any of the same fragments.
+    #[default]
+    Serializable,
+    /// Concurrent `Delete` and `Update` transactions on the same fragments are
+    /// rebased onto each other by merging their deletion vectors. They only fail
+    /// if both deleted the same row.
+    Snapshot,
+}
+
 #[derive(Debug, Clone)]
 pub struct CommitConfig {
     pub num_retries: u32,
-    // TODO: add isolation_level
+    pub isolation_level: IsolationLevel,
 }
 
 impl Default for CommitConfig {
     fn default() -> Self {
-        Self { num_retries: 5 }
+        Self {
+            num_retries: 5,
+            isolation_level: IsolationLevel::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_conditional_put_mode() {
+        let params = |key: &str, value: &str| {
+            Some(ObjectStoreParams {
+                storage_options: Some(HashMap::from([(key.to_string(), value.to_string())])),
+                ..Default::default()
+            })
+        };
+        assert!(has_conditional_put(&params("aws_conditional_put", "etag")));
+        assert!(has_conditional_put(&params("conditional_put", " etag ")));
+        assert!(has_conditional_put(&params(
+            "AWS_CONDITIONAL_PUT",
+            "dynamo:my_table:2000"
+        )));
+        assert!(!has_conditional_put(&params("aws_conditional_put", "")));
+        assert!(!has_conditional_put(&params(
+            "aws_conditional_put",
+            "false"
+        )));
+        assert!(!has_conditional_put(&params(
+            "aws_conditional_put",
+            "dynamo:"
+        )));
+        assert!(!has_conditional_put(&params("aws_region", "etag")));
+        assert!(!has_conditional_put(&None));
     }
 }
@@ -0,0 +1,463 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! An [ExternalManifestStore] backed by files in a local or shared directory.
+//!
+//! Each dataset gets an append-only log of `(version, path)` entries in the
+//! store directory. Writers take an exclusive lock file (created with
+//! `O_CREAT | O_EXCL`) before checking and appending to the log, so several
+//! processes on a shared POSIX filesystem (including NFS) can commit safely
+//! through [ExternalManifestCommitHandler](super::external_manifest::ExternalManifestCommitHandler).
+//! The lock file holds a random token identifying its owner, and a lock is only
+//! ever removed by whoever observed that token: its owner when it is done, or a
+//! waiter that found the lock abandoned.
+//! Readers do not take the lock. An entry is only visible once its line has
+//! been fully written, and later entries for the same version replace earlier ones.
+
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path as StdPath, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use async_trait::async_trait;
+use lance_core::{Error, Result};
+use serde::{Deserialize, Serialize};
+use snafu::{location, Location};
+
+use super::external_manifest::ExternalManifestStore;
+
+const LOG_EXTENSION: &str = "log";
+const LOCK_EXTENSION: &str = "lock";
+
+/// The default time after which a lock file is considered abandoned
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LogEntry {
+    base_uri: String,
+    version: u64,
+    path: String,
+}
+
+/// External manifest store that keeps a lock-protected log per dataset in a directory
+#[derive(Debug, Clone)]
+pub struct FileExternalManifestStore {
+    root: PathBuf,
+    lock_timeout: Duration,
+}
+
+impl FileExternalManifestStore {
+    /// Create a store in the given directory, creating it if needed
+    pub fn try_new(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        std::fs::create_dir_all(&root)?;
+        Ok(Self {
+            root,
+            lock_timeout: DEFAULT_LOCK_TIMEOUT,
+        })
+    }
+
+    /// Set how old a lock file must be before it is considered abandoned and removed.
+    ///
+    /// This should be much longer than a single commit takes.
+    pub fn with_lock_timeout(mut self, lock_timeout: Duration) -> Self {
+        self.lock_timeout = lock_timeout;
+        self
+    }
+
+    // The log file name must be stable across processes and versions of this
+    // library, so use FNV-1a rather than the std hasher.  The full base uri
+    // is stored in each entry to guard against collisions.
+    fn file_stem(base_uri: &str) -> String {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in base_uri.as_bytes() {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        format!("{:016x}", hash)
+    }
+
+    fn log_path(&self, base_uri: &str) -> PathBuf {
+        self.root
+            .join(format!("{}.{}", Self::file_stem(base_uri), LOG_EXTENSION))
+    }
+
+    fn lock_path(&self, base_uri: &str) -> PathBuf {
+        self.root
+            .join(format!("{}.{}", Self::file_stem(base_uri), LOCK_EXTENSION))
+    }
+
+    fn read_log(log_path: &StdPath) -> Result<String> {
+        let mut contents = String::new();
+        match File::open(log_path) {
+            Ok(mut file) => {
+                file.read_to_string(&mut contents)?;
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+        Ok(contents)
+    }
+
+    // Parse all committed entries for the dataset, ordered by version
+    fn parse_entries(contents: &str, base_uri: &str) -> Vec<(u64, String)> {
+        let mut entries = std::collections::BTreeMap::new();
+        // Only complete lines are committed.  A trailing line without a newline
+        // is still being written (or was abandoned) and is ignored.
+        let mut lines = contents.split('\n').collect::<Vec<_>>();
+        lines.pop();
+        for line in lines {
+            let entry: LogEntry = match serde_json::from_str(line) {
+                Ok(entry) => entry,
+                // Left over from a writer that crashed mid-append
+                Err(_) => continue,
+            };
+            if entry.base_uri == base_uri {
+                entries.insert(entry.version, entry.path);
+            }
+        }
+        entries.into_iter().collect()
+    }
+
+    fn read_entries(log_path: &StdPath, base_uri: &str) -> Result<Vec<(u64, String)>> {
+        Ok(Self::parse_entries(&Self::read_log(log_path)?, base_uri))
+    }
+
+    fn acquire_lock(&self, base_uri: &str) -> Result<LockGuard> {
+        let lock_path = self.lock_path(base_uri);
+        let token = uuid::Uuid::new_v4().to_string();
+        let start = Instant::now();
+        let mut backoff = Duration::from_millis(1);
+        loop {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&lock_path)
+            {
+                Ok(mut file) => {
+                    let guard = LockGuard {
+                        path: lock_path,
+                        token,
+                    };
+                    file.write_all(guard.token.as_bytes())?;
+                    file.sync_all()?;
+                    return Ok(guard);
+                }
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => {}
+                Err(err) => return Err(err.into()),
+            }
+
+            // Read the owner and the age from the same open file, so that both
+            // describe the same lock even if it is replaced in the meantime.
+            let stale_owner = File::open(&lock_path).ok().and_then(|mut file| {
+                let modified = file.metadata().and_then(|meta| meta.modified()).ok()?;
+                let age = SystemTime::now().duration_since(modified).ok()?;
+                let mut owner = String::new();
+                file.read_to_string(&mut owner).ok()?;
+                (age > self.lock_timeout).then_some(owner)
+            });
+            if let Some(owner) = stale_owner {
+                // The holder most likely died
+                if remove_lock_if_owned(&lock_path, &owner)? {
+                    log::warn!("Removed abandoned lock file {}", lock_path.display());
+                }
+                continue;
+            }
+
+            if start.elapsed() > self.lock_timeout * 2 {
+                return Err(Error::IO {
+                    message: format!(
+                        "Timed out waiting for external manifest lock {}",
+                        lock_path.display()
+                    ),
+                    location: location!(),
+                });
+            }
+            std::thread::sleep(backoff);
+            backoff = (backoff * 2).min(Duration::from_millis(100));
+        }
+    }
+
+    // Append an entry for the version while holding the lock.  `should_exist`
+    // controls whether the version must already be present.
+    fn put(&self, base_uri: &str, version: u64, path: &str, should_exist: bool) -> Result<()> {
+        let lock = self.acquire_lock(base_uri)?;
+        let log_path = self.log_path(base_uri);
+        let contents = Self::read_log(&log_path)?;
+        let exists = Self::parse_entries(&contents, base_uri)
+            .iter()
+            .any(|(existing, _)| *existing == version);
+        if exists != should_exist {
+            return Err(Error::IO {
+                message: if exists {
+                    format!(
+                        "manifest already exists for uri: {}, version: {}",
+                        base_uri, version
+                    )
+                } else {
+                    format!(
+                        "manifest does not exist for uri: {}, version: {}",
+                        base_uri, version
+                    )
+                },
+                location: location!(),
+            });
+        }
+
+        let mut line = String::new();
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            // Terminate a line left behind by a writer that crashed mid-append
+            line.push('\n');
+        }
+        line.push_str(&serde_json::to_string(&LogEntry {
+            base_uri: base_uri.to_string(),
+            version,
+            path: path.to_string(),
+        })?);
+        line.push('\n');
+        // Don't append if the lock was taken as abandoned while we were slow
+        lock.check_held()?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_path)?;
+        file.write_all(line.as_bytes())?;
+        file.sync_all()?;
+        Ok(())
+    }
+
+    async fn run_blocking<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(Self) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || f(store))
+            .await
+            .map_err(|err| Error::Internal {
+                message: format!("External manifest store task failed: {}", err),
+                location: location!(),
+            })?
+    }
+}
+
+/// Remove the lock file at `lock_path` if it still belongs to `owner`.
+///
+/// Returns false if the lock is gone or belongs to someone else.
+fn remove_lock_if_owned(lock_path: &StdPath, owner: &str) -> Result<bool> {
+    match std::fs::read_to_string(lock_path) {
+        Ok(contents) if contents == owner => {}
+        Ok(_) => return Ok(false),
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err.into()),
+    }
+    // Move the lock aside before checking the owner again, so that the lock
+    // we remove is the one we checked even if it was replaced after the read.
+    let moved_path =
+        lock_path.with_extension(format!("{}.{}", LOCK_EXTENSION, uuid::Uuid::new_v4()));
+    match std::fs::rename(lock_path, &moved_path) {
+        Ok(()) => {}
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err.into()),
+    }
+    let owned = std::fs::read_to_string(&moved_path)? == owner;
+    if !owned {
+        // Someone else took the lock in between, put it back.  Linking fails
+        // rather than replacing a lock taken while it was moved aside.
+        if let Err(err) = std::fs::hard_link(&moved_path, lock_path) {
+            log::warn!(
+                "Failed to restore lock file {}: {}",
+                lock_path.display(),
+                err
+            );
+        }
+    }
+    std::fs::remove_file(&moved_path)?;
+    Ok(owned)
+}
+
+// Removes the lock file when the write is finished, unless the lock was
+// taken over as abandoned in the meantime
+struct LockGuard {
+    path: PathBuf,
+    token: String,
+}
+
+impl LockGuard {
+    fn check_held(&self) -> Result<()> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(contents) if contents == self.token => Ok(()),
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Err(Error::IO {
+                message: format!(
+                    "External manifest lock {} was removed as abandoned while held",
+                    self.path.display()
+                ),
+                location: location!(),
+            }),
+        }
+    }
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        match remove_lock_if_owned(&self.path, &self.token) {
+            Ok(true) => {}
+            Ok(false) => log::warn!(
+                "Lock file {} was removed as abandoned while held",
+                self.path.display()
+            ),
+            Err(err) => log::warn!(
+                "Failed to remove lock file {}: {}",
+                self.path.display(),
+                err
+            ),
+        }
+    }
+}
+
+#[async_trait]
+impl ExternalManifestStore for FileExternalManifestStore {
+    async fn get(&self, base_uri: &str, version: u64) -> Result<String> {
+        let base_uri = base_uri.to_string();
+        self.run_blocking(move |store| {
+            Self::read_entries(&store.log_path(&base_uri), &base_uri)?
+                .into_iter()
+                .find(|(existing, _)| *existing == version)
+                .map(|(_, path)| path)
+                .ok_or_else(|| Error::NotFound {
+                    uri: format!(
+                        "external manifest not found: base_uri: {}; version: {}",
+                        base_uri, version
+                    ),
+                    location: location!(),
+                })
+        })
+        .await
+    }
+
+    async fn get_latest_version(&self, base_uri: &str) -> Result<Option<(u64, String)>> {
+        let base_uri = base_uri.to_string();
+        self.run_blocking(move |store| {
+            Ok(Self::read_entries(&store.log_path(&base_uri), &base_uri)?.pop())
+        })
+        .await
+    }
+
+    async fn put_if_not_exists(&self, base_uri: &str, version: u64, path: &str) -> Result<()> {
+        let (base_uri, path) = (base_uri.to_string(), path.to_string());
+        self.run_blocking(move |store| store.put(&base_uri, version, &path, false))
+            .await
+    }
+
+    async fn put_if_exists(&self, base_uri: &str, version: u64, path: &str) -> Result<()> {
+        let (base_uri, path) = (base_uri.to_string(), path.to_string());
+        self.run_blocking(move |store| store.put(&base_uri, version, &path, true))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_put_and_get() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileExternalManifestStore::try_new(dir.path()).unwrap();
+
+        assert_eq!(store.get_latest_version("ds").await.unwrap(), None);
+        assert!(matches!(
+            store.get("ds", 1).await,
+            Err(Error::NotFound { .. })
+        ));
+        assert!(store.put_if_exists("ds", 1, "staged-1").await.is_err());
+
+        store.put_if_not_exists("ds", 1, "staged-1").await.unwrap();
+        store.put_if_not_exists("ds", 2, "staged-2").await.unwrap();
+        assert!(store.put_if_not_exists("ds", 2, "other").await.is_err());
+        store.put_if_exists("ds", 2, "2.manifest").await.unwrap();
+        store
+            .put_if_not_exists("other", 5, "5.manifest")
+            .await
+            .unwrap();
+
+        // A second instance (e.g. another process) sees the same state
+        let store = FileExternalManifestStore::try_new(dir.path()).unwrap();
+        assert_eq!(store.get("ds", 1).await.unwrap(), "staged-1");
+        assert_eq!(store.get("ds", 2).await.unwrap(), "2.manifest");
+        assert_eq!(
+            store.get_latest_version("ds").await.unwrap(),
+            Some((2, "2.manifest".to_string()))
+        );
+        assert_eq!(
+            store.get_latest_version("other").await.unwrap(),
+            Some((5, "5.manifest".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_torn_write_and_stale_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileExternalManifestStore::try_new(dir.path())
+            .unwrap()
+            .with_lock_timeout(Duration::from_millis(50));
+        store
+            .put_if_not_exists("ds", 1, "1.manifest")
+            .await
+            .unwrap();
+
+        // A writer died half way through appending and left its lock behind
+        let mut log = OpenOptions::new()
+            .append(true)
+            .open(store.log_path("ds"))
+            .unwrap();
+        log.write_all(b"{\"base_uri\":\"ds\",\"vers").unwrap();
+        File::create(store.lock_path("ds")).unwrap();
+        assert_eq!(
+            store.get_latest_version("ds").await.unwrap(),
+            Some((1, "1.manifest".to_string()))
+        );
+
+        std::thread::sleep(Duration::from_millis(100));
+        store
+            .put_if_not_exists("ds", 2, "2.manifest")
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get_latest_version("ds").await.unwrap(),
+            Some((2, "2.manifest".to_string()))
+        );
+        assert!(!store.lock_path("ds").exists());
+    }
+
+    #[tokio::test]
+    async fn test_stale_lock_holder_still_alive() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileExternalManifestStore::try_new(dir.path())
+            .unwrap()
+            .with_lock_timeout(Duration::from_millis(50));
+
+        // The first holder is too slow and its lock is taken over
+        let slow = store.acquire_lock("ds").unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        let current = store.acquire_lock("ds").unwrap();
+        assert_ne!(slow.token, current.token);
+        assert!(slow.check_held().is_err());
+        current.check_held().unwrap();
+
+        // The slow holder finishing doesn't release the new holder's lock
+        drop(slow);
+        current.check_held().unwrap();
+        let contents = std::fs::read_to_string(store.lock_path("ds")).unwrap();
+        assert_eq!(contents, current.token);
+
+        // A lock that is no longer stale when it is checked again is kept
+        assert!(!remove_lock_if_owned(&store.lock_path("ds"), "other").unwrap());
+        current.check_held().unwrap();
+
+        drop(current);
+        assert!(!store.lock_path("ds").exists());
+        let leftovers = std::fs::read_dir(dir.path()).unwrap().count();
+        assert_eq!(leftovers, 0);
+    }
+}
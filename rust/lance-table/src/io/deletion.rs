@@ -59,11 +59,13 @@ pub async fn write_deletion_file(
         DeletionVector::NoDeletions => Ok(None),
         DeletionVector::Set(set) => {
             let id = rand::thread_rng().gen::<u64>();
-            let deletion_file = DeletionFile {
+            let mut deletion_file = DeletionFile {
                 read_version,
                 id,
                 file_type: DeletionFileType::Array,
                 num_deleted_rows: Some(set.len()),
+                encryption_key_id: object_store.encryption_key_id(),
+                checksum: None,
             };
             let path = deletion_file_path(base, fragment_id, &deletion_file);
 
@@ -87,24 +89,26 @@ pub async fn write_deletion_file(
                 // Drop writer so out is no longer borrowed.
             }
 
-            object_store.inner.put(&path, out.into()).await?;
+            deletion_file.checksum = Some(object_store.write_object(&path, out.into()).await?);
 
             Ok(Some(deletion_file))
         }
         DeletionVector::Bitmap(bitmap) => {
             let id = rand::thread_rng().gen::<u64>();
-            let deletion_file = DeletionFile {
+            let mut deletion_file = DeletionFile {
                 read_version,
                 id,
                 file_type: DeletionFileType::Bitmap,
                 num_deleted_rows: Some(bitmap.len() as usize),
+                encryption_key_id: object_store.encryption_key_id(),
+                checksum: None,
             };
             let path = deletion_file_path(base, fragment_id, &deletion_file);
 
             let mut out: Vec<u8> = Vec::new();
             bitmap.serialize_into(&mut out)?;
 
-            object_store.inner.put(&path, out.into()).await?;
+            deletion_file.checksum = Some(object_store.write_object(&path, out.into()).await?);
 
             Ok(Some(deletion_file))
         }
@@ -124,12 +128,13 @@ pub async fn read_deletion_file(
     let Some(deletion_file) = &fragment.deletion_file else {
         return Ok(None);
     };
-
     match deletion_file.file_type {
         DeletionFileType::Array => {
             let path = deletion_file_path(base, fragment.id, deletion_file);
 
-            let data = object_store.inner.get(&path).await?.bytes().await?;
+            let data = object_store
+                .read_object_with_key_id(&path, deletion_file.encryption_key_id.as_deref())
+                .await?;
             let data = std::io::Cursor::new(data);
             let mut batches: Vec<RecordBatch> = ArrowFileReader::try_new(data, None)?
                 .collect::<std::result::Result<_, ArrowError>>()
@@ -183,7 +188,9 @@ pub async fn read_deletion_file(
         DeletionFileType::Bitmap => {
             let path = deletion_file_path(base, fragment.id, deletion_file);
 
-            let data = object_store.inner.get(&path).await?.bytes().await?;
+            let data = object_store
+                .read_object_with_key_id(&path, deletion_file.encryption_key_id.as_deref())
+                .await?;
             let reader = data.reader();
             let bitmap = RoaringBitmap::deserialize_from(reader)
                 .map_err(box_error)
@@ -194,11 +201,51 @@ pub async fn read_deletion_file(
     }
 }
 
+/// Merge the deletions of two concurrent transactions on the same fragment.
+///
+/// Both `ours` and `theirs` were derived from `base`, the deletion vector of the
+/// fragment when the transactions started. The result holds the rows deleted by
+/// either of them.
+///
+/// Returns `Err(row)` with the smallest row offset deleted by both transactions.
+pub fn merge_deletion_vectors(
+    base: &DeletionVector,
+    ours: &DeletionVector,
+    theirs: &DeletionVector,
+) -> std::result::Result<DeletionVector, u32> {
+    let base = RoaringBitmap::from(base);
+    let ours_new = RoaringBitmap::from(ours) - &base;
+    let theirs = RoaringBitmap::from(theirs);
+    if let Some(row) = (&ours_new & &(&theirs - &base)).min() {
+        return Err(row);
+    }
+    Ok(DeletionVector::Bitmap(theirs | ours_new))
+}
+
 #[cfg(test)]
 mod test {
 
     use super::*;
 
+    #[test]
+    fn test_merge_deletion_vectors() {
+        let base = DeletionVector::from_iter([1, 2]);
+        let ours = DeletionVector::from_iter([1, 2, 5, 7]);
+        let theirs = DeletionVector::Set(HashSet::from_iter([1, 2, 3, 10]));
+        let merged = merge_deletion_vectors(&base, &ours, &theirs).unwrap();
+        assert_eq!(
+            merged.into_iter().collect::<Vec<_>>(),
+            vec![1, 2, 3, 5, 7, 10]
+        );
+
+        // Rows deleted before both transactions started don't overlap.
+        let merged = merge_deletion_vectors(&base, &base, &base).unwrap();
+        assert_eq!(merged.len(), 2);
+
+        let theirs = DeletionVector::from_iter([1, 2, 7, 5]);
+        assert_eq!(merge_deletion_vectors(&base, &ours, &theirs), Err(5));
+    }
+
     #[tokio::test]
     async fn test_write_no_deletions() {
         let dv = DeletionVector::NoDeletions;
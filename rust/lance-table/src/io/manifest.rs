@@ -1,11 +1,12 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
-use std::{ops::Range, sync::Arc};
+use std::{collections::HashMap, ops::Range, sync::Arc};
 
 use async_trait::async_trait;
 use byteorder::{ByteOrder, LittleEndian};
 use bytes::{Bytes, BytesMut};
+use futures::{stream, StreamExt, TryStreamExt};
 use lance_arrow::DataTypeExt;
 use lance_file::writer::ManifestProvider;
 use object_store::path::Path;
@@ -13,7 +14,7 @@ use prost::Message;
 use snafu::{location, Location};
 use tracing::instrument;
 
-use lance_core::{datatypes::Schema, Error, Result};
+use lance_core::{cache::FileMetadataCache, datatypes::Schema, Error, Result};
 use lance_io::{
     encodings::{binary::BinaryEncoder, plain::PlainEncoder, Encoder},
     object_store::ObjectStore,
@@ -22,13 +23,35 @@ use lance_io::{
     utils::read_message,
 };
 
-use crate::format::{pb, Index, Manifest, MAGIC};
+use crate::format::{pb, Fragment, FragmentListRef, FragmentLists, Index, Manifest, MAGIC};
+
+/// Directory, relative to the dataset root, that holds fragment list files
+pub const FRAGMENT_LISTS_DIR: &str = "_fragments";
+const FRAGMENT_LIST_EXTENSION: &str = "binpb";
+const FRAGMENT_LIST_IO_PARALLELISM: usize = 16;
 
 /// Read Manifest on URI.
 ///
-/// This only reads manifest files. It does not read data files.
+/// This only reads manifest files. It does not read data files.  If the
+/// manifest stores its fragments in fragment list files then those are
+/// read as well.
 #[instrument(level = "debug", skip(object_store))]
 pub async fn read_manifest(object_store: &ObjectStore, path: &Path) -> Result<Manifest> {
+    let mut manifest = read_manifest_root(object_store, path).await?;
+    if manifest.needs_fragment_lists() {
+        let base_path = dataset_base_path(path);
+        load_fragment_lists(object_store, &base_path, &mut manifest, None).await?;
+    }
+    Ok(manifest)
+}
+
+/// Read a manifest without loading its fragment list files.
+///
+/// This is enough to inspect the version, timestamp, schema and flags of a
+/// manifest.  The fragments will be empty if [Manifest::needs_fragment_lists]
+/// is true, see [load_fragment_lists].
+#[instrument(level = "debug", skip(object_store))]
+pub async fn read_manifest_root(object_store: &ObjectStore, path: &Path) -> Result<Manifest> {
     let file_size = object_store.inner.head(path).await?.size;
     const PREFETCH_SIZE: usize = 64 * 1024;
     let initial_start = std::cmp::max(file_size as i64 - PREFETCH_SIZE as i64, 0) as usize;
@@ -93,6 +116,223 @@ pub async fn read_manifest(object_store: &ObjectStore, path: &Path) -> Result<Ma
     Ok(Manifest::from(proto))
 }
 
+// Manifests live in `{base}/_versions/`, except for `{base}/_latest.manifest`
+fn dataset_base_path(manifest_path: &Path) -> Path {
+    let mut parts = manifest_path.parts().collect::<Vec<_>>();
+    parts.pop();
+    if parts
+        .last()
+        .map(|part| part.as_ref() == "_versions")
+        .unwrap_or(false)
+    {
+        parts.pop();
+    }
+    Path::from_iter(parts)
+}
+
+fn fragment_list_path(base_path: &Path, list: &FragmentListRef) -> Path {
+    base_path
+        .parts()
+        .chain(Path::from(list.path.as_str()).parts())
+        .collect()
+}
+
+async fn read_fragment_list(
+    object_store: &ObjectStore,
+    base_path: &Path,
+    list: &FragmentListRef,
+    cache: Option<&FileMetadataCache>,
+) -> Result<Arc<Vec<Fragment>>> {
+    let path = fragment_list_path(base_path, list);
+    // List files are immutable so they can be shared between versions
+    if let Some(fragments) = cache.and_then(|cache| cache.get::<Vec<Fragment>>(&path)) {
+        return Ok(fragments);
+    }
+    let bytes = object_store.inner.get(&path).await?.bytes().await?;
+    let proto = pb::FragmentList::decode(bytes)?;
+    if proto.fragments.len() as u64 != list.num_fragments {
+        return Err(Error::corrupt_file(
+            path,
+            format!(
+                "expected {} fragments in fragment list but found {}",
+                list.num_fragments,
+                proto.fragments.len()
+            ),
+            location!(),
+        ));
+    }
+    let fragments = Arc::new(
+        proto
+            .fragments
+            .iter()
+            .map(Fragment::from)
+            .collect::<Vec<_>>(),
+    );
+    if let Some(cache) = cache {
+        cache.insert(path, fragments.clone());
+    }
+    Ok(fragments)
+}
+
+/// Load the fragments of a manifest from its fragment list files.
+///
+/// This is a no-op if the fragments are stored inline or already loaded.
+pub async fn load_fragment_lists(
+    object_store: &ObjectStore,
+    base_path: &Path,
+    manifest: &mut Manifest,
+    cache: Option<&FileMetadataCache>,
+) -> Result<()> {
+    if !manifest.needs_fragment_lists() {
+        return Ok(());
+    }
+    let lists = manifest
+        .fragment_lists
+        .as_ref()
+        .map(|lists| lists.lists.clone())
+        .unwrap_or_default();
+    // Create the futures up front, a closure here makes the future not Send
+    let reads = lists
+        .iter()
+        .map(|list| read_fragment_list(object_store, base_path, list, cache))
+        .collect::<Vec<_>>();
+    let loaded = stream::iter(reads)
+        .buffered(FRAGMENT_LIST_IO_PARALLELISM)
+        .try_collect::<Vec<_>>()
+        .await?;
+    let fragments = loaded
+        .iter()
+        .flat_map(|fragments| fragments.iter().cloned())
+        .collect::<Vec<_>>();
+    manifest.set_loaded_fragments(Arc::new(fragments));
+    Ok(())
+}
+
+async fn write_fragment_list(
+    object_store: &ObjectStore,
+    base_path: &Path,
+    fragments: &[Fragment],
+) -> Result<FragmentListRef> {
+    let list = FragmentListRef {
+        path: format!(
+            "{}/{}.{}",
+            FRAGMENT_LISTS_DIR,
+            uuid::Uuid::new_v4(),
+            FRAGMENT_LIST_EXTENSION
+        ),
+        num_fragments: fragments.len() as u64,
+    };
+    let proto = pb::FragmentList {
+        fragments: fragments.iter().map(pb::DataFragment::from).collect(),
+    };
+    object_store
+        .inner
+        .put(
+            &fragment_list_path(base_path, &list),
+            proto.encode_to_vec().into(),
+        )
+        .await?;
+    Ok(list)
+}
+
+enum FragmentListEntry {
+    Existing(FragmentListRef),
+    New(Range<usize>),
+}
+
+// Split a run of fragments that need new list files into lists of at most `list_size`
+fn push_new_lists(entries: &mut Vec<FragmentListEntry>, range: Range<usize>, list_size: usize) {
+    let mut start = range.start;
+    while start < range.end {
+        let end = (start + list_size).min(range.end);
+        entries.push(FragmentListEntry::New(start..end));
+        start = end;
+    }
+}
+
+/// Write the fragments of a manifest to fragment list files.
+///
+/// Lists of the previous layout (from [Manifest::new_from_previous]) that
+/// still hold the same run of fragments are reused, so only the lists that
+/// changed are written.  The exception is a partially filled list at the end,
+/// which is rewritten together with newly appended fragments so that every
+/// append doesn't add a small file.  New lists hold at most `list_size` fragments.
+///
+/// Afterwards the manifest refers to the lists instead of storing its
+/// fragments inline.
+pub async fn write_fragment_lists(
+    object_store: &ObjectStore,
+    base_path: &Path,
+    manifest: &mut Manifest,
+    list_size: usize,
+) -> Result<()> {
+    let list_size = list_size.max(1);
+    let fragments = manifest.fragments.clone();
+    let previous = manifest.fragment_lists.take().unwrap_or_default();
+
+    // Previous lists by the id of their first fragment
+    let mut reusable = HashMap::new();
+    if let Some(previous_fragments) = previous.fragments.as_ref() {
+        let mut offset = 0;
+        for (idx, list) in previous.lists.iter().enumerate() {
+            let end = offset + list.num_fragments as usize;
+            if end > previous_fragments.len() {
+                break;
+            }
+            if let Some(first) = previous_fragments.get(offset) {
+                let is_tail = idx + 1 == previous.lists.len();
+                reusable.insert(first.id, (list, &previous_fragments[offset..end], is_tail));
+            }
+            offset = end;
+        }
+    }
+
+    let mut entries = Vec::new();
+    let mut pending_start = 0;
+    let mut pos = 0;
+    while pos < fragments.len() {
+        if let Some((list, list_fragments, is_tail)) = reusable.get(&fragments[pos].id) {
+            let end = pos + list_fragments.len();
+            let unchanged = end <= fragments.len() && fragments[pos..end] == **list_fragments;
+            let grow_tail = *is_tail && list_fragments.len() < list_size && end < fragments.len();
+            if unchanged && !grow_tail {
+                push_new_lists(&mut entries, pending_start..pos, list_size);
+                entries.push(FragmentListEntry::Existing((*list).clone()));
+                pos = end;
+                pending_start = pos;
+                continue;
+            }
+        }
+        pos += 1;
+    }
+    push_new_lists(&mut entries, pending_start..fragments.len(), list_size);
+
+    let writes = entries
+        .into_iter()
+        .map(|entry| {
+            let fragments = fragments.clone();
+            async move {
+                match entry {
+                    FragmentListEntry::Existing(list) => Ok(list),
+                    FragmentListEntry::New(range) => {
+                        write_fragment_list(object_store, base_path, &fragments[range]).await
+                    }
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    let lists = stream::iter(writes)
+        .buffered(FRAGMENT_LIST_IO_PARALLELISM)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    manifest.fragment_lists = Some(FragmentLists {
+        lists,
+        fragments: Some(fragments),
+    });
+    Ok(())
+}
+
 #[instrument(level = "debug", skip(object_store, manifest))]
 pub async fn read_manifest_indexes(
     object_store: &ObjectStore,
@@ -294,4 +534,98 @@ mod test {
         let schema = ArrowSchema::from(reader.schema());
         assert_eq!(schema.metadata().get("lance:extra").unwrap(), "for_test");
     }
+
+    #[tokio::test]
+    async fn test_fragment_lists() {
+        let store = ObjectStore::memory();
+        let base = Path::from("ds");
+        let arrow_schema = ArrowSchema::new(vec![ArrowField::new("a", DataType::Int64, false)]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+        let make_fragments = |ids: Range<u64>| {
+            ids.map(|id| {
+                Fragment::with_file_legacy(id, &format!("{}.lance", id), &schema, Some(10))
+            })
+            .collect::<Vec<_>>()
+        };
+        let list_sizes = |manifest: &Manifest| {
+            manifest
+                .fragment_lists
+                .as_ref()
+                .unwrap()
+                .lists
+                .iter()
+                .map(|list| list.num_fragments)
+                .collect::<Vec<_>>()
+        };
+        let num_list_files = || async {
+            store
+                .read_dir(base.child(FRAGMENT_LISTS_DIR))
+                .await
+                .unwrap()
+                .len()
+        };
+
+        let mut manifest = Manifest::new(schema.clone(), Arc::new(make_fragments(0..5)));
+        manifest.fragment_lists = Some(Default::default());
+        write_fragment_lists(&store, &base, &mut manifest, 2)
+            .await
+            .unwrap();
+        assert_eq!(list_sizes(&manifest), vec![2, 2, 1]);
+        assert_eq!(num_list_files().await, 3);
+
+        // Appending rewrites the small tail list together with the new fragments
+        let mut manifest =
+            Manifest::new_from_previous(&manifest, schema.clone(), Arc::new(make_fragments(0..7)));
+        write_fragment_lists(&store, &base, &mut manifest, 2)
+            .await
+            .unwrap();
+        assert_eq!(list_sizes(&manifest), vec![2, 2, 2, 1]);
+        assert_eq!(num_list_files().await, 5);
+
+        // Changing one fragment only rewrites the list holding it
+        let mut fragments = manifest.fragments.as_ref().clone();
+        fragments[3].physical_rows = Some(5);
+        let mut manifest =
+            Manifest::new_from_previous(&manifest, schema.clone(), Arc::new(fragments));
+        write_fragment_lists(&store, &base, &mut manifest, 2)
+            .await
+            .unwrap();
+        assert_eq!(list_sizes(&manifest), vec![2, 2, 2, 1]);
+        assert_eq!(num_list_files().await, 6);
+
+        let path = base.child("_versions").child("3.manifest");
+        let mut writer = store.create(&path).await.unwrap();
+        let pos = write_manifest(&mut writer, &mut manifest, None)
+            .await
+            .unwrap();
+        writer
+            .write_magics(pos, MAJOR_VERSION, MINOR_VERSION, MAGIC)
+            .await
+            .unwrap();
+        writer.shutdown().await.unwrap();
+
+        let root = read_manifest_root(&store, &path).await.unwrap();
+        assert!(root.needs_fragment_lists());
+        assert!(root.fragments.is_empty());
+
+        let loaded = read_manifest(&store, &path).await.unwrap();
+        assert!(!loaded.needs_fragment_lists());
+        assert_eq!(loaded, manifest);
+        assert_eq!(
+            loaded.fragments_by_offset_range(25..35).len(),
+            manifest.fragments_by_offset_range(25..35).len()
+        );
+
+        // Lists are read through the cache when one is given
+        let cache = FileMetadataCache::new(100);
+        let mut cached = root.clone();
+        load_fragment_lists(&store, &base, &mut cached, Some(&cache))
+            .await
+            .unwrap();
+        assert_eq!(cached.fragments, manifest.fragments);
+        let first_list = &manifest.fragment_lists.as_ref().unwrap().lists[0];
+        assert!(cache
+            .get::<Vec<Fragment>>(&fragment_list_path(&base, first_list))
+            .is_some());
+    }
 }
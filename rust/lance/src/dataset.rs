@@ -18,10 +18,13 @@ use chrono::{prelude::*, Duration};
 use datafusion::error::DataFusionError;
 use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
 use futures::future::BoxFuture;
-use futures::stream::{self, StreamExt, TryStreamExt};
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
 use futures::{Future, FutureExt, Stream};
 use lance_arrow::SchemaExt;
-use lance_core::datatypes::{Field, SchemaCompareOptions};
+use lance_core::datatypes::{
+    Field, SchemaCompareOptions, PRIMARY_KEY_ENFORCED_META_KEY, PRIMARY_KEY_META_KEY,
+};
+use lance_core::utils::address::RowAddress;
 use lance_datafusion::utils::reader_to_stream;
 use lance_file::datatypes::populate_schema_dictionary;
 use lance_io::object_store::{ObjectStore, ObjectStoreParams};
@@ -30,10 +33,14 @@ use lance_io::traits::WriteExt;
 use lance_io::utils::{read_metadata_offset, read_struct};
 use lance_table::format::{Fragment, Index, Manifest, MAGIC, MAJOR_VERSION, MINOR_VERSION};
 use lance_table::io::commit::{commit_handler_from_url, CommitError, CommitHandler, CommitLock};
-use lance_table::io::manifest::{read_manifest, write_manifest};
+use lance_table::io::deletion::read_deletion_file;
+use lance_table::io::manifest::{
+    load_fragment_lists, read_manifest, write_fragment_lists, write_manifest,
+};
 use log::warn;
 use object_store::path::Path;
 use prost::Message;
+use roaring::RoaringBitmap;
 use snafu::{location, Location};
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ops::Range;
@@ -49,10 +56,13 @@ mod hash_joiner;
 pub mod index;
 pub mod optimize;
 pub mod progress;
+pub mod repair;
 pub mod scanner;
 pub mod transaction;
 pub mod updater;
 mod utils;
+pub mod verify;
+mod version_index;
 mod write;
 
 use self::builder::DatasetBuilder;
@@ -61,21 +71,32 @@ use self::feature_flags::{apply_feature_flags, can_read_dataset, can_write_datas
 use self::fragment::FileFragment;
 use self::scanner::{DatasetRecordBatchStream, Scanner};
 use self::transaction::{Operation, Transaction};
+pub(crate) use self::version_index::VersionIndexUpdate;
+use self::write::constraints::{check_constraints, fill_defaults};
+pub(crate) use self::write::primary_key::check_added_keys;
+use self::write::primary_key::{check_unique, validate_primary_key};
+use self::write::schema_evolution::{append_transaction, evolve_schema};
 use self::write::write_fragments_internal;
 use crate::datatypes::Schema;
 use crate::error::box_error;
-use crate::io::commit::{commit_new_dataset, commit_transaction};
+use crate::io::commit::{commit_new_dataset, commit_transaction, CommitConfig};
 use crate::io::exec::Planner;
 use crate::session::Session;
 use crate::utils::temporal::{timestamp_to_nanos, utc_now, SystemTime};
 use crate::{Error, Result};
 use hash_joiner::HashJoiner;
 pub use lance_core::ROW_ID;
+pub use repair::{RepairMode, RepairStats};
+pub use verify::VerificationReport;
+pub use version_index::VersionFilter;
 pub use write::merge_insert::{
-    MergeInsertBuilder, MergeInsertJob, WhenMatched, WhenNotMatched, WhenNotMatchedBySource,
+    MergeInsertBuilder, MergeInsertJob, MergeStats, SourceDedupeBehavior, WhenMatched,
+    WhenNotMatched, WhenNotMatchedBySource, DEFAULT_MAX_INDEXED_LOOKUP_RATIO,
+    DEFAULT_MAX_INDEXED_LOOKUP_ROWS,
 };
+pub use write::stats::{OperationStats, RowSink};
 pub use write::update::{UpdateBuilder, UpdateJob};
-pub use write::{write_fragments, WriteMode, WriteParams};
+pub use write::{write_fragments, SchemaEvolution, WriteMode, WriteParams};
 
 const INDICES_DIR: &str = "_indices";
 
@@ -91,6 +112,11 @@ pub struct Dataset {
     pub(crate) base: Path,
     pub(crate) manifest: Arc<Manifest>,
     pub(crate) session: Arc<Session>,
+    /// `manifest` with its fragments, if they are kept in fragment list files.
+    ///
+    /// The lists are only read when the fragments are first needed.  This is
+    /// shared between clones of the dataset so they are read once.
+    loaded_manifest: Arc<tokio::sync::OnceCell<Arc<Manifest>>>,
 }
 
 /// Dataset Version
@@ -103,15 +129,35 @@ pub struct Version {
 
     /// Key-value pairs of metadata.
     pub metadata: BTreeMap<String, String>,
+
+    /// Name of the operation that created the version, if known.
+    pub operation: Option<String>,
+
+    /// Optional version tag.
+    pub tag: Option<String>,
+
+    /// Number of rows in the version, if known.
+    pub num_rows: Option<u64>,
 }
 
 /// Convert Manifest to Data Version.
 impl From<&Manifest> for Version {
     fn from(m: &Manifest) -> Self {
+        let num_rows = if m.needs_fragment_lists() {
+            None
+        } else {
+            m.fragments
+                .iter()
+                .map(|f| f.num_rows().map(|rows| rows as u64))
+                .sum()
+        };
         Self {
             version: m.version,
             timestamp: m.timestamp(),
             metadata: BTreeMap::default(),
+            operation: None,
+            tag: m.tag.clone(),
+            num_rows,
         }
     }
 }
@@ -327,6 +373,48 @@ impl Dataset {
         .await
     }
 
+    /// The manifest with its fragments, reading the fragment list files if they
+    /// have not been read yet.
+    ///
+    /// The lists are read through the session metadata cache, so versions that
+    /// share lists only read them once.
+    pub(crate) async fn loaded_manifest(&self) -> Result<Arc<Manifest>> {
+        if !self.manifest.needs_fragment_lists() {
+            return Ok(self.manifest.clone());
+        }
+        let manifest = self
+            .loaded_manifest
+            .get_or_try_init(|| async {
+                let mut manifest = self.manifest.as_ref().clone();
+                load_fragment_lists(
+                    &self.object_store,
+                    &self.base,
+                    &mut manifest,
+                    Some(&self.session.file_metadata_cache),
+                )
+                .await?;
+                Ok::<_, Error>(Arc::new(manifest))
+            })
+            .await?;
+        Ok(manifest.clone())
+    }
+
+    /// Load the fragments from the fragment list files, if they are not loaded yet.
+    pub async fn load_fragments(&mut self) -> Result<()> {
+        self.manifest = self.loaded_manifest().await?;
+        Ok(())
+    }
+
+    /// A copy of this dataset with its fragments loaded
+    pub(crate) async fn with_fragments(&self) -> Result<Self> {
+        let mut dataset = self.clone();
+        dataset.load_fragments().await?;
+        Ok(dataset)
+    }
+
+    /// Open the manifest at `manifest_path`.
+    ///
+    /// Fragments kept in fragment list files are not read until they are needed.
     async fn checkout_manifest(
         object_store: Arc<ObjectStore>,
         base_path: Path,
@@ -381,6 +469,7 @@ impl Dataset {
             manifest: Arc::new(manifest),
             commit_handler,
             session,
+            loaded_manifest: Default::default(),
         })
     }
 
@@ -432,29 +521,42 @@ impl Dataset {
             None
         } else {
             // pull the store params from write params because there might be creds in there
-            Some(
-                DatasetBuilder::from_uri(uri)
-                    .with_read_params(ReadParams {
-                        store_options: params.store_params.clone(),
-                        commit_handler: params.commit_handler.clone(),
-                        ..Default::default()
-                    })
-                    .load()
-                    .await?,
-            )
+            let mut dataset = DatasetBuilder::from_uri(uri)
+                .with_read_params(ReadParams {
+                    store_options: params.store_params.clone(),
+                    commit_handler: params.commit_handler.clone(),
+                    ..Default::default()
+                })
+                .load()
+                .await?;
+            // Evolving the schema looks at the existing fragments, and the commit needs
+            // them anyway
+            dataset.load_fragments().await?;
+            Some(dataset)
+        };
+
+        // append + input missing columns with a default value = fill them in
+        let (stream, schema) = match (&params.mode, dataset.as_ref()) {
+            (WriteMode::Append, Some(d)) => fill_defaults(stream, schema, d.schema())?,
+            _ => (stream, schema),
         };
 
-        // append + input schema different from existing schema = error
+        // append + input schema different from existing schema = error, unless the
+        // schema of the dataset can be evolved to accept the input
+        let mut schema_changes = None;
         if matches!(params.mode, WriteMode::Append) {
             if let Some(d) = dataset.as_ref() {
-                let m = d.manifest.as_ref();
-                schema.check_compatible(
-                    &m.schema,
-                    &SchemaCompareOptions {
-                        compare_dictionary: true,
-                        ..Default::default()
-                    },
-                )?;
+                schema_changes = evolve_schema(d, &schema, params.schema_evolution)?;
+                if schema_changes.is_none() {
+                    let m = d.manifest.as_ref();
+                    schema.check_compatible(
+                        &m.schema,
+                        &SchemaCompareOptions {
+                            compare_dictionary: true,
+                            ..Default::default()
+                        },
+                    )?;
+                }
             }
         }
 
@@ -472,35 +574,58 @@ impl Dataset {
             }
         }
 
+        // The written rows must satisfy the constraints of the schema
+        let stream = match (&params.mode, dataset.as_ref()) {
+            (WriteMode::Append, Some(d)) => check_constraints(stream, d.schema())?,
+            _ => check_constraints(stream, &schema)?,
+        };
+
         let object_store = Arc::new(object_store);
-        let fragments = write_fragments_internal(
-            dataset.as_ref(),
-            object_store.clone(),
-            &base,
-            &schema,
-            stream,
-            params.clone(),
-        )
-        .await?;
+        let transaction = if let Some(changes) = schema_changes {
+            append_transaction(
+                dataset.as_ref().unwrap(),
+                object_store.clone(),
+                changes,
+                stream,
+                &params,
+            )
+            .await?
+        } else {
+            let fragments = write_fragments_internal(
+                dataset.as_ref(),
+                object_store.clone(),
+                &base,
+                &schema,
+                stream,
+                params.clone(),
+            )
+            .await?;
 
-        let operation = match params.mode {
-            WriteMode::Create | WriteMode::Overwrite => Operation::Overwrite { schema, fragments },
-            WriteMode::Append => Operation::Append { fragments },
-        };
+            let operation = match params.mode {
+                WriteMode::Create | WriteMode::Overwrite => {
+                    Operation::Overwrite { schema, fragments }
+                }
+                WriteMode::Append => Operation::Append { fragments },
+            };
 
-        let transaction = Transaction::new(
-            dataset.as_ref().map(|ds| ds.manifest.version).unwrap_or(0),
-            operation,
-            None,
-        );
+            Transaction::new(
+                dataset.as_ref().map(|ds| ds.manifest.version).unwrap_or(0),
+                operation,
+                None,
+            )
+        };
 
+        let manifest_config = ManifestWriteConfig {
+            use_fragment_lists: params.use_fragment_lists,
+            ..Default::default()
+        };
         let manifest = if let Some(dataset) = &dataset {
             commit_transaction(
                 dataset,
                 &object_store,
                 commit_handler.as_ref(),
                 &transaction,
-                &Default::default(),
+                &manifest_config,
                 &Default::default(),
             )
             .await?
@@ -510,7 +635,7 @@ impl Dataset {
                 commit_handler.as_ref(),
                 &base,
                 &transaction,
-                &Default::default(),
+                &manifest_config,
             )
             .await?
         };
@@ -521,6 +646,7 @@ impl Dataset {
             manifest: Arc::new(manifest.clone()),
             session: Arc::new(Session::default()),
             commit_handler,
+            loaded_manifest: Default::default(),
         })
     }
 
@@ -560,28 +686,35 @@ impl Dataset {
         }
 
         let (stream, schema) = reader_to_stream(batches).await?;
+        let (stream, schema) = fill_defaults(stream, schema, self.schema())?;
+        let stream = check_constraints(stream, self.schema())?;
 
-        // Return Error if append and input schema differ
-        self.manifest.schema.check_compatible(
-            &schema,
-            &SchemaCompareOptions {
-                compare_dictionary: true,
-                ..Default::default()
-            },
-        )?;
+        let transaction = if let Some(changes) =
+            evolve_schema(self, &schema, params.schema_evolution)?
+        {
+            append_transaction(self, self.object_store.clone(), changes, stream, &params).await?
+        } else {
+            // Return Error if append and input schema differ
+            self.manifest.schema.check_compatible(
+                &schema,
+                &SchemaCompareOptions {
+                    compare_dictionary: true,
+                    ..Default::default()
+                },
+            )?;
 
-        let fragments = write_fragments_internal(
-            Some(self),
-            self.object_store.clone(),
-            &self.base,
-            &schema,
-            stream,
-            params.clone(),
-        )
-        .await?;
+            let fragments = write_fragments_internal(
+                Some(self),
+                self.object_store.clone(),
+                &self.base,
+                &schema,
+                stream,
+                params.clone(),
+            )
+            .await?;
 
-        let transaction =
-            Transaction::new(self.manifest.version, Operation::Append { fragments }, None);
+            Transaction::new(self.manifest.version, Operation::Append { fragments }, None)
+        };
 
         let new_manifest = commit_transaction(
             self,
@@ -618,6 +751,10 @@ impl Dataset {
     }
 
     /// Get the full manifest of the dataset version.
+    ///
+    /// If the dataset keeps its fragments in fragment list files, the fragments of
+    /// the manifest are only filled in once they have been loaded, for example with
+    /// [Self::load_fragments].
     pub fn manifest(&self) -> &Manifest {
         &self.manifest
     }
@@ -814,6 +951,7 @@ impl Dataset {
             manifest: Arc::new(manifest.clone()),
             session: Arc::new(Session::default()),
             commit_handler,
+            loaded_manifest: Default::default(),
         })
     }
 
@@ -834,6 +972,8 @@ impl Dataset {
         left_on: &str,
         right_on: &str,
     ) -> Result<()> {
+        // New field ids must not reuse those of dropped fields still in the fragments
+        self.load_fragments().await?;
         // Sanity check.
         if self.schema().field(left_on).is_none() {
             return Err(Error::invalid_input(
@@ -877,7 +1017,7 @@ impl Dataset {
 
         // Write new data file to each fragment. Parallelism is done over columns,
         // so no parallelism done at this level.
-        let updated_fragments: Vec<Fragment> = stream::iter(self.get_fragments())
+        let updated_fragments: Vec<Fragment> = stream::iter(self.get_fragments().await?)
             .then(|f| {
                 let joiner = joiner.clone();
                 async move { f.merge(left_on, &joiner).await.map(|f| f.metadata) }
@@ -949,7 +1089,7 @@ impl Dataset {
                 .count_rows()
                 .await? as usize)
         } else {
-            let cnts = stream::iter(self.get_fragments())
+            let cnts = stream::iter(self.get_fragments().await?)
                 .map(|f| async move { f.count_rows().await })
                 .buffer_unordered(16)
                 .try_collect::<Vec<_>>()
@@ -968,7 +1108,7 @@ impl Dataset {
         let mut sorted_indices: Vec<usize> = (0..row_indices.len()).collect();
         sorted_indices.sort_by_key(|&i| row_indices[i]);
 
-        let fragments = self.get_fragments();
+        let fragments = self.get_fragments().await?;
 
         // We will split into sub-requests for each fragment.
         let mut sub_requests: Vec<(&FileFragment, Range<usize>)> = Vec::new();
@@ -1100,7 +1240,7 @@ impl Dataset {
                 *row_ids.last().expect("empty range passed to take_rows") as u32 as usize;
             let range = range_start..(range_end + 1);
 
-            let fragment = self.get_fragment(fragment_id).ok_or_else(|| {
+            let fragment = self.get_fragment(fragment_id).await?.ok_or_else(|| {
                 Error::invalid_input(
                     format!("row_id belongs to non-existant fragment: {start}"),
                     location!(),
@@ -1135,15 +1275,18 @@ impl Dataset {
                     }
                 };
 
-                let fragment = self.get_fragment(fragment_id as usize).ok_or_else(|| {
-                    Error::invalid_input(
-                        format!(
-                            "row_id {} belongs to non-existant fragment: {}",
-                            row_ids[range.start], fragment_id
-                        ),
-                        location!(),
-                    )
-                })?;
+                let fragment = self
+                    .get_fragment(fragment_id as usize)
+                    .await?
+                    .ok_or_else(|| {
+                        Error::invalid_input(
+                            format!(
+                                "row_id {} belongs to non-existant fragment: {}",
+                                row_ids[range.start], fragment_id
+                            ),
+                            location!(),
+                        )
+                    })?;
                 let row_ids: Vec<u32> = row_ids[range].iter().map(|x| *x as u32).collect();
 
                 let batch_fut = do_take(fragment, row_ids, projection.clone(), false);
@@ -1179,7 +1322,7 @@ impl Dataset {
                     .or_insert_with(|| vec![offset]);
             });
 
-            let fragments = self.get_fragments();
+            let fragments = self.get_fragments().await?;
             let fragment_and_indices = fragments.into_iter().filter_map(|f| {
                 let local_row_ids = row_ids_per_fragment.remove(&(f.id() as u64))?;
                 Some((f, local_row_ids))
@@ -1273,18 +1416,67 @@ impl Dataset {
     }
 
     /// Delete rows based on a predicate.
-    pub async fn delete(&mut self, predicate: &str) -> Result<()> {
+    pub async fn delete(&mut self, predicate: &str) -> Result<OperationStats> {
+        self.delete_with_config(predicate, &Default::default())
+            .await
+    }
+
+    /// Delete rows based on a predicate, committing with the given [`CommitConfig`].
+    ///
+    /// With [`IsolationLevel::Snapshot`](crate::io::commit::IsolationLevel::Snapshot),
+    /// the delete is rebased onto concurrent deletes and updates of the same fragments,
+    /// unless they deleted the same rows.
+    pub async fn delete_with_config(
+        &mut self,
+        predicate: &str,
+        commit_config: &CommitConfig,
+    ) -> Result<OperationStats> {
+        self.delete_impl(predicate, commit_config, None).await
+    }
+
+    /// Delete rows based on a predicate, sending the deleted rows to `sink`.
+    ///
+    /// The rows are sent before the delete is committed.  If the sink fails, nothing is
+    /// deleted.
+    pub async fn delete_returning(
+        &mut self,
+        predicate: &str,
+        commit_config: &CommitConfig,
+        sink: Arc<dyn RowSink>,
+    ) -> Result<OperationStats> {
+        self.delete_impl(predicate, commit_config, Some(sink)).await
+    }
+
+    async fn delete_impl(
+        &mut self,
+        predicate: &str,
+        commit_config: &CommitConfig,
+        sink: Option<Arc<dyn RowSink>>,
+    ) -> Result<OperationStats> {
+        if let Some(sink) = sink {
+            let mut deleted_rows = self
+                .scan()
+                .with_row_id()
+                .filter(predicate)?
+                .try_into_stream()
+                .await?;
+            while let Some(batch) = deleted_rows.try_next().await? {
+                sink.write_batch(batch).await?;
+            }
+        }
+
         let mut updated_fragments: Vec<Fragment> = Vec::new();
         let mut deleted_fragment_ids: Vec<u64> = Vec::new();
-        stream::iter(self.get_fragments())
+        let mut num_deleted_rows = 0;
+        stream::iter(self.get_fragments().await?)
             .map(|f| async move {
                 let old_fragment = f.metadata.clone();
-                let new_fragment = f.delete(predicate).await?.map(|f| f.metadata);
-                Ok((old_fragment, new_fragment))
+                let (new_fragment, num_deleted) = f.delete_counted(predicate).await?;
+                Ok((old_fragment, new_fragment.map(|f| f.metadata), num_deleted))
             })
             .buffer_unordered(num_cpus::get())
             // Drop the fragments that were deleted.
-            .try_for_each(|(old_fragment, new_fragment)| {
+            .try_for_each(|(old_fragment, new_fragment, num_deleted)| {
                 if let Some(new_fragment) = new_fragment {
                     if new_fragment != old_fragment {
                         updated_fragments.push(new_fragment);
@@ -1292,16 +1484,153 @@ impl Dataset {
                 } else {
                     deleted_fragment_ids.push(old_fragment.id);
                 }
+                num_deleted_rows += num_deleted as u64;
                 futures::future::ready(Ok::<_, crate::Error>(()))
             })
             .await?;
 
+        let mut stats =
+            OperationStats::from_changes(self, &deleted_fragment_ids, &updated_fragments, &[])
+                .await?;
+        stats.num_rows_affected = num_deleted_rows;
+
+        self.commit_delete(
+            updated_fragments,
+            deleted_fragment_ids,
+            predicate.to_string(),
+            commit_config,
+        )
+        .await?;
+        Ok(stats)
+    }
+
+    /// Delete rows by their row ids.
+    ///
+    /// The ids are grouped by fragment and the deletion files are written directly, without
+    /// reading any data.  Ids of rows which are already deleted are ignored.  An id outside of
+    /// the fragments of the dataset is an error.
+    pub async fn delete_rows(&mut self, row_ids: &[u64]) -> Result<OperationStats> {
+        let mut by_fragment: BTreeMap<u32, RoaringBitmap> = BTreeMap::new();
+        for row_id in row_ids {
+            let address = RowAddress::new_from_id(*row_id);
+            by_fragment
+                .entry(address.fragment_id())
+                .or_default()
+                .insert(address.row_id());
+        }
+
+        let mut fragments = Vec::with_capacity(by_fragment.len());
+        for (fragment_id, rows) in by_fragment {
+            let fragment = self
+                .get_fragment(fragment_id as usize)
+                .await?
+                .ok_or_else(|| {
+                    Error::invalid_input(
+                        format!(
+                            "Cannot delete rows of fragment {}, it does not exist",
+                            fragment_id
+                        ),
+                        location!(),
+                    )
+                })?;
+            fragments.push((fragment, rows));
+        }
+
+        enum FragmentChange {
+            Unchanged,
+            Modified(Fragment),
+            Removed(u64),
+        }
+
+        let this = &*self;
+        let results = stream::iter(fragments)
+            .map(|(fragment, rows)| async move {
+                let physical_rows = fragment.physical_rows().await?;
+                if let Some(max) = rows.max().filter(|max| *max as usize >= physical_rows) {
+                    return Err(Error::invalid_input(
+                        format!(
+                            "Cannot delete row {} of fragment {}, it only has {} rows",
+                            max,
+                            fragment.id(),
+                            physical_rows
+                        ),
+                        location!(),
+                    ));
+                }
+                let deletion_vector =
+                    read_deletion_file(&this.base, &fragment.metadata, &this.object_store)
+                        .await?
+                        .unwrap_or_default();
+                let num_deleted = rows
+                    .iter()
+                    .filter(|row| !deletion_vector.contains(*row))
+                    .count();
+                if num_deleted == 0 {
+                    return Ok((FragmentChange::Unchanged, 0));
+                }
+                let id = fragment.id() as u64;
+                let change = match fragment.extend_deletions(rows).await? {
+                    Some(new_fragment) => FragmentChange::Modified(new_fragment.metadata),
+                    None => FragmentChange::Removed(id),
+                };
+                Ok((change, num_deleted))
+            })
+            .buffer_unordered(num_cpus::get())
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let mut updated_fragments = Vec::new();
+        let mut deleted_fragment_ids = Vec::new();
+        let mut num_deleted_rows = 0;
+        for (change, num_deleted) in results {
+            match change {
+                FragmentChange::Unchanged => {}
+                FragmentChange::Modified(fragment) => updated_fragments.push(fragment),
+                FragmentChange::Removed(fragment_id) => deleted_fragment_ids.push(fragment_id),
+            }
+            num_deleted_rows += num_deleted as u64;
+        }
+
+        let mut stats =
+            OperationStats::from_changes(self, &deleted_fragment_ids, &updated_fragments, &[])
+                .await?;
+        stats.num_rows_affected = num_deleted_rows;
+        if num_deleted_rows == 0 {
+            return Ok(stats);
+        }
+
+        let mut predicate = row_ids
+            .iter()
+            .take(10)
+            .map(|row_id| row_id.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        if row_ids.len() > 10 {
+            predicate += &format!(", ... ({} row ids)", row_ids.len());
+        }
+        self.commit_delete(
+            updated_fragments,
+            deleted_fragment_ids,
+            format!("_rowid IN ({})", predicate),
+            &Default::default(),
+        )
+        .await?;
+        Ok(stats)
+    }
+
+    async fn commit_delete(
+        &mut self,
+        updated_fragments: Vec<Fragment>,
+        deleted_fragment_ids: Vec<u64>,
+        predicate: String,
+        commit_config: &CommitConfig,
+    ) -> Result<()> {
         let transaction = Transaction::new(
             self.manifest.version,
             Operation::Delete {
                 updated_fragments,
                 deleted_fragment_ids,
-                predicate: predicate.to_string(),
+                predicate,
             },
             None,
         );
@@ -1312,17 +1641,16 @@ impl Dataset {
             self.commit_handler.as_ref(),
             &transaction,
             &Default::default(),
-            &Default::default(),
+            commit_config,
         )
         .await?;
 
         self.manifest = Arc::new(manifest);
-
         Ok(())
     }
 
     pub async fn count_deleted_rows(&self) -> Result<usize> {
-        futures::stream::iter(self.get_fragments())
+        futures::stream::iter(self.get_fragments().await?)
             .map(|f| async move { f.count_deletions().await })
             .buffer_unordered(num_cpus::get() * 4)
             .try_fold(0, |acc, x| futures::future::ready(Ok(acc + x)))
@@ -1363,23 +1691,41 @@ impl Dataset {
 
     /// Get all versions.
     pub async fn versions(&self) -> Result<Vec<Version>> {
-        let mut versions: Vec<Version> = self
-            .commit_handler
-            .list_manifests(&self.base, &self.object_store.inner)
+        self.list_versions(VersionFilter::default())
             .await?
-            .try_filter_map(|path| async move {
-                match read_manifest(&self.object_store, &path).await {
-                    Ok(manifest) => Ok(Some(Version::from(&manifest))),
-                    Err(e) => Err(e),
-                }
-            })
             .try_collect()
-            .await?;
+            .await
+    }
 
-        // TODO: this API should support pagination
-        versions.sort_by_key(|v| v.version);
+    /// List the versions matching the filter, oldest first.
+    ///
+    /// Versions are read from the version index a page at a time as the
+    /// stream is consumed, and pages outside of the filter are skipped.
+    pub async fn list_versions(
+        &self,
+        filter: VersionFilter,
+    ) -> Result<BoxStream<'_, Result<Version>>> {
+        version_index::list_versions(
+            &self.object_store,
+            self.commit_handler.as_ref(),
+            &self.base,
+            filter,
+        )
+        .await
+    }
 
-        Ok(versions)
+    /// Find the newest version created at or before `timestamp`.
+    ///
+    /// Returns None if the dataset has no such version.  The version can then
+    /// be loaded with [Self::checkout_version].
+    pub async fn version_at(&self, timestamp: DateTime<Utc>) -> Result<Option<Version>> {
+        version_index::version_at(
+            &self.object_store,
+            self.commit_handler.as_ref(),
+            &self.base,
+            timestamp,
+        )
+        .await
     }
 
     /// Get the latest version of the dataset
@@ -1392,7 +1738,7 @@ impl Dataset {
     }
 
     pub fn count_fragments(&self) -> usize {
-        self.manifest.fragments.len()
+        self.manifest.num_fragments()
     }
 
     pub fn schema(&self) -> &Schema {
@@ -1401,41 +1747,44 @@ impl Dataset {
 
     /// Get fragments.
     ///
-    /// If `filter` is provided, only fragments with the given name will be returned.
-    pub fn get_fragments(&self) -> Vec<FileFragment> {
-        let dataset = Arc::new(self.clone());
-        self.manifest
+    /// If the dataset keeps its fragments in fragment list files, they are read
+    /// the first time the fragments are needed.
+    pub async fn get_fragments(&self) -> Result<Vec<FileFragment>> {
+        let dataset = Arc::new(self.with_fragments().await?);
+        Ok(dataset
+            .manifest
             .fragments
             .iter()
             .map(|f| FileFragment::new(dataset.clone(), f.clone()))
-            .collect()
+            .collect())
     }
 
-    pub fn get_fragment(&self, fragment_id: usize) -> Option<FileFragment> {
-        let dataset = Arc::new(self.clone());
-        let fragment = self
+    pub async fn get_fragment(&self, fragment_id: usize) -> Result<Option<FileFragment>> {
+        let dataset = Arc::new(self.with_fragments().await?);
+        let fragment = dataset
             .manifest
             .fragments
             .iter()
-            .find(|f| f.id == fragment_id as u64)?;
-        Some(FileFragment::new(dataset, fragment.clone()))
+            .find(|f| f.id == fragment_id as u64)
+            .cloned();
+        Ok(fragment.map(|fragment| FileFragment::new(dataset, fragment)))
     }
 
-    pub(crate) fn fragments(&self) -> &Arc<Vec<Fragment>> {
-        &self.manifest.fragments
+    pub(crate) async fn fragments(&self) -> Result<Arc<Vec<Fragment>>> {
+        Ok(self.loaded_manifest().await?.fragments.clone())
     }
 
     /// Gets the number of files that are so small they don't even have a full
     /// group. These are considered too small because reading many of them is
     /// much less efficient than reading a single file because the separate files
     /// split up what would otherwise be single IO requests into multiple.
-    pub async fn num_small_files(&self, max_rows_per_group: usize) -> usize {
-        futures::stream::iter(self.get_fragments())
+    pub async fn num_small_files(&self, max_rows_per_group: usize) -> Result<usize> {
+        Ok(futures::stream::iter(self.get_fragments().await?)
             .map(|f| async move { f.physical_rows().await })
             .buffered(num_cpus::get() * 4)
             .try_filter(|row_count| futures::future::ready(*row_count < max_rows_per_group))
             .count()
-            .await
+            .await)
     }
 
     pub async fn validate(&self) -> Result<()> {
@@ -1463,7 +1812,7 @@ impl Dataset {
         }
 
         // All fragments have equal lengths
-        futures::stream::iter(self.get_fragments())
+        futures::stream::iter(self.get_fragments().await?)
             .map(|f| async move { f.validate().await })
             .buffer_unordered(num_cpus::get() * 4)
             .try_collect::<Vec<()>>()
@@ -1580,6 +1929,7 @@ impl Dataset {
         transforms: NewColumnTransform,
         read_columns: Option<Vec<String>>,
     ) -> Result<()> {
+        self.load_fragments().await?;
         // We just transform the SQL expression into a UDF backed by DataFusion
         // physical expressions.
         let (
@@ -1705,7 +2055,7 @@ impl Dataset {
     ) -> Result<Vec<Fragment>> {
         let read_columns_ref = read_columns.as_deref();
         let mapper_ref = mapper.as_ref();
-        let fragments = futures::stream::iter(self.get_fragments())
+        let fragments = futures::stream::iter(self.get_fragments().await?)
             .then(|fragment| {
                 let cache_ref = result_cache.clone();
                 let schemas_ref = &schemas;
@@ -1765,6 +2115,7 @@ impl Dataset {
     ///
     /// If a column has an index, it's index will be preserved.
     pub async fn alter_columns(&mut self, alterations: &[ColumnAlteration]) -> Result<()> {
+        self.load_fragments().await?;
         // Validate we aren't making nullable columns non-nullable and that all
         // the referenced columns actually exist.
         let mut new_schema = self.schema().clone();
@@ -1844,68 +2195,7 @@ impl Dataset {
             )
         } else {
             // Otherwise, we need to re-write the relevant fields.
-            let read_columns = cast_fields
-                .iter()
-                .map(|(old, _new)| {
-                    let parts = self.schema().field_ancestry_by_id(old.id).unwrap();
-                    let part_names = parts.iter().map(|p| p.name.clone()).collect::<Vec<_>>();
-                    part_names.join(".")
-                })
-                .collect::<Vec<_>>();
-
-            let new_ids = cast_fields
-                .iter()
-                .map(|(_old, new)| new.id)
-                .collect::<Vec<_>>();
-            // This schema contains the exact field ids we want to write the new fields with.
-            let new_col_schema = new_schema.project_by_ids(&new_ids);
-
-            let mapper = move |batch: &RecordBatch| {
-                let mut fields = Vec::with_capacity(cast_fields.len());
-                let mut columns = Vec::with_capacity(batch.num_columns());
-                for (old, new) in &cast_fields {
-                    let old_column = batch[&old.name].clone();
-                    let new_column = lance_arrow::cast::cast_with_options(
-                        &old_column,
-                        &new.data_type(),
-                        // Safe: false means it will error if the cast is lossy.
-                        &CastOptions {
-                            safe: false,
-                            ..Default::default()
-                        },
-                    )?;
-                    columns.push(new_column);
-                    fields.push(Arc::new(ArrowField::from(new)));
-                }
-                let schema = Arc::new(ArrowSchema::new(fields));
-                Ok(RecordBatch::try_new(schema, columns)?)
-            };
-            let mapper = Box::new(mapper);
-
-            let fragments = self
-                .add_columns_impl(
-                    Some(read_columns),
-                    mapper,
-                    None,
-                    Some((new_col_schema, new_schema.clone())),
-                )
-                .await?;
-
-            // Some data files may no longer contain any columns in the dataset (e.g. if every
-            // remaining column has been altered into a different data file) and so we remove them
-            let schema_field_ids = new_schema.field_ids().into_iter().collect::<Vec<_>>();
-            let fragments = fragments
-                .into_iter()
-                .map(|mut frag| {
-                    frag.files.retain(|f| {
-                        f.fields
-                            .iter()
-                            .any(|field| schema_field_ids.contains(field))
-                    });
-                    frag
-                })
-                .collect::<Vec<_>>();
-
+            let fragments = self.cast_columns_impl(cast_fields, &new_schema).await?;
             Transaction::new(
                 self.manifest.version,
                 Operation::Merge {
@@ -1933,31 +2223,103 @@ impl Dataset {
         Ok(())
     }
 
-    /// Remove columns from the dataset.
-    ///
-    /// This is a metadata-only operation and does not remove the data from the
-    /// underlying storage. In order to remove the data, you must subsequently
-    /// call `compact_files` to rewrite the data without the removed columns and
-    /// then call `cleanup_files` to remove the old files.
-    pub async fn drop_columns(&mut self, columns: &[&str]) -> Result<()> {
-        // Check if columns are present in the dataset and construct the new schema.
-        for col in columns {
-            if self.schema().field(col).is_none() {
-                return Err(Error::invalid_input(
-                    format!("Column {} does not exist in the dataset", col),
-                    location!(),
-                ));
+    /// Rewrites the `(old, new)` fields of every fragment, casting the old values to the
+    /// type of the new field.  `new_schema` is the schema of the dataset after the cast.
+    async fn cast_columns_impl(
+        &self,
+        cast_fields: Vec<(Field, Field)>,
+        new_schema: &Schema,
+    ) -> Result<Vec<Fragment>> {
+        let read_columns = cast_fields
+            .iter()
+            .map(|(old, _new)| {
+                let parts = self.schema().field_ancestry_by_id(old.id).unwrap();
+                let part_names = parts.iter().map(|p| p.name.clone()).collect::<Vec<_>>();
+                part_names.join(".")
+            })
+            .collect::<Vec<_>>();
+
+        let new_ids = cast_fields
+            .iter()
+            .map(|(_old, new)| new.id)
+            .collect::<Vec<_>>();
+        // This schema contains the exact field ids we want to write the new fields with.
+        let new_col_schema = new_schema.project_by_ids(&new_ids);
+
+        let mapper = move |batch: &RecordBatch| {
+            let mut fields = Vec::with_capacity(cast_fields.len());
+            let mut columns = Vec::with_capacity(batch.num_columns());
+            for (old, new) in &cast_fields {
+                let old_column = batch[&old.name].clone();
+                let new_column = lance_arrow::cast::cast_with_options(
+                    &old_column,
+                    &new.data_type(),
+                    // Safe: false means it will error if the cast is lossy.
+                    &CastOptions {
+                        safe: false,
+                        ..Default::default()
+                    },
+                )?;
+                columns.push(new_column);
+                fields.push(Arc::new(ArrowField::from(new)));
             }
-        }
+            let schema = Arc::new(ArrowSchema::new(fields));
+            Ok(RecordBatch::try_new(schema, columns)?)
+        };
+        let mapper = Box::new(mapper);
 
-        let columns_to_remove = self.manifest.schema.project(columns)?;
-        let new_schema = self.manifest.schema.exclude(columns_to_remove)?;
+        let fragments = self
+            .add_columns_impl(
+                Some(read_columns),
+                mapper,
+                None,
+                Some((new_col_schema, new_schema.clone())),
+            )
+            .await?;
 
-        if new_schema.fields.is_empty() {
-            return Err(Error::invalid_input(
-                "Cannot drop all columns from a dataset",
-                location!(),
-            ));
+        // Some data files may no longer contain any columns in the dataset (e.g. if every
+        // remaining column has been altered into a different data file) and so we remove them
+        let schema_field_ids = new_schema.field_ids().into_iter().collect::<Vec<_>>();
+        let fragments = fragments
+            .into_iter()
+            .map(|mut frag| {
+                frag.files.retain(|f| {
+                    f.live_fields()
+                        .any(|field| schema_field_ids.contains(&field))
+                });
+                frag
+            })
+            .collect::<Vec<_>>();
+
+        Ok(fragments)
+    }
+
+    /// Declares the primary key of the dataset
+    ///
+    /// The key is stored in the schema metadata.  [`MergeInsertBuilder`] matches rows on it
+    /// when no keys are given.  If `enforce` is true, commits that add rows with duplicate keys,
+    /// such as appends and the inserts of a merge insert, are rejected, including ones made by
+    /// concurrent commits.  This requires a scalar index on one of the key columns and the
+    /// existing rows to have unique keys.
+    ///
+    /// Passing no columns removes the primary key.
+    pub async fn set_primary_key(&mut self, columns: &[&str], enforce: bool) -> Result<()> {
+        let mut new_schema = self.schema().clone();
+        if columns.is_empty() {
+            new_schema.metadata.remove(PRIMARY_KEY_META_KEY);
+            new_schema.metadata.remove(PRIMARY_KEY_ENFORCED_META_KEY);
+        } else {
+            validate_primary_key(&new_schema, columns)?;
+            if enforce {
+                check_unique(self, columns).await?;
+            }
+            new_schema
+                .metadata
+                .insert(PRIMARY_KEY_META_KEY.to_string(), columns.join(","));
+            new_schema.metadata.insert(
+                PRIMARY_KEY_ENFORCED_META_KEY.to_string(),
+                enforce.to_string(),
+            );
         }
 
         let transaction = Transaction::new(
@@ -1980,19 +2342,197 @@ impl Dataset {
 
         Ok(())
     }
-}
 
-#[derive(Debug)]
-pub(crate) struct ManifestWriteConfig {
-    auto_set_feature_flags: bool,  // default true
-    timestamp: Option<SystemTime>, // default None
-}
+    /// Remove columns from the dataset.
+    ///
+    /// This is a metadata-only operation and does not remove the data from the
+    /// underlying storage. In order to remove the data, you must subsequently
+    /// call [`Self::reclaim_dropped_columns`] to rewrite the data files without the
+    /// removed columns and then call `cleanup_old_versions` to remove the old files.
+    pub async fn drop_columns(&mut self, columns: &[&str]) -> Result<()> {
+        // Check if columns are present in the dataset and construct the new schema.
+        for col in columns {
+            if self.schema().field(col).is_none() {
+                return Err(Error::invalid_input(
+                    format!("Column {} does not exist in the dataset", col),
+                    location!(),
+                ));
+            }
+        }
+
+        if let Some(col) = self
+            .schema()
+            .primary_key()
+            .into_iter()
+            .find(|c| columns.contains(c))
+        {
+            return Err(Error::invalid_input(
+                format!(
+                    "Cannot drop column {} because it is part of the primary key",
+                    col
+                ),
+                location!(),
+            ));
+        }
+
+        let columns_to_remove = self.manifest.schema.project(columns)?;
+        let new_schema = self.manifest.schema.exclude(columns_to_remove)?;
+
+        if new_schema.fields.is_empty() {
+            return Err(Error::invalid_input(
+                "Cannot drop all columns from a dataset",
+                location!(),
+            ));
+        }
+
+        // Columns added after a fragment was written are not stored in it, a fragment needs
+        // at least one stored column left to read its rows.
+        let remaining_field_ids = new_schema
+            .fields_pre_order()
+            .map(|f| f.id)
+            .collect::<HashSet<_>>();
+        if let Some(fragment) = self.fragments().await?.iter().find(|fragment| {
+            !fragment.files.iter().any(|file| {
+                file.live_fields()
+                    .any(|id| remaining_field_ids.contains(&id))
+            })
+        }) {
+            return Err(Error::invalid_input(
+                format!(
+                    "Cannot drop columns {:?} because fragment {} does not store any other column, compact it first",
+                    columns, fragment.id
+                ),
+                location!(),
+            ));
+        }
+
+        let transaction = Transaction::new(
+            self.manifest.version,
+            Operation::Project { schema: new_schema },
+            None,
+        );
+
+        let manifest = commit_transaction(
+            self,
+            &self.object_store,
+            self.commit_handler.as_ref(),
+            &transaction,
+            &Default::default(),
+            &Default::default(),
+        )
+        .await?;
+
+        self.manifest = Arc::new(manifest);
+
+        Ok(())
+    }
+
+    /// Rewrite the data files that still contain dropped or replaced columns.
+    ///
+    /// Data files that only contain columns no longer in the dataset are removed from
+    /// their fragment.  In each fragment, the remaining columns of the other data files
+    /// with such columns are rewritten into a single new data file.  Rows keep their
+    /// addresses, so indices stay valid.  The old files are freed by
+    /// `cleanup_old_versions` once no version references them.
+    ///
+    /// Returns an error if a fragment does not store any of the remaining columns, for
+    /// example if they were all added after it was written.  A fragment needs at least one
+    /// data file to read its rows, [`optimize::compact_files`] rewrites it with all columns.
+    pub async fn reclaim_dropped_columns(&mut self) -> Result<()> {
+        self.load_fragments().await?;
+        let schema = self.schema().clone();
+        let schema_field_ids = schema.field_ids().into_iter().collect::<HashSet<_>>();
+        let dataset = Arc::new(self.clone());
+
+        let mut changed = false;
+        let mut fragments = Vec::with_capacity(self.manifest.fragments.len());
+        for fragment in self.manifest.fragments.iter() {
+            let mut fragment = fragment.clone();
+            let num_files = fragment.files.len();
+            fragment.files.retain(|f| {
+                f.live_fields()
+                    .any(|field| schema_field_ids.contains(&field))
+            });
+            if fragment.files.is_empty() {
+                return Err(Error::invalid_input(
+                    format!(
+                        "Fragment {} does not store any of the remaining columns, compact it before reclaiming the dropped ones",
+                        fragment.id
+                    ),
+                    location!(),
+                ));
+            }
+            changed |= fragment.files.len() != num_files;
+
+            // The files that also have some dropped or replaced fields
+            let (to_rewrite, keep): (Vec<_>, Vec<_>) =
+                fragment.files.iter().cloned().partition(|f| {
+                    f.fields
+                        .iter()
+                        .any(|id| f.replaced_fields.contains(id) || !schema_field_ids.contains(id))
+                });
+            if to_rewrite.is_empty() {
+                fragments.push(fragment);
+                continue;
+            }
+            changed = true;
+
+            let live_ids = to_rewrite
+                .iter()
+                .flat_map(|f| f.live_fields())
+                .filter(|id| schema_field_ids.contains(id))
+                .collect::<Vec<_>>();
+            let new_file = FileFragment::new(dataset.clone(), fragment.clone())
+                .rewrite_fields(&live_ids)
+                .await?;
+            fragment.files = keep;
+            fragment.files.push(new_file);
+            fragments.push(fragment);
+        }
+
+        if !changed {
+            return Ok(());
+        }
+
+        let transaction = Transaction::new(
+            self.manifest.version,
+            Operation::Merge { fragments, schema },
+            None,
+        );
+
+        let manifest = commit_transaction(
+            self,
+            &self.object_store,
+            self.commit_handler.as_ref(),
+            &transaction,
+            &Default::default(),
+            &Default::default(),
+        )
+        .await?;
+
+        self.manifest = Arc::new(manifest);
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct ManifestWriteConfig {
+    auto_set_feature_flags: bool,   // default true
+    timestamp: Option<SystemTime>,  // default None
+    use_fragment_lists: bool,       // default false
+    fragment_list_size: usize,      // default 1024
+    version_index_page_size: usize, // default 1000
+}
 
 impl Default for ManifestWriteConfig {
     fn default() -> Self {
         Self {
             auto_set_feature_flags: true,
             timestamp: None,
+            use_fragment_lists: false,
+            fragment_list_size: 1024,
+            version_index_page_size: version_index::DEFAULT_PAGE_SIZE,
         }
     }
 }
@@ -2005,7 +2545,16 @@ pub(crate) async fn write_manifest_file(
     manifest: &mut Manifest,
     indices: Option<Vec<Index>>,
     config: &ManifestWriteConfig,
+    version_index: Option<VersionIndexUpdate<'_>>,
 ) -> std::result::Result<(), CommitError> {
+    if config.use_fragment_lists && manifest.fragment_lists.is_none() {
+        manifest.fragment_lists = Some(Default::default());
+    }
+    if manifest.fragment_lists.is_some() {
+        // Lists are immutable, so they can be written before the commit.  If the
+        // commit fails they are left unreferenced and removed by cleanup.
+        write_fragment_lists(object_store, base_path, manifest, config.fragment_list_size).await?;
+    }
     if config.auto_set_feature_flags {
         apply_feature_flags(manifest);
     }
@@ -2013,6 +2562,18 @@ pub(crate) async fn write_manifest_file(
 
     manifest.update_max_fragment_id();
 
+    manifest.version_index_file = None;
+    if let Some(update) = version_index {
+        version_index::write_version_index(
+            object_store,
+            base_path,
+            manifest,
+            &update,
+            config.version_index_page_size,
+        )
+        .await?;
+    }
+
     commit_handler
         .commit(
             manifest,
@@ -2072,17 +2633,19 @@ fn check_row_ids(row_ids: &[u64]) -> RowIdMeta {
 
 #[cfg(test)]
 mod tests {
+    use std::ops::Range;
     use std::sync::Mutex;
     use std::vec;
 
     use super::*;
     use crate::arrow::FixedSizeListArrayExt;
     use crate::dataset::optimize::{compact_files, CompactionOptions};
+    use crate::dataset::write::stats::tests::VecSink;
     use crate::dataset::WriteMode::Overwrite;
     use crate::index::scalar::ScalarIndexParams;
     use crate::index::vector::VectorIndexParams;
 
-    use arrow_array::types::Int64Type;
+    use arrow_array::types::{Int64Type, UInt32Type};
     use arrow_array::{
         builder::StringDictionaryBuilder, cast::as_string_array, types::Int32Type, ArrayRef,
         DictionaryArray, Float32Array, Int32Array, Int64Array, Int8Array, Int8DictionaryArray,
@@ -2095,9 +2658,12 @@ mod tests {
     use lance_arrow::bfloat16::{self, ARROW_EXT_META_KEY, ARROW_EXT_NAME_KEY, BFLOAT16_EXT_NAME};
     use lance_datagen::{array, gen, BatchCount, RowCount};
     use lance_index::{vector::DIST_COL, DatasetIndexExt, IndexType};
+    use lance_io::encryption::{KeyProvider, LocalFileKeyProvider, ENCRYPTION_MAGIC};
+    use lance_io::object_store::ObjectStoreParams;
     use lance_linalg::distance::MetricType;
     use lance_table::format::WriterVersion;
-    use lance_table::io::deletion::read_deletion_file;
+    use lance_table::io::deletion::{deletion_file_path, read_deletion_file};
+    use lance_table::io::manifest::{read_manifest_root, FRAGMENT_LISTS_DIR};
     use lance_testing::datagen::generate_random_array;
     use pretty_assertions::assert_eq;
     use tempfile::{tempdir, TempDir};
@@ -2187,6 +2753,8 @@ mod tests {
         assert_eq!(
             actual_ds
                 .fragments()
+                .await
+                .unwrap()
                 .iter()
                 .map(|f| f.id)
                 .collect::<Vec<_>>(),
@@ -2332,7 +2900,7 @@ mod tests {
 
         assert_eq!(dataset.count_rows(None).await.unwrap(), num_rows);
 
-        let fragments = dataset.get_fragments();
+        let fragments = dataset.get_fragments().await.unwrap();
         assert_eq!(fragments.len(), 10);
         assert_eq!(dataset.count_fragments(), 10);
         for fragment in &fragments {
@@ -2405,8 +2973,8 @@ mod tests {
         );
 
         // Write with custom manifest
-        manifest.writer_feature_flags = 5; // Set another flag
-        manifest.reader_feature_flags = 5;
+        manifest.writer_feature_flags = 9; // Set another flag
+        manifest.reader_feature_flags = 9;
         manifest.version += 1;
         write_manifest_file(
             dataset.object_store(),
@@ -2417,7 +2985,9 @@ mod tests {
             &ManifestWriteConfig {
                 auto_set_feature_flags: false,
                 timestamp: None,
+                ..Default::default()
             },
+            None,
         )
         .await
         .unwrap();
@@ -2446,6 +3016,226 @@ mod tests {
         assert!(matches!(write_result, Err(Error::NotSupported { .. })));
     }
 
+    #[tokio::test]
+    async fn test_encrypted_dataset() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().join("data");
+        let test_uri = test_uri.to_str().unwrap();
+        let key_provider: Arc<dyn KeyProvider> = Arc::new(
+            LocalFileKeyProvider::try_new(test_dir.path().join("keys"), "master-1").unwrap(),
+        );
+
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..100))],
+        )
+        .unwrap();
+        let write_params = WriteParams {
+            max_rows_per_file: 50,
+            store_params: Some(ObjectStoreParams {
+                key_provider: Some(key_provider.clone()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let mut dataset = Dataset::write(batches, test_uri, Some(write_params))
+            .await
+            .unwrap();
+        dataset
+            .create_index(
+                &["i"],
+                IndexType::Scalar,
+                None,
+                &ScalarIndexParams::default(),
+                false,
+            )
+            .await
+            .unwrap();
+        dataset.delete("i < 10").await.unwrap();
+
+        let is_encrypted = |path: Path| {
+            let object_store = dataset.object_store.clone();
+            async move {
+                let data = object_store.inner.get(&path).await.unwrap();
+                let data = data.bytes().await.unwrap();
+                data.starts_with(ENCRYPTION_MAGIC)
+            }
+        };
+        let fragments = dataset.get_fragments().await.unwrap();
+        assert_eq!(fragments.len(), 2);
+        for fragment in &fragments {
+            let data_file = &fragment.metadata().files[0];
+            assert_eq!(data_file.encryption_key_id.as_deref(), Some("master-1"));
+            assert!(is_encrypted(dataset.data_dir().child(data_file.path.as_str())).await);
+        }
+        let deletion_file = fragments[0].metadata().deletion_file.clone().unwrap();
+        assert_eq!(deletion_file.encryption_key_id.as_deref(), Some("master-1"));
+        let deletion_path = deletion_file_path(&dataset.base, 0, &deletion_file);
+        assert!(is_encrypted(deletion_path).await);
+        let index_dir = dataset.indices_dir();
+        let index_files = dataset
+            .object_store
+            .read_dir_all(&index_dir, None)
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert!(!index_files.is_empty());
+        for index_file in index_files {
+            assert!(is_encrypted(index_file.location).await);
+        }
+
+        // Reading back, through the index as well
+        let dataset = DatasetBuilder::from_uri(test_uri)
+            .with_key_provider(key_provider)
+            .load()
+            .await
+            .unwrap();
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 90);
+        let batch = dataset
+            .scan()
+            .filter("i = 42")
+            .unwrap()
+            .try_into_batch()
+            .await
+            .unwrap();
+        assert_eq!(batch.num_rows(), 1);
+
+        // Without the key provider the manifest is readable but the data is not
+        let plain_dataset = Dataset::open(test_uri).await.unwrap();
+        let err = plain_dataset.scan().try_into_batch().await.unwrap_err();
+        assert!(err.to_string().contains("no key provider"), "{}", err);
+
+        // A data file replaced by its plaintext is rejected
+        let fragment = dataset.get_fragment(1).await.unwrap().unwrap();
+        let path = dataset
+            .data_dir()
+            .child(fragment.metadata().files[0].path.as_str());
+        let plaintext = dataset.object_store.read_object(&path).await.unwrap();
+        dataset
+            .object_store
+            .inner
+            .put(&path, plaintext)
+            .await
+            .unwrap();
+        let err = fragment.scan().try_into_batch().await.unwrap_err();
+        assert!(err.to_string().contains("no encryption header"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_fragment_lists() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let make_batches = |range: Range<i32>| {
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(Int32Array::from_iter_values(range))],
+            )
+            .unwrap();
+            RecordBatchIterator::new(vec![Ok(batch)], schema.clone())
+        };
+        let write_params = WriteParams {
+            max_rows_per_file: 5,
+            use_fragment_lists: true,
+            ..Default::default()
+        };
+        let dataset = Dataset::write(make_batches(0..20), test_uri, Some(write_params))
+            .await
+            .unwrap();
+        assert_eq!(dataset.get_fragments().await.unwrap().len(), 4);
+
+        let list_files = |dataset: &Dataset| {
+            let dir = dataset.base.child(FRAGMENT_LISTS_DIR);
+            let object_store = dataset.object_store.clone();
+            async move { object_store.read_dir(dir).await.unwrap().len() }
+        };
+        let latest_root = |dataset: &Dataset| {
+            let dataset = dataset.clone();
+            async move {
+                let path = dataset
+                    .commit_handler
+                    .resolve_latest_version(&dataset.base, &dataset.object_store().inner)
+                    .await
+                    .unwrap();
+                read_manifest_root(dataset.object_store(), &path)
+                    .await
+                    .unwrap()
+            }
+        };
+        let root = latest_root(&dataset).await;
+        assert!(root.fragments.is_empty());
+        assert_eq!(root.fragment_lists.as_ref().unwrap().lists.len(), 1);
+        assert_eq!(
+            root.reader_feature_flags,
+            feature_flags::FLAG_FRAGMENT_LISTS
+        );
+        assert_eq!(list_files(&dataset).await, 1);
+
+        // Later commits keep the layout without being asked to
+        let write_params = WriteParams {
+            max_rows_per_file: 5,
+            mode: WriteMode::Append,
+            ..Default::default()
+        };
+        let mut dataset = Dataset::write(make_batches(20..40), test_uri, Some(write_params))
+            .await
+            .unwrap();
+        dataset.delete("i < 12").await.unwrap();
+        let root = latest_root(&dataset).await;
+        assert!(root.fragments.is_empty());
+        assert_eq!(
+            root.reader_feature_flags,
+            feature_flags::FLAG_DELETION_FILES | feature_flags::FLAG_FRAGMENT_LISTS
+        );
+        assert_eq!(list_files(&dataset).await, 3);
+
+        let dataset = Dataset::open(test_uri).await.unwrap();
+        dataset.validate().await.unwrap();
+        assert_eq!(dataset.get_fragments().await.unwrap().len(), 6);
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 28);
+        assert_eq!(dataset.versions().await.unwrap().len(), 3);
+        let first = dataset.checkout_version(1).await.unwrap();
+        assert_eq!(first.count_rows(None).await.unwrap(), 20);
+
+        // Opening the dataset reads no list files, they are read once the fragments
+        // are needed
+        let lists_dir = test_dir.path().join(FRAGMENT_LISTS_DIR);
+        let moved_dir = test_dir.path().join("moved");
+        std::fs::rename(&lists_dir, &moved_dir).unwrap();
+        let dataset = Dataset::open(test_uri).await.unwrap();
+        let first = dataset.checkout_version(1).await.unwrap();
+        assert!(dataset.manifest.needs_fragment_lists());
+        assert_eq!(dataset.count_fragments(), 6);
+        assert_eq!(first.count_fragments(), 4);
+        assert_eq!(dataset.schema().fields.len(), 1);
+        assert!(dataset.get_fragments().await.is_err());
+        assert!(dataset.count_rows(None).await.is_err());
+
+        std::fs::rename(&moved_dir, &lists_dir).unwrap();
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 28);
+        assert_eq!(dataset.get_fragments().await.unwrap().len(), 6);
+        assert_eq!(first.get_fragments().await.unwrap().len(), 4);
+        // The loaded fragments are shared with clones of the dataset
+        assert!(dataset.clone().manifest.needs_fragment_lists());
+        assert!(Arc::ptr_eq(
+            &dataset.clone().fragments().await.unwrap(),
+            &dataset.fragments().await.unwrap()
+        ));
+    }
+
     #[tokio::test]
     async fn append_dataset() {
         let test_dir = tempdir().unwrap();
@@ -2516,6 +3306,8 @@ mod tests {
         assert_eq!(
             actual_ds
                 .fragments()
+                .await
+                .unwrap()
                 .iter()
                 .map(|f| f.id)
                 .collect::<Vec<_>>(),
@@ -2570,10 +3362,12 @@ mod tests {
         let actual_ds = Dataset::open(test_uri).await.unwrap();
         assert_eq!(actual_ds.version().version, 2);
         // validate fragment ids
-        assert_eq!(actual_ds.fragments().len(), 2);
+        assert_eq!(actual_ds.fragments().await.unwrap().len(), 2);
         assert_eq!(
             actual_ds
                 .fragments()
+                .await
+                .unwrap()
                 .iter()
                 .map(|f| f.id)
                 .collect::<Vec<_>>(),
@@ -2598,56 +3392,285 @@ mod tests {
         let struct_arr: StructArray = actual_batch.into();
         let sorted_arr = take(&struct_arr, &sorted_indices, None).unwrap();
 
-        let expected_struct_arr: StructArray = expected_batch.into();
-        assert_eq!(&expected_struct_arr, as_struct_array(sorted_arr.as_ref()));
+        let expected_struct_arr: StructArray = expected_batch.into();
+        assert_eq!(&expected_struct_arr, as_struct_array(sorted_arr.as_ref()));
+
+        actual_ds.validate().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_self_dataset_append_schema_different() {
+        let test_dir = tempdir().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batches = vec![RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..20))],
+        )
+        .unwrap()];
+
+        let other_schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "i",
+            DataType::Int64,
+            false,
+        )]));
+        let other_batches = vec![RecordBatch::try_new(
+            other_schema.clone(),
+            vec![Arc::new(Int64Array::from_iter_values(0..20))],
+        )
+        .unwrap()];
+
+        let test_uri = test_dir.path().to_str().unwrap();
+        let mut write_params = WriteParams {
+            max_rows_per_file: 40,
+            max_rows_per_group: 10,
+            ..Default::default()
+        };
+        let batches = RecordBatchIterator::new(batches.into_iter().map(Ok), schema.clone());
+        let mut ds = Dataset::write(batches, test_uri, Some(write_params.clone()))
+            .await
+            .unwrap();
+
+        write_params.mode = WriteMode::Append;
+        let other_batches =
+            RecordBatchIterator::new(other_batches.into_iter().map(Ok), other_schema.clone());
+
+        let result = ds.append(other_batches, Some(write_params.clone())).await;
+        // Error because schema is different
+        assert!(matches!(result, Err(Error::SchemaMismatch { .. })))
+    }
+
+    #[tokio::test]
+    async fn test_append_schema_evolution_add_columns() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..20))],
+        )
+        .unwrap();
+        let write_params = WriteParams {
+            max_rows_per_file: 10,
+            ..Default::default()
+        };
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        Dataset::write(reader, test_uri, Some(write_params))
+            .await
+            .unwrap();
+
+        let new_schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("s", DataType::Utf8, true),
+            Field::new("i", DataType::Int32, false),
+        ]));
+        let new_batch = RecordBatch::try_new(
+            new_schema.clone(),
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    (20..30).map(|v| v.to_string()),
+                )),
+                Arc::new(Int32Array::from_iter_values(20..30)),
+            ],
+        )
+        .unwrap();
+        let mut write_params = WriteParams {
+            mode: WriteMode::Append,
+            ..Default::default()
+        };
+
+        // Strict is the default, and rejects the new column
+        let reader = RecordBatchIterator::new(vec![Ok(new_batch.clone())], new_schema.clone());
+        let result = Dataset::write(reader, test_uri, Some(write_params.clone())).await;
+        assert!(matches!(result, Err(Error::SchemaMismatch { .. })));
+
+        write_params.schema_evolution = SchemaEvolution::AddMissingColumns;
+        let reader = RecordBatchIterator::new(vec![Ok(new_batch)], new_schema.clone());
+        let dataset = Dataset::write(reader, test_uri, Some(write_params.clone()))
+            .await
+            .unwrap();
+        dataset.validate().await.unwrap();
+        assert_eq!(dataset.version().version, 2);
+        assert_eq!(dataset.get_fragments().await.unwrap().len(), 3);
+        let field_names = dataset
+            .schema()
+            .fields
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(field_names, vec!["i", "s"]);
+
+        // The rows written before the column was added read it as null
+        let batch = dataset.scan().try_into_batch().await.unwrap();
+        let expected_s = StringArray::from_iter(
+            std::iter::repeat(None)
+                .take(20)
+                .chain((20..30).map(|v| Some(v.to_string()))),
+        );
+        assert_eq!(batch["i"].as_ref(), &Int32Array::from_iter_values(0..30));
+        assert_eq!(batch["s"].as_ref(), &expected_s);
+
+        let batch = dataset
+            .scan()
+            .project(&["s"])
+            .unwrap()
+            .filter("s IS NULL")
+            .unwrap()
+            .try_into_batch()
+            .await
+            .unwrap();
+        assert_eq!(batch.num_rows(), 20);
+        assert_eq!(batch.num_columns(), 1);
+
+        let taken = dataset
+            .take(&[5, 25], &dataset.schema().project(&["s"]).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            taken["s"].as_ref(),
+            &StringArray::from(vec![None, Some("25")])
+        );
+
+        // New columns must be nullable
+        let bad_schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("i", DataType::Int32, false),
+            Field::new("s", DataType::Utf8, true),
+            Field::new("x", DataType::Int32, false),
+        ]));
+        let bad_batch = RecordBatch::try_new(
+            bad_schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..2)),
+                Arc::new(StringArray::from(vec!["a", "b"])),
+                Arc::new(Int32Array::from_iter_values(0..2)),
+            ],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(bad_batch)], bad_schema);
+        let result = Dataset::write(reader, test_uri, Some(write_params.clone())).await;
+        assert!(matches!(result, Err(Error::InvalidInput { .. })));
 
-        actual_ds.validate().await.unwrap();
+        // Columns added later get larger field ids than the missing column, the old
+        // fragments are still valid
+        let mut dataset = dataset;
+        dataset
+            .add_columns(
+                NewColumnTransform::SqlExpressions(vec![("t".into(), "i * 2".into())]),
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(dataset.schema().field("t").unwrap().id > dataset.schema().field("s").unwrap().id);
+        dataset.validate().await.unwrap();
+        let batch = dataset.scan().try_into_batch().await.unwrap();
+        assert_eq!(batch["s"].as_ref(), &expected_s);
+
+        // Non-nullable columns must be stored in every fragment
+        let i_id = dataset.schema().field("i").unwrap().id;
+        let mut fragment = dataset.get_fragments().await.unwrap()[0].metadata().clone();
+        fragment.files[0].fields.retain(|id| *id != i_id);
+        let err = FileFragment::new(Arc::new(dataset), fragment)
+            .validate()
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("is missing"), "{}", err);
     }
 
     #[tokio::test]
-    async fn test_self_dataset_append_schema_different() {
+    async fn test_append_schema_evolution_widen_types() {
         let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
 
-        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
-            "i",
-            DataType::Int32,
-            false,
-        )]));
-        let batches = vec![RecordBatch::try_new(
-            schema.clone(),
-            vec![Arc::new(Int32Array::from_iter_values(0..20))],
-        )
-        .unwrap()];
+        let make_batch = |i_type: DataType, f_type: DataType, range: Range<i32>| {
+            let schema = Arc::new(ArrowSchema::new(vec![
+                Field::new("i", i_type.clone(), false),
+                Field::new("f", f_type.clone(), true),
+            ]));
+            let i = arrow::compute::cast(&Int32Array::from_iter_values(range.clone()), &i_type);
+            let f = arrow::compute::cast(&Int32Array::from_iter_values(range), &f_type);
+            let batch = RecordBatch::try_new(schema.clone(), vec![i.unwrap(), f.unwrap()]);
+            RecordBatchIterator::new(vec![batch], schema)
+        };
 
-        let other_schema = Arc::new(ArrowSchema::new(vec![Field::new(
-            "i",
-            DataType::Int64,
-            false,
-        )]));
-        let other_batches = vec![RecordBatch::try_new(
-            other_schema.clone(),
-            vec![Arc::new(Int64Array::from_iter_values(0..20))],
+        let mut dataset = Dataset::write(
+            make_batch(DataType::Int32, DataType::Float32, 0..10),
+            test_uri,
+            None,
         )
-        .unwrap()];
+        .await
+        .unwrap();
 
-        let test_uri = test_dir.path().to_str().unwrap();
-        let mut write_params = WriteParams {
-            max_rows_per_file: 40,
-            max_rows_per_group: 10,
+        // Adding columns does not allow changing types
+        let params = WriteParams {
+            schema_evolution: SchemaEvolution::AddMissingColumns,
             ..Default::default()
         };
-        let batches = RecordBatchIterator::new(batches.into_iter().map(Ok), schema.clone());
-        let mut ds = Dataset::write(batches, test_uri, Some(write_params.clone()))
+        let result = dataset
+            .append(
+                make_batch(DataType::Int64, DataType::Float32, 10..20),
+                Some(params),
+            )
+            .await;
+        assert!(matches!(result, Err(Error::InvalidInput { .. })));
+
+        let params = WriteParams {
+            schema_evolution: SchemaEvolution::WidenTypes,
+            ..Default::default()
+        };
+        dataset
+            .append(
+                make_batch(DataType::Int64, DataType::Float64, 10..20),
+                Some(params.clone()),
+            )
+            .await
+            .unwrap();
+        dataset.validate().await.unwrap();
+        assert_eq!(dataset.version().version, 2);
+        assert_eq!(
+            dataset.schema().field("i").unwrap().data_type(),
+            DataType::Int64
+        );
+        assert_eq!(
+            dataset.schema().field("f").unwrap().data_type(),
+            DataType::Float64
+        );
+
+        // Narrower data is converted to the type of the dataset
+        dataset
+            .append(
+                make_batch(DataType::Int32, DataType::Float32, 20..30),
+                Some(params.clone()),
+            )
             .await
             .unwrap();
+        assert_eq!(
+            dataset.schema().field("i").unwrap().data_type(),
+            DataType::Int64
+        );
 
-        write_params.mode = WriteMode::Append;
-        let other_batches =
-            RecordBatchIterator::new(other_batches.into_iter().map(Ok), other_schema.clone());
+        let batch = dataset.scan().try_into_batch().await.unwrap();
+        assert_eq!(batch["i"].as_ref(), &Int64Array::from_iter_values(0..30));
+        assert_eq!(
+            batch["f"].as_ref(),
+            &Float64Array::from_iter_values((0..30).map(|v| v as f64))
+        );
 
-        let result = ds.append(other_batches, Some(write_params.clone())).await;
-        // Error because schema is different
-        assert!(matches!(result, Err(Error::SchemaMismatch { .. })))
+        // Types that cannot be widened into each other are not allowed
+        let result = dataset
+            .append(
+                make_batch(DataType::Float64, DataType::Float64, 30..40),
+                Some(params),
+            )
+            .await;
+        assert!(matches!(result, Err(Error::InvalidInput { .. })));
     }
 
     #[tokio::test]
@@ -2742,7 +3765,7 @@ mod tests {
             .await
             .unwrap();
 
-        let fragments = dataset.get_fragments();
+        let fragments = dataset.get_fragments().await.unwrap();
         assert_eq!(fragments.len(), 1);
         assert_eq!(dataset.manifest.max_fragment_id(), Some(0));
 
@@ -2765,7 +3788,7 @@ mod tests {
             .await
             .unwrap();
 
-        let fragments = dataset.get_fragments();
+        let fragments = dataset.get_fragments().await.unwrap();
         assert_eq!(fragments.len(), 1);
         // Fragment ids reset after overwrite.
         assert_eq!(fragments[0].id(), 0);
@@ -2983,7 +4006,7 @@ mod tests {
 
         let dataset = Dataset::open(test_uri).await.unwrap();
         dataset.validate().await.unwrap();
-        assert_eq!(10, dataset.fragments().len());
+        assert_eq!(10, dataset.fragments().await.unwrap().len());
         assert_eq!(400, dataset.count_rows(None).await.unwrap());
         assert_eq!(
             200,
@@ -3213,7 +4236,7 @@ mod tests {
         let mut dataset = Dataset::write(batches, test_uri, None).await?;
 
         let lance_schema = dataset.schema().clone();
-        let original_fragments = dataset.fragments().to_vec();
+        let original_fragments = dataset.fragments().await.unwrap().to_vec();
 
         dataset.drop_columns(&["x"]).await?;
         dataset.validate().await?;
@@ -3222,7 +4245,10 @@ mod tests {
         assert_eq!(dataset.schema(), &expected_schema);
 
         assert_eq!(dataset.version().version, 2);
-        assert_eq!(dataset.fragments().as_ref(), &original_fragments);
+        assert_eq!(
+            dataset.fragments().await.unwrap().as_ref(),
+            &original_fragments
+        );
 
         dataset.drop_columns(&["s.d"]).await?;
         dataset.validate().await?;
@@ -3244,7 +4270,178 @@ mod tests {
         assert_eq!(actual_data, expected_data);
 
         assert_eq!(dataset.version().version, 3);
-        assert_eq!(dataset.fragments().as_ref(), &original_fragments);
+        assert_eq!(
+            dataset.fragments().await.unwrap().as_ref(),
+            &original_fragments
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reclaim_dropped_columns() -> Result<()> {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("i", DataType::Int32, false),
+            Field::new(
+                "s",
+                DataType::Struct(ArrowFields::from(vec![
+                    Field::new("d", DataType::Int32, true),
+                    Field::new("l", DataType::Int32, true),
+                ])),
+                true,
+            ),
+            Field::new("x", DataType::Float32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2, 3])),
+                Arc::new(StructArray::from(vec![
+                    (
+                        Arc::new(ArrowField::new("d", DataType::Int32, true)),
+                        Arc::new(Int32Array::from(vec![10, 20, 30])) as ArrayRef,
+                    ),
+                    (
+                        Arc::new(ArrowField::new("l", DataType::Int32, true)),
+                        Arc::new(Int32Array::from(vec![100, 200, 300])),
+                    ),
+                ])),
+                Arc::new(Float32Array::from(vec![1.0, 2.0, 3.0])),
+            ],
+        )?;
+
+        let test_dir = tempdir()?;
+        let test_uri = test_dir.path().to_str().unwrap();
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let mut dataset = Dataset::write(batches, test_uri, None).await?;
+        dataset
+            .add_columns(
+                NewColumnTransform::SqlExpressions(vec![("y".into(), "2 * i".into())]),
+                None,
+            )
+            .await?;
+        dataset.delete("i = 2").await?;
+
+        // Nothing has been dropped
+        dataset.reclaim_dropped_columns().await?;
+        assert_eq!(dataset.version().version, 3);
+
+        dataset.drop_columns(&["x", "s.d", "y"]).await?;
+        let expected_data = dataset.scan().try_into_batch().await?;
+        let row_ids = dataset
+            .scan()
+            .with_row_id()
+            .try_into_batch()
+            .await?
+            .column_by_name(ROW_ID)
+            .unwrap()
+            .clone();
+
+        dataset.reclaim_dropped_columns().await?;
+        dataset.validate().await?;
+        assert_eq!(dataset.version().version, 5);
+
+        // The file with only `y` is removed and the other one is rewritten
+        let fragment = &dataset.fragments().await.unwrap()[0];
+        assert_eq!(fragment.files.len(), 1);
+        let field_ids = dataset.schema().field_ids();
+        assert_eq!(fragment.files[0].fields, field_ids);
+        assert!(fragment.deletion_file.is_some());
+
+        assert_eq!(dataset.scan().try_into_batch().await?, expected_data);
+        let new_row_ids = dataset
+            .scan()
+            .with_row_id()
+            .try_into_batch()
+            .await?
+            .column_by_name(ROW_ID)
+            .unwrap()
+            .clone();
+        assert_eq!(
+            new_row_ids.as_primitive::<UInt64Type>(),
+            row_ids.as_primitive::<UInt64Type>()
+        );
+
+        // A fragment would not store any column, because the remaining one was added by a
+        // later append
+        let test_dir = tempdir()?;
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("i", DataType::Int32, false),
+            Field::new("x", DataType::Int32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])),
+                Arc::new(Int32Array::from(vec![3, 4])),
+            ],
+        )?;
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let mut dataset = Dataset::write(batches, test_uri, None).await?;
+        dataset
+            .add_columns(
+                NewColumnTransform::SqlExpressions(vec![("y".into(), "2 * i".into())]),
+                None,
+            )
+            .await?;
+        let new_schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("i", DataType::Int32, false),
+            Field::new("x", DataType::Int32, false),
+            Field::new("y", DataType::Int32, false),
+            Field::new("z", DataType::Utf8, true),
+        ]));
+        let new_batch = RecordBatch::try_new(
+            new_schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![5])),
+                Arc::new(Int32Array::from(vec![6])),
+                Arc::new(Int32Array::from(vec![10])),
+                Arc::new(StringArray::from(vec!["z"])),
+            ],
+        )?;
+        let write_params = WriteParams {
+            mode: WriteMode::Append,
+            schema_evolution: SchemaEvolution::AddMissingColumns,
+            ..Default::default()
+        };
+        let batches = RecordBatchIterator::new(vec![Ok(new_batch)], new_schema);
+        dataset = Dataset::write(batches, test_uri, Some(write_params)).await?;
+
+        let err = dataset.drop_columns(&["i", "x", "y"]).await.unwrap_err();
+        assert!(err.to_string().contains("fragment 0"), "{}", err);
+
+        // Such a fragment may have been left by an older version, reclaiming must not
+        // remove all of its data files
+        let z_only = dataset.schema().project(&["z"])?;
+        let mut dropped = Dataset::commit(
+            test_uri,
+            Operation::Project { schema: z_only },
+            Some(dataset.version().version),
+            None,
+            None,
+        )
+        .await?;
+        let files = dropped.fragments().await?[0].files.clone();
+        let err = dropped.reclaim_dropped_columns().await.unwrap_err();
+        assert!(err.to_string().contains("Fragment 0"), "{}", err);
+        assert_eq!(dropped.fragments().await?[0].files, files);
+
+        // Once compacted, the fragments store the null-filled column
+        dataset.restore().await?;
+        compact_files(&mut dataset, Default::default(), None).await?;
+        dataset.drop_columns(&["i", "x", "y"]).await?;
+        dataset.reclaim_dropped_columns().await?;
+        dataset.validate().await?;
+        for fragment in dataset.fragments().await?.iter() {
+            assert_eq!(fragment.files.len(), 1);
+            assert_eq!(fragment.files[0].fields, dataset.schema().field_ids());
+        }
+        let batch = dataset.scan().try_into_batch().await?;
+        assert_eq!(
+            batch["z"].as_string::<i32>(),
+            &StringArray::from(vec![None, None, Some("z")])
+        );
 
         Ok(())
     }
@@ -3386,7 +4583,7 @@ mod tests {
             .unwrap();
 
         let dataset = Dataset::open(test_uri).await.unwrap();
-        assert_eq!(dataset.fragments().len(), 2);
+        assert_eq!(dataset.fragments().await.unwrap().len(), 2);
         assert_eq!(dataset.manifest.max_fragment_id(), Some(1));
 
         let right_schema = Arc::new(ArrowSchema::new(vec![
@@ -3409,9 +4606,9 @@ mod tests {
         dataset.validate().await.unwrap();
 
         assert_eq!(dataset.version().version, 3);
-        assert_eq!(dataset.fragments().len(), 2);
-        assert_eq!(dataset.fragments()[0].files.len(), 2);
-        assert_eq!(dataset.fragments()[1].files.len(), 2);
+        assert_eq!(dataset.fragments().await.unwrap().len(), 2);
+        assert_eq!(dataset.fragments().await.unwrap()[0].files.len(), 2);
+        assert_eq!(dataset.fragments().await.unwrap()[1].files.len(), 2);
         assert_eq!(dataset.manifest.max_fragment_id(), Some(1));
 
         let actual_batches = dataset
@@ -3459,6 +4656,127 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[tokio::test]
+    async fn test_delete_returning() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "i",
+            DataType::UInt32,
+            false,
+        )]));
+        let data = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(UInt32Array::from_iter_values(0..100))],
+        )
+        .unwrap();
+        let batches = RecordBatchIterator::new(vec![Ok(data)], schema.clone());
+        let write_params = WriteParams {
+            max_rows_per_file: 50,
+            ..Default::default()
+        };
+        let mut dataset = Dataset::write(batches, test_uri, Some(write_params))
+            .await
+            .unwrap();
+
+        let sink = Arc::new(VecSink::default());
+        let stats = dataset
+            .delete_returning("i >= 45 AND i < 55", &Default::default(), sink.clone())
+            .await
+            .unwrap();
+        assert_eq!(stats.num_rows_affected, 10);
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 90);
+
+        let deleted = sink.0.lock().unwrap().clone();
+        let deleted = concat_batches(&deleted[0].schema(), &deleted).unwrap();
+        assert_eq!(
+            deleted["i"].as_primitive::<UInt32Type>().values(),
+            &(45..55).collect::<Vec<_>>()
+        );
+        let mut row_ids = (45..50).collect::<Vec<u64>>();
+        row_ids.extend((0..5).map(|i| (1 << 32) + i));
+        assert_eq!(
+            deleted[ROW_ID].as_primitive::<UInt64Type>().values(),
+            &row_ids
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_rows() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "i",
+            DataType::UInt32,
+            false,
+        )]));
+        let data = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(UInt32Array::from_iter_values(0..100))],
+        )
+        .unwrap();
+        let batches = RecordBatchIterator::new(vec![Ok(data)], schema.clone());
+        let write_params = WriteParams {
+            max_rows_per_file: 50,
+            ..Default::default()
+        };
+        let mut dataset = Dataset::write(batches, test_uri, Some(write_params))
+            .await
+            .unwrap();
+
+        let row_ids = |dataset: Dataset, filter: &'static str| async move {
+            let batch = dataset
+                .scan()
+                .with_row_id()
+                .filter(filter)
+                .unwrap()
+                .try_into_batch()
+                .await
+                .unwrap();
+            batch[ROW_ID].as_primitive::<UInt64Type>().values().to_vec()
+        };
+
+        let to_delete = row_ids(dataset.clone(), "i % 10 = 0").await;
+        assert_eq!(to_delete.len(), 10);
+        let stats = dataset.delete_rows(&to_delete).await.unwrap();
+        assert_eq!(stats.num_rows_affected, 10);
+        assert_eq!(stats.num_fragments_touched, 2);
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 90);
+        assert_eq!(
+            dataset
+                .count_rows(Some("i % 10 = 0".to_string()))
+                .await
+                .unwrap(),
+            0
+        );
+        dataset.validate().await.unwrap();
+
+        // Deleting the same rows again does nothing
+        let version = dataset.version().version;
+        let stats = dataset.delete_rows(&to_delete).await.unwrap();
+        assert_eq!(stats.num_rows_affected, 0);
+        assert_eq!(dataset.version().version, version);
+
+        // Deleting all the remaining rows of a fragment removes it
+        let to_delete = row_ids(dataset.clone(), "i >= 50").await;
+        let stats = dataset.delete_rows(&to_delete).await.unwrap();
+        assert_eq!(stats.num_rows_affected, 45);
+        assert_eq!(dataset.get_fragments().await.unwrap().len(), 1);
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 45);
+
+        // Rows that do not exist
+        assert!(matches!(
+            dataset.delete_rows(&[5 << 32]).await,
+            Err(Error::InvalidInput { .. })
+        ));
+        assert!(matches!(
+            dataset.delete_rows(&[50]).await,
+            Err(Error::InvalidInput { .. })
+        ));
+    }
+
     #[tokio::test]
     async fn test_delete() {
         fn sequence_data(range: Range<u32>) -> RecordBatch {
@@ -3490,11 +4808,12 @@ mod tests {
             .unwrap();
 
         // Delete nothing
-        dataset.delete("i < 0").await.unwrap();
+        let stats = dataset.delete("i < 0").await.unwrap();
+        assert_eq!(stats, OperationStats::default());
         dataset.validate().await.unwrap();
 
         // We should not have any deletion file still
-        let fragments = dataset.get_fragments();
+        let fragments = dataset.get_fragments().await.unwrap();
         assert_eq!(fragments.len(), 2);
         assert_eq!(dataset.count_fragments(), 2);
         assert_eq!(dataset.count_deleted_rows().await.unwrap(), 0);
@@ -3503,12 +4822,16 @@ mod tests {
         assert!(fragments[1].metadata.deletion_file.is_none());
 
         // Delete rows
-        dataset.delete("i < 10 OR i >= 90").await.unwrap();
+        let stats = dataset.delete("i < 10 OR i >= 90").await.unwrap();
+        assert_eq!(stats.num_rows_affected, 20);
+        assert_eq!(stats.num_fragments_touched, 2);
+        assert_eq!(stats.num_files_added, 2);
+        assert_eq!(stats.num_files_removed, 0);
         dataset.validate().await.unwrap();
 
         // Verify result:
         // There should be a deletion file in the metadata
-        let fragments = dataset.get_fragments();
+        let fragments = dataset.get_fragments().await.unwrap();
         assert_eq!(fragments.len(), 2);
         assert_eq!(dataset.count_fragments(), 2);
         assert!(fragments[0].metadata.deletion_file.is_some());
@@ -3565,7 +4888,7 @@ mod tests {
 
         // Verify result
         assert_eq!(dataset.count_deleted_rows().await.unwrap(), 30);
-        let fragments = dataset.get_fragments();
+        let fragments = dataset.get_fragments().await.unwrap();
         assert_eq!(fragments.len(), 2);
         assert!(fragments[0].metadata.deletion_file.is_some());
         let deletion_vector = read_deletion_file(&path, &fragments[0].metadata, &store)
@@ -3588,7 +4911,7 @@ mod tests {
         dataset.validate().await.unwrap();
 
         // Verify second fragment is fully gone
-        let fragments = dataset.get_fragments();
+        let fragments = dataset.get_fragments().await.unwrap();
         assert_eq!(fragments.len(), 1);
         assert_eq!(dataset.count_fragments(), 1);
         assert_eq!(fragments[0].id(), 0);
@@ -3610,7 +4933,7 @@ mod tests {
 
         dataset.validate().await.unwrap();
 
-        let fragments = dataset.get_fragments();
+        let fragments = dataset.get_fragments().await.unwrap();
         assert_eq!(fragments.len(), 2);
         assert_eq!(dataset.count_fragments(), 2);
         // Fragment id picks up where we left off
@@ -3647,7 +4970,7 @@ mod tests {
         // Checkout a previous version
         let mut dataset = dataset.checkout_version(1).await.unwrap();
         assert_eq!(dataset.manifest.version, 1);
-        let fragments = dataset.get_fragments();
+        let fragments = dataset.get_fragments().await.unwrap();
         assert_eq!(fragments.len(), 1);
         assert_eq!(dataset.count_fragments(), 1);
         assert_eq!(fragments[0].metadata.deletion_file, None);
@@ -3662,7 +4985,7 @@ mod tests {
         // Delete some rows again (make sure we can still write as usual)
         dataset.delete("i > 30").await.unwrap();
         assert_eq!(dataset.manifest.version, 4);
-        let fragments = dataset.get_fragments();
+        let fragments = dataset.get_fragments().await.unwrap();
         assert_eq!(fragments.len(), 1);
         assert_eq!(dataset.count_fragments(), 1);
         assert!(fragments[0].metadata.deletion_file.is_some());
@@ -3847,8 +5170,8 @@ mod tests {
         let dataset = Dataset::write(reader, test_uri, None).await.unwrap();
         dataset.validate().await.unwrap();
 
-        assert!(dataset.num_small_files(1024).await > 0);
-        assert!(dataset.num_small_files(512).await == 0);
+        assert!(dataset.num_small_files(1024).await.unwrap() > 0);
+        assert!(dataset.num_small_files(512).await.unwrap() == 0);
     }
 
     #[tokio::test]
@@ -4018,7 +5341,7 @@ mod tests {
         let dataset = Dataset::open(test_uri).await.unwrap();
         assert_eq!(dataset.count_rows(None).await.unwrap(), 90);
         assert_eq!(dataset.count_deleted_rows().await.unwrap(), 10);
-        let total_physical_rows = futures::stream::iter(dataset.get_fragments())
+        let total_physical_rows = futures::stream::iter(dataset.get_fragments().await.unwrap())
             .then(|f| async move { f.physical_rows().await })
             .try_fold(0, |acc, x| async move { Ok(acc + x) })
             .await
@@ -4044,7 +5367,7 @@ mod tests {
         // Assert num rows, deletions, and physical rows are all correct.
         assert_eq!(dataset.count_rows(None).await.unwrap(), 95);
         assert_eq!(dataset.count_deleted_rows().await.unwrap(), 10);
-        let total_physical_rows = futures::stream::iter(dataset.get_fragments())
+        let total_physical_rows = futures::stream::iter(dataset.get_fragments().await.unwrap())
             .then(|f| async move { f.physical_rows().await })
             .try_fold(0, |acc, x| async move { Ok(acc + x) })
             .await
@@ -4087,7 +5410,7 @@ mod tests {
         let dataset = Dataset::open(test_uri).await.unwrap();
         assert_eq!(dataset.count_rows(None).await.unwrap(), 92);
         assert_eq!(dataset.count_deleted_rows().await.unwrap(), 10);
-        let total_physical_rows = futures::stream::iter(dataset.get_fragments())
+        let total_physical_rows = futures::stream::iter(dataset.get_fragments().await.unwrap())
             .then(|f| async move { f.physical_rows().await })
             .try_fold(0, |acc, x| async move { Ok(acc + x) })
             .await
@@ -4113,12 +5436,16 @@ mod tests {
         // Assert statistics are all now correct.
         let physical_rows: Vec<_> = dataset
             .get_fragments()
+            .await
+            .unwrap()
             .iter()
             .map(|f| f.metadata.physical_rows)
             .collect();
         assert_eq!(physical_rows, vec![Some(100), Some(2), Some(5)]);
         let num_deletions: Vec<_> = dataset
             .get_fragments()
+            .await
+            .unwrap()
             .iter()
             .map(|f| {
                 f.metadata
@@ -4680,7 +6007,7 @@ mod tests {
         let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
         let mut dataset = Dataset::write(batches, test_uri, None).await?;
 
-        let original_fragments = dataset.fragments().to_vec();
+        let original_fragments = dataset.fragments().await.unwrap().to_vec();
 
         // Rename a top-level column
         dataset
@@ -4690,7 +6017,10 @@ mod tests {
             .await?;
         dataset.validate().await?;
         assert_eq!(dataset.manifest.version, 2);
-        assert_eq!(dataset.fragments().as_ref(), &original_fragments);
+        assert_eq!(
+            dataset.fragments().await.unwrap().as_ref(),
+            &original_fragments
+        );
 
         let expected_schema = ArrowSchema::new_with_metadata(
             vec![
@@ -4722,7 +6052,10 @@ mod tests {
             .await?;
         dataset.validate().await?;
         assert_eq!(dataset.manifest.version, 3);
-        assert_eq!(dataset.fragments().as_ref(), &original_fragments);
+        assert_eq!(
+            dataset.fragments().await.unwrap().as_ref(),
+            &original_fragments
+        );
 
         let expected_schema = ArrowSchema::new_with_metadata(
             vec![
@@ -4825,7 +6158,7 @@ mod tests {
         assert_eq!(&ArrowSchema::from(dataset.schema()), &expected_schema);
 
         // Each fragment gains a file with the new columns
-        dataset.fragments().iter().for_each(|f| {
+        dataset.fragments().await.unwrap().iter().for_each(|f| {
             assert_eq!(f.files.len(), 2);
         });
 
@@ -4852,7 +6185,7 @@ mod tests {
         assert_eq!(indices.len(), 1);
 
         // Each fragment gains a file with the new columns
-        dataset.fragments().iter().for_each(|f| {
+        dataset.fragments().await.unwrap().iter().for_each(|f| {
             assert_eq!(f.files.len(), 3);
         });
 
@@ -4891,7 +6224,7 @@ mod tests {
         assert_eq!(indices.len(), 0);
 
         // Each fragment gains a file with the new columns, but then the original file is dropped
-        dataset.fragments().iter().for_each(|f| {
+        dataset.fragments().await.unwrap().iter().for_each(|f| {
             assert_eq!(f.files.len(), 4);
         });
 
@@ -2,6 +2,7 @@
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 use std::{collections::HashMap, sync::Arc, time::Duration};
 
+use lance_io::encryption::KeyProvider;
 use lance_io::object_store::{ObjectStore, ObjectStoreParams};
 use lance_table::io::commit::{commit_handler_from_url, CommitHandler};
 use object_store::{aws::AwsCredentialProvider, DynObjectStore};
@@ -93,6 +94,12 @@ impl DatasetBuilder {
         self
     }
 
+    /// Encrypt new files and decrypt existing ones with data keys wrapped by `key_provider`.
+    pub fn with_key_provider(mut self, key_provider: Arc<dyn KeyProvider>) -> Self {
+        self.options.key_provider = Some(key_provider);
+        self
+    }
+
     /// Directly set the object store to use.
     pub fn with_object_store(
         mut self,
@@ -183,15 +190,16 @@ impl DatasetBuilder {
         }?;
 
         match &self.options.object_store {
-            Some(store) => Ok((
-                ObjectStore::new(
+            Some(store) => {
+                let mut object_store = ObjectStore::new(
                     store.0.clone(),
                     store.1.clone(),
                     self.options.block_size,
                     self.options.object_store_wrapper,
-                ),
-                commit_handler,
-            )),
+                );
+                object_store.set_key_provider(self.options.key_provider);
+                Ok((object_store, commit_handler))
+            }
             None => {
                 let (store, _path) =
                     ObjectStore::from_uri_and_params(&self.table_uri, &self.options).await?;
@@ -16,6 +16,12 @@
 //!   any fragment in a valid manifest file then it will be deleted.
 //! * Unreferenced index files - If an index file is not referenced by
 //!   any valid manifest file then it will be deleted.
+//! * Unreferenced fragment list files - If a fragment list file is not
+//!   referenced by any valid manifest file then it will be deleted.
+//! * Unreferenced version index pages - If a page of the version index is not
+//!   referenced by any valid manifest file (or by the newest page of one)
+//!   then it will be deleted.  Removed versions are recorded in the version
+//!   index before their manifests are deleted.
 //!
 //! It is also difficult to distinguish between a data/tx/idx file which was
 //! leftover from an abandoned transaction and a data file which is part
@@ -40,8 +46,11 @@ use lance_io::object_store::ObjectStore;
 use lance_table::{
     format::{Index, Manifest},
     io::{
+        commit::parse_version_from_path,
         deletion::deletion_file_path,
-        manifest::{read_manifest, read_manifest_indexes},
+        manifest::{
+            load_fragment_lists, read_manifest_indexes, read_manifest_root, FRAGMENT_LISTS_DIR,
+        },
     },
 };
 use object_store::path::Path;
@@ -51,6 +60,7 @@ use std::{
     sync::{Mutex, MutexGuard},
 };
 
+use super::version_index::{self, VERSION_INDEX_DIR};
 use crate::{utils::temporal::utc_now, Dataset};
 
 #[derive(Clone, Debug, Default)]
@@ -58,6 +68,8 @@ struct ReferencedFiles {
     data_paths: HashSet<Path>,
     delete_paths: HashSet<Path>,
     tx_paths: HashSet<Path>,
+    fragment_list_paths: HashSet<Path>,
+    version_index_paths: HashSet<Path>,
     index_uuids: HashSet<String>,
 }
 
@@ -140,7 +152,15 @@ impl<'a> CleanupTask<'a> {
         // ignore it then we might delete valid data files thinking they are not
         // referenced.
 
-        let manifest = read_manifest(&self.dataset.object_store, &path).await?;
+        let mut manifest = read_manifest_root(&self.dataset.object_store, &path).await?;
+        // Fragment lists are shared between versions, so read them through the cache
+        load_fragment_lists(
+            &self.dataset.object_store,
+            &self.dataset.base,
+            &mut manifest,
+            Some(&self.dataset.session.file_metadata_cache),
+        )
+        .await?;
         let dataset_version = self.dataset.version().version;
         // Don't delete the latest version, even if it is old.  Also don't delete manifests
         // if their version is newer than the dataset version.  These are either in-progress
@@ -148,10 +168,28 @@ impl<'a> CleanupTask<'a> {
         let is_latest = dataset_version <= manifest.version;
         let in_working_set = is_latest || manifest.timestamp() >= self.before;
         let indexes = read_manifest_indexes(&self.dataset.object_store, &path, &manifest).await?;
+        // Full pages of the version index are only referenced from the newest page
+        let mut version_index_paths = Vec::new();
+        if let Some(page_path) = manifest
+            .version_index_file
+            .as_ref()
+            .filter(|_| in_working_set)
+        {
+            let page =
+                version_index::read_page(&self.dataset.object_store, &self.dataset.base, page_path)
+                    .await?;
+            for previous in page.previous_pages {
+                version_index_paths.push(Path::parse(&previous.path)?);
+            }
+        }
 
         let mut inspection = inspection.lock().unwrap();
 
         self.process_manifest(&manifest, &indexes, in_working_set, &mut inspection)?;
+        inspection
+            .referenced_files
+            .version_index_paths
+            .extend(version_index_paths);
         if !in_working_set {
             inspection.old_manifests.push(path.clone());
         }
@@ -193,6 +231,18 @@ impl<'a> CleanupTask<'a> {
                 .tx_paths
                 .insert(Path::parse("_transactions")?.child(relative_tx_path.as_str()));
         }
+        if let Some(version_index_file) = &manifest.version_index_file {
+            referenced_files
+                .version_index_paths
+                .insert(Path::parse(version_index_file)?);
+        }
+        if let Some(fragment_lists) = &manifest.fragment_lists {
+            for list in &fragment_lists.lists {
+                referenced_files
+                    .fragment_list_paths
+                    .insert(Path::parse(&list.path)?);
+            }
+        }
 
         for index in indexes {
             let uuid_str = index.uuid.to_string();
@@ -230,6 +280,18 @@ impl<'a> CleanupTask<'a> {
         let old_manifests = inspection.old_manifests.clone();
         let num_old_manifests = old_manifests.len();
 
+        if !old_manifests.is_empty() {
+            let removed_versions = old_manifests
+                .iter()
+                .filter_map(|path| parse_version_from_path(path).ok());
+            version_index::record_removed_versions(
+                &self.dataset.object_store,
+                &self.dataset.base,
+                removed_versions,
+            )
+            .await?;
+        }
+
         // Ideally this collect shouldn't be needed here but it seems necessary
         // to avoid https://github.com/rust-lang/rust/issues/102211
         let manifest_bytes_removed = stream::iter(&old_manifests)
@@ -349,6 +411,29 @@ impl<'a> CleanupTask<'a> {
                     Ok(None)
                 }
             }
+            Some("binpb") => {
+                let (referenced, verified) =
+                    if relative_path.as_ref().starts_with(FRAGMENT_LISTS_DIR) {
+                        (
+                            &inspection.referenced_files.fragment_list_paths,
+                            &inspection.verified_files.fragment_list_paths,
+                        )
+                    } else if relative_path.as_ref().starts_with(VERSION_INDEX_DIR) {
+                        (
+                            &inspection.referenced_files.version_index_paths,
+                            &inspection.verified_files.version_index_paths,
+                        )
+                    } else {
+                        return Ok(None);
+                    };
+                if referenced.contains(&relative_path) {
+                    Ok(None)
+                } else if !maybe_in_progress || verified.contains(&relative_path) {
+                    Ok(Some(path))
+                } else {
+                    Ok(None)
+                }
+            }
             Some("txn") => {
                 if relative_path.as_ref().starts_with("_transactions") {
                     if inspection
@@ -489,7 +574,14 @@ mod tests {
             let times_map = self.last_modified_times.clone();
             policy.set_before_policy(
                 "record_file_time",
-                Arc::new(move |_, path| {
+                Arc::new(move |op, path| {
+                    // Fragment lists and version index pages are shared between
+                    // versions and read by every commit, but never modified
+                    let shared = path.as_ref().contains(&format!("/{}/", FRAGMENT_LISTS_DIR))
+                        || path.as_ref().contains(&format!("/{}/", VERSION_INDEX_DIR));
+                    if shared && (op.starts_with("get") || op == "head") {
+                        return Ok(());
+                    }
                     let mut times_map = times_map.lock().unwrap();
                     times_map.insert(path.clone(), utc_now());
                     Ok(())
@@ -516,6 +608,8 @@ mod tests {
         num_index_files: usize,
         num_delete_files: usize,
         num_tx_files: usize,
+        num_fragment_list_files: usize,
+        num_version_index_files: usize,
         num_bytes: u64,
     }
 
@@ -695,9 +789,15 @@ mod tests {
                 num_index_files: 0,
                 num_manifest_files: 0,
                 num_tx_files: 0,
+                num_fragment_list_files: 0,
+                num_version_index_files: 0,
                 num_bytes: 0,
             };
             while let Some(path) = file_stream.try_next().await? {
+                // Cleanup writes this file itself, so it would skew the byte counts
+                if path.location.extension() == Some("json") {
+                    continue;
+                }
                 file_count.num_bytes += path.size as u64;
                 match path.location.extension() {
                     Some("lance") => file_count.num_data_files += 1,
@@ -705,6 +805,10 @@ mod tests {
                     Some("arrow") | Some("bin") => file_count.num_delete_files += 1,
                     Some("idx") => file_count.num_index_files += 1,
                     Some("txn") => file_count.num_tx_files += 1,
+                    Some("binpb") if path.location.as_ref().contains(FRAGMENT_LISTS_DIR) => {
+                        file_count.num_fragment_list_files += 1
+                    }
+                    Some("binpb") => file_count.num_version_index_files += 1,
                     _ => (),
                 }
             }
@@ -762,6 +866,72 @@ mod tests {
         assert_gt!(after_count.num_tx_files, 0);
     }
 
+    #[tokio::test]
+    async fn cleanup_unreferenced_fragment_lists() {
+        // Appending rewrites the partially filled last fragment list, so the
+        // original list is only referenced by the first version
+        let fixture = MockDatasetFixture::try_new().unwrap();
+        Dataset::write(
+            some_batch(),
+            &fixture.dataset_path,
+            Some(WriteParams {
+                store_params: Some(fixture.os_params()),
+                use_fragment_lists: true,
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+        fixture
+            .clock
+            .set_system_time(TimeDelta::try_days(10).unwrap());
+        fixture.append_some_data().await.unwrap();
+
+        let before_count = fixture.count_files().await.unwrap();
+        assert_eq!(before_count.num_fragment_list_files, 2);
+        let num_rows = fixture.count_rows().await.unwrap();
+
+        let before = utc_now() - TimeDelta::try_days(7).unwrap();
+        let removed = fixture.run_cleanup(before).await.unwrap();
+
+        let after_count = fixture.count_files().await.unwrap();
+        assert_eq!(removed.old_versions, 1);
+        assert_eq!(
+            removed.bytes_removed,
+            before_count.num_bytes - after_count.num_bytes
+        );
+        assert_eq!(after_count.num_fragment_list_files, 1);
+        assert_eq!(after_count.num_data_files, 2);
+        assert_eq!(fixture.count_rows().await.unwrap(), num_rows);
+    }
+
+    #[tokio::test]
+    async fn cleanup_removes_versions_from_index() {
+        let fixture = MockDatasetFixture::try_new().unwrap();
+        fixture.create_some_data().await.unwrap();
+        fixture
+            .clock
+            .set_system_time(TimeDelta::try_days(10).unwrap());
+        fixture.append_some_data().await.unwrap();
+        fixture.append_some_data().await.unwrap();
+
+        let before_count = fixture.count_files().await.unwrap();
+        assert_eq!(before_count.num_version_index_files, 3);
+
+        let before = utc_now() - TimeDelta::try_days(7).unwrap();
+        let removed = fixture.run_cleanup(before).await.unwrap();
+        assert_eq!(removed.old_versions, 1);
+
+        // The page of the removed version is no longer needed
+        let after_count = fixture.count_files().await.unwrap();
+        assert_eq!(after_count.num_version_index_files, 2);
+        let versions = fixture.open().await.unwrap().versions().await.unwrap();
+        assert_eq!(
+            versions.iter().map(|v| v.version).collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+    }
+
     #[tokio::test]
     async fn do_not_cleanup_newer_data() {
         // Even though an old manifest is removed the data files should
@@ -6,6 +6,8 @@
 use lance_table::format::Manifest;
 
 pub const FLAG_DELETION_FILES: u64 = 1;
+pub const FLAG_FRAGMENT_LISTS: u64 = 2;
+pub const FLAG_REPLACED_FIELDS: u64 = 4;
 
 /// Set the reader and writer feature flags in the manifest based on the contents of the manifest.
 pub fn apply_feature_flags(manifest: &mut Manifest) {
@@ -22,14 +24,29 @@ pub fn apply_feature_flags(manifest: &mut Manifest) {
         manifest.reader_feature_flags |= FLAG_DELETION_FILES;
         manifest.writer_feature_flags |= FLAG_DELETION_FILES;
     }
+    if manifest.fragment_lists.is_some() {
+        // The fragments are only visible to readers and writers that can load the lists
+        manifest.reader_feature_flags |= FLAG_FRAGMENT_LISTS;
+        manifest.writer_feature_flags |= FLAG_FRAGMENT_LISTS;
+    }
+    let has_replaced_fields = manifest
+        .fragments
+        .iter()
+        .flat_map(|frag| frag.files.iter())
+        .any(|file| !file.replaced_fields.is_empty());
+    if has_replaced_fields {
+        // Readers that ignore replaced fields would read stale values from the older files
+        manifest.reader_feature_flags |= FLAG_REPLACED_FIELDS;
+        manifest.writer_feature_flags |= FLAG_REPLACED_FIELDS;
+    }
 }
 
 pub fn can_read_dataset(reader_flags: u64) -> bool {
-    reader_flags <= 1
+    reader_flags <= (FLAG_DELETION_FILES | FLAG_FRAGMENT_LISTS | FLAG_REPLACED_FIELDS)
 }
 
 pub fn can_write_dataset(writer_flags: u64) -> bool {
-    writer_flags <= 1
+    writer_flags <= (FLAG_DELETION_FILES | FLAG_FRAGMENT_LISTS | FLAG_REPLACED_FIELDS)
 }
 
 #[cfg(test)]
@@ -40,13 +57,23 @@ mod tests {
     fn test_read_check() {
         assert!(can_read_dataset(0));
         assert!(can_read_dataset(super::FLAG_DELETION_FILES));
-        assert!(!can_read_dataset(super::FLAG_DELETION_FILES + 1));
+        assert!(can_read_dataset(super::FLAG_FRAGMENT_LISTS));
+        assert!(can_read_dataset(
+            super::FLAG_DELETION_FILES | super::FLAG_FRAGMENT_LISTS
+        ));
+        assert!(can_read_dataset(super::FLAG_REPLACED_FIELDS));
+        assert!(!can_read_dataset(8));
     }
 
     #[test]
     fn test_write_check() {
         assert!(can_write_dataset(0));
         assert!(can_write_dataset(super::FLAG_DELETION_FILES));
-        assert!(!can_write_dataset(super::FLAG_DELETION_FILES + 1));
+        assert!(can_write_dataset(super::FLAG_FRAGMENT_LISTS));
+        assert!(can_write_dataset(
+            super::FLAG_DELETION_FILES | super::FLAG_FRAGMENT_LISTS
+        ));
+        assert!(can_write_dataset(super::FLAG_REPLACED_FIELDS));
+        assert!(!can_write_dataset(8));
     }
 }
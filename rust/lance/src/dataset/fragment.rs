@@ -10,7 +10,7 @@ use std::sync::Arc;
 
 use arrow::compute::concat_batches;
 use arrow_array::cast::as_primitive_array;
-use arrow_array::{RecordBatch, RecordBatchReader, UInt32Array, UInt64Array};
+use arrow_array::{new_null_array, RecordBatch, RecordBatchReader, UInt32Array, UInt64Array};
 use arrow_schema::Schema as ArrowSchema;
 use datafusion::logical_expr::Expr;
 use datafusion::scalar::ScalarValue;
@@ -319,9 +319,14 @@ impl FileFragment {
             ));
         }
 
-        let (object_store, base_path) = ObjectStore::from_uri(dataset_uri).await?;
+        let (object_store, base_path) = ObjectStore::from_uri_and_params(
+            dataset_uri,
+            &params.store_params.clone().unwrap_or_default(),
+        )
+        .await?;
         let filename = format!("{}.lance", Uuid::new_v4());
         let mut fragment = Fragment::with_file_legacy(id as u64, &filename, &schema, None);
+        fragment.files[0].encryption_key_id = object_store.encryption_key_id();
         let full_path = base_path.child(DATA_DIR).child(filename.clone());
         let mut writer = FileWriter::<ManifestDescribing>::try_new(
             &object_store,
@@ -340,6 +345,7 @@ impl FileFragment {
         }
 
         fragment.physical_rows = Some(writer.finish().await?);
+        fragment.files[0].checksum = Some(writer.checksum());
 
         progress.complete(&fragment).await?;
 
@@ -429,6 +435,10 @@ impl FileFragment {
         projection: Option<&Schema>,
         with_row_id: bool,
     ) -> Result<Option<(Box<dyn GenericFileReader>, Arc<Schema>)>> {
+        self.dataset.object_store.check_can_decrypt(
+            &self.dataset.data_dir().child(data_file.path.as_str()),
+            data_file.encryption_key_id.as_deref(),
+        )?;
         let full_schema = self.dataset.schema();
         // The data file may contain fields that are not part of the dataset any longer, remove those
         let data_file_schema = data_file.schema(full_schema);
@@ -441,9 +451,15 @@ impl FileFragment {
             if with_row_id || !schema_per_file.fields.is_empty() {
                 let path = self.dataset.data_dir().child(data_file.path.as_str());
                 let field_id_offset = Self::get_field_id_offset(data_file);
-                let mut reader = FileReader::try_new_with_fragment_id(
-                    &self.dataset.object_store,
+                let object_reader = self
+                    .dataset
+                    .object_store
+                    .open_with_key_id(&path, data_file.encryption_key_id.as_deref())
+                    .await?;
+                let mut reader = FileReader::try_new_from_reader(
                     &path,
+                    object_reader.into(),
+                    None,
                     self.schema().clone(),
                     self.id() as u32,
                     field_id_offset as i32,
@@ -466,7 +482,9 @@ impl FileFragment {
             } else {
                 let path = self.dataset.data_dir().child(data_file.path.as_str());
                 let store_scheduler = StoreScheduler::new(self.dataset.object_store.clone(), 16);
-                let file_scheduler = store_scheduler.open_file(&path).await?;
+                let file_scheduler = store_scheduler
+                    .open_file_with_key_id(&path, data_file.encryption_key_id.as_deref())
+                    .await?;
                 let schema = arrow_schema::Schema::from(&schema_per_file);
                 let reader =
                     Arc::new(v2::reader::FileReader::try_open(file_scheduler, schema).await?);
@@ -491,6 +509,25 @@ impl FileFragment {
             }
         }
 
+        // The projection may only contain columns that were added to the dataset after this
+        // fragment was written, which are read as nulls.  We still need to read one column to
+        // know how many rows there are.
+        if !projection.fields.is_empty() && opened_files.iter().all(|(_, s)| s.fields.is_empty()) {
+            if let Some(data_file) = self.metadata.files.first() {
+                let file_schema = data_file.schema(self.schema());
+                let file_schema = if data_file.is_legacy_file() {
+                    file_schema.project(&[&file_schema.fields[0].name])?
+                } else {
+                    file_schema
+                };
+                opened_files = self
+                    .open_reader(data_file, Some(&file_schema), with_row_id)
+                    .await?
+                    .into_iter()
+                    .collect();
+            }
+        }
+
         Ok(opened_files)
     }
 
@@ -600,7 +637,7 @@ impl FileFragment {
     /// * All fields in the schema have a corresponding field in one of the data
     ///  files
     /// * All data files exist and have the same length
-    /// * Field ids are distinct between data files.
+    /// * Field ids are distinct between data files, except for replaced fields.
     /// * Deletion file exists and has rowids in the correct range
     /// * `Fragment.physical_rows` matches length of file
     /// * `DeletionFile.num_deleted_rows` matches length of deletion vector
@@ -622,6 +659,9 @@ impl FileFragment {
                     ));
                 }
 
+                if data_file.replaced_fields.contains(field_id) {
+                    continue;
+                }
                 if !seen_fields.insert(field_id) {
                     return Err(Error::corrupt_file(
                         self.dataset
@@ -637,8 +677,10 @@ impl FileFragment {
             }
         }
 
+        // Nullable columns added after the fragment was written are not stored in it, and
+        // are read as nulls.  Only non-nullable fields must be stored.
         for field in self.schema().fields_pre_order() {
-            if !seen_fields.contains(&field.id) {
+            if !field.nullable && !seen_fields.contains(&field.id) {
                 return Err(Error::corrupt_file(
                     self.dataset
                         .data_dir()
@@ -925,6 +967,40 @@ impl FileFragment {
         Ok(Updater::new(self.clone(), reader, deletion_vector, schemas))
     }
 
+    /// Write the given fields of the fragment to a new data file.
+    ///
+    /// The rows keep their offsets in the fragment, deleted rows included, so that their
+    /// addresses do not change.  The fragment is not modified: the caller replaces the data
+    /// files that had these fields with the returned one.
+    pub(crate) async fn rewrite_fields(&self, field_ids: &[i32]) -> Result<DataFile> {
+        let schema = self.dataset.schema();
+        let write_schema = schema.project_by_ids(field_ids);
+        // Read the leaf fields, so that structs only have the children being rewritten
+        let read_columns = write_schema
+            .fields_pre_order()
+            .filter(|f| f.children.is_empty())
+            .map(|f| {
+                write_schema
+                    .field_ancestry_by_id(f.id)
+                    .unwrap()
+                    .iter()
+                    .map(|p| p.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(".")
+            })
+            .collect::<Vec<_>>();
+
+        let mut updater = self
+            .updater(Some(&read_columns), Some((write_schema, schema.clone())))
+            .await?;
+        while let Some(batch) = updater.next().await? {
+            let batch = batch.clone();
+            updater.update(batch).await?;
+        }
+        let mut fragment = updater.finish().await?;
+        Ok(fragment.files.pop().unwrap())
+    }
+
     pub(crate) async fn merge(mut self, join_column: &str, joiner: &HashJoiner) -> Result<Self> {
         let mut updater = self.updater(Some(&[join_column]), None).await?;
 
@@ -944,6 +1020,12 @@ impl FileFragment {
     /// fragment with the updated deletion vector. This must be persisted to
     /// the manifest.
     pub async fn delete(self, predicate: &str) -> Result<Option<Self>> {
+        Ok(self.delete_counted(predicate).await?.0)
+    }
+
+    /// Delete rows from the fragment, like [Self::delete], and also return the
+    /// number of rows that were deleted by this call.
+    pub(crate) async fn delete_counted(self, predicate: &str) -> Result<(Option<Self>, usize)> {
         // Load existing deletion vector
         let mut deletion_vector = read_deletion_file(
             &self.dataset.base,
@@ -960,9 +1042,10 @@ impl FileFragment {
 
         let predicate_lower = predicate.trim().to_lowercase();
         if predicate_lower == "true" {
-            return Ok(None);
+            let num_deleted = self.physical_rows().await? - starting_length;
+            return Ok((None, num_deleted));
         } else if predicate_lower == "false" {
-            return Ok(Some(self));
+            return Ok((Some(self), 0));
         }
 
         scanner
@@ -976,10 +1059,11 @@ impl FileFragment {
         // occurred so we also catch expressions that are equivalent to `true`
         if let Some(predicate) = &scanner.filter {
             if matches!(predicate, Expr::Literal(ScalarValue::Boolean(Some(false)))) {
-                return Ok(Some(self));
+                return Ok((Some(self), 0));
             }
             if matches!(predicate, Expr::Literal(ScalarValue::Boolean(Some(true)))) {
-                return Ok(None);
+                let num_deleted = self.physical_rows().await? - starting_length;
+                return Ok((None, num_deleted));
             }
         }
 
@@ -1002,11 +1086,12 @@ impl FileFragment {
             .await?;
 
         // If we haven't deleted any additional rows, we can return the fragment as-is.
-        if deletion_vector.len() == starting_length {
-            return Ok(Some(self));
+        let num_deleted = deletion_vector.len() - starting_length;
+        if num_deleted == 0 {
+            return Ok((Some(self), 0));
         }
 
-        self.write_deletions(deletion_vector).await
+        Ok((self.write_deletions(deletion_vector).await?, num_deleted))
     }
 
     pub(crate) async fn extend_deletions(
@@ -1122,6 +1207,21 @@ impl std::fmt::Display for FragmentReader {
     }
 }
 
+/// Projects a batch read from the data files onto the output schema
+///
+/// Columns of the output schema that are not stored in any of the data files of the
+/// fragment (because they were added to the dataset later) are filled with nulls.
+fn project_to_output(batch: RecordBatch, output_schema: &ArrowSchema) -> Result<RecordBatch> {
+    let mut batch = batch;
+    for field in output_schema.fields() {
+        if batch.column_by_name(field.name()).is_none() {
+            let nulls = new_null_array(field.data_type(), batch.num_rows());
+            batch = batch.try_with_column(field.as_ref().clone(), nulls)?;
+        }
+    }
+    Ok(batch.project_by_schema(output_schema)?)
+}
+
 fn merge_batches(batches: &[RecordBatch]) -> Result<RecordBatch> {
     if batches.is_empty() {
         return Err(Error::IO {
@@ -1257,7 +1357,7 @@ impl FragmentReader {
             .map(|(reader, schema)| read_fn(reader.as_ref(), schema))
             .collect::<Vec<_>>();
         let batches = try_join_all(futures).await?;
-        Ok(merge_batches(&batches)?.project_by_schema(&self.output_schema)?)
+        project_to_output(merge_batches(&batches)?, &self.output_schema)
     }
 
     pub(crate) async fn legacy_read_batch(
@@ -1286,12 +1386,24 @@ impl FragmentReader {
         params: impl Into<ReadBatchParams> + Clone,
         projection: &Schema,
     ) -> Result<RecordBatch> {
+        // If none of the projected columns are stored in the data files then we read the
+        // columns opened for the first data file, to know the number of rows.
+        let only_new_columns = self.readers.iter().all(|(_, schema)| {
+            schema
+                .intersection(projection)
+                .map(|s| s.fields.is_empty())
+                .unwrap_or(false)
+        }) && !projection.fields.is_empty();
         let read_tasks = self
             .readers
             .iter()
             .enumerate()
             .map(|(reader_idx, (reader, schema))| {
-                let projection = schema.intersection(projection);
+                let projection = if only_new_columns && reader_idx == 0 {
+                    Ok(schema.as_ref().clone())
+                } else {
+                    schema.intersection(projection)
+                };
                 let params = params.clone();
 
                 let reader = reader.as_legacy();
@@ -1332,9 +1444,7 @@ impl FragmentReader {
             output_schema
         };
 
-        let result = merge_batches(&batches)?.project_by_schema(&output_schema)?;
-
-        Ok(result)
+        project_to_output(merge_batches(&batches)?, &output_schema)
     }
 
     fn new_read_impl(
@@ -1425,11 +1535,7 @@ impl FragmentReader {
                 .map(move |batch_fut| {
                     let output_schema = output_schema.clone();
                     batch_fut
-                        .map(move |batch| {
-                            batch?
-                                .project_by_schema(&output_schema)
-                                .map_err(Error::from)
-                        })
+                        .map(move |batch| project_to_output(batch?, &output_schema))
                         .boxed()
                 })
                 .boxed(),
@@ -1569,7 +1675,7 @@ mod tests {
         let test_dir = tempdir().unwrap();
         let test_uri = test_dir.path().to_str().unwrap();
         let dataset = create_dataset(test_uri).await;
-        let fragment = &dataset.get_fragments()[2];
+        let fragment = &dataset.get_fragments().await.unwrap()[2];
         let mut scanner = fragment.scan();
         let batches = scanner
             .with_row_id()
@@ -1603,7 +1709,7 @@ mod tests {
         let test_dir = tempdir().unwrap();
         let test_uri = test_dir.path().to_str().unwrap();
         let dataset = create_dataset_v2(test_uri).await;
-        let fragment = &dataset.get_fragments()[2];
+        let fragment = &dataset.get_fragments().await.unwrap()[2];
         let mut scanner = fragment.scan();
         let batches = scanner
             .with_row_id()
@@ -1651,7 +1757,7 @@ mod tests {
         let mut dataset = create_dataset(test_uri).await;
         dataset.delete("i >= 0 and i < 15").await.unwrap();
 
-        let fragment = &dataset.get_fragments()[0];
+        let fragment = &dataset.get_fragments().await.unwrap()[0];
         let mut reader = fragment.open(dataset.schema(), true).await.unwrap();
         reader.with_make_deletions_null();
 
@@ -1682,6 +1788,8 @@ mod tests {
         let mut dataset = create_dataset(test_uri).await;
         let fragment = dataset
             .get_fragments()
+            .await
+            .unwrap()
             .into_iter()
             .find(|f| f.id() == 3)
             .unwrap();
@@ -1702,6 +1810,8 @@ mod tests {
         // Deleted rows are skipped
         let fragment = dataset
             .get_fragments()
+            .await
+            .unwrap()
             .into_iter()
             .find(|f| f.id() == 3)
             .unwrap();
@@ -1730,6 +1840,8 @@ mod tests {
         let mut dataset = create_dataset(test_uri).await;
         let fragment = dataset
             .get_fragments()
+            .await
+            .unwrap()
             .into_iter()
             .find(|f| f.id() == 3)
             .unwrap();
@@ -1750,6 +1862,8 @@ mod tests {
         // Cannot get rows 2 and 4 anymore
         let fragment = dataset
             .get_fragments()
+            .await
+            .unwrap()
             .into_iter()
             .find(|f| f.id() == 3)
             .unwrap();
@@ -1797,7 +1911,7 @@ mod tests {
         let dataset_rows = dataset.count_rows(None).await.unwrap();
 
         let mut paths: Vec<String> = Vec::new();
-        for f in dataset.get_fragments() {
+        for f in dataset.get_fragments().await.unwrap() {
             for file in Fragment::from(f.clone()).files {
                 let p = file.path.clone();
                 paths.push(p);
@@ -1825,7 +1939,7 @@ mod tests {
 
         // Fragments will have number of rows recorded in metadata, even though
         // we passed `None` when constructing the `FileFragment`.
-        let fragments = new_dataset.get_fragments();
+        let fragments = new_dataset.get_fragments().await.unwrap();
         assert_eq!(fragments.len(), 5);
         for f in fragments {
             assert_eq!(f.metadata.num_rows(), Some(40));
@@ -1839,7 +1953,7 @@ mod tests {
         let test_dir = tempdir().unwrap();
         let test_uri = test_dir.path().to_str().unwrap();
         let dataset = create_dataset(test_uri).await;
-        let fragment = dataset.get_fragments().pop().unwrap();
+        let fragment = dataset.get_fragments().await.unwrap().pop().unwrap();
 
         assert_eq!(fragment.count_rows().await.unwrap(), 40);
         assert_eq!(fragment.physical_rows().await.unwrap(), 40);
@@ -1877,7 +1991,7 @@ mod tests {
                 assert_eq!(dataset.count_rows(None).await.unwrap(), 195);
             }
 
-            let fragment = &mut dataset.get_fragment(0).unwrap();
+            let fragment = &mut dataset.get_fragment(0).await.unwrap().unwrap();
             let mut updater = fragment.updater(Some(&["i"]), None).await.unwrap();
             let new_schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
                 "double_i",
@@ -2106,7 +2220,7 @@ mod tests {
             None,
         )
         .await?;
-        let fragment = dataset.get_fragments().pop().unwrap();
+        let fragment = dataset.get_fragments().await.unwrap().pop().unwrap();
 
         // Write batch_s using add_columns
         let mut updater = fragment.updater(Some(&["i"]), None).await?;
@@ -2136,6 +2250,8 @@ mod tests {
         // Also take, read_range, and read_batch_projected
         let reader = dataset
             .get_fragments()
+            .await
+            .unwrap()
             .first()
             .unwrap()
             .open(dataset.schema(), false)
@@ -2184,7 +2300,7 @@ mod tests {
         )
         .await?;
 
-        let fragment = dataset.get_fragments().pop().unwrap();
+        let fragment = dataset.get_fragments().await.unwrap().pop().unwrap();
 
         let reader = fragment
             .open(&dataset.schema().project::<&str>(&[])?, true)
@@ -47,13 +47,13 @@
 //! // Write 100 small files
 //! let write_params = WriteParams { max_rows_per_file: 100, ..Default::default()};
 //! let mut dataset = Dataset::write(reader, &uri, Some(write_params)).await.unwrap();
-//! assert_eq!(dataset.get_fragments().len(), 100);
+//! assert_eq!(dataset.get_fragments().await.unwrap().len(), 100);
 //!
 //! // Use compact_files() to consolidate the data to 1 fragment
 //! let metrics = compact_files(&mut dataset, Default::default(), None).await.unwrap();
 //! assert_eq!(metrics.fragments_removed, 100);
 //! assert_eq!(metrics.fragments_added, 1);
-//! assert_eq!(dataset.get_fragments().len(), 1);
+//! assert_eq!(dataset.get_fragments().await.unwrap().len(), 1);
 //! # })
 //! ```
 //!
@@ -162,6 +162,14 @@ pub struct CompactionOptions {
     pub materialize_deletions_threshold: f32,
     /// The number of threads to use. Defaults to the number of cores.
     pub num_threads: usize,
+    /// Max number of data files per fragment. Defaults to `None`, no limit.
+    ///
+    /// Each [`Dataset::add_columns`] or [`Dataset::merge`] adds a data file to every
+    /// fragment.  Fragments with more data files than this, that are not otherwise
+    /// compacted, are rewritten into a single data file.  Their rows keep their
+    /// addresses, so indices do not need to be remapped.
+    #[serde(default)]
+    pub max_data_files_per_fragment: Option<usize>,
 }
 
 impl Default for CompactionOptions {
@@ -173,6 +181,7 @@ impl Default for CompactionOptions {
             materialize_deletions: true,
             materialize_deletions_threshold: 0.1,
             num_threads: num_cpus::get(),
+            max_data_files_per_fragment: None,
         }
     }
 }
@@ -216,7 +225,8 @@ pub struct CompactionMetrics {
     /// The number of files that have been removed, including deletion files.
     pub files_removed: usize,
     /// The number of files that have been added, which is always equal to the
-    /// number of fragments.
+    /// number of fragments added plus the number of fragments whose data files
+    /// have been consolidated.
     pub files_added: usize,
 }
 
@@ -235,6 +245,7 @@ impl AddAssign for CompactionMetrics {
 ///  * Removes deleted rows from fragments.
 ///  * Removes dropped columns from fragments.
 ///  * Merges fragments that are too small.
+///  * Consolidates the data files of fragments that have too many of them.
 ///
 /// This method tries to preserve the insertion order of rows in the dataset.
 ///
@@ -347,6 +358,10 @@ impl CompactionPlan {
 pub struct TaskData {
     /// The fragments to compact.
     pub fragments: Vec<Fragment>,
+    /// If true, the data files of the single fragment are rewritten into one data
+    /// file, in place, instead of compacting it into a new fragment.
+    #[serde(default)]
+    pub consolidate_files: bool,
 }
 
 /// A standalone task that can be serialized and sent to another machine for
@@ -493,11 +508,12 @@ pub async fn plan_compaction(
     debug_assert!(
         dataset
             .get_fragments()
+            .await?
             .windows(2)
             .all(|w| w[0].id() < w[1].id()),
         "fragments in manifest are not sorted"
     );
-    let mut fragment_metrics = futures::stream::iter(dataset.get_fragments())
+    let mut fragment_metrics = futures::stream::iter(dataset.get_fragments().await?)
         .map(|fragment| async move {
             match collect_metrics(&fragment).await {
                 Ok(metrics) => Ok((fragment.metadata, metrics)),
@@ -518,6 +534,7 @@ pub async fn plan_compaction(
 
     let mut candidate_bins: Vec<CandidateBin> = Vec::new();
     let mut current_bin: Option<CandidateBin> = None;
+    let mut consolidate_tasks = Vec::new();
     let mut i = 0;
 
     while let Some(res) = fragment_metrics.next().await {
@@ -538,6 +555,17 @@ pub async fn plan_compaction(
 
         let indices = indices_containing_frag(fragment.id as u32);
 
+        let too_many_files = options
+            .max_data_files_per_fragment
+            .map(|max| fragment.files.len() > max)
+            .unwrap_or(false);
+        if candidacy.is_none() && too_many_files {
+            consolidate_tasks.push(TaskData {
+                fragments: vec![fragment.clone()],
+                consolidate_files: true,
+            });
+        }
+
         match (candidacy, &mut current_bin) {
             (None, None) => {} // keep searching
             (Some(candidacy), None) => {
@@ -585,16 +613,35 @@ pub async fn plan_compaction(
         candidate_bins.push(bin);
     }
 
+    let (noop_bins, candidate_bins): (Vec<_>, Vec<_>) =
+        candidate_bins.into_iter().partition(|bin| bin.is_noop());
+    // A fragment that had no neighbors to be compacted with may still have too many files
+    consolidate_tasks.extend(
+        noop_bins
+            .into_iter()
+            .flat_map(|bin| bin.fragments)
+            .filter(|fragment| {
+                options
+                    .max_data_files_per_fragment
+                    .map(|max| fragment.files.len() > max)
+                    .unwrap_or(false)
+            })
+            .map(|fragment| TaskData {
+                fragments: vec![fragment],
+                consolidate_files: true,
+            }),
+    );
     let final_bins = candidate_bins
         .into_iter()
-        .filter(|bin| !bin.is_noop())
         .flat_map(|bin| bin.split_for_size(options.target_rows_per_fragment))
         .map(|bin| TaskData {
             fragments: bin.fragments,
+            consolidate_files: false,
         });
 
     let mut compaction_plan = CompactionPlan::new(dataset.manifest.version, options.clone());
     compaction_plan.extend_tasks(final_bins);
+    compaction_plan.extend_tasks(consolidate_tasks);
 
     Ok(compaction_plan)
 }
@@ -611,6 +658,10 @@ pub struct RewriteResult {
     /// The original fragments being replaced
     pub original_fragments: Vec<Fragment>,
     pub row_id_map: HashMap<u64, Option<u64>>,
+    /// True if the data files of the single original fragment were consolidated without
+    /// changing the addresses of its rows, so indices do not need to be remapped.
+    #[serde(default)]
+    pub in_place: bool,
 }
 
 /// Iterator that yields row_ids that are in the given fragments but not in
@@ -768,9 +819,14 @@ async fn rewrite_files(
             read_version: dataset.manifest.version,
             original_fragments: task.fragments,
             row_id_map: HashMap::new(),
+            in_place: false,
         });
     }
 
+    if task.consolidate_files {
+        return consolidate_files(dataset, task).await;
+    }
+
     let previous_writer_version = &dataset.manifest.writer_version;
     // The versions of Lance prior to when we started writing the writer version
     // sometimes wrote incorrect `Fragment.phyiscal_rows` values, so we should
@@ -836,6 +892,35 @@ async fn rewrite_files(
         read_version: dataset.manifest.version,
         original_fragments: task.fragments,
         row_id_map,
+        in_place: false,
+    })
+}
+
+/// Rewrite the data files of the single fragment of a task into one data file.
+///
+/// The fragment keeps its id and deletion file, and its rows keep their addresses.
+async fn consolidate_files(dataset: Cow<'_, Dataset>, task: TaskData) -> Result<RewriteResult> {
+    let fragment = task.fragments[0].clone();
+    let dataset = Arc::new(dataset.into_owned());
+    let new_file = FileFragment::new(dataset.clone(), fragment.clone())
+        .rewrite_fields(&dataset.schema().field_ids())
+        .await?;
+
+    let metrics = CompactionMetrics {
+        files_removed: fragment.files.len(),
+        files_added: 1,
+        ..Default::default()
+    };
+    Ok(RewriteResult {
+        metrics,
+        new_fragments: vec![Fragment {
+            files: vec![new_file],
+            ..fragment
+        }],
+        read_version: dataset.manifest.version,
+        original_fragments: task.fragments,
+        row_id_map: HashMap::new(),
+        in_place: true,
     })
 }
 
@@ -864,14 +949,17 @@ pub async fn commit_compaction(
         let rewrite_group = RewriteGroup {
             old_fragments: task.original_fragments,
             new_fragments: task.new_fragments,
+            in_place: task.in_place,
         };
         row_id_map.extend(task.row_id_map);
         rewrite_groups.push(rewrite_group);
     }
 
     let index_remapper = options.create_remapper(dataset)?;
+    // Fragments rewritten in place keep the addresses of their rows
     let affected_ids = rewrite_groups
         .iter()
+        .filter(|group| !group.in_place)
         .flat_map(|group| group.old_fragments.iter().map(|frag| frag.id))
         .collect::<Vec<_>>();
 
@@ -913,12 +1001,17 @@ pub async fn commit_compaction(
 #[cfg(test)]
 mod tests {
 
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::Int64Type;
     use arrow_array::{Float32Array, Int64Array, RecordBatch, RecordBatchIterator};
     use arrow_schema::{DataType, Field, Schema};
     use arrow_select::concat::concat_batches;
+    use lance_index::IndexType;
     use tempfile::tempdir;
 
     use super::*;
+    use crate::dataset::NewColumnTransform;
+    use crate::index::scalar::ScalarIndexParams;
 
     #[test]
     fn test_missing_indices() {
@@ -1349,6 +1442,8 @@ mod tests {
 
         let fragment_ids = dataset
             .get_fragments()
+            .await
+            .unwrap()
             .iter()
             .map(|f| f.id())
             .collect::<Vec<_>>();
@@ -1436,6 +1531,93 @@ mod tests {
         assert_eq!(scanned_data, data);
     }
 
+    #[tokio::test]
+    async fn test_consolidate_data_files() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        // Create a table with 2 fragments that each get 3 data files
+        let data = sample_data();
+        let reader = RecordBatchIterator::new(vec![Ok(data.clone())], data.schema());
+        let write_params = WriteParams {
+            max_rows_per_file: 5_000,
+            max_rows_per_group: 1_000,
+            ..Default::default()
+        };
+        let mut dataset = Dataset::write(reader, test_uri, Some(write_params))
+            .await
+            .unwrap();
+        for (name, expr) in [("b", "a * 2"), ("c", "a + 1")] {
+            dataset
+                .add_columns(
+                    NewColumnTransform::SqlExpressions(vec![(name.into(), expr.into())]),
+                    None,
+                )
+                .await
+                .unwrap();
+        }
+        dataset
+            .create_index(
+                &["a"],
+                IndexType::Scalar,
+                None,
+                &ScalarIndexParams::default(),
+                false,
+            )
+            .await
+            .unwrap();
+        dataset.delete("a < 10").await.unwrap();
+        let index_uuid = dataset.load_indices().await.unwrap()[0].uuid;
+
+        let mut scanner = dataset.scan();
+        scanner.with_row_id();
+        let expected = scanner.try_into_batch().await.unwrap();
+
+        // Fragments are not compacted because they are large enough
+        let options = CompactionOptions {
+            target_rows_per_fragment: 5_000,
+            max_data_files_per_fragment: Some(3),
+            ..Default::default()
+        };
+        let plan = plan_compaction(&dataset, &options).await.unwrap();
+        assert_eq!(plan.num_tasks(), 0);
+
+        let options = CompactionOptions {
+            max_data_files_per_fragment: Some(2),
+            ..options
+        };
+        let plan = plan_compaction(&dataset, &options).await.unwrap();
+        assert_eq!(plan.num_tasks(), 2);
+        assert!(plan.tasks().iter().all(|task| task.consolidate_files));
+
+        let metrics = compact_files(&mut dataset, options, None).await.unwrap();
+        assert_eq!(metrics.files_removed, 6);
+        assert_eq!(metrics.files_added, 2);
+        assert_eq!(metrics.fragments_removed, 0);
+        assert_eq!(metrics.fragments_added, 0);
+
+        dataset.validate().await.unwrap();
+        let fragments = dataset.get_fragments().await.unwrap();
+        assert_eq!(
+            fragments.iter().map(|f| f.id()).collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+        for fragment in &fragments {
+            assert_eq!(fragment.metadata().files.len(), 1);
+        }
+        assert!(fragments[0].metadata().deletion_file.is_some());
+
+        // Row addresses are unchanged, so the index is not remapped and still valid
+        let mut scanner = dataset.scan();
+        scanner.with_row_id();
+        assert_eq!(scanner.try_into_batch().await.unwrap(), expected);
+        assert_eq!(dataset.load_indices().await.unwrap()[0].uuid, index_uuid);
+        let mut scanner = dataset.scan();
+        scanner.filter("a = 7000").unwrap().project(&["c"]).unwrap();
+        let batch = scanner.try_into_batch().await.unwrap();
+        assert_eq!(batch["c"].as_primitive::<Int64Type>().values(), &[7001]);
+    }
+
     #[tokio::test]
     async fn test_compact_deletions() {
         // For files that have few rows, we don't want to compact just 1 since
@@ -1482,7 +1664,7 @@ mod tests {
         assert_eq!(metrics.files_removed, 2);
         assert_eq!(metrics.fragments_added, 1);
 
-        let fragments = dataset.get_fragments();
+        let fragments = dataset.get_fragments().await.unwrap();
         assert_eq!(fragments.len(), 1);
         assert!(fragments[0].metadata.deletion_file.is_none());
     }
@@ -0,0 +1,460 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Repair of fragments with missing or corrupt files.
+//!
+//! [`Dataset::verify`] reports the damaged fragments of a dataset.
+//! [`Dataset::repair`] takes those fragments and either drops them or
+//! restores their damaged files from an older version of the dataset that
+//! references an intact file with identical data (the same fields and the same
+//! checksum).  Either way the result is committed as a single
+//! [`Operation::Repair`], which also removes dropped fragments from the
+//! fragment bitmaps of the indices.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use lance_table::format::{DataFile, DeletionFile, Fragment};
+use lance_table::io::deletion::deletion_file_path;
+use snafu::{location, Location};
+
+use super::transaction::{Operation, Transaction};
+use super::verify::DamagedFragment;
+use super::Dataset;
+use crate::io::commit::commit_transaction;
+use crate::{Error, Result};
+
+/// What [`Dataset::repair`] does with a damaged fragment.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RepairMode {
+    /// Drop the damaged fragments and their rows.
+    #[default]
+    Drop,
+    /// Restore the damaged files from older versions of the dataset. Fails
+    /// if any fragment cannot be restored.
+    Restore,
+    /// Restore the fragments that can be restored and drop the others.
+    RestoreOrDrop,
+}
+
+/// The result of [`Dataset::repair`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepairStats {
+    /// Fragments that were dropped from the dataset.
+    pub removed_fragment_ids: Vec<u64>,
+    /// Fragments whose damaged files were restored.
+    pub restored_fragment_ids: Vec<u64>,
+}
+
+impl Dataset {
+    /// Repair the damaged fragments found by [`Dataset::verify`].
+    ///
+    /// Does nothing if `damaged` is empty.
+    pub async fn repair(
+        &mut self,
+        damaged: &[DamagedFragment],
+        mode: RepairMode,
+    ) -> Result<RepairStats> {
+        let mut stats = RepairStats::default();
+        if damaged.is_empty() {
+            return Ok(stats);
+        }
+
+        let mut restored_fragments = Vec::new();
+        for damaged_fragment in damaged {
+            let fragment = self
+                .manifest
+                .fragments
+                .iter()
+                .find(|f| f.id == damaged_fragment.fragment_id)
+                .ok_or_else(|| {
+                    Error::invalid_input(
+                        format!(
+                            "Cannot repair fragment {} which is not part of version {}",
+                            damaged_fragment.fragment_id,
+                            self.version().version
+                        ),
+                        location!(),
+                    )
+                })?;
+            let restored = match mode {
+                RepairMode::Drop => None,
+                RepairMode::Restore | RepairMode::RestoreOrDrop => {
+                    self.restore_fragment(fragment, damaged_fragment).await?
+                }
+            };
+            match restored {
+                Some(restored) => {
+                    stats.restored_fragment_ids.push(restored.id);
+                    restored_fragments.push(restored);
+                }
+                None if mode == RepairMode::Restore => {
+                    return Err(Error::invalid_input(
+                        format!(
+                            "Fragment {} cannot be restored: no older version references intact copies of its damaged files",
+                            fragment.id
+                        ),
+                        location!(),
+                    ));
+                }
+                None => stats.removed_fragment_ids.push(fragment.id),
+            }
+        }
+
+        let predicate = format!(
+            "fragment_id IN ({})",
+            damaged
+                .iter()
+                .map(|f| f.fragment_id.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let transaction = Transaction::new(
+            self.manifest.version,
+            Operation::Repair {
+                removed_fragment_ids: stats.removed_fragment_ids.clone(),
+                restored_fragments,
+                predicate,
+            },
+            None,
+        );
+
+        let manifest = commit_transaction(
+            self,
+            &self.object_store,
+            self.commit_handler.as_ref(),
+            &transaction,
+            &Default::default(),
+            &Default::default(),
+        )
+        .await?;
+
+        self.manifest = Arc::new(manifest);
+
+        Ok(stats)
+    }
+
+    /// Look through older versions, newest first, for intact files holding the
+    /// same data as the damaged files of `fragment`.
+    async fn restore_fragment(
+        &self,
+        fragment: &Fragment,
+        damaged: &DamagedFragment,
+    ) -> Result<Option<Fragment>> {
+        let damaged_paths = damaged
+            .files
+            .iter()
+            .map(|f| f.path.as_str())
+            .collect::<HashSet<_>>();
+        let is_damaged_data_file = |data_file: &DataFile| {
+            let path = self.data_dir().child(data_file.path.as_str());
+            damaged_paths.contains(path.as_ref())
+        };
+        let deletion_path = fragment
+            .deletion_file
+            .as_ref()
+            .map(|deletion_file| deletion_file_path(&self.base, fragment.id, deletion_file));
+        let deletion_damaged = deletion_path
+            .as_ref()
+            .is_some_and(|path| damaged_paths.contains(path.as_ref()));
+
+        // Files without a checksum cannot be matched with an older copy.
+        if fragment
+            .files
+            .iter()
+            .any(|f| is_damaged_data_file(f) && f.checksum.is_none())
+            || (deletion_damaged
+                && fragment
+                    .deletion_file
+                    .as_ref()
+                    .is_some_and(|f| f.checksum.is_none()))
+        {
+            return Ok(None);
+        }
+
+        let mut versions = self
+            .versions()
+            .await?
+            .into_iter()
+            .map(|v| v.version)
+            .filter(|v| *v < self.manifest.version)
+            .collect::<Vec<_>>();
+        versions.sort_unstable_by(|a, b| b.cmp(a));
+
+        let mut restored = fragment.clone();
+        let mut replaced_files = HashMap::new();
+        let mut deletion_restored = !deletion_damaged;
+        for version in versions {
+            let old_dataset = match self.checkout_version(version).await {
+                Ok(old_dataset) => old_dataset,
+                // The manifest may have been cleaned up.
+                Err(Error::NotFound { .. }) => continue,
+                Err(e) => return Err(e),
+            };
+            let Some(old_fragment) = old_dataset
+                .manifest
+                .fragments
+                .iter()
+                .find(|f| f.id == fragment.id)
+            else {
+                continue;
+            };
+
+            for (idx, data_file) in fragment.files.iter().enumerate() {
+                if !is_damaged_data_file(data_file) || replaced_files.contains_key(&idx) {
+                    continue;
+                }
+                if let Some(candidate) = self
+                    .find_intact_data_file(data_file, &old_fragment.files)
+                    .await?
+                {
+                    replaced_files.insert(idx, candidate);
+                }
+            }
+            if !deletion_restored {
+                if let Some(candidate) = self
+                    .find_intact_deletion_file(fragment, old_fragment)
+                    .await?
+                {
+                    restored.deletion_file = Some(candidate);
+                    deletion_restored = true;
+                }
+            }
+
+            let damaged_data_files = fragment
+                .files
+                .iter()
+                .filter(|f| is_damaged_data_file(f))
+                .count();
+            if deletion_restored && replaced_files.len() == damaged_data_files {
+                for (idx, data_file) in replaced_files {
+                    restored.files[idx] = data_file;
+                }
+                return Ok(Some(restored));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn find_intact_data_file(
+        &self,
+        damaged: &DataFile,
+        candidates: &[DataFile],
+    ) -> Result<Option<DataFile>> {
+        for candidate in candidates {
+            if candidate.path == damaged.path
+                || candidate.fields != damaged.fields
+                || candidate.column_indices != damaged.column_indices
+                || candidate.checksum != damaged.checksum
+            {
+                continue;
+            }
+            let path = self.data_dir().child(candidate.path.as_str());
+            if self
+                .verify_file(&path, candidate.checksum, true)
+                .await?
+                .is_none()
+            {
+                return Ok(Some(candidate.clone()));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn find_intact_deletion_file(
+        &self,
+        fragment: &Fragment,
+        old_fragment: &Fragment,
+    ) -> Result<Option<DeletionFile>> {
+        let (Some(damaged), Some(candidate)) =
+            (&fragment.deletion_file, &old_fragment.deletion_file)
+        else {
+            return Ok(None);
+        };
+        if candidate.id == damaged.id
+            || candidate.num_deleted_rows != damaged.num_deleted_rows
+            || candidate.checksum != damaged.checksum
+        {
+            return Ok(None);
+        }
+        let path = deletion_file_path(&self.base, fragment.id, candidate);
+        let intact = self
+            .verify_file(&path, candidate.checksum, true)
+            .await?
+            .is_none();
+        Ok(intact.then(|| candidate.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator};
+    use arrow_schema::{DataType, Field, Schema as ArrowSchema};
+    use lance_index::{DatasetIndexExt, IndexType};
+
+    use crate::dataset::WriteParams;
+    use crate::index::scalar::ScalarIndexParams;
+
+    async fn create_dataset(test_uri: &str, num_fragments: i32) -> Dataset {
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(
+                0..(num_fragments * 100),
+            ))],
+        )
+        .unwrap();
+        let write_params = WriteParams {
+            max_rows_per_file: 100,
+            ..Default::default()
+        };
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        Dataset::write(reader, test_uri, Some(write_params))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_repair_drop() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let mut dataset = create_dataset(test_uri, 3).await;
+        dataset
+            .create_index(
+                &["i"],
+                IndexType::Scalar,
+                None,
+                &ScalarIndexParams::default(),
+                false,
+            )
+            .await
+            .unwrap();
+
+        let fragment = dataset.get_fragment(1).await.unwrap().unwrap();
+        let path = dataset
+            .data_dir()
+            .child(fragment.metadata.files[0].path.as_str());
+        dataset.object_store.delete(&path).await.unwrap();
+
+        let report = dataset.verify(false).await.unwrap();
+        assert_eq!(report.damaged_fragments.len(), 1);
+        let stats = dataset
+            .repair(&report.damaged_fragments, RepairMode::RestoreOrDrop)
+            .await
+            .unwrap();
+        assert_eq!(stats.removed_fragment_ids, vec![1]);
+        assert!(stats.restored_fragment_ids.is_empty());
+
+        assert!(dataset.verify(true).await.unwrap().is_ok());
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 200);
+        let indices = dataset.load_indices().await.unwrap();
+        let bitmap = indices[0].fragment_bitmap.as_ref().unwrap();
+        assert_eq!(bitmap.iter().collect::<Vec<_>>(), vec![0, 2]);
+        let batch = dataset
+            .scan()
+            .filter("i = 150 OR i = 250")
+            .unwrap()
+            .try_into_batch()
+            .await
+            .unwrap();
+        assert_eq!(batch.num_rows(), 1);
+
+        // The repair is recorded in the transaction file
+        let transaction = dataset.read_transaction().await.unwrap().unwrap();
+        assert!(matches!(
+            transaction.operation,
+            Operation::Repair { ref removed_fragment_ids, ref predicate, .. }
+                if removed_fragment_ids == &[1] && predicate == "fragment_id IN (1)"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_repair_restore() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let dataset = create_dataset(test_uri, 2).await;
+        let original_version = dataset.version().version;
+
+        // Rewrite fragment 0 so that it points at a copy of its data file.
+        let mut fragments = dataset
+            .get_fragments()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|f| f.metadata)
+            .collect::<Vec<_>>();
+        let original = dataset
+            .data_dir()
+            .child(fragments[0].files[0].path.as_str());
+        let copy_name = "copy.lance";
+        let copy = dataset.data_dir().child(copy_name);
+        let data = dataset.object_store.read_object(&original).await.unwrap();
+        dataset
+            .object_store
+            .write_object(&copy, data)
+            .await
+            .unwrap();
+        fragments[0].files[0].path = copy_name.to_string();
+        let mut dataset = Dataset::commit(
+            test_uri,
+            Operation::Merge {
+                fragments,
+                schema: dataset.schema().clone(),
+            },
+            Some(original_version),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        // Lose both the copy and the only file of fragment 1
+        dataset.object_store.delete(&copy).await.unwrap();
+        let fragment1_path = dataset.data_dir().child(
+            dataset
+                .get_fragment(1)
+                .await
+                .unwrap()
+                .unwrap()
+                .metadata
+                .files[0]
+                .path
+                .as_str(),
+        );
+        dataset.object_store.delete(&fragment1_path).await.unwrap();
+
+        let report = dataset.verify(false).await.unwrap();
+        assert_eq!(report.damaged_fragments.len(), 2);
+
+        // Fragment 1 has no intact copy
+        let err = dataset
+            .repair(&report.damaged_fragments, RepairMode::Restore)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Fragment 1 cannot be restored"));
+
+        let stats = dataset
+            .repair(&report.damaged_fragments, RepairMode::RestoreOrDrop)
+            .await
+            .unwrap();
+        assert_eq!(stats.restored_fragment_ids, vec![0]);
+        assert_eq!(stats.removed_fragment_ids, vec![1]);
+
+        let fragments = dataset.get_fragments().await.unwrap();
+        assert_eq!(fragments.len(), 1);
+        assert_eq!(
+            dataset
+                .data_dir()
+                .child(fragments[0].metadata.files[0].path.as_str()),
+            original
+        );
+        assert!(dataset.verify(true).await.unwrap().is_ok());
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 100);
+    }
+}
@@ -30,9 +30,10 @@ use datafusion::scalar::ScalarValue;
 use datafusion_physical_expr::PhysicalExpr;
 use futures::stream::{Stream, StreamExt};
 use futures::TryStreamExt;
-use lance_arrow::floats::{coerce_float_vector, FloatType};
+use lance_arrow::sparse::{is_sparse_vector, SparseVectorArray};
 use lance_core::{ROW_ID, ROW_ID_FIELD};
 use lance_datafusion::exec::{execute_plan, LanceExecutionOptions};
+use lance_index::vector::utils::{coerce_query_vector, is_vector_element};
 use lance_index::vector::{Query, DIST_COL};
 use lance_index::{scalar::expression::ScalarIndexExpr, DatasetIndexExt};
 use lance_io::stream::RecordBatchStream;
@@ -436,19 +437,8 @@ impl Scanner {
             location: location!(),
         })?;
         let key = match field.data_type() {
-            DataType::FixedSizeList(dt, _) => {
-                if dt.data_type().is_floating() {
-                    coerce_float_vector(q, FloatType::try_from(dt.data_type())?)?
-                } else {
-                    return Err(Error::IO {
-                        message: format!(
-                            "Column {} is not a vector column (type: {})",
-                            column,
-                            field.data_type()
-                        ),
-                        location: location!(),
-                    });
-                }
+            DataType::FixedSizeList(dt, _) if is_vector_element(&dt) => {
+                coerce_query_vector(q, &dt)?
             }
             _ => {
                 return Err(Error::IO {
@@ -464,7 +454,7 @@ impl Scanner {
 
         self.nearest = Some(Query {
             column: column.to_string(),
-            key: key.into(),
+            key,
             k,
             nprobes: 1,
             ef: None,
@@ -475,6 +465,53 @@ impl Scanner {
         Ok(self)
     }
 
+    /// Find k-nearest neighbor within a sparse vector column, by dot product.
+    ///
+    /// The query vector is given as parallel `indices` and `values` slices.
+    pub fn nearest_sparse(
+        &mut self,
+        column: &str,
+        indices: &[u32],
+        values: &[f32],
+        k: usize,
+    ) -> Result<&mut Self> {
+        self.ensure_not_fragment_scan()?;
+
+        if k == 0 {
+            return Err(Error::IO {
+                message: "k must be positive".to_string(),
+                location: location!(),
+            });
+        }
+        let field = self.dataset.schema().field(column).ok_or(Error::IO {
+            message: format!("Column {} not found", column),
+            location: location!(),
+        })?;
+        if !is_sparse_vector(&field.data_type()) {
+            return Err(Error::IO {
+                message: format!(
+                    "Column {} is not a sparse vector column (type: {})",
+                    column,
+                    field.data_type()
+                ),
+                location: location!(),
+            });
+        }
+        let key = SparseVectorArray::from_pairs([(indices, values)])?.into_struct_array();
+
+        self.nearest = Some(Query {
+            column: column.to_string(),
+            key: Arc::new(key),
+            k,
+            nprobes: 1,
+            ef: None,
+            refine_factor: None,
+            metric_type: MetricType::Dot,
+            use_index: true,
+        });
+        Ok(self)
+    }
+
     pub fn nprobs(&mut self, n: usize) -> &mut Self {
         if let Some(q) = self.nearest.as_mut() {
             q.nprobes = n;
@@ -816,10 +853,12 @@ impl Scanner {
             // This tests if any of the fragments are missing the physical_rows property (old style)
             // If they are then we cannot use scalar indices
             if filter_plan.index_query.is_some() {
+                let dataset_fragments;
                 let fragments = if let Some(fragments) = self.fragments.as_ref() {
                     fragments
                 } else {
-                    self.dataset.fragments()
+                    dataset_fragments = self.dataset.fragments().await?;
+                    dataset_fragments.as_ref()
                 };
                 let mut has_missing_row_count = false;
                 for frag in fragments {
@@ -869,12 +908,14 @@ impl Scanner {
                         .await?
                 }
                 (None, Some(_)) if self.use_stats => {
-                    self.pushdown_scan(false, filter_plan.refine_expr.take().unwrap())?
+                    self.pushdown_scan(false, filter_plan.refine_expr.take().unwrap())
+                        .await?
                 }
                 (None, _) => {
                     // The source is a full scan of the table
                     let with_row_id = filter_plan.has_refine() || self.with_row_id;
                     self.scan(with_row_id, false, self.phyical_columns.clone().into())
+                        .await?
                 }
             }
         };
@@ -987,11 +1028,12 @@ impl Scanner {
         let schema = self.dataset.schema();
         if let Some(field) = schema.field(&q.column) {
             match field.data_type() {
-                DataType::FixedSizeList(subfield, _) if subfield.data_type().is_floating() => {}
+                DataType::FixedSizeList(subfield, _) if is_vector_element(&subfield) => {}
+                data_type if is_sparse_vector(&data_type) => {}
                 _ => {
                     return Err(Error::IO {
                         message: format!(
-                            "Vector search error: column {} is not a vector type: expected FixedSizeList<Float32>, got {}",
+                            "Vector search error: column {} is not a vector type: expected FixedSizeList of floats, int8 or uint8, or a sparse vector, got {}",
                             q.column, field.data_type(),
                         ),
                         location: location!(),
@@ -1055,7 +1097,7 @@ impl Scanner {
                 self.scalar_indexed_scan(&vector_scan_projection, index_query)
                     .await?
             } else {
-                self.scan(true, true, vector_scan_projection)
+                self.scan(true, true, vector_scan_projection).await?
             };
             if let Some(refine_expr) = &filter_plan.refine_expr {
                 let planner = Planner::new(plan.schema());
@@ -1172,7 +1214,7 @@ impl Scanner {
         let fragments = if let Some(fragment) = self.fragments.as_ref() {
             fragment.clone()
         } else {
-            (**self.dataset.fragments()).clone()
+            self.dataset.fragments().await?.as_ref().clone()
         };
 
         // Figure out which fragments are covered by ALL of the indices we are using
@@ -1254,16 +1296,16 @@ impl Scanner {
     ///
     /// Setting `with_make_deletions_null` will use the validity of the _rowid
     /// column as a selection vector. Read more in [crate::io::FileReader].
-    pub(crate) fn scan(
+    pub(crate) async fn scan(
         &self,
         with_row_id: bool,
         with_make_deletions_null: bool,
         projection: Arc<Schema>,
-    ) -> Arc<dyn ExecutionPlan> {
+    ) -> Result<Arc<dyn ExecutionPlan>> {
         let fragments = if let Some(fragment) = self.fragments.as_ref() {
             Arc::new(fragment.clone())
         } else {
-            self.dataset.fragments().clone()
+            self.dataset.fragments().await?
         };
         let ordered = if self.ordering.is_some() || self.nearest.is_some() {
             // If we are sorting the results there is no need to scan in order
@@ -1271,13 +1313,13 @@ impl Scanner {
         } else {
             self.ordered
         };
-        self.scan_fragments(
+        Ok(self.scan_fragments(
             with_row_id,
             with_make_deletions_null,
             projection,
             fragments,
             ordered,
-        )
+        ))
     }
 
     fn scan_fragments(
@@ -1301,7 +1343,7 @@ impl Scanner {
         ))
     }
 
-    fn pushdown_scan(
+    async fn pushdown_scan(
         &self,
         make_deletions_null: bool,
         predicate: Expr,
@@ -1317,7 +1359,7 @@ impl Scanner {
         let fragments = if let Some(fragment) = self.fragments.as_ref() {
             Arc::new(fragment.clone())
         } else {
-            self.dataset.fragments().clone()
+            self.dataset.fragments().await?
         };
 
         Ok(Arc::new(LancePushdownScanExec::try_new(
@@ -1376,7 +1418,7 @@ impl Scanner {
                 // of the filter columns to determine the valid row ids.
                 let columns_in_filter = Planner::column_names_in_expr(refine_expr);
                 let filter_schema = Arc::new(self.dataset.schema().project(&columns_in_filter)?);
-                let filter_input = self.scan(true, true, filter_schema);
+                let filter_input = self.scan(true, true, filter_schema).await?;
                 let planner = Planner::new(filter_input.schema());
                 let physical_refine_expr = planner.create_physical_expr(refine_expr)?;
                 let filtered_row_ids =
@@ -2193,8 +2235,20 @@ mod test {
         write_batch(batch2.clone()).await.unwrap();
 
         let dataset = Arc::new(Dataset::open(test_uri).await.unwrap());
-        let fragment1 = dataset.get_fragment(0).unwrap().metadata().clone();
-        let fragment2 = dataset.get_fragment(1).unwrap().metadata().clone();
+        let fragment1 = dataset
+            .get_fragment(0)
+            .await
+            .unwrap()
+            .unwrap()
+            .metadata()
+            .clone();
+        let fragment2 = dataset
+            .get_fragment(1)
+            .await
+            .unwrap()
+            .unwrap()
+            .metadata()
+            .clone();
 
         // 1 then 2
         let mut scanner = dataset.scan();
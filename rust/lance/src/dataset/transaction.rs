@@ -137,6 +137,20 @@ pub enum Operation {
 
     /// Project to a new schema. This only changes the schema, not the data.
     Project { schema: Schema },
+
+    /// Repair fragments with missing or corrupt files.
+    ///
+    /// Removed fragments are dropped from the dataset and from the fragment
+    /// bitmaps of the indices covering them. Restored fragments keep their id
+    /// but point at intact files holding identical data.
+    Repair {
+        /// Ids of fragments that have been dropped
+        removed_fragment_ids: Vec<u64>,
+        /// Fragments whose damaged files have been replaced
+        restored_fragments: Vec<Fragment>,
+        /// Describes the fragments that were repaired
+        predicate: String,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -149,6 +163,9 @@ pub struct RewrittenIndex {
 pub struct RewriteGroup {
     pub old_fragments: Vec<Fragment>,
     pub new_fragments: Vec<Fragment>,
+    /// True if the group rewrites the data files of a single fragment without changing its
+    /// id, and so the addresses of its rows.
+    pub in_place: bool,
 }
 
 impl Operation {
@@ -190,6 +207,16 @@ impl Operation {
                     .map(|f| f.id)
                     .chain(removed_fragment_ids.iter().copied()),
             ),
+            Self::Repair {
+                removed_fragment_ids,
+                restored_fragments,
+                ..
+            } => Box::new(
+                restored_fragments
+                    .iter()
+                    .map(|f| f.id)
+                    .chain(removed_fragment_ids.iter().copied()),
+            ),
         }
     }
 
@@ -212,6 +239,7 @@ impl Operation {
             Self::Restore { .. } => "Restore",
             Self::Update { .. } => "Update",
             Self::Project { .. } => "Project",
+            Self::Repair { .. } => "Repair",
         }
     }
 }
@@ -230,10 +258,8 @@ impl Transaction {
     /// Returns true if the transaction cannot be committed if the other
     /// transaction is committed first.
     pub fn conflicts_with(&self, other: &Self) -> bool {
-        // TODO: this assume IsolationLevel is Serializable, but we could also
-        // support Snapshot Isolation, which is more permissive. In particular,
-        // it would allow a Delete transaction to succeed after a concurrent
-        // Append, even if the Append added rows that would be deleted.
+        // This assumes IsolationLevel::Serializable. Under snapshot isolation, some
+        // of these conflicts can be resolved, see [`Self::rebasable_onto`].
         match &self.operation {
             Operation::Append { .. } => match &other.operation {
                 // Append is compatible with anything that doesn't change the schema
@@ -243,6 +269,7 @@ impl Transaction {
                 Operation::Delete { .. } | Operation::Update { .. } => false,
                 Operation::ReserveFragments { .. } => false,
                 Operation::Project { .. } => false,
+                Operation::Repair { .. } => false,
                 _ => true,
             },
             Operation::Rewrite { .. } => match &other.operation {
@@ -252,7 +279,10 @@ impl Transaction {
                 // fragments we don't touch.
                 Operation::Append { .. } => false,
                 Operation::ReserveFragments { .. } => false,
-                Operation::Delete { .. } | Operation::Rewrite { .. } | Operation::Update { .. } => {
+                Operation::Delete { .. }
+                | Operation::Rewrite { .. }
+                | Operation::Update { .. }
+                | Operation::Repair { .. } => {
                     // As long as they rewrite disjoint fragments they shouldn't conflict.
                     self.operation.modifies_same_ids(&other.operation)
                 }
@@ -288,13 +318,28 @@ impl Transaction {
             Operation::Delete { .. } | Operation::Update { .. } => match &other.operation {
                 Operation::CreateIndex { .. } => false,
                 Operation::ReserveFragments { .. } => false,
-                Operation::Delete { .. } | Operation::Rewrite { .. } | Operation::Update { .. } => {
+                Operation::Delete { .. }
+                | Operation::Rewrite { .. }
+                | Operation::Update { .. }
+                | Operation::Repair { .. } => {
                     // If we update the same fragments, we conflict.
                     self.operation.modifies_same_ids(&other.operation)
                 }
                 Operation::Project { .. } => false,
                 _ => true,
             },
+            Operation::Repair { .. } => match &other.operation {
+                // Repair only touches the damaged fragments. It conflicts with
+                // CreateIndex since the new index may cover a dropped fragment.
+                Operation::Append { .. } => false,
+                Operation::ReserveFragments { .. } => false,
+                Operation::Project { .. } => false,
+                Operation::Delete { .. }
+                | Operation::Rewrite { .. }
+                | Operation::Update { .. }
+                | Operation::Repair { .. } => self.operation.modifies_same_ids(&other.operation),
+                _ => true,
+            },
             // Merge changes the schema, but preserves row ids, so the only operations
             // it's compatible with is CreateIndex and ReserveFragments.
             Operation::Merge { .. } => !matches!(
@@ -310,6 +355,21 @@ impl Transaction {
         }
     }
 
+    /// Returns true if a conflict with `other` can be resolved under snapshot
+    /// isolation, by rebasing this transaction onto `other`.
+    ///
+    /// Concurrent `Delete` and `Update` transactions only change the deletion
+    /// vectors of the fragments they share, so they can be merged row by row.
+    pub fn rebasable_onto(&self, other: &Self) -> bool {
+        matches!(
+            self.operation,
+            Operation::Delete { .. } | Operation::Update { .. }
+        ) && matches!(
+            other.operation,
+            Operation::Delete { .. } | Operation::Update { .. }
+        )
+    }
+
     fn fragments_with_ids<'a, T>(
         new_fragments: T,
         fragment_id: &'a mut u64,
@@ -494,9 +554,8 @@ impl Transaction {
                     .collect::<HashSet<_>>();
                 for fragment in final_fragments.iter_mut() {
                     fragment.files.retain(|file| {
-                        file.fields
-                            .iter()
-                            .any(|field_id| remaining_field_ids.contains(field_id))
+                        file.live_fields()
+                            .any(|field_id| remaining_field_ids.contains(&field_id))
                     });
                 }
 
@@ -504,6 +563,23 @@ impl Transaction {
                 // remove those indices as well.
                 Self::retain_relevant_indices(&mut final_indices, &schema)
             }
+            Operation::Repair {
+                removed_fragment_ids,
+                restored_fragments,
+                ..
+            } => {
+                final_fragments.extend(maybe_existing_fragments?.iter().filter_map(|f| {
+                    if removed_fragment_ids.contains(&f.id) {
+                        return None;
+                    }
+                    if let Some(restored) = restored_fragments.iter().find(|rf| rf.id == f.id) {
+                        Some(restored.clone())
+                    } else {
+                        Some(f.clone())
+                    }
+                }));
+                Self::handle_repair_indices(&mut final_indices, removed_fragment_ids);
+            }
             Operation::Restore { .. } => {
                 unreachable!()
             }
@@ -619,10 +695,25 @@ impl Transaction {
                 groups,
             )?);
             index.uuid = rewritten_index.new_id;
+            // The remapped index has new files, whose checksums we don't know.
+            index.file_checksums.clear();
         }
         Ok(())
     }
 
+    /// Remove the dropped fragments from the fragment bitmaps of the indices.
+    ///
+    /// Restored fragments hold identical data, so they remain indexed.
+    fn handle_repair_indices(indices: &mut [Index], removed_fragment_ids: &[u64]) {
+        for index in indices.iter_mut() {
+            if let Some(fragment_bitmap) = index.fragment_bitmap.as_mut() {
+                for fragment_id in removed_fragment_ids {
+                    fragment_bitmap.remove(*fragment_id as u32);
+                }
+            }
+        }
+    }
+
     fn handle_rewrite_fragments(
         final_fragments: &mut Vec<Fragment>,
         groups: &[RewriteGroup],
@@ -630,6 +721,17 @@ impl Transaction {
         version: u64,
     ) -> Result<()> {
         for group in groups {
+            if group.in_place
+                && !matches!(
+                    (group.old_fragments.as_slice(), group.new_fragments.as_slice()),
+                    ([old], [new]) if old.id == new.id
+                )
+            {
+                return Err(Error::invalid_input(
+                    "an in-place rewrite must replace a single fragment with the same id",
+                    location!(),
+                ));
+            }
             // If the old fragments are contiguous, find the range
             let replace_range = {
                 let start = final_fragments.iter().enumerate().find(|(_, f)| f.id == group.old_fragments[0].id)
@@ -649,7 +751,12 @@ impl Transaction {
                 }
             };
 
-            let new_fragments = Self::fragments_with_ids(group.new_fragments.clone(), fragment_id);
+            // A fragment rewritten in place keeps its id, even if it is zero
+            let new_fragments = if group.in_place {
+                group.new_fragments.clone()
+            } else {
+                Self::fragments_with_ids(group.new_fragments.clone(), fragment_id).collect()
+            };
             if let Some(replace_range) = replace_range {
                 // Efficiently path using slice
                 final_fragments.splice(replace_range, new_fragments);
@@ -712,6 +819,7 @@ impl TryFrom<&pb::Transaction> for Transaction {
                     vec![RewriteGroup {
                         old_fragments: old_fragments.iter().map(Fragment::from).collect(),
                         new_fragments: new_fragments.iter().map(Fragment::from).collect(),
+                        in_place: false,
                     }]
                 };
                 let rewritten_indices = rewritten_indices
@@ -757,6 +865,15 @@ impl TryFrom<&pb::Transaction> for Transaction {
                 updated_fragments: updated_fragments.iter().map(Fragment::from).collect(),
                 new_fragments: new_fragments.iter().map(Fragment::from).collect(),
             },
+            Some(pb::transaction::Operation::Repair(pb::transaction::Repair {
+                removed_fragment_ids,
+                restored_fragments,
+                predicate,
+            })) => Operation::Repair {
+                removed_fragment_ids: removed_fragment_ids.clone(),
+                restored_fragments: restored_fragments.iter().map(Fragment::from).collect(),
+                predicate: predicate.clone(),
+            },
             Some(pb::transaction::Operation::Project(pb::transaction::Project { schema })) => {
                 Operation::Project {
                     schema: Schema::from(&Fields(schema.clone())),
@@ -814,6 +931,7 @@ impl TryFrom<&pb::transaction::rewrite::RewriteGroup> for RewriteGroup {
         Ok(Self {
             old_fragments: message.old_fragments.iter().map(Fragment::from).collect(),
             new_fragments: message.new_fragments.iter().map(Fragment::from).collect(),
+            in_place: message.in_place,
         })
     }
 }
@@ -898,6 +1016,18 @@ impl From<&Transaction> for pb::Transaction {
                     schema: Fields::from(schema).0,
                 })
             }
+            Operation::Repair {
+                removed_fragment_ids,
+                restored_fragments,
+                predicate,
+            } => pb::transaction::Operation::Repair(pb::transaction::Repair {
+                removed_fragment_ids: removed_fragment_ids.clone(),
+                restored_fragments: restored_fragments
+                    .iter()
+                    .map(pb::DataFragment::from)
+                    .collect(),
+                predicate: predicate.clone(),
+            }),
         };
 
         Self {
@@ -931,6 +1061,7 @@ impl From<&RewriteGroup> for pb::transaction::rewrite::RewriteGroup {
                 .iter()
                 .map(pb::DataFragment::from)
                 .collect(),
+            in_place: value.in_place,
         }
     }
 }
@@ -1018,6 +1149,7 @@ mod tests {
             fields: vec![0],
             dataset_version: 1,
             fragment_bitmap: None,
+            file_checksums: Default::default(),
         };
         let fragment0 = Fragment::new(0);
         let fragment1 = Fragment::new(1);
@@ -1048,6 +1180,7 @@ mod tests {
                 groups: vec![RewriteGroup {
                     old_fragments: vec![fragment0.clone()],
                     new_fragments: vec![fragment1.clone()],
+                    in_place: false,
                 }],
                 rewritten_indices: vec![],
             },
@@ -1057,6 +1190,11 @@ mod tests {
                 updated_fragments: vec![fragment0.clone()],
                 new_fragments: vec![fragment2.clone()],
             },
+            Operation::Repair {
+                removed_fragment_ids: vec![2],
+                restored_fragments: vec![],
+                predicate: "fragment_id IN (2)".to_string(),
+            },
         ];
         let other_transactions = other_operations
             .iter()
@@ -1070,7 +1208,7 @@ mod tests {
                 Operation::Append {
                     fragments: vec![fragment0.clone()],
                 },
-                [false, false, false, true, true, false, false, false],
+                [false, false, false, true, true, false, false, false, false],
             ),
             (
                 Operation::Delete {
@@ -1079,7 +1217,7 @@ mod tests {
                     deleted_fragment_ids: vec![],
                     predicate: "x > 2".to_string(),
                 },
-                [true, false, false, true, true, false, false, true],
+                [true, false, false, true, true, false, false, true, false],
             ),
             (
                 Operation::Delete {
@@ -1088,7 +1226,7 @@ mod tests {
                     deleted_fragment_ids: vec![],
                     predicate: "x > 2".to_string(),
                 },
-                [true, false, true, true, true, true, false, true],
+                [true, false, true, true, true, true, false, true, true],
             ),
             (
                 Operation::Overwrite {
@@ -1097,7 +1235,9 @@ mod tests {
                 },
                 // No conflicts: overwrite can always happen since it doesn't
                 // depend on previous state of the table.
-                [false, false, false, false, false, false, false, false],
+                [
+                    false, false, false, false, false, false, false, false, false,
+                ],
             ),
             (
                 Operation::CreateIndex {
@@ -1105,7 +1245,7 @@ mod tests {
                     removed_indices: vec![index0.clone()],
                 },
                 // Will only conflict with operations that modify row ids.
-                [false, false, false, false, true, true, false, false],
+                [false, false, false, false, true, true, false, false, true],
             ),
             (
                 // Rewrite that affects different fragments
@@ -1113,10 +1253,11 @@ mod tests {
                     groups: vec![RewriteGroup {
                         old_fragments: vec![fragment1.clone()],
                         new_fragments: vec![fragment0.clone()],
+                        in_place: false,
                     }],
                     rewritten_indices: Vec::new(),
                 },
-                [false, true, false, true, true, false, false, true],
+                [false, true, false, true, true, false, false, true, false],
             ),
             (
                 // Rewrite that affects the same fragments
@@ -1124,10 +1265,11 @@ mod tests {
                     groups: vec![RewriteGroup {
                         old_fragments: vec![fragment0.clone(), fragment2.clone()],
                         new_fragments: vec![fragment0.clone()],
+                        in_place: false,
                     }],
                     rewritten_indices: Vec::new(),
                 },
-                [false, true, true, true, true, true, false, true],
+                [false, true, true, true, true, true, false, true, true],
             ),
             (
                 Operation::Merge {
@@ -1135,12 +1277,12 @@ mod tests {
                     schema: Schema::default(),
                 },
                 // Merge conflicts with everything except CreateIndex and ReserveFragments.
-                [true, false, true, true, true, true, false, true],
+                [true, false, true, true, true, true, false, true, true],
             ),
             (
                 Operation::ReserveFragments { num_fragments: 2 },
                 // ReserveFragments only conflicts with Overwrite and Restore.
-                [false, false, false, false, true, false, false, false],
+                [false, false, false, false, true, false, false, false, false],
             ),
             (
                 Operation::Update {
@@ -1149,7 +1291,15 @@ mod tests {
                     removed_fragment_ids: vec![],
                     new_fragments: vec![fragment2.clone()],
                 },
-                [true, false, true, true, true, true, false, true],
+                [true, false, true, true, true, true, false, true, false],
+            ),
+            (
+                Operation::Repair {
+                    removed_fragment_ids: vec![1],
+                    restored_fragments: vec![],
+                    predicate: "fragment_id IN (1)".to_string(),
+                },
+                [false, true, false, true, true, false, false, true, false],
             ),
         ];
 
@@ -1184,6 +1334,7 @@ mod tests {
                 old_fragments: vec![Fragment::new(1), Fragment::new(2)],
                 // These two fragments were previously reserved
                 new_fragments: vec![Fragment::new(15), Fragment::new(16)],
+                in_place: false,
             },
             // These are not contiguous, so they will be inserted at the end.
             RewriteGroup {
@@ -1191,6 +1342,13 @@ mod tests {
                 // We pretend this id was not reserved.  Does not happen in practice today
                 // but we want to leave the door open.
                 new_fragments: vec![Fragment::new(0)],
+                in_place: false,
+            },
+            // Rewritten in place, so it keeps its id even though it is zero.
+            RewriteGroup {
+                old_fragments: vec![Fragment::new(0)],
+                new_fragments: vec![Fragment::new(0)],
+                in_place: true,
             },
         ];
 
@@ -1220,5 +1378,36 @@ mod tests {
         ];
 
         assert_eq!(final_fragments, expected_fragments);
+
+        // A group that is not marked in place gets new ids, even for a single fragment
+        let mut final_fragments = existing_fragments.clone();
+        let rewrite_groups = vec![RewriteGroup {
+            old_fragments: vec![Fragment::new(0)],
+            new_fragments: vec![Fragment::new(0)],
+            in_place: false,
+        }];
+        Transaction::handle_rewrite_fragments(
+            &mut final_fragments,
+            &rewrite_groups,
+            &mut fragment_id,
+            version,
+        )
+        .unwrap();
+        assert_eq!(fragment_id, 22);
+        assert_eq!(final_fragments[0], Fragment::new(21));
+
+        // An in-place group must replace a single fragment with the same id
+        let rewrite_groups = vec![RewriteGroup {
+            old_fragments: vec![Fragment::new(1)],
+            new_fragments: vec![Fragment::new(2)],
+            in_place: true,
+        }];
+        assert!(Transaction::handle_rewrite_fragments(
+            &mut existing_fragments.clone(),
+            &rewrite_groups,
+            &mut fragment_id,
+            version,
+        )
+        .is_err());
     }
 }
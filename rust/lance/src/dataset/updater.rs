@@ -111,12 +111,16 @@ impl Updater {
     /// Internal use only.
     async fn new_writer(&mut self, schema: Schema) -> Result<FileWriter<ManifestDescribing>> {
         let file_name = format!("{}.lance", Uuid::new_v4());
+        let object_store = self.fragment.dataset().object_store.clone();
         self.fragment.metadata.add_file_legacy(&file_name, &schema);
+        if let Some(data_file) = self.fragment.metadata.files.last_mut() {
+            data_file.encryption_key_id = object_store.encryption_key_id();
+        }
 
         let full_path = self.fragment.dataset().data_dir().child(file_name.as_str());
 
         FileWriter::try_new(
-            self.fragment.dataset().object_store.as_ref(),
+            object_store.as_ref(),
             &full_path,
             schema,
             &Default::default(),
@@ -124,6 +128,15 @@ impl Updater {
         .await
     }
 
+    /// The offsets, within the fragment, of the rows of the last batch returned
+    /// by [`Updater::next`]. Deleted rows are not part of the batch.
+    pub(crate) fn last_row_offsets(&self) -> Vec<u32> {
+        let row_id_stride = self.reader.legacy_num_rows_in_batch(self.batch_id - 1) as u32;
+        (self.start_row_id..(self.start_row_id + row_id_stride))
+            .filter(|row_id| !self.deletion_vector.contains(*row_id))
+            .collect()
+    }
+
     /// Update one batch.
     pub async fn update(&mut self, batch: RecordBatch) -> Result<()> {
         let Some(last) = self.last_input.as_ref() else {
@@ -198,6 +211,9 @@ impl Updater {
     pub async fn finish(&mut self) -> Result<Fragment> {
         if let Some(writer) = self.writer.as_mut() {
             writer.finish().await?;
+            if let Some(data_file) = self.fragment.metadata.files.last_mut() {
+                data_file.checksum = Some(writer.checksum());
+            }
         }
 
         Ok(self.fragment.metadata().clone())
@@ -0,0 +1,353 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Integrity verification of the files referenced by a dataset.
+//!
+//! [`Dataset::validate`] only checks structural invariants of the manifest.
+//! [`Dataset::verify`] goes further and looks at the files themselves:
+//!
+//! * A shallow verification checks that every data, deletion and index file
+//!   referenced by the manifest exists.
+//! * A deep verification streams every file and compares its CRC32C with the
+//!   checksum recorded in the manifest.  Damaged v2 data files are opened and
+//!   their per-buffer checksums are checked to pinpoint the damaged page.
+//!
+//! Files written before checksums were recorded are only checked for
+//! existence.
+
+use futures::{stream, StreamExt, TryStreamExt};
+use lance_file::v2;
+use lance_index::DatasetIndexExt;
+use lance_io::scheduler::StoreScheduler;
+use lance_table::format::{DataFile, Fragment, Index};
+use lance_table::io::deletion::deletion_file_path;
+use object_store::path::Path;
+use uuid::Uuid;
+
+use super::Dataset;
+use crate::Result;
+
+/// A file which failed verification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DamagedFile {
+    /// Path of the file, relative to the object store root.
+    pub path: String,
+    /// Why the file is considered damaged.
+    pub reason: String,
+}
+
+/// A fragment with at least one damaged data or deletion file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DamagedFragment {
+    pub fragment_id: u64,
+    pub files: Vec<DamagedFile>,
+}
+
+/// An index with at least one damaged file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DamagedIndex {
+    pub name: String,
+    pub uuid: Uuid,
+    pub files: Vec<DamagedFile>,
+}
+
+/// The result of [`Dataset::verify`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerificationReport {
+    /// Number of files that were checked.
+    pub files_checked: usize,
+    pub damaged_fragments: Vec<DamagedFragment>,
+    pub damaged_indices: Vec<DamagedIndex>,
+}
+
+impl VerificationReport {
+    /// True if no damage was found.
+    pub fn is_ok(&self) -> bool {
+        self.damaged_fragments.is_empty() && self.damaged_indices.is_empty()
+    }
+}
+
+impl Dataset {
+    /// Verify the files referenced by this version of the dataset.
+    ///
+    /// When `deep` is false only the existence of each file is checked.  When
+    /// `deep` is true every file is read and its checksum compared with the one
+    /// recorded in the manifest.
+    ///
+    /// Damage is reported in the returned [`VerificationReport`]; an error is
+    /// only returned if the verification itself could not run.
+    pub async fn verify(&self, deep: bool) -> Result<VerificationReport> {
+        let mut report = VerificationReport::default();
+
+        let manifest = self.loaded_manifest().await?;
+        let fragments = stream::iter(manifest.fragments.iter())
+            .map(|fragment| self.verify_fragment(fragment, deep))
+            .buffered(num_cpus::get())
+            .try_collect::<Vec<_>>()
+            .await?;
+        for (checked, damaged) in fragments {
+            report.files_checked += checked;
+            report.damaged_fragments.extend(damaged);
+        }
+
+        let indices = self.load_indices().await?;
+        for index in indices.iter() {
+            let (checked, damaged) = self.verify_index(index, deep).await?;
+            report.files_checked += checked;
+            report.damaged_indices.extend(damaged);
+        }
+
+        Ok(report)
+    }
+
+    async fn verify_fragment(
+        &self,
+        fragment: &Fragment,
+        deep: bool,
+    ) -> Result<(usize, Option<DamagedFragment>)> {
+        let mut checked = 0;
+        let mut files = Vec::new();
+        for data_file in &fragment.files {
+            let path = self.data_dir().child(data_file.path.as_str());
+            checked += 1;
+            let mut reason = self.verify_file(&path, data_file.checksum, deep).await?;
+            if deep && !data_file.is_legacy_file() {
+                // The page checksums tell us which part of the file is damaged.
+                if let Some(page_reason) = self.verify_pages(&path, data_file).await {
+                    reason = Some(page_reason);
+                }
+            }
+            if let Some(reason) = reason {
+                files.push(DamagedFile {
+                    path: path.to_string(),
+                    reason,
+                });
+            }
+        }
+        if let Some(deletion_file) = &fragment.deletion_file {
+            let path = deletion_file_path(&self.base, fragment.id, deletion_file);
+            checked += 1;
+            if let Some(reason) = self
+                .verify_file(&path, deletion_file.checksum, deep)
+                .await?
+            {
+                files.push(DamagedFile {
+                    path: path.to_string(),
+                    reason,
+                });
+            }
+        }
+        let damaged = (!files.is_empty()).then_some(DamagedFragment {
+            fragment_id: fragment.id,
+            files,
+        });
+        Ok((checked, damaged))
+    }
+
+    async fn verify_index(
+        &self,
+        index: &Index,
+        deep: bool,
+    ) -> Result<(usize, Option<DamagedIndex>)> {
+        let index_dir = self.indices_dir().child(index.uuid.to_string());
+        let mut checked = 0;
+        let mut files = Vec::new();
+        if index.file_checksums.is_empty() {
+            // Nothing recorded, the best we can do is check the index was written.
+            checked += 1;
+            let found = self
+                .object_store
+                .read_dir_all(&index_dir, None)
+                .await?
+                .try_next()
+                .await?;
+            if found.is_none() {
+                files.push(DamagedFile {
+                    path: index_dir.to_string(),
+                    reason: "index directory is missing or empty".to_string(),
+                });
+            }
+        } else {
+            let mut names = index.file_checksums.keys().collect::<Vec<_>>();
+            names.sort();
+            for name in names {
+                let path = Path::parse(format!("{}/{}", index_dir, name))?;
+                checked += 1;
+                let expected = index.file_checksums.get(name).copied();
+                if let Some(reason) = self.verify_file(&path, expected, deep).await? {
+                    files.push(DamagedFile {
+                        path: path.to_string(),
+                        reason,
+                    });
+                }
+            }
+        }
+        let damaged = (!files.is_empty()).then(|| DamagedIndex {
+            name: index.name.clone(),
+            uuid: index.uuid,
+            files,
+        });
+        Ok((checked, damaged))
+    }
+
+    /// Check a single file, returning the reason it is damaged, if any.
+    pub(crate) async fn verify_file(
+        &self,
+        path: &Path,
+        expected: Option<u32>,
+        deep: bool,
+    ) -> Result<Option<String>> {
+        if !self.object_store.exists(path).await? {
+            return Ok(Some("file is missing".to_string()));
+        }
+        let expected = match expected {
+            Some(expected) if deep => expected,
+            _ => return Ok(None),
+        };
+        let actual = self.object_store.checksum(path).await?;
+        Ok((actual != expected).then(|| {
+            format!(
+                "checksum mismatch: expected {:08x}, found {:08x}",
+                expected, actual
+            )
+        }))
+    }
+
+    /// Check the per-buffer checksums of a v2 data file.
+    async fn verify_pages(&self, path: &Path, data_file: &DataFile) -> Option<String> {
+        if self
+            .object_store
+            .check_can_decrypt(path, data_file.encryption_key_id.as_deref())
+            .is_err()
+        {
+            return None;
+        }
+        let store_scheduler = StoreScheduler::new(self.object_store.clone(), 16);
+        let file_scheduler = store_scheduler
+            .open_file_with_key_id(path, data_file.encryption_key_id.as_deref())
+            .await
+            .ok()?;
+        let schema = arrow_schema::Schema::from(&data_file.schema(self.schema()));
+        let reader = match v2::reader::FileReader::try_open(file_scheduler, schema).await {
+            Ok(reader) => reader,
+            Err(e) => return Some(format!("cannot open file: {}", e)),
+        };
+        reader.verify_checksums().await.err().map(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator};
+    use arrow_schema::{DataType, Field, Schema as ArrowSchema};
+    use lance_index::IndexType;
+    use std::sync::Arc;
+
+    use crate::dataset::WriteParams;
+    use crate::index::scalar::ScalarIndexParams;
+
+    async fn corrupt(dataset: &Dataset, path: &Path) {
+        let data = dataset.object_store.read_object(path).await.unwrap();
+        let mut data = data.to_vec();
+        let middle = data.len() / 2;
+        data[middle] ^= 0xFF;
+        dataset
+            .object_store
+            .write_object(path, data.into())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_verify() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..100))],
+        )
+        .unwrap();
+        let write_params = WriteParams {
+            max_rows_per_file: 50,
+            ..Default::default()
+        };
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let mut dataset = Dataset::write(reader, test_uri, Some(write_params))
+            .await
+            .unwrap();
+        dataset.delete("i = 60").await.unwrap();
+        dataset
+            .create_index(
+                &["i"],
+                IndexType::Scalar,
+                None,
+                &ScalarIndexParams::default(),
+                false,
+            )
+            .await
+            .unwrap();
+
+        let fragments = dataset.get_fragments().await.unwrap();
+        assert!(fragments
+            .iter()
+            .all(|f| f.metadata.files.iter().all(|f| f.checksum.is_some())));
+        let index = &dataset.load_indices().await.unwrap()[0];
+        assert!(!index.file_checksums.is_empty());
+
+        let report = dataset.verify(true).await.unwrap();
+        assert!(report.is_ok(), "{:?}", report);
+        // 2 data files, 1 deletion file, and the index files
+        assert_eq!(report.files_checked, 3 + index.file_checksums.len());
+
+        // Corrupt the first data file and the deletion file
+        let data_path = dataset
+            .data_dir()
+            .child(fragments[0].metadata.files[0].path.as_str());
+        corrupt(&dataset, &data_path).await;
+        let deletion_file = fragments[1].metadata.deletion_file.as_ref().unwrap();
+        let deletion_path = deletion_file_path(&dataset.base, 1, deletion_file);
+        corrupt(&dataset, &deletion_path).await;
+
+        // Shallow verification only notices missing files
+        assert!(dataset.verify(false).await.unwrap().is_ok());
+
+        let report = dataset.verify(true).await.unwrap();
+        assert_eq!(report.damaged_fragments.len(), 2);
+        assert_eq!(report.damaged_fragments[0].fragment_id, 0);
+        assert_eq!(
+            report.damaged_fragments[0].files[0].path,
+            data_path.to_string()
+        );
+        assert!(report.damaged_fragments[0].files[0]
+            .reason
+            .contains("checksum mismatch"));
+        assert_eq!(report.damaged_fragments[1].fragment_id, 1);
+        assert_eq!(
+            report.damaged_fragments[1].files[0].path,
+            deletion_path.to_string()
+        );
+        assert!(report.damaged_indices.is_empty());
+
+        // Remove an index file
+        let index_file = index.file_checksums.keys().next().unwrap();
+        let index_path = Path::parse(format!(
+            "{}/{}/{}",
+            dataset.indices_dir(),
+            index.uuid,
+            index_file
+        ))
+        .unwrap();
+        dataset.object_store.delete(&index_path).await.unwrap();
+        let report = dataset.verify(false).await.unwrap();
+        assert_eq!(report.damaged_indices.len(), 1);
+        assert_eq!(report.damaged_indices[0].uuid, index.uuid);
+        assert_eq!(report.damaged_indices[0].files[0].reason, "file is missing");
+    }
+}
@@ -0,0 +1,699 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! An index of the versions of a dataset.
+//!
+//! Listing versions by scanning `_versions/` and reading every manifest gets
+//! slow once a dataset has many versions.  Instead each commit records a small
+//! summary of the new version (timestamp, operation, tag and row count) in the
+//! version index:
+//!
+//! * The newest page of the index holds up to [DEFAULT_PAGE_SIZE] summaries
+//!   and is referenced from the manifest of the version.  Pages are immutable,
+//!   so each commit writes a new copy of the newest page with its own summary
+//!   added.
+//! * Once a page is full the next commit starts a new page, which references
+//!   all of the full pages along with their version and time ranges.  Readers
+//!   only load the pages that can match their filter.
+//! * `cleanup_old_versions` records the versions it removes in small files
+//!   next to the pages, and those versions are skipped when listing.  Each
+//!   cleanup writes a new file, so concurrent cleanups never overwrite each
+//!   other's records.
+//!
+//! Versions written before a dataset had an index are still found by listing
+//! the manifests.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use chrono::prelude::*;
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use lance_io::object_store::ObjectStore;
+use lance_table::format::{pb, Manifest};
+use lance_table::io::commit::CommitHandler;
+use lance_table::io::manifest::read_manifest_root;
+use object_store::path::Path;
+use prost::Message;
+use serde::{Deserialize, Serialize};
+
+use super::Version;
+use crate::Result;
+
+/// Directory, relative to the dataset root, that holds the version index
+pub const VERSION_INDEX_DIR: &str = "_version_index";
+/// The number of versions in a full page
+pub const DEFAULT_PAGE_SIZE: usize = 1000;
+const PAGE_EXTENSION: &str = "binpb";
+const REMOVED_VERSIONS_DIR: &str = "removed_versions";
+const REMOVED_VERSIONS_EXTENSION: &str = "json";
+
+/// Filter for [Dataset::list_versions](crate::Dataset::list_versions)
+///
+/// All bounds are optional.  Versions must match all of the given bounds.
+#[derive(Debug, Clone, Default)]
+pub struct VersionFilter {
+    /// Only include versions greater than or equal to this version
+    pub min_version: Option<u64>,
+    /// Only include versions less than or equal to this version
+    pub max_version: Option<u64>,
+    /// Only include versions created at or after this time
+    pub after: Option<DateTime<Utc>>,
+    /// Only include versions created before this time
+    pub before: Option<DateTime<Utc>>,
+}
+
+fn to_nanos(timestamp: &DateTime<Utc>) -> u64 {
+    timestamp.timestamp_nanos_opt().unwrap_or_default().max(0) as u64
+}
+
+fn from_nanos(nanos: u64) -> DateTime<Utc> {
+    let seconds = (nanos / 1_000_000_000) as i64;
+    let nanos = (nanos % 1_000_000_000) as u32;
+    DateTime::from_timestamp(seconds, nanos).unwrap_or_default()
+}
+
+impl VersionFilter {
+    fn matches(&self, version: u64, timestamp_nanos: u64) -> bool {
+        self.min_version.map(|min| version >= min).unwrap_or(true)
+            && self.max_version.map(|max| version <= max).unwrap_or(true)
+            && self
+                .after
+                .map(|after| timestamp_nanos >= to_nanos(&after))
+                .unwrap_or(true)
+            && self
+                .before
+                .map(|before| timestamp_nanos < to_nanos(&before))
+                .unwrap_or(true)
+    }
+
+    fn may_match_page(&self, page: &pb::VersionIndexPageRef) -> bool {
+        self.min_version
+            .map(|min| page.max_version >= min)
+            .unwrap_or(true)
+            && self
+                .max_version
+                .map(|max| page.min_version <= max)
+                .unwrap_or(true)
+            && self
+                .after
+                .map(|after| page.max_timestamp_nanos >= to_nanos(&after))
+                .unwrap_or(true)
+            && self
+                .before
+                .map(|before| page.min_timestamp_nanos < to_nanos(&before))
+                .unwrap_or(true)
+    }
+}
+
+impl From<&pb::VersionSummary> for Version {
+    fn from(summary: &pb::VersionSummary) -> Self {
+        Self {
+            version: summary.version,
+            timestamp: from_nanos(summary.timestamp_nanos),
+            metadata: BTreeMap::default(),
+            operation: Some(summary.operation.clone()).filter(|op| !op.is_empty()),
+            tag: Some(summary.tag.clone()).filter(|tag| !tag.is_empty()),
+            num_rows: summary.num_rows,
+        }
+    }
+}
+
+/// What a commit records in the version index
+pub struct VersionIndexUpdate<'a> {
+    /// The version the commit is based on, if there is one
+    pub previous: Option<&'a Manifest>,
+    /// The name of the committed operation
+    pub operation: &'a str,
+}
+
+fn index_path(base: &Path, relative_path: &str) -> Path {
+    base.parts()
+        .chain(Path::from(relative_path).parts())
+        .collect()
+}
+
+pub async fn read_page(
+    object_store: &ObjectStore,
+    base: &Path,
+    relative_path: &str,
+) -> Result<pb::VersionIndexPage> {
+    let bytes = object_store
+        .inner
+        .get(&index_path(base, relative_path))
+        .await?
+        .bytes()
+        .await?;
+    Ok(pb::VersionIndexPage::decode(bytes)?)
+}
+
+fn page_ref(path: String, versions: &[pb::VersionSummary]) -> pb::VersionIndexPageRef {
+    let timestamps = versions.iter().map(|v| v.timestamp_nanos);
+    pb::VersionIndexPageRef {
+        path,
+        min_version: versions.first().map(|v| v.version).unwrap_or_default(),
+        max_version: versions.last().map(|v| v.version).unwrap_or_default(),
+        min_timestamp_nanos: timestamps.clone().min().unwrap_or_default(),
+        max_timestamp_nanos: timestamps.max().unwrap_or_default(),
+    }
+}
+
+/// Add the version of `manifest` to the version index.
+///
+/// This writes the new newest page and points the manifest at it.  It must be
+/// called once the manifest's version and timestamp are final.
+pub async fn write_version_index(
+    object_store: &ObjectStore,
+    base: &Path,
+    manifest: &mut Manifest,
+    update: &VersionIndexUpdate<'_>,
+    page_size: usize,
+) -> Result<()> {
+    let summary = pb::VersionSummary {
+        version: manifest.version,
+        timestamp_nanos: manifest.timestamp_nanos as u64,
+        operation: update.operation.to_string(),
+        tag: manifest.tag.clone().unwrap_or_default(),
+        num_rows: manifest
+            .fragments
+            .iter()
+            .map(|fragment| fragment.num_rows().map(|rows| rows as u64))
+            .sum(),
+    };
+
+    let previous_path = update
+        .previous
+        .and_then(|previous| previous.version_index_file.clone());
+    let page = match previous_path {
+        Some(previous_path) => {
+            let mut page = read_page(object_store, base, &previous_path).await?;
+            page.versions.retain(|v| v.version < summary.version);
+            if page.versions.len() >= page_size.max(1) {
+                let mut previous_pages = std::mem::take(&mut page.previous_pages);
+                previous_pages.push(page_ref(previous_path, &page.versions));
+                pb::VersionIndexPage {
+                    versions: vec![summary],
+                    previous_pages,
+                    first_version: page.first_version,
+                }
+            } else {
+                page.versions.push(summary);
+                page
+            }
+        }
+        // Either a new dataset or one that was written without an index
+        None => pb::VersionIndexPage {
+            first_version: summary.version,
+            versions: vec![summary],
+            previous_pages: vec![],
+        },
+    };
+
+    let relative_path = format!(
+        "{}/{}.{}",
+        VERSION_INDEX_DIR,
+        uuid::Uuid::new_v4(),
+        PAGE_EXTENSION
+    );
+    object_store
+        .inner
+        .put(
+            &index_path(base, &relative_path),
+            page.encode_to_vec().into(),
+        )
+        .await?;
+    manifest.version_index_file = Some(relative_path);
+    Ok(())
+}
+
+/// Versions removed by cleanup, as sorted, non-overlapping inclusive ranges
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RemovedVersions {
+    ranges: Vec<(u64, u64)>,
+}
+
+impl RemovedVersions {
+    pub fn contains(&self, version: u64) -> bool {
+        let idx = self.ranges.partition_point(|(_, end)| *end < version);
+        self.ranges
+            .get(idx)
+            .map(|(start, _)| *start <= version)
+            .unwrap_or(false)
+    }
+
+    fn insert(&mut self, versions: impl IntoIterator<Item = u64>) {
+        self.insert_ranges(versions.into_iter().map(|v| (v, v)));
+    }
+
+    fn insert_ranges(&mut self, ranges: impl IntoIterator<Item = (u64, u64)>) {
+        let mut all = self
+            .ranges
+            .iter()
+            .copied()
+            .chain(ranges)
+            .collect::<Vec<_>>();
+        all.sort_unstable();
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(all.len());
+        for (start, end) in all {
+            match merged.last_mut() {
+                Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        self.ranges = merged;
+    }
+}
+
+fn removed_versions_dir(base: &Path) -> Path {
+    base.child(VERSION_INDEX_DIR).child(REMOVED_VERSIONS_DIR)
+}
+
+/// Read all files of removed versions, returning their paths and the union
+/// of the versions they record.
+async fn read_removed_version_files(
+    object_store: &ObjectStore,
+    base: &Path,
+) -> Result<(Vec<Path>, RemovedVersions)> {
+    let dir = removed_versions_dir(base);
+    let files = object_store
+        .inner
+        .list(Some(&dir))
+        .try_collect::<Vec<_>>()
+        .await?;
+    let mut paths = Vec::with_capacity(files.len());
+    let mut removed = RemovedVersions::default();
+    for file in files {
+        if file.location.extension() != Some(REMOVED_VERSIONS_EXTENSION) {
+            continue;
+        }
+        let bytes = match object_store.inner.get(&file.location).await {
+            Ok(result) => result.bytes().await?,
+            // Already merged into a newer file by a concurrent cleanup
+            Err(object_store::Error::NotFound { .. }) => continue,
+            Err(err) => return Err(err.into()),
+        };
+        let file_removed: RemovedVersions = serde_json::from_slice(&bytes)?;
+        removed.insert_ranges(file_removed.ranges);
+        paths.push(file.location);
+    }
+    Ok((paths, removed))
+}
+
+pub async fn read_removed_versions(
+    object_store: &ObjectStore,
+    base: &Path,
+) -> Result<RemovedVersions> {
+    Ok(read_removed_version_files(object_store, base).await?.1)
+}
+
+/// Record that the given versions are about to be removed.
+///
+/// This should be done before the manifests are deleted, so that the index
+/// never lists a version that is gone.
+///
+/// The versions are written to a new file, along with the versions of the
+/// files that already exist.  Those files are deleted afterwards, so every
+/// removed version stays recorded in at least one file even if cleanups run
+/// concurrently.
+pub async fn record_removed_versions(
+    object_store: &ObjectStore,
+    base: &Path,
+    versions: impl IntoIterator<Item = u64>,
+) -> Result<()> {
+    let (merged_paths, mut removed) = read_removed_version_files(object_store, base).await?;
+    removed.insert(versions);
+    let bytes = serde_json::to_vec(&removed)?;
+    let path = removed_versions_dir(base).child(format!(
+        "{}.{}",
+        uuid::Uuid::new_v4(),
+        REMOVED_VERSIONS_EXTENSION
+    ));
+    object_store.inner.put(&path, bytes.into()).await?;
+    for merged_path in merged_paths {
+        match object_store.inner.delete(&merged_path).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Find versions by listing and reading all manifests, for datasets (or the
+/// part of their history) without a version index.
+async fn scan_manifests(
+    object_store: &ObjectStore,
+    commit_handler: &dyn CommitHandler,
+    base: &Path,
+    filter: &VersionFilter,
+    before_version: Option<u64>,
+) -> Result<Vec<Version>> {
+    let mut versions: Vec<Version> = commit_handler
+        .list_manifests(base, &object_store.inner)
+        .await?
+        .try_filter_map(|path| async move {
+            // Only the root manifest is needed, not the fragment lists
+            let manifest = read_manifest_root(object_store, &path).await?;
+            let keep = filter.matches(manifest.version, manifest.timestamp_nanos as u64)
+                && before_version
+                    .map(|before| manifest.version < before)
+                    .unwrap_or(true);
+            Ok(keep.then(|| Version::from(&manifest)))
+        })
+        .try_collect()
+        .await?;
+    versions.sort_by_key(|v| v.version);
+    Ok(versions)
+}
+
+/// The pages of the index for the given filter, oldest first
+fn matching_pages(
+    page: pb::VersionIndexPage,
+    filter: &VersionFilter,
+) -> (Vec<pb::VersionIndexPageRef>, Vec<pb::VersionSummary>) {
+    let previous_pages = page
+        .previous_pages
+        .into_iter()
+        .filter(|page| filter.may_match_page(page))
+        .collect();
+    (previous_pages, page.versions)
+}
+
+/// List the versions of a dataset, oldest first, reading pages as needed.
+pub async fn list_versions<'a>(
+    object_store: &'a ObjectStore,
+    commit_handler: &dyn CommitHandler,
+    base: &'a Path,
+    filter: VersionFilter,
+) -> Result<BoxStream<'a, Result<Version>>> {
+    let latest_path = commit_handler
+        .resolve_latest_version(base, &object_store.inner)
+        .await?;
+    let latest = read_manifest_root(object_store, &latest_path).await?;
+    let Some(page_path) = latest.version_index_file.as_ref() else {
+        let versions = scan_manifests(object_store, commit_handler, base, &filter, None).await?;
+        return Ok(stream::iter(versions.into_iter().map(Ok)).boxed());
+    };
+
+    let page = read_page(object_store, base, page_path).await?;
+    let removed = Arc::new(read_removed_versions(object_store, base).await?);
+    let unindexed =
+        if page.first_version > 1 && filter.min_version.unwrap_or(0) < page.first_version {
+            scan_manifests(
+                object_store,
+                commit_handler,
+                base,
+                &filter,
+                Some(page.first_version),
+            )
+            .await?
+        } else {
+            vec![]
+        };
+
+    let (previous_pages, newest) = matching_pages(page, &filter);
+    let previous = stream::iter(previous_pages)
+        .then(move |page_ref| async move {
+            read_page(object_store, base, &page_ref.path)
+                .await
+                .map(|page| stream::iter(page.versions.into_iter().map(Ok)))
+        })
+        .try_flatten();
+    let filter = Arc::new(filter);
+    let indexed = previous
+        .chain(stream::iter(newest.into_iter().map(Ok)))
+        .try_filter_map(move |summary| {
+            let keep = filter.matches(summary.version, summary.timestamp_nanos)
+                && !removed.contains(summary.version);
+            std::future::ready(Ok(keep.then(|| Version::from(&summary))))
+        });
+    Ok(stream::iter(unindexed.into_iter().map(Ok))
+        .chain(indexed)
+        .boxed())
+}
+
+/// Find the newest version created at or before `timestamp`.
+pub async fn version_at(
+    object_store: &ObjectStore,
+    commit_handler: &dyn CommitHandler,
+    base: &Path,
+    timestamp: DateTime<Utc>,
+) -> Result<Option<Version>> {
+    let filter = VersionFilter {
+        before: Some(timestamp + chrono::Duration::nanoseconds(1)),
+        ..Default::default()
+    };
+    let latest_path = commit_handler
+        .resolve_latest_version(base, &object_store.inner)
+        .await?;
+    let latest = read_manifest_root(object_store, &latest_path).await?;
+    let Some(page_path) = latest.version_index_file.as_ref() else {
+        let versions = scan_manifests(object_store, commit_handler, base, &filter, None).await?;
+        return Ok(versions.into_iter().last());
+    };
+
+    let page = read_page(object_store, base, page_path).await?;
+    let removed = read_removed_versions(object_store, base).await?;
+    let first_version = page.first_version;
+    let find = |versions: &[pb::VersionSummary]| {
+        versions
+            .iter()
+            .rev()
+            .find(|v| filter.matches(v.version, v.timestamp_nanos) && !removed.contains(v.version))
+            .map(Version::from)
+    };
+
+    // Search from the newest page backwards, skipping pages that only have newer versions
+    let (previous_pages, newest) = matching_pages(page, &filter);
+    if let Some(version) = find(&newest) {
+        return Ok(Some(version));
+    }
+    for page_ref in previous_pages.iter().rev() {
+        let page = read_page(object_store, base, &page_ref.path).await?;
+        if let Some(version) = find(&page.versions) {
+            return Ok(Some(version));
+        }
+    }
+    if first_version > 1 {
+        let versions = scan_manifests(
+            object_store,
+            commit_handler,
+            base,
+            &filter,
+            Some(first_version),
+        )
+        .await?;
+        return Ok(versions.into_iter().last());
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator};
+    use arrow_schema::{DataType, Field, Schema as ArrowSchema};
+    use chrono::TimeDelta;
+    use lance_core::datatypes::Schema;
+    use lance_core::utils::testing::MockClock;
+    use tempfile::tempdir;
+
+    use crate::dataset::{WriteMode, WriteParams};
+    use crate::Dataset;
+
+    #[test]
+    fn test_removed_versions() {
+        let mut removed = RemovedVersions::default();
+        assert!(!removed.contains(1));
+        removed.insert([3, 1, 2, 7]);
+        removed.insert([5, 6]);
+        assert_eq!(removed.ranges, vec![(1, 3), (5, 7)]);
+        assert!(removed.contains(1));
+        assert!(removed.contains(3));
+        assert!(!removed.contains(4));
+        assert!(removed.contains(6));
+        assert!(!removed.contains(8));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_removed_versions() {
+        let test_dir = tempdir().unwrap();
+        let (object_store, base) = ObjectStore::from_uri(test_dir.path().to_str().unwrap())
+            .await
+            .unwrap();
+        let num_files = || {
+            let object_store = object_store.clone();
+            let dir = removed_versions_dir(&base);
+            async move {
+                object_store
+                    .inner
+                    .list(Some(&dir))
+                    .try_collect::<Vec<_>>()
+                    .await
+                    .unwrap()
+                    .len()
+            }
+        };
+
+        // Both cleanups read before either writes, so neither sees the other
+        let (first, second) = futures::join!(
+            record_removed_versions(&object_store, &base, [1, 2]),
+            record_removed_versions(&object_store, &base, [5])
+        );
+        first.unwrap();
+        second.unwrap();
+        let removed = read_removed_versions(&object_store, &base).await.unwrap();
+        assert_eq!(removed.ranges, vec![(1, 2), (5, 5)]);
+
+        // The next cleanup merges the existing files into its own
+        record_removed_versions(&object_store, &base, [3])
+            .await
+            .unwrap();
+        assert_eq!(num_files().await, 1);
+        let removed = read_removed_versions(&object_store, &base).await.unwrap();
+        assert_eq!(removed.ranges, vec![(1, 3), (5, 5)]);
+    }
+
+    #[tokio::test]
+    async fn test_page_sealing() {
+        let object_store = ObjectStore::memory();
+        let base = Path::from("ds");
+        let mut previous: Option<Manifest> = None;
+        for version in 1..=7 {
+            let mut manifest = Manifest::new(Schema::default(), Arc::new(vec![]));
+            manifest.version = version;
+            manifest.timestamp_nanos = version as u128 * 1_000_000_000;
+            let update = VersionIndexUpdate {
+                previous: previous.as_ref(),
+                operation: "Append",
+            };
+            write_version_index(&object_store, &base, &mut manifest, &update, 3)
+                .await
+                .unwrap();
+            previous = Some(manifest);
+        }
+
+        let newest = previous.unwrap();
+        let page = read_page(
+            &object_store,
+            &base,
+            newest.version_index_file.as_ref().unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(page.first_version, 1);
+        assert_eq!(page.versions.len(), 1);
+        let sealed = page
+            .previous_pages
+            .iter()
+            .map(|page| (page.min_version, page.max_version))
+            .collect::<Vec<_>>();
+        assert_eq!(sealed, vec![(1, 3), (4, 6)]);
+
+        let filter = VersionFilter {
+            min_version: Some(5),
+            ..Default::default()
+        };
+        let (previous_pages, newest_versions) = matching_pages(page.clone(), &filter);
+        assert_eq!(previous_pages.len(), 1);
+        assert_eq!(newest_versions.len(), 1);
+
+        let filter = VersionFilter {
+            before: Some(from_nanos(3_500_000_000)),
+            ..Default::default()
+        };
+        let (previous_pages, _) = matching_pages(page, &filter);
+        assert_eq!(previous_pages.len(), 1);
+        assert_eq!(previous_pages[0].max_version, 3);
+    }
+
+    #[tokio::test]
+    async fn test_list_versions() {
+        let clock = MockClock::new();
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let make_batches = |range: std::ops::Range<i32>| {
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(Int32Array::from_iter_values(range))],
+            )
+            .unwrap();
+            RecordBatchIterator::new(vec![Ok(batch)], schema.clone())
+        };
+        let day = |days: i64| from_nanos(0) + TimeDelta::try_days(days).unwrap();
+
+        clock.set_system_time(TimeDelta::try_days(1).unwrap());
+        Dataset::write(make_batches(0..10), test_uri, None)
+            .await
+            .unwrap();
+        clock.set_system_time(TimeDelta::try_days(2).unwrap());
+        let write_params = WriteParams {
+            mode: WriteMode::Append,
+            ..Default::default()
+        };
+        let mut dataset = Dataset::write(make_batches(10..20), test_uri, Some(write_params))
+            .await
+            .unwrap();
+        clock.set_system_time(TimeDelta::try_days(3).unwrap());
+        dataset.delete("i < 5").await.unwrap();
+        clock.set_system_time(TimeDelta::try_days(4).unwrap());
+        let mut dataset = dataset.checkout_version(1).await.unwrap();
+        dataset.restore().await.unwrap();
+
+        let versions = dataset.versions().await.unwrap();
+        assert_eq!(
+            versions.iter().map(|v| v.version).collect::<Vec<_>>(),
+            vec![1, 2, 3, 4]
+        );
+        assert_eq!(
+            versions
+                .iter()
+                .map(|v| v.operation.clone().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["Overwrite", "Append", "Delete", "Restore"]
+        );
+        assert_eq!(
+            versions.iter().map(|v| v.num_rows).collect::<Vec<_>>(),
+            vec![Some(10), Some(20), Some(15), Some(10)]
+        );
+        assert_eq!(versions[2].timestamp, day(3));
+
+        let by_version = dataset
+            .list_versions(VersionFilter {
+                min_version: Some(2),
+                max_version: Some(3),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let by_time = dataset
+            .list_versions(VersionFilter {
+                after: Some(day(2)),
+                before: Some(day(4)),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(by_version.len(), 2);
+        assert_eq!(by_version[0].version, 2);
+        assert_eq!(by_time.len(), 2);
+        assert_eq!(by_time[1].version, 3);
+
+        let at = dataset
+            .version_at(day(3) + TimeDelta::try_hours(1).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(at.unwrap().version, 3);
+        assert!(dataset.version_at(day(0)).await.unwrap().is_none());
+    }
+}
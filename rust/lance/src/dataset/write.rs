@@ -24,11 +24,16 @@ use uuid::Uuid;
 
 use crate::Dataset;
 
+use self::constraints::{check_constraints, fill_defaults};
 use super::builder::DatasetBuilder;
 use super::progress::{NoopFragmentWriteProgress, WriteFragmentProgress};
 use super::DATA_DIR;
 
+pub mod constraints;
 pub mod merge_insert;
+pub mod primary_key;
+pub mod schema_evolution;
+pub mod stats;
 pub mod update;
 
 /// The mode to write dataset.
@@ -58,6 +63,47 @@ impl TryFrom<&str> for WriteMode {
     }
 }
 
+/// How an append handles data whose schema differs from the schema of the dataset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SchemaEvolution {
+    /// The schema of the appended data must match the schema of the dataset.
+    #[default]
+    Strict,
+    /// Columns of the appended data that are not in the dataset are added to it.
+    ///
+    /// The new columns must be nullable.  Existing rows read them as null.
+    ///
+    /// An append that changes the schema is committed as a merge rather than
+    /// an append, so it conflicts with every concurrent write except index
+    /// creation and fails with a commit conflict if another writer commits
+    /// first.  Appends that don't change the schema are not affected.
+    AddMissingColumns,
+    /// Like [`SchemaEvolution::AddMissingColumns`], and columns may also have a
+    /// different type in the appended data if one type can be losslessly widened
+    /// into the other (e.g. int32 and int64).
+    ///
+    /// If the appended data has the wider type, the column of the dataset is
+    /// converted to it, the same way [`Dataset::alter_columns`] does.  Otherwise
+    /// the appended data is converted to the type of the dataset.
+    WidenTypes,
+}
+
+impl TryFrom<&str> for SchemaEvolution {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "strict" => Ok(Self::Strict),
+            "add_missing_columns" => Ok(Self::AddMissingColumns),
+            "widen_types" => Ok(Self::WidenTypes),
+            _ => Err(Error::invalid_input(
+                format!("Invalid schema evolution mode: {}", value),
+                location!(),
+            )),
+        }
+    }
+}
+
 /// Dataset Write Parameters
 #[derive(Debug, Clone)]
 pub struct WriteParams {
@@ -101,6 +147,19 @@ pub struct WriteParams {
     /// Unless you are intentionally testing the v2 writer, you should leave this as false
     /// as the v2 writer is still experimental and not fully implemented.
     pub use_experimental_writer: bool,
+
+    /// If set to true then fragment metadata will be stored in fragment list files
+    /// instead of inline in the manifest.
+    ///
+    /// This keeps commits to datasets with many fragments cheap, since only the
+    /// lists that changed are written.  Once enabled, later versions of the dataset
+    /// keep using fragment lists.  Older versions of Lance cannot read such datasets.
+    pub use_fragment_lists: bool,
+
+    /// How appends handle data with a schema different from the dataset.
+    ///
+    /// The default is [`SchemaEvolution::Strict`].
+    pub schema_evolution: SchemaEvolution,
 }
 
 impl Default for WriteParams {
@@ -116,6 +175,8 @@ impl Default for WriteParams {
             progress: Arc::new(NoopFragmentWriteProgress::new()),
             commit_handler: None,
             use_experimental_writer: false,
+            use_fragment_lists: false,
+            schema_evolution: SchemaEvolution::default(),
         }
     }
 }
@@ -161,6 +222,14 @@ pub async fn write_fragments(
     };
 
     let (stream, schema) = reader_to_stream(Box::new(data)).await?;
+    let (stream, schema) = match &dataset {
+        Some(dataset) => fill_defaults(stream, schema, dataset.schema())?,
+        None => (stream, schema),
+    };
+    let stream = check_constraints(
+        stream,
+        dataset.as_ref().map(|d| d.schema()).unwrap_or(&schema),
+    )?;
     write_fragments_internal(
         dataset.as_ref(),
         Arc::new(object_store),
@@ -211,6 +280,7 @@ pub async fn write_fragments_internal(
     // for now as it doesn't hurt anything
     let mut buffered_reader = chunk_stream(data, params.max_rows_per_group);
 
+    let encryption_key_id = object_store.encryption_key_id();
     let writer_generator = WriterGenerator::new(
         object_store,
         base_dir,
@@ -246,7 +316,9 @@ pub async fn write_fragments_internal(
             params.progress.complete(fragments.last().unwrap()).await?;
             let last_fragment = fragments.last_mut().unwrap();
             last_fragment.physical_rows = Some(num_rows as usize);
-            last_fragment.files.push(data_file);
+            last_fragment
+                .files
+                .push(data_file.with_encryption_key_id(encryption_key_id.clone()));
             num_rows_in_current_file = 0;
         }
     }
@@ -256,7 +328,9 @@ pub async fn write_fragments_internal(
         let (num_rows, data_file) = writer.finish().await?;
         let last_fragment = fragments.last_mut().unwrap();
         last_fragment.physical_rows = Some(num_rows as usize);
-        last_fragment.files.push(data_file);
+        last_fragment
+            .files
+            .push(data_file.with_encryption_key_id(encryption_key_id));
     }
 
     Ok(fragments)
@@ -291,10 +365,10 @@ impl<M: ManifestProvider + Send + Sync> GenericWriter for (FileWriter<M>, String
         Ok(self.0.tell().await? as u64)
     }
     async fn finish(&mut self) -> Result<(u32, DataFile)> {
-        Ok((
-            self.0.finish().await? as u32,
-            DataFile::new_legacy(self.1.clone(), self.0.schema()),
-        ))
+        let num_rows = self.0.finish().await? as u32;
+        let data_file = DataFile::new_legacy(self.1.clone(), self.0.schema())
+            .with_checksum(Some(self.0.checksum()));
+        Ok((num_rows, data_file))
     }
 }
 
@@ -331,7 +405,7 @@ impl GenericWriter for v2::writer::FileWriter {
             MINOR_VERSION_NEXT as u32,
         );
         let num_rows = self.finish().await? as u32;
-        Ok((num_rows, data_file))
+        Ok((num_rows, data_file.with_checksum(Some(self.checksum()))))
     }
 }
 
@@ -0,0 +1,530 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Column default values and NOT NULL / CHECK constraints.
+//!
+//! Defaults and CHECK constraints are stored in the metadata of the fields of the schema,
+//! under [`DEFAULT_VALUE_META_KEY`] and [`CHECK_CONSTRAINT_META_KEY`].  NOT NULL is the
+//! nullability of the top-level fields.
+//!
+//! [`DEFAULT_VALUE_META_KEY`]: lance_core::datatypes::DEFAULT_VALUE_META_KEY
+//! [`CHECK_CONSTRAINT_META_KEY`]: lance_core::datatypes::CHECK_CONSTRAINT_META_KEY
+
+use std::sync::Arc;
+
+use arrow_array::cast::AsArray;
+use arrow_array::{Array, RecordBatch};
+use arrow_schema::{Field as ArrowField, Schema as ArrowSchema, SchemaRef};
+use datafusion::error::DataFusionError;
+use datafusion::logical_expr::Expr;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{PhysicalExpr, SendableRecordBatchStream};
+use datafusion::scalar::ScalarValue;
+use futures::StreamExt;
+use lance_core::datatypes::{Field, Schema};
+use lance_core::{Error, Result};
+use snafu::{location, Location};
+
+use crate::io::exec::Planner;
+
+#[derive(Debug)]
+struct CheckConstraint {
+    column: String,
+    sql: String,
+    expr: Arc<dyn PhysicalExpr>,
+}
+
+/// The NOT NULL and CHECK constraints of a schema, planned for data with a given schema
+#[derive(Debug)]
+pub struct Constraints {
+    not_null: Vec<String>,
+    checks: Vec<CheckConstraint>,
+}
+
+impl Constraints {
+    /// Plans the constraints of `schema` for data with `data_schema`
+    ///
+    /// Constraints on columns that are not in the data are not checked, the data does not
+    /// change the values they constrain.  A CHECK constraint that reads some of the columns of
+    /// the data but not all of them cannot be checked, and is an error.
+    pub fn try_new(schema: &Schema, data_schema: SchemaRef) -> Result<Self> {
+        let not_null = schema
+            .fields
+            .iter()
+            .filter(|f| !f.nullable && data_schema.field_with_name(&f.name).is_ok())
+            .map(|f| f.name.clone())
+            .collect();
+
+        let planner = Planner::new(Arc::new(ArrowSchema::from(schema)));
+        let data_planner = Planner::new(data_schema.clone());
+        let mut checks = Vec::new();
+        for field in schema.fields_pre_order() {
+            let Some(sql) = field.check_constraint() else {
+                continue;
+            };
+            let invalid = |err: Error| {
+                Error::invalid_input(
+                    format!(
+                        "Invalid check constraint \"{}\" on column \"{}\": {}",
+                        sql, field.name, err
+                    ),
+                    location!(),
+                )
+            };
+            let expr = planner
+                .parse_filter(sql)
+                .and_then(|expr| planner.optimize_expr(expr))
+                .map_err(invalid)?;
+            let (present, missing): (Vec<_>, Vec<_>) = Planner::column_names_in_expr(&expr)
+                .into_iter()
+                .map(|name| name.split('.').next().unwrap().to_string())
+                .partition(|name| data_schema.field_with_name(name).is_ok());
+            if present.is_empty() {
+                continue;
+            }
+            if let Some(name) = missing.first() {
+                return Err(Error::invalid_input(
+                    format!(
+                        "Check constraint \"{}\" on column \"{}\" cannot be checked without the column \"{}\", which the data does not have",
+                        sql, field.name, name
+                    ),
+                    location!(),
+                ));
+            }
+            // The expression is planned again with the types of the data
+            let expr = data_planner
+                .parse_filter(sql)
+                .and_then(|expr| data_planner.optimize_expr(expr))
+                .and_then(|expr| data_planner.create_physical_expr(&expr))
+                .map_err(invalid)?;
+            checks.push(CheckConstraint {
+                column: field.name.clone(),
+                sql: sql.to_string(),
+                expr,
+            });
+        }
+
+        Ok(Self { not_null, checks })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.not_null.is_empty() && self.checks.is_empty()
+    }
+
+    /// The schema of the checked data, with the NOT NULL columns marked as non-nullable
+    fn output_schema(&self, schema: &ArrowSchema) -> ArrowSchema {
+        let fields = schema
+            .fields()
+            .iter()
+            .map(|f| {
+                f.as_ref()
+                    .clone()
+                    .with_nullable(f.is_nullable() && !self.not_null.contains(f.name()))
+            })
+            .collect::<Vec<_>>();
+        ArrowSchema::new_with_metadata(fields, schema.metadata().clone())
+    }
+
+    /// Checks the rows of `batch`, whose first row is row `first_row` of the written data
+    ///
+    /// Returns the batch with the NOT NULL columns marked as non-nullable.
+    pub fn check(&self, batch: RecordBatch, first_row: usize) -> Result<RecordBatch> {
+        for name in &self.not_null {
+            let Some(column) = batch.column_by_name(name) else {
+                continue;
+            };
+            if column.null_count() > 0 {
+                let row = (0..column.len()).find(|i| column.is_null(*i)).unwrap();
+                return Err(Error::invalid_input(
+                    format!(
+                        "Row {} violates the NOT NULL constraint on column \"{}\"",
+                        first_row + row,
+                        name
+                    ),
+                    location!(),
+                ));
+            }
+        }
+
+        for check in &self.checks {
+            let result = check.expr.evaluate(&batch)?.into_array(batch.num_rows())?;
+            let result = result.as_boolean_opt().ok_or_else(|| {
+                Error::invalid_input(
+                    format!(
+                        "Check constraint \"{}\" on column \"{}\" is not a boolean expression",
+                        check.sql, check.column
+                    ),
+                    location!(),
+                )
+            })?;
+            if let Some(row) = (0..result.len()).find(|i| result.is_valid(*i) && !result.value(*i))
+            {
+                return Err(Error::invalid_input(
+                    format!(
+                        "Row {} violates the check constraint \"{}\" on column \"{}\"",
+                        first_row + row,
+                        check.sql,
+                        check.column
+                    ),
+                    location!(),
+                ));
+            }
+        }
+
+        let schema = batch.schema();
+        if !schema
+            .fields()
+            .iter()
+            .any(|f| f.is_nullable() && self.not_null.contains(f.name()))
+        {
+            return Ok(batch);
+        }
+        Ok(RecordBatch::try_new(
+            Arc::new(self.output_schema(&schema)),
+            batch.columns().to_vec(),
+        )?)
+    }
+}
+
+/// Checks the NOT NULL and CHECK constraints of `schema` on the rows of `data` as they are read
+///
+/// Returns `data` unchanged if it is not constrained.
+pub fn check_constraints(
+    data: SendableRecordBatchStream,
+    schema: &Schema,
+) -> Result<SendableRecordBatchStream> {
+    let constraints = Constraints::try_new(schema, data.schema())?;
+    if constraints.is_empty() {
+        return Ok(data);
+    }
+    let output_schema = Arc::new(constraints.output_schema(&data.schema()));
+    let mut num_rows = 0;
+    let data = data.map(move |batch| {
+        let batch = batch?;
+        let first_row = num_rows;
+        num_rows += batch.num_rows();
+        constraints
+            .check(batch, first_row)
+            .map_err(|err| DataFusionError::External(Box::new(err)))
+    });
+    Ok(Box::pin(RecordBatchStreamAdapter::new(output_schema, data)))
+}
+
+/// Parses the default value of `field`, if it has one
+fn default_value(field: &Field) -> Result<Option<ScalarValue>> {
+    let Some(sql) = field.default_value() else {
+        return Ok(None);
+    };
+    let planner = Planner::new(Arc::new(ArrowSchema::empty()));
+    let expr = planner.optimize_expr(planner.parse_expr(sql)?)?;
+    match expr {
+        Expr::Literal(value) => Ok(Some(value.cast_to(&field.data_type())?)),
+        _ => Err(Error::invalid_input(
+            format!(
+                "Default value \"{}\" of column \"{}\" is not a constant",
+                sql, field.name
+            ),
+            location!(),
+        )),
+    }
+}
+
+/// Adds the columns of `dataset` that `data` omits and that have a default value
+///
+/// The columns of the dataset are put in the order of the dataset, followed by the
+/// other columns of `data`.  Returns `data` and `schema` unchanged if no column is
+/// omitted.
+pub fn fill_defaults(
+    data: SendableRecordBatchStream,
+    schema: Schema,
+    dataset: &Schema,
+) -> Result<(SendableRecordBatchStream, Schema)> {
+    let source = data.schema();
+    let mut defaults = Vec::new();
+    for field in &dataset.fields {
+        if source.field_with_name(&field.name).is_err() {
+            if let Some(value) = default_value(field)? {
+                defaults.push((field.name.clone(), value));
+            }
+        }
+    }
+    if defaults.is_empty() {
+        return Ok((data, schema));
+    }
+
+    enum OutputColumn {
+        Data(usize),
+        Default(ScalarValue),
+    }
+
+    let mut fields = Vec::new();
+    let mut columns = Vec::new();
+    for field in &dataset.fields {
+        if let Ok(idx) = source.index_of(&field.name) {
+            fields.push(source.fields()[idx].clone());
+            columns.push(OutputColumn::Data(idx));
+        } else if let Some((_, value)) = defaults.iter().find(|(name, _)| name == &field.name) {
+            fields.push(Arc::new(ArrowField::new(
+                field.name.clone(),
+                field.data_type(),
+                field.nullable,
+            )));
+            columns.push(OutputColumn::Default(value.clone()));
+        }
+    }
+    for (idx, field) in source.fields().iter().enumerate() {
+        if dataset.field(field.name()).is_none() {
+            fields.push(field.clone());
+            columns.push(OutputColumn::Data(idx));
+        }
+    }
+    let output_schema = Arc::new(ArrowSchema::new_with_metadata(
+        fields,
+        source.metadata().clone(),
+    ));
+
+    let stream_schema = output_schema.clone();
+    let data = data.map(move |batch| {
+        let batch = batch?;
+        let arrays = columns
+            .iter()
+            .map(|column| match column {
+                OutputColumn::Data(idx) => Ok(batch.column(*idx).clone()),
+                OutputColumn::Default(value) => value.to_array_of_size(batch.num_rows()),
+            })
+            .collect::<std::result::Result<Vec<_>, DataFusionError>>()?;
+        Ok(RecordBatch::try_new(stream_schema.clone(), arrays)?)
+    });
+    let data = Box::pin(RecordBatchStreamAdapter::new(output_schema.clone(), data));
+
+    // Keep the fields of `schema`, which may carry dictionaries
+    let mut output_schema = Schema {
+        fields: output_schema
+            .fields()
+            .iter()
+            .map(|field| match schema.field(field.name()) {
+                Some(field) => Ok(field.clone()),
+                None => Field::try_from(field.as_ref()),
+            })
+            .collect::<Result<Vec<_>>>()?,
+        metadata: schema.metadata.clone(),
+    };
+    output_schema.set_field_id(None);
+    Ok((data, output_schema))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use arrow_array::{Int32Array, RecordBatchIterator, StringArray};
+    use arrow_schema::DataType;
+    use lance_core::datatypes::{CHECK_CONSTRAINT_META_KEY, DEFAULT_VALUE_META_KEY};
+    use tempfile::tempdir;
+
+    use crate::dataset::{
+        MergeInsertBuilder, UpdateBuilder, WhenMatched, WhenNotMatched, WriteMode, WriteParams,
+    };
+    use crate::Dataset;
+
+    fn batch(schema: &Arc<ArrowSchema>, ids: Vec<i32>, xs: Vec<Option<i32>>) -> RecordBatch {
+        RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(ids)),
+                Arc::new(Int32Array::from(xs)),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_constraints() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("id", DataType::Int32, false),
+            ArrowField::new("x", DataType::Int32, true).with_metadata(HashMap::from([(
+                CHECK_CONSTRAINT_META_KEY.to_string(),
+                "x >= 0".to_string(),
+            )])),
+            ArrowField::new("tag", DataType::Utf8, true).with_metadata(HashMap::from([(
+                DEFAULT_VALUE_META_KEY.to_string(),
+                "'none'".to_string(),
+            )])),
+        ]));
+        let data = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![0, 1])),
+                Arc::new(Int32Array::from(vec![Some(0), None])),
+                Arc::new(StringArray::from(vec!["a", "b"])),
+            ],
+        )
+        .unwrap();
+        let mut dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(data)], schema.clone()),
+            test_uri,
+            None,
+        )
+        .await
+        .unwrap();
+
+        // Appended data may omit the column with a default value
+        let append_schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("id", DataType::Int32, false),
+            ArrowField::new("x", DataType::Int32, true),
+        ]));
+        let append =
+            |data: RecordBatch| RecordBatchIterator::new(vec![Ok(data)], append_schema.clone());
+        dataset
+            .append(append(batch(&append_schema, vec![2], vec![Some(2)])), None)
+            .await
+            .unwrap();
+        let tags = dataset
+            .scan()
+            .project(&["tag"])
+            .unwrap()
+            .try_into_batch()
+            .await
+            .unwrap();
+        assert_eq!(
+            tags["tag"].as_ref(),
+            &StringArray::from(vec!["a", "b", "none"])
+        );
+
+        // The first violating row is reported
+        let err = dataset
+            .append(
+                append(batch(
+                    &append_schema,
+                    vec![3, 4, 5],
+                    vec![Some(1), Some(-1), Some(-2)],
+                )),
+                None,
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }));
+        assert!(err
+            .to_string()
+            .contains("Row 1 violates the check constraint \"x >= 0\" on column \"x\""));
+
+        // Rows for which the check is null satisfy it
+        Dataset::write(
+            append(batch(&append_schema, vec![3, 4], vec![None, None])),
+            test_uri,
+            Some(WriteParams {
+                mode: WriteMode::Append,
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+        let dataset = Arc::new(Dataset::open(test_uri).await.unwrap());
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 5);
+
+        let err = UpdateBuilder::new(dataset.clone())
+            .update_where("id = 2")
+            .unwrap()
+            .set("x", "-5")
+            .unwrap()
+            .build()
+            .unwrap()
+            .execute()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }));
+
+        let err = UpdateBuilder::new(dataset.clone())
+            .update_where("id = 2")
+            .unwrap()
+            .set("id", "NULL")
+            .unwrap()
+            .build()
+            .unwrap()
+            .execute()
+            .await
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Row 0 violates the NOT NULL constraint on column \"id\""));
+
+        let source = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![0, 20])),
+                Arc::new(Int32Array::from(vec![5, -3])),
+                Arc::new(StringArray::from(vec!["c", "d"])),
+            ],
+        )
+        .unwrap();
+        let err = MergeInsertBuilder::try_new(dataset.clone(), vec!["id".to_string()])
+            .unwrap()
+            .when_matched(WhenMatched::UpdateAll)
+            .try_build()
+            .unwrap()
+            .execute_reader(Box::new(RecordBatchIterator::new(
+                vec![Ok(source)],
+                schema.clone(),
+            )))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }));
+
+        // A partial merge insert cannot update only some of the columns a check reads
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("id", DataType::Int32, false),
+            ArrowField::new("lo", DataType::Int32, true),
+            ArrowField::new("hi", DataType::Int32, true).with_metadata(HashMap::from([(
+                CHECK_CONSTRAINT_META_KEY.to_string(),
+                "hi >= lo".to_string(),
+            )])),
+        ]));
+        let data = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![0])),
+                Arc::new(Int32Array::from(vec![0])),
+                Arc::new(Int32Array::from(vec![1])),
+            ],
+        )
+        .unwrap();
+        let dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(data)], schema.clone()),
+            test_uri,
+            None,
+        )
+        .await
+        .unwrap();
+        let source_schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("id", DataType::Int32, false),
+            ArrowField::new("hi", DataType::Int32, true),
+        ]));
+        let source = batch(&source_schema, vec![0], vec![Some(-1)]);
+        let err = MergeInsertBuilder::try_new(Arc::new(dataset), vec!["id".to_string()])
+            .unwrap()
+            .when_matched(WhenMatched::UpdateAll)
+            .when_not_matched(WhenNotMatched::DoNothing)
+            .try_build()
+            .unwrap()
+            .execute_reader(Box::new(RecordBatchIterator::new(
+                vec![Ok(source)],
+                source_schema,
+            )))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }));
+        assert!(
+            err.to_string()
+                .contains("cannot be checked without the column \"lo\""),
+            "{}",
+            err
+        );
+    }
+}
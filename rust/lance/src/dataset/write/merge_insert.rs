@@ -15,19 +15,39 @@
 //! This match condition is currently limited to an key-match.  This means we consider a row to be a match if the
 //! key columns are identical in both the source and the target.  This means that you will need some kind of
 //! meaningful key column to be able to perform a merge insert.
+//!
+//! If one of the key columns has a scalar index, and the source has at most a configurable number of rows, the
+//! index is used to look up the matching target rows and only those rows are read.  Otherwise the source is
+//! joined with a full scan of the target table.
+//!
+//! If the source table contains the same key more than once it is deduplicated before the merge, as configured
+//! by [`SourceDedupeBehavior`].  By default this is an error.
+//!
+//! The source table may contain only the key columns and a subset of the other columns.  In that
+//! case matched rows are updated in place: the new values of the source columns are written to new
+//! data files of the fragments holding the matched rows, the way `add_columns` does, and the rest of
+//! each row is left untouched.  Such a source cannot insert new rows, cannot update columns covered by an
+//! index, and is not supported on datasets with fragments written with the v2 file format.  The new values
+//! of the matched rows are spilled to a temporary local file until they are written to the fragments.
 
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, HashMap, HashSet},
     sync::{Arc, Mutex},
 };
 
+use arrow::compute::CastOptions;
 use arrow_array::{
     cast::AsArray, types::UInt64Type, BooleanArray, RecordBatch, RecordBatchReader, StructArray,
 };
+use arrow_row::{RowConverter, SortField};
 use arrow_schema::{DataType, Field, Schema};
+use arrow_select::interleave::interleave;
 use datafusion::{
     execution::context::{SessionConfig, SessionContext},
-    logical_expr::{Expr, JoinType},
+    logical_expr::{
+        aggregate_function::AggregateFunction as BuiltInAggregate, expr::AggregateFunction, Expr,
+        JoinType,
+    },
     physical_plan::{
         joins::{HashJoinExec, PartitionMode},
         repartition::RepartitionExec,
@@ -47,21 +67,32 @@ use lance_core::{
     datatypes::SchemaCompareOptions,
     error::{box_error, InvalidInputSnafu},
     utils::futures::Capacity,
-    Error, Result,
+    Error, Result, ROW_ID,
 };
 use lance_datafusion::{
     exec::{execute_plan, LanceExecutionOptions, OneShotExec},
     utils::reader_to_stream,
 };
+use lance_file::{reader::FileReader, writer::FileWriter};
 use lance_index::DatasetIndexExt;
-use lance_table::format::{Fragment, Index};
+use lance_io::object_store::ObjectStore;
+use lance_table::{
+    format::{Fragment, Index, SelfDescribingFileReader},
+    io::manifest::ManifestDescribing,
+};
 use log::info;
+use object_store::path::Path;
 use roaring::RoaringTreemap;
 use snafu::{location, Location, ResultExt};
+use tempfile::TempDir;
 
 use crate::{
     datafusion::dataframe::SessionContextExt,
-    dataset::transaction::{Operation, Transaction},
+    dataset::{
+        fragment::FileFragment,
+        transaction::{Operation, Transaction},
+        write::stats::{send_rows_to_sink, OperationStats, RowSink},
+    },
     index::DatasetIndexInternalExt,
     io::{
         commit::commit_transaction,
@@ -70,6 +101,7 @@ use crate::{
     Dataset,
 };
 
+use super::constraints::{check_constraints, Constraints};
 use super::write_fragments_internal;
 
 // "update if" expressions typically compare fields from the source table to the target table.
@@ -177,6 +209,42 @@ impl WhenMatched {
     }
 }
 
+/// Describes how rows of the source table with the same key are combined into a single row
+///
+/// The merge is only well defined if each key appears at most once in the source table
+#[derive(Debug, Clone, PartialEq)]
+pub enum SourceDedupeBehavior {
+    /// Fail the operation if a key appears more than once
+    Fail,
+    /// Keep the first row seen for each key
+    FirstSeen,
+    /// Keep the row with the largest value in the given column
+    LastBy(String),
+    /// Combine the rows with the same key using a DataFusion aggregate expression per column
+    ///
+    /// For example, `max(col("updated_at"))`.  Columns without an expression keep the first
+    /// value seen.
+    Aggregate(HashMap<String, Expr>),
+}
+
+/// Statistics about a completed merge insert operation
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MergeStats {
+    /// Number of source rows inserted as new rows
+    pub num_inserted_rows: u64,
+    /// Number of target rows updated with a source row
+    pub num_updated_rows: u64,
+    /// Number of target rows deleted because they did not match the source
+    pub num_deleted_rows: u64,
+    /// Number of source rows dropped or combined because they repeated a key
+    pub num_deduplicated_rows: u64,
+    /// Whether the matching target rows were looked up with a scalar index instead of
+    /// joining the source with a full scan of the target table
+    pub used_index: bool,
+    /// The fragments and files changed by the operation
+    pub operation: OperationStats,
+}
+
 /// Describes how rows should be handled when there is no matching row in the target table
 ///
 /// These are new rows which do not match any old data
@@ -199,8 +267,34 @@ struct MergeInsertParams {
     insert_not_matched: bool,
     // Controls whether data that is not matched by the source is deleted or not
     delete_not_matched_by_source: WhenNotMatchedBySource,
+    // If true, a scalar index on a key column may be used to look up the matching rows
+    use_index: bool,
+    // The largest source, relative to the number of target rows, the matching rows are
+    // looked up for with an index
+    max_indexed_lookup_ratio: f64,
+    // The most source rows the matching rows are looked up for with an index
+    max_indexed_lookup_rows: usize,
+    // How to combine source rows with the same key
+    source_dedupe_behavior: SourceDedupeBehavior,
+    // Receives the old version of the updated and deleted target rows
+    old_rows_sink: Option<Arc<dyn RowSink>>,
 }
 
+// The number of source rows combined into each deduplicated row
+const DUPLICATE_COUNT_COLUMN: &str = "_merge_insert_duplicates";
+
+/// The default for [`MergeInsertBuilder::max_indexed_lookup_ratio`]
+///
+/// The indexed lookup reads the matched target rows one by one, once the source is a sizable
+/// part of the target a sequential full scan is cheaper.
+pub const DEFAULT_MAX_INDEXED_LOOKUP_RATIO: f64 = 0.1;
+
+/// The default for [`MergeInsertBuilder::max_indexed_lookup_rows`]
+///
+/// The indexed lookup buffers the source in memory, so for larger sources a full scan is used
+/// instead, regardless of the size of the target.
+pub const DEFAULT_MAX_INDEXED_LOOKUP_ROWS: usize = 32 * 1024;
+
 /// A MergeInsertJob inserts new rows, deletes old rows, and updates existing rows all as
 /// part of a single transaction.
 pub struct MergeInsertJob {
@@ -260,10 +354,23 @@ impl MergeInsertBuilder {
     ///  - rows in the old data that do not match will be left as-is
     ///
     /// Use the methods on this builder to customize that behavior
+    ///
+    /// If `on` is empty the rows are matched on the primary key of the dataset, see
+    /// [`Dataset::set_primary_key`].
     pub fn try_new(dataset: Arc<Dataset>, on: Vec<String>) -> Result<Self> {
+        let on = if on.is_empty() {
+            dataset
+                .schema()
+                .primary_key()
+                .into_iter()
+                .map(String::from)
+                .collect()
+        } else {
+            on
+        };
         if on.is_empty() {
             return Err(Error::invalid_input(
-                "A merge insert operation must specify at least one on key",
+                "A merge insert operation must specify at least one on key, or the dataset must have a primary key",
                 location!(),
             ));
         }
@@ -274,6 +381,11 @@ impl MergeInsertBuilder {
                 when_matched: WhenMatched::DoNothing,
                 insert_not_matched: true,
                 delete_not_matched_by_source: WhenNotMatchedBySource::Keep,
+                use_index: true,
+                max_indexed_lookup_ratio: DEFAULT_MAX_INDEXED_LOOKUP_RATIO,
+                max_indexed_lookup_rows: DEFAULT_MAX_INDEXED_LOOKUP_ROWS,
+                source_dedupe_behavior: SourceDedupeBehavior::Fail,
+                old_rows_sink: None,
             },
         })
     }
@@ -303,6 +415,53 @@ impl MergeInsertBuilder {
         self
     }
 
+    /// Specify whether a scalar index on one of the on keys may be used to find the matching
+    /// rows (the default)
+    ///
+    /// Even when allowed, the index is only used if the source is small compared to the target
+    /// table, see [`Self::max_indexed_lookup_ratio`] and [`Self::max_indexed_lookup_rows`].
+    /// Otherwise the source is joined with a full scan of the target table.
+    pub fn use_index(&mut self, use_index: bool) -> &mut Self {
+        self.params.use_index = use_index;
+        self
+    }
+
+    /// Specify the largest source, as a fraction of the number of rows in the target table,
+    /// the matching rows are looked up for with a scalar index, defaults to
+    /// [`DEFAULT_MAX_INDEXED_LOOKUP_RATIO`]
+    ///
+    /// A larger source is joined with a full scan of the target table.
+    pub fn max_indexed_lookup_ratio(&mut self, ratio: f64) -> &mut Self {
+        self.params.max_indexed_lookup_ratio = ratio;
+        self
+    }
+
+    /// Specify the most source rows the matching rows are looked up for with a scalar index,
+    /// defaults to [`DEFAULT_MAX_INDEXED_LOOKUP_ROWS`]
+    ///
+    /// This caps [`Self::max_indexed_lookup_ratio`] for large target tables.  The source is
+    /// buffered in memory until the limit is reached, a larger source is joined with a full
+    /// scan of the target table.
+    pub fn max_indexed_lookup_rows(&mut self, max_rows: usize) -> &mut Self {
+        self.params.max_indexed_lookup_rows = max_rows;
+        self
+    }
+
+    /// Specify how source rows with the same key are combined
+    ///
+    /// By default the operation fails if a key appears more than once in the source
+    pub fn source_dedupe_behavior(&mut self, behavior: SourceDedupeBehavior) -> &mut Self {
+        self.params.source_dedupe_behavior = behavior;
+        self
+    }
+
+    /// Send the target rows which are deleted, and the old version of the target rows which
+    /// are updated, to `sink` before the operation is committed
+    pub fn old_rows_sink(&mut self, sink: Arc<dyn RowSink>) -> &mut Self {
+        self.params.old_rows_sink = Some(sink);
+        self
+    }
+
     /// Crate a merge insert job
     pub fn try_build(&mut self) -> Result<MergeInsertJob> {
         if !self.params.insert_not_matched
@@ -325,7 +484,7 @@ impl MergeInsertJob {
     pub async fn execute_reader(
         self,
         source: Box<dyn RecordBatchReader + Send>,
-    ) -> Result<Arc<Dataset>> {
+    ) -> Result<(Arc<Dataset>, MergeStats)> {
         let (source, _) = reader_to_stream(source).await?;
         self.execute(source).await
     }
@@ -333,7 +492,7 @@ impl MergeInsertJob {
     fn check_compatible_schema(&self, schema: &Schema) -> Result<()> {
         let lance_schema: lance_core::datatypes::Schema = schema.try_into()?;
         lance_schema.check_compatible(
-            self.dataset.schema(),
+            &self.target_schema(schema)?,
             &SchemaCompareOptions {
                 compare_dictionary: true,
                 ..Default::default()
@@ -341,18 +500,135 @@ impl MergeInsertJob {
         )
     }
 
-    async fn join_key_as_scalar_index(&self) -> Result<Option<Index>> {
-        if self.params.on.len() != 1 {
-            Ok(None)
+    fn is_partial_source(&self, schema: &Schema) -> bool {
+        self.dataset
+            .schema()
+            .fields
+            .iter()
+            .any(|field| schema.field_with_name(&field.name).is_err())
+    }
+
+    // The columns of the target table which are joined with the source table
+    fn target_schema(&self, schema: &Schema) -> Result<lance_core::datatypes::Schema> {
+        if self.is_partial_source(schema) {
+            let columns = schema
+                .fields()
+                .iter()
+                .map(|f| f.name().as_str())
+                .collect::<Vec<_>>();
+            self.dataset.schema().project(&columns)
         } else {
-            let col = &self.params.on[0];
-            self.dataset.load_scalar_index_for_column(col).await
+            Ok(self.dataset.schema().clone())
+        }
+    }
+
+    // If the source only has a subset of the columns, returns the (non-key) columns to update
+    async fn partial_update_columns(&self, schema: &Schema) -> Result<Option<Vec<String>>> {
+        if !self.is_partial_source(schema) {
+            return Ok(None);
+        }
+        for key in &self.params.on {
+            if schema.field_with_name(key).is_err() {
+                return Err(Error::invalid_input(
+                    format!("The source is missing the on key '{}'", key),
+                    location!(),
+                ));
+            }
+        }
+        self.check_compatible_schema(schema)?;
+        if self.params.insert_not_matched {
+            return Err(Error::invalid_input(
+                "A source with only a subset of the columns can only update matched rows, use WhenNotMatched::DoNothing",
+                location!(),
+            ));
+        }
+        let columns = schema
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .filter(|name| !self.params.on.contains(name))
+            .collect::<Vec<_>>();
+
+        // Indices store the old values of the columns, and their rows are not moved.
+        let field_ids = self
+            .dataset
+            .schema()
+            .project(&columns)?
+            .field_ids()
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let indices = self.dataset.load_indices().await?;
+        if let Some(index) = indices
+            .iter()
+            .find(|index| index.fields.iter().any(|id| field_ids.contains(id)))
+        {
+            return Err(Error::invalid_input(
+                format!(
+                    "Cannot update the columns of index '{}' in place, the source must contain all columns",
+                    index.name
+                ),
+                location!(),
+            ));
         }
+
+        if let Some(fragment) = self
+            .dataset
+            .get_fragments()
+            .await?
+            .iter()
+            .find(|f| f.metadata.files.iter().any(|file| !file.is_legacy_file()))
+        {
+            return Err(Error::NotSupported {
+                source: format!(
+                    "merge insert with a subset of the columns on fragment {} written with the v2 format",
+                    fragment.id()
+                )
+                .into(),
+                location: location!(),
+            });
+        }
+        Ok(Some(columns))
+    }
+
+    // Returns the first on key with a scalar index, and the index
+    async fn join_key_as_scalar_index(&self) -> Result<Option<(String, Index)>> {
+        for col in &self.params.on {
+            if let Some(index) = self.dataset.load_scalar_index_for_column(col).await? {
+                return Ok(Some((col.clone(), index)));
+            }
+        }
+        Ok(None)
+    }
+
+    // Reads ahead in the source until more than `max_rows` rows are seen or the source is
+    // exhausted.  Returns the (unchanged) source and whether it has more than `max_rows` rows.
+    async fn peek_source(
+        mut source: SendableRecordBatchStream,
+        max_rows: usize,
+    ) -> Result<(SendableRecordBatchStream, bool)> {
+        let schema = source.schema();
+        let mut buffered = Vec::new();
+        let mut num_rows = 0;
+        while num_rows <= max_rows {
+            match source.try_next().await? {
+                Some(batch) => {
+                    num_rows += batch.num_rows();
+                    buffered.push(Ok(batch));
+                }
+                None => break,
+            }
+        }
+        let stream = stream::iter(buffered).chain(source);
+        Ok((
+            Box::pin(RecordBatchStreamAdapter::new(schema, stream)),
+            num_rows > max_rows,
+        ))
     }
 
     async fn create_indexed_scan_joined_stream(
         &self,
         source: SendableRecordBatchStream,
+        index_column: String,
         index: Index,
     ) -> Result<SendableRecordBatchStream> {
         // This relies on a few non-standard physical operators and so we cannot use the
@@ -367,9 +643,9 @@ impl MergeInsertJob {
         let shared_input = Arc::new(ReplayExec::new(Capacity::Unbounded, input));
 
         // 3 - Use the index to map input to row addresses
-        // First, we need to project to the key column
+        // First, we need to project to the indexed key column
         let schema = shared_input.schema();
-        let field = schema.field_with_name(&self.params.on[0])?;
+        let field = schema.field_with_name(&index_column)?;
         let key_only_schema =
             lance_core::datatypes::Schema::try_from(&Schema::new(vec![field.clone()]))?;
         let index_mapper_input = Arc::new(ProjectionExec::try_new(
@@ -377,8 +653,8 @@ impl MergeInsertJob {
             Arc::new(key_only_schema),
         )?);
 
-        // Then we pass the key column into the index mapper
-        let index_column = self.params.on[0].clone();
+        // Then we pass the key column into the index mapper.  If there are several keys the
+        // target rows which only match the indexed key are discarded by the join below.
         let index_mapper = Arc::new(MapIndexExec::new(
             self.dataset.clone(),
             index_column.clone(),
@@ -386,10 +662,12 @@ impl MergeInsertJob {
         ));
 
         // 4 - Take the mapped row addresses
+        let is_partial_source = self.is_partial_source(&schema);
+        let target_schema = self.target_schema(&schema)?;
         let mut target = Arc::new(TakeExec::try_new(
             self.dataset.clone(),
             index_mapper,
-            Arc::new(self.dataset.schema().clone()),
+            Arc::new(target_schema.clone()),
             num_cpus::get(),
         )?) as Arc<dyn ExecutionPlan>;
 
@@ -406,9 +684,16 @@ impl MergeInsertJob {
         // 5a - We also need to scan any new unindexed data and union it in
         let unindexed_fragments = self.dataset.unindexed_fragments(&index.name).await?;
         if !unindexed_fragments.is_empty() {
-            let unindexed_data = self
-                .dataset
-                .scan()
+            let mut scanner = self.dataset.scan();
+            if is_partial_source {
+                let columns = target_schema
+                    .fields
+                    .iter()
+                    .map(|f| f.name.as_str())
+                    .collect::<Vec<_>>();
+                scanner.project(&columns)?;
+            }
+            let unindexed_data = scanner
                 .with_row_id()
                 .with_fragments(unindexed_fragments)
                 .create_plan()
@@ -422,13 +707,24 @@ impl MergeInsertJob {
         }
 
         // 6 - Finally, join the input (source table) with the taken data (target table)
-        let source_key = Column::new_with_schema(&index_column, shared_input.schema().as_ref())?;
-        let target_key = Column::new_with_schema(&index_column, target.schema().as_ref())?;
+        let on = self
+            .params
+            .on
+            .iter()
+            .map(|key| {
+                let source_key = Column::new_with_schema(key, shared_input.schema().as_ref())?;
+                let target_key = Column::new_with_schema(key, target.schema().as_ref())?;
+                Ok((
+                    Arc::new(source_key) as Arc<dyn PhysicalExpr>,
+                    Arc::new(target_key) as Arc<dyn PhysicalExpr>,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
         let joined = Arc::new(
             HashJoinExec::try_new(
                 shared_input,
                 target,
-                vec![(Arc::new(target_key), Arc::new(source_key))],
+                on,
                 None,
                 &JoinType::Full,
                 PartitionMode::CollectLeft,
@@ -454,7 +750,16 @@ impl MergeInsertJob {
         let session_ctx = SessionContext::new_with_config(session_config);
         let schema = source.schema();
         self.check_compatible_schema(&schema)?;
-        let existing = session_ctx.read_lance(self.dataset.clone(), true)?;
+        let mut existing = session_ctx.read_lance(self.dataset.clone(), true)?;
+        if self.is_partial_source(&schema) {
+            let mut columns = schema
+                .fields()
+                .iter()
+                .map(|f| f.name().as_str())
+                .collect::<Vec<_>>();
+            columns.push(ROW_ID);
+            existing = existing.select_columns(&columns)?;
+        }
         let new_data = session_ctx.read_one_shot(source)?;
         let join_cols = self
             .params
@@ -466,24 +771,40 @@ impl MergeInsertJob {
         Ok(joined.execute_stream().await?)
     }
 
+    // Returns the joined stream and whether the matching rows are looked up with an index
     async fn create_joined_stream(
         &self,
         source: SendableRecordBatchStream,
-    ) -> Result<SendableRecordBatchStream> {
+    ) -> Result<(SendableRecordBatchStream, bool)> {
         // We need to do a full index scan if we're deleting source data
         let can_use_scalar_index = matches!(
             self.params.delete_not_matched_by_source,
             WhenNotMatchedBySource::Keep
         );
-        if can_use_scalar_index {
-            if let Some(index) = self.join_key_as_scalar_index().await? {
-                self.create_indexed_scan_joined_stream(source, index).await
+        if !self.params.use_index {
+            Ok((self.create_full_table_joined_stream(source).await?, false))
+        } else if can_use_scalar_index {
+            if let Some((column, index)) = self.join_key_as_scalar_index().await? {
+                let target_rows = self.dataset.count_rows(None).await?;
+                let max_rows = ((target_rows as f64 * self.params.max_indexed_lookup_ratio)
+                    as usize)
+                    .min(self.params.max_indexed_lookup_rows);
+                let (source, is_large) = Self::peek_source(source, max_rows).await?;
+                if is_large {
+                    info!("The merge insert source has more than {} rows ({} target rows), using a full table scan instead of the index on {}", max_rows, target_rows, column);
+                    Ok((self.create_full_table_joined_stream(source).await?, false))
+                } else {
+                    let joined = self
+                        .create_indexed_scan_joined_stream(source, column, index)
+                        .await?;
+                    Ok((joined, true))
+                }
             } else {
-                self.create_full_table_joined_stream(source).await
+                Ok((self.create_full_table_joined_stream(source).await?, false))
             }
         } else {
             info!("The merge insert operation is configured to delete rows from the target table, this requires a potentially costly full table scan");
-            self.create_full_table_joined_stream(source).await
+            Ok((self.create_full_table_joined_stream(source).await?, false))
         }
     }
 
@@ -491,48 +812,418 @@ impl MergeInsertJob {
     ///
     /// This will take in the source, merge it with the existing target data, and insert new
     /// rows, update existing rows, and delete existing rows
-    pub async fn execute(self, source: SendableRecordBatchStream) -> Result<Arc<Dataset>> {
+    ///
+    /// If the source has only the on keys and a subset of the other columns then the matched
+    /// rows are updated in place.  This returns an error if the job inserts rows, if one of the
+    /// updated columns is indexed, or if the dataset has fragments written with the v2 file
+    /// format.
+    pub async fn execute(
+        self,
+        source: SendableRecordBatchStream,
+    ) -> Result<(Arc<Dataset>, MergeStats)> {
         let schema = source.schema();
+        let update_columns = self.partial_update_columns(&schema).await?;
+        // The matched rows of a partial source are checked as they are spilled
+        let partial_constraints = match update_columns {
+            Some(_) => Some(Constraints::try_new(self.dataset.schema(), schema.clone())?),
+            None => None,
+        };
 
-        let joined = self.create_joined_stream(source).await?;
-        let merger = Merger::try_new(self.params, schema.clone())?;
+        let stats = Arc::new(Mutex::new(MergeStats::default()));
+        let old_rows_sink = self.params.old_rows_sink.clone();
+        let source = self.dedupe_source(source, stats.clone())?;
+        let (joined, used_index) = self.create_joined_stream(source).await?;
+        stats.lock().unwrap().used_index = used_index;
+        let merger = Merger::try_new(
+            self.params,
+            schema.clone(),
+            update_columns.is_some(),
+            stats.clone(),
+        )?;
         let deleted_rows = merger.deleted_rows.clone();
         let stream = joined
             .and_then(move |batch| merger.clone().execute_batch(batch))
             .try_flatten();
-        let stream = RecordBatchStreamAdapter::new(schema, stream);
 
-        let new_fragments = write_fragments_internal(
-            None,
-            self.dataset.object_store.clone(),
-            &self.dataset.base,
-            self.dataset.schema(),
-            Box::pin(stream),
-            Default::default(),
-        )
-        .await?;
+        let (new_fragments, updated_rows) = if let Some(constraints) = partial_constraints {
+            // A partial source never inserts rows, the stream only has the matched rows
+            let updated_rows = UpdatedRows::try_spill(constraints, stream).await?;
+            (Vec::new(), Some(updated_rows))
+        } else {
+            let stream = RecordBatchStreamAdapter::new(schema, stream);
+            let stream = check_constraints(Box::pin(stream), self.dataset.schema())?;
+            let new_fragments = write_fragments_internal(
+                None,
+                self.dataset.object_store.clone(),
+                &self.dataset.base,
+                self.dataset.schema(),
+                stream,
+                Default::default(),
+            )
+            .await?;
+            (new_fragments, None)
+        };
 
-        // Apply deletions
         let removed_row_ids = Arc::into_inner(deleted_rows).unwrap().into_inner().unwrap();
+
+        // Update matched rows in place
+        let updated_fragments = match (update_columns, updated_rows) {
+            (Some(columns), Some(updated_rows)) => {
+                if let Some(sink) = &old_rows_sink {
+                    let mut old_rows = removed_row_ids.clone();
+                    old_rows.extend(updated_rows.row_ids());
+                    send_rows_to_sink(&self.dataset, &old_rows, sink.as_ref()).await?;
+                }
+                Self::update_fragments_in_place(&self.dataset, &columns, updated_rows).await?
+            }
+            _ => {
+                if let Some(sink) = &old_rows_sink {
+                    send_rows_to_sink(&self.dataset, &removed_row_ids, sink.as_ref()).await?;
+                }
+                Vec::new()
+            }
+        };
+
+        // Apply deletions
         let (old_fragments, removed_fragment_ids) =
-            Self::apply_deletions(&self.dataset, &removed_row_ids).await?;
+            Self::apply_deletions(&self.dataset, updated_fragments, &removed_row_ids).await?;
+
+        let mut operation = OperationStats::from_changes(
+            &self.dataset,
+            &removed_fragment_ids,
+            &old_fragments,
+            &new_fragments,
+        )
+        .await?;
 
         // Commit updated and new fragments
-        Self::commit(
+        let dataset = Self::commit(
             self.dataset,
             removed_fragment_ids,
             old_fragments,
             new_fragments,
         )
+        .await?;
+        let mut stats = stats.lock().unwrap().clone();
+        operation.num_rows_affected =
+            stats.num_inserted_rows + stats.num_updated_rows + stats.num_deleted_rows;
+        stats.operation = operation;
+        Ok((dataset, stats))
+    }
+
+    // Combines the source rows which have the same key, as configured by the source dedupe
+    // behavior.  The returned stream has the schema of the source.
+    fn dedupe_source(
+        &self,
+        source: SendableRecordBatchStream,
+        stats: Arc<Mutex<MergeStats>>,
+    ) -> Result<SendableRecordBatchStream> {
+        let schema = source.schema();
+        let column = |name: &str| Expr::Column(datafusion::common::Column::new_unqualified(name));
+        let first_value = |name: &str| {
+            Expr::AggregateFunction(AggregateFunction::new(
+                BuiltInAggregate::FirstValue,
+                vec![column(name)],
+                false,
+                None,
+                None,
+            ))
+        };
+        let behavior = &self.params.source_dedupe_behavior;
+        match behavior {
+            SourceDedupeBehavior::LastBy(order_by) if schema.field_with_name(order_by).is_err() => {
+                return Err(Error::invalid_input(
+                    format!("The source has no column '{}' to deduplicate by", order_by),
+                    location!(),
+                ));
+            }
+            SourceDedupeBehavior::Aggregate(aggregates) => {
+                if let Some(name) = aggregates.keys().find(|name| {
+                    schema.field_with_name(name).is_err() || self.params.on.contains(name)
+                }) {
+                    return Err(Error::invalid_input(
+                        format!(
+                            "Cannot deduplicate column '{}', it is not a non-key column of the source",
+                            name
+                        ),
+                        location!(),
+                    ));
+                }
+            }
+            SourceDedupeBehavior::Fail => return self.fail_on_duplicate_keys(source),
+            _ => {}
+        }
+
+        let mut aggregates = schema
+            .fields()
+            .iter()
+            .filter(|field| !self.params.on.contains(field.name()))
+            .map(|field| {
+                let name = field.name().as_str();
+                let aggregate = match behavior {
+                    SourceDedupeBehavior::Fail | SourceDedupeBehavior::FirstSeen => {
+                        first_value(name)
+                    }
+                    SourceDedupeBehavior::LastBy(order_by) => {
+                        Expr::AggregateFunction(AggregateFunction::new(
+                            BuiltInAggregate::LastValue,
+                            vec![column(name)],
+                            false,
+                            None,
+                            Some(vec![column(order_by).sort(true, false)]),
+                        ))
+                    }
+                    SourceDedupeBehavior::Aggregate(aggregates) => aggregates
+                        .get(name)
+                        .cloned()
+                        .unwrap_or_else(|| first_value(name)),
+                };
+                aggregate.alias(name)
+            })
+            .collect::<Vec<_>>();
+        aggregates.push(
+            Expr::AggregateFunction(AggregateFunction::new(
+                BuiltInAggregate::Count,
+                vec![Expr::Literal(ScalarValue::Int64(Some(1)))],
+                false,
+                None,
+                None,
+            ))
+            .alias(DUPLICATE_COUNT_COLUMN),
+        );
+        let keys = self
+            .params
+            .on
+            .iter()
+            .map(|key| column(key))
+            .collect::<Vec<_>>();
+        // Restore the order of the source columns.  The types are restored below, an aggregate
+        // such as `sum` may return a wider type.
+        let mut projection = schema
+            .fields()
+            .iter()
+            .map(|field| column(field.name()))
+            .collect::<Vec<_>>();
+        projection.push(column(DUPLICATE_COUNT_COLUMN));
+
+        let session_config = SessionConfig::default().with_target_partitions(1);
+        let session_ctx = SessionContext::new_with_config(session_config);
+        let deduped = session_ctx
+            .read_one_shot(source)?
+            .aggregate(keys, aggregates)?
+            .select(projection)?;
+
+        let stream_schema = schema.clone();
+        let stream = futures::stream::once(async move { deduped.execute_stream().await })
+            .try_flatten()
+            .map(move |batch| {
+                let batch = batch?;
+                let counts = batch
+                    .column(batch.num_columns() - 1)
+                    .as_primitive::<arrow_array::types::Int64Type>();
+                let num_duplicates = counts.values().iter().map(|c| (c - 1) as u64).sum::<u64>();
+                stats.lock().unwrap().num_deduplicated_rows += num_duplicates;
+                // The aggregates are nullable and may be wider, restore the source types and
+                // nullability.  The cast fails rather than wrap around if a value does not fit.
+                let columns = stream_schema
+                    .fields()
+                    .iter()
+                    .zip(batch.columns())
+                    .map(|(field, column)| {
+                        if column.data_type() == field.data_type() {
+                            return Ok(column.clone());
+                        }
+                        lance_arrow::cast::cast_with_options(
+                            column,
+                            field.data_type(),
+                            &CastOptions {
+                                safe: false,
+                                ..Default::default()
+                            },
+                        )
+                        .map_err(|err| {
+                            datafusion::error::DataFusionError::External(Box::new(
+                                Error::invalid_input(
+                                    format!(
+                                        "The deduplicated values of column '{}' do not fit its type {}: {}",
+                                        field.name(),
+                                        field.data_type(),
+                                        err
+                                    ),
+                                    location!(),
+                                ),
+                            ))
+                        })
+                    })
+                    .collect::<datafusion::error::Result<Vec<_>>>()?;
+                Ok(RecordBatch::try_new(stream_schema.clone(), columns)?)
+            });
+        Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
+    }
+
+    // Fails the source stream as soon as a key is seen a second time.  The source is still
+    // streamed, only the encoded keys are kept in memory.
+    fn fail_on_duplicate_keys(
+        &self,
+        source: SendableRecordBatchStream,
+    ) -> Result<SendableRecordBatchStream> {
+        let schema = source.schema();
+        let key_indices = self
+            .params
+            .on
+            .iter()
+            .map(|key| schema.index_of(key))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let converter = RowConverter::new(
+            key_indices
+                .iter()
+                .map(|&i| SortField::new(schema.field(i).data_type().clone()))
+                .collect(),
+        )?;
+        let mut seen = HashSet::new();
+        let on = self.params.on.clone();
+        let stream = source.map(move |batch| {
+            let batch = batch?;
+            let keys = key_indices
+                .iter()
+                .map(|&i| batch.column(i).clone())
+                .collect::<Vec<_>>();
+            let rows = converter.convert_columns(&keys)?;
+            if rows.iter().any(|row| !seen.insert(row.owned())) {
+                return Err(datafusion::error::DataFusionError::External(Box::new(
+                    Error::invalid_input(
+                        format!(
+                            "The source contains rows with a duplicate value of the on keys {:?}, use a source dedupe behavior to combine them",
+                            on
+                        ),
+                        location!(),
+                    ),
+                )));
+            }
+            Ok(batch)
+        });
+        Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
+    }
+
+    // Write the new values of `columns` for the matched rows into new data files of their
+    // fragments, returns the updated fragments
+    async fn update_fragments_in_place(
+        dataset: &Dataset,
+        columns: &[String],
+        updated_rows: UpdatedRows,
+    ) -> Result<Vec<Fragment>> {
+        if columns.is_empty() || updated_rows.rows_by_fragment.is_empty() {
+            return Ok(Vec::new());
+        }
+        let reader = updated_rows.open().await?;
+        let UpdatedRows {
+            mut rows_by_fragment,
+            ..
+        } = updated_rows;
+
+        let fragments = dataset.get_fragments().await?;
+        stream::iter(fragments.into_iter().filter_map(|fragment| {
+            rows_by_fragment
+                .remove(&(fragment.id() as u64))
+                .map(|rows| (fragment, rows))
+        }))
+        .map(|(fragment, rows)| {
+            let reader = &reader;
+            async move {
+                // Only the new values of the rows of this fragment are read back
+                let mut indices = rows.values().copied().collect::<Vec<_>>();
+                indices.sort_unstable();
+                let new_values = reader.take(&indices, reader.schema(), None).await?;
+                let rows = rows
+                    .into_iter()
+                    .map(|(offset, idx)| (offset, indices.binary_search(&idx).unwrap()))
+                    .collect();
+                Self::update_fragment(fragment, columns, &new_values, &rows).await
+            }
+        })
+        .buffer_unordered(num_cpus::get())
+        .try_collect()
         .await
     }
 
+    async fn update_fragment(
+        fragment: FileFragment,
+        columns: &[String],
+        new_values: &RecordBatch,
+        rows: &HashMap<u32, usize>,
+    ) -> Result<Fragment> {
+        let dataset = fragment.dataset();
+        let write_schema = dataset.schema().project(columns)?;
+        let mut updater = fragment
+            .updater(
+                Some(columns),
+                Some((write_schema, dataset.schema().clone())),
+            )
+            .await?;
+        while let Some(batch) = updater.next().await? {
+            let batch = batch.clone();
+            let indices = updater
+                .last_row_offsets()
+                .iter()
+                .enumerate()
+                .map(|(idx, offset)| match rows.get(offset) {
+                    Some(new_idx) => (1, *new_idx),
+                    None => (0, idx),
+                })
+                .collect::<Vec<_>>();
+            let columns = batch
+                .schema()
+                .fields()
+                .iter()
+                .zip(batch.columns())
+                .map(|(field, old)| {
+                    let new = new_values.column_by_name(field.name()).unwrap();
+                    let new = arrow::compute::cast(new, old.data_type())?;
+                    interleave(&[old.as_ref(), new.as_ref()], &indices)
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            updater
+                .update(RecordBatch::try_new(batch.schema(), columns)?)
+                .await?;
+        }
+        let num_files = fragment.metadata.files.len();
+        let mut metadata = updater.finish().await?;
+        if metadata.files.len() == num_files {
+            // The fragment has no rows
+            return Ok(metadata);
+        }
+
+        // The values in the new file supersede the ones in the older files
+        let new_fields = metadata.files[num_files].fields.clone();
+        for data_file in metadata.files[..num_files].iter_mut() {
+            for field_id in &new_fields {
+                if data_file.fields.contains(field_id)
+                    && !data_file.replaced_fields.contains(field_id)
+                {
+                    data_file.replaced_fields.push(*field_id);
+                }
+            }
+        }
+        metadata
+            .files
+            .retain(|data_file| data_file.live_fields().next().is_some());
+        Ok(metadata)
+    }
+
     // Delete a batch of rows by id, returns the fragments modified and the fragments removed
+    //
+    // `updated_fragments` are fragments whose columns were updated in place, they are
+    // returned as modified unless all of their rows are deleted
     async fn apply_deletions(
         dataset: &Dataset,
+        updated_fragments: Vec<Fragment>,
         removed_row_ids: &RoaringTreemap,
     ) -> Result<(Vec<Fragment>, Vec<u64>)> {
         let bitmaps = Arc::new(removed_row_ids.bitmaps().collect::<BTreeMap<_, _>>());
+        let mut updated_fragments = updated_fragments
+            .into_iter()
+            .map(|f| (f.id, f))
+            .collect::<HashMap<_, _>>();
 
         enum FragmentChange {
             Unchanged,
@@ -540,11 +1231,25 @@ impl MergeInsertJob {
             Removed(u64),
         }
 
+        let fragments = dataset
+            .get_fragments()
+            .await?
+            .into_iter()
+            .map(|mut fragment| {
+                if let Some(updated) = updated_fragments.remove(&(fragment.id() as u64)) {
+                    fragment.metadata = updated;
+                    (fragment, true)
+                } else {
+                    (fragment, false)
+                }
+            })
+            .collect::<Vec<_>>();
+
         let mut updated_fragments = Vec::new();
         let mut removed_fragments = Vec::new();
 
-        let mut stream = futures::stream::iter(dataset.get_fragments())
-            .map(move |fragment| {
+        let mut stream = futures::stream::iter(fragments)
+            .map(move |(fragment, updated)| {
                 let bitmaps_ref = bitmaps.clone();
                 async move {
                     let fragment_id = fragment.id();
@@ -556,6 +1261,8 @@ impl MergeInsertJob {
                             Ok(None) => Ok(FragmentChange::Removed(fragment_id as u64)),
                             Err(e) => Err(e),
                         }
+                    } else if updated {
+                        Ok(FragmentChange::Modified(fragment.metadata))
                     } else {
                         Ok(FragmentChange::Unchanged)
                     }
@@ -605,6 +1312,85 @@ impl MergeInsertJob {
     }
 }
 
+// The new values of the target rows which are updated in place
+//
+// These are spilled to a temporary local file so that a large partial update does not need to
+// hold all of the matched rows in memory.  Only the row ids are kept in memory.
+struct UpdatedRows {
+    // Removes the spill file when dropped
+    _dir: TempDir,
+    object_store: ObjectStore,
+    path: Path,
+    // For each fragment, maps the offset of an updated row to the index of its new values in
+    // the spill file
+    rows_by_fragment: BTreeMap<u64, HashMap<u32, u32>>,
+}
+
+impl UpdatedRows {
+    // Writes the matched rows to the spill file, each batch has the source columns followed by
+    // the row id of the target row
+    async fn try_spill(
+        constraints: Constraints,
+        stream: impl Stream<Item = datafusion::common::Result<RecordBatch>>,
+    ) -> Result<Self> {
+        let mut stream = std::pin::pin!(stream);
+        let dir = TempDir::new()?;
+        let object_store = ObjectStore::local();
+        let path = Path::from_filesystem_path(dir.path())?.child("updated_rows.lance");
+
+        let mut writer: Option<FileWriter<ManifestDescribing>> = None;
+        let mut rows_by_fragment: BTreeMap<u64, HashMap<u32, u32>> = BTreeMap::new();
+        let mut num_rows = 0;
+        while let Some(batch) = stream.try_next().await? {
+            let row_ids = batch.column(batch.num_columns() - 1).clone();
+            let batch = batch.project(&Vec::from_iter(0..batch.num_columns() - 1))?;
+            let batch = constraints.check(batch, num_rows)?;
+            // If several source rows match the same target row, the last one wins
+            for (idx, row_id) in row_ids
+                .as_primitive::<UInt64Type>()
+                .values()
+                .iter()
+                .enumerate()
+            {
+                rows_by_fragment
+                    .entry(row_id >> 32)
+                    .or_default()
+                    .insert(*row_id as u32, (num_rows + idx) as u32);
+            }
+            num_rows += batch.num_rows();
+            if writer.is_none() {
+                let schema = lance_core::datatypes::Schema::try_from(batch.schema().as_ref())?;
+                writer = Some(
+                    FileWriter::try_new(&object_store, &path, schema, &Default::default()).await?,
+                );
+            }
+            writer.as_mut().unwrap().write(&[batch]).await?;
+        }
+        if let Some(mut writer) = writer {
+            writer.finish().await?;
+        }
+        Ok(Self {
+            _dir: dir,
+            object_store,
+            path,
+            rows_by_fragment,
+        })
+    }
+
+    fn row_ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.rows_by_fragment
+            .iter()
+            .flat_map(|(fragment_id, rows)| {
+                rows.keys()
+                    .map(move |offset| (fragment_id << 32) | *offset as u64)
+            })
+    }
+
+    async fn open(&self) -> Result<FileReader> {
+        FileReader::try_new_self_described(&self.object_store, &self.path, None).await
+    }
+}
+
 // A sync-safe structure that is shared by all of the "process batch" tasks.
 //
 // Note: we are not currently using parallelism but this still needs to be sync because it is
@@ -613,6 +1399,11 @@ impl MergeInsertJob {
 struct Merger {
     // As the merger runs it will update the list of deleted rows
     deleted_rows: Arc<Mutex<RoaringTreemap>>,
+    // If true, the source has a subset of the columns and the source columns and row id of the
+    // matched rows are returned so that they can be updated in place
+    update_in_place: bool,
+    // Counts of inserted, updated and deleted rows
+    stats: Arc<Mutex<MergeStats>>,
     // Physical delete expression, only set if params.delete_not_matched_by_source is DeleteIf
     delete_expr: Option<Arc<dyn PhysicalExpr>>,
     // Physical "when matched update if" expression, only set if params.when_matched is UpdateIf
@@ -625,7 +1416,12 @@ struct Merger {
 
 impl Merger {
     // Creates a new merger with an empty set of deleted rows, compiles expressions, if present
-    fn try_new(params: MergeInsertParams, schema: Arc<Schema>) -> Result<Self> {
+    fn try_new(
+        params: MergeInsertParams,
+        schema: Arc<Schema>,
+        update_in_place: bool,
+        stats: Arc<Mutex<MergeStats>>,
+    ) -> Result<Self> {
         let delete_expr = if let WhenNotMatchedBySource::DeleteIf(expr) =
             &params.delete_not_matched_by_source
         {
@@ -655,6 +1451,8 @@ impl Merger {
         };
         Ok(Self {
             deleted_rows: Arc::new(Mutex::new(RoaringTreemap::new())),
+            update_in_place,
+            stats,
             delete_expr,
             match_filter_expr,
             params,
@@ -739,6 +1537,7 @@ impl Merger {
         // There is no contention on this mutex.  We're only using it to bypass the rust
         // borrow checker (the stream needs to be `sync` since it crosses an await point)
         let mut deleted_row_ids = self.deleted_rows.lock().unwrap();
+        let mut stats = self.stats.lock().unwrap();
 
         if self.params.when_matched != WhenMatched::DoNothing {
             let mut matched = arrow::compute::filter_record_batch(&batch, &in_both)?;
@@ -762,7 +1561,12 @@ impl Merger {
             }
             // If the filter eliminated all rows then its important we don't try and write
             // the batch at all.  Writing an empty batch currently panics
-            if matched.num_rows() > 0 {
+            stats.num_updated_rows += matched.num_rows() as u64;
+            if matched.num_rows() > 0 && self.update_in_place {
+                let mut cols = left_cols.clone();
+                cols.push(row_id_col);
+                batches.push(Ok(matched.project(&cols)?));
+            } else if matched.num_rows() > 0 {
                 let row_ids = matched.column(row_id_col).as_primitive::<UInt64Type>();
                 deleted_row_ids.extend(row_ids.values());
                 let matched = matched.project(&left_cols)?;
@@ -781,6 +1585,7 @@ impl Merger {
         if self.params.insert_not_matched {
             let not_matched = arrow::compute::filter_record_batch(&batch, &left_only)?;
             let not_matched = not_matched.project(&left_cols)?;
+            stats.num_inserted_rows += not_matched.num_rows() as u64;
             // See comment above explaining this schema replacement
             let not_matched = RecordBatch::try_new(
                 self.schema.clone(),
@@ -792,6 +1597,7 @@ impl Merger {
             WhenNotMatchedBySource::Delete => {
                 let unmatched = arrow::compute::filter(batch.column(row_id_col), &right_only)?;
                 let row_ids = unmatched.as_primitive::<UInt64Type>();
+                stats.num_deleted_rows += row_ids.len() as u64;
                 deleted_row_ids.extend(row_ids.values());
             }
             WhenNotMatchedBySource::DeleteIf(_) => {
@@ -806,11 +1612,13 @@ impl Merger {
                             mask.as_boolean(),
                         )?;
                         let row_ids = row_ids.as_primitive::<UInt64Type>();
+                        stats.num_deleted_rows += row_ids.len() as u64;
                         deleted_row_ids.extend(row_ids.values());
                     }
                     ColumnarValue::Scalar(scalar) => {
                         if let ScalarValue::Boolean(Some(true)) = scalar {
                             let row_ids = unmatched.column(row_id_col).as_primitive::<UInt64Type>();
+                            stats.num_deleted_rows += row_ids.len() as u64;
                             deleted_row_ids.extend(row_ids.values());
                         }
                     }
@@ -833,7 +1641,10 @@ mod tests {
     use tempfile::tempdir;
 
     use crate::{
-        dataset::{WriteMode, WriteParams},
+        dataset::{
+            feature_flags::FLAG_REPLACED_FIELDS, write::stats::tests::VecSink, WriteMode,
+            WriteParams,
+        },
         index::scalar::ScalarIndexParams,
     };
 
@@ -857,7 +1668,7 @@ mod tests {
         .await
         .unwrap();
 
-        let merged_dataset = job.execute(new_stream).await.unwrap();
+        let (merged_dataset, _) = job.execute(new_stream).await.unwrap();
 
         let batches = merged_dataset
             .scan()
@@ -1143,7 +1954,7 @@ mod tests {
         ));
 
         // Run merge_insert
-        let ds = MergeInsertBuilder::try_new(ds.clone(), vec!["key".to_string()])
+        let (ds, _) = MergeInsertBuilder::try_new(ds.clone(), vec!["key".to_string()])
             .unwrap()
             .when_not_matched(WhenNotMatched::DoNothing)
             .when_matched(WhenMatched::UpdateAll)
@@ -1169,7 +1980,7 @@ mod tests {
             schema.clone(),
         ));
         // Run merge_insert
-        let ds = MergeInsertBuilder::try_new(ds.clone(), vec!["key".to_string()])
+        let (ds, _) = MergeInsertBuilder::try_new(ds.clone(), vec!["key".to_string()])
             .unwrap()
             .when_not_matched(WhenNotMatched::DoNothing)
             .when_matched(WhenMatched::UpdateAll)
@@ -1189,7 +2000,7 @@ mod tests {
         ));
         // Run merge_insert one last time.  The index is now completely out of date.  Every
         // row it points to is a deleted row.  Make sure that doesn't break.
-        let ds = MergeInsertBuilder::try_new(ds.clone(), vec!["key".to_string()])
+        let (ds, _) = MergeInsertBuilder::try_new(ds.clone(), vec!["key".to_string()])
             .unwrap()
             .when_not_matched(WhenNotMatched::DoNothing)
             .when_matched(WhenMatched::UpdateAll)
@@ -1201,4 +2012,475 @@ mod tests {
 
         assert_eq!(ds.count_rows(None).await.unwrap(), 2048);
     }
+
+    #[tokio::test]
+    async fn test_indexed_merge_insert_composite_key() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::UInt32, false),
+            Field::new("b", DataType::UInt32, false),
+            Field::new("value", DataType::UInt32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(UInt32Array::from_iter_values((0..1000).map(|i| i / 2))),
+                Arc::new(UInt32Array::from_iter_values((0..1000).map(|i| i % 2))),
+                Arc::new(UInt32Array::from_iter_values(0..1000)),
+            ],
+        )
+        .unwrap();
+        let batches = RecordBatchIterator::new([Ok(batch)], schema.clone());
+        let mut ds = Dataset::write(batches, test_uri, None).await.unwrap();
+        // Only one of the keys is indexed
+        ds.create_index(
+            &["a"],
+            IndexType::Scalar,
+            None,
+            &ScalarIndexParams::default(),
+            false,
+        )
+        .await
+        .unwrap();
+        let ds = Arc::new(ds);
+
+        let source_batch = |keys: &[(u32, u32)]| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(UInt32Array::from_iter_values(keys.iter().map(|k| k.0))),
+                    Arc::new(UInt32Array::from_iter_values(keys.iter().map(|k| k.1))),
+                    Arc::new(UInt32Array::from_iter_values(keys.iter().map(|_| 9999))),
+                ],
+            )
+            .unwrap()
+        };
+        let upsert = |ds: Arc<Dataset>,
+                      batches: Vec<RecordBatch>,
+                      use_index: bool,
+                      max_ratio: f64,
+                      max_rows: usize| {
+            let schema = schema.clone();
+            async move {
+                // Start from the original data each time
+                let mut ds = (*ds).clone();
+                ds.restore().await.unwrap();
+                MergeInsertBuilder::try_new(Arc::new(ds), vec!["b".to_string(), "a".to_string()])
+                    .unwrap()
+                    .when_matched(WhenMatched::UpdateAll)
+                    .use_index(use_index)
+                    .max_indexed_lookup_ratio(max_ratio)
+                    .max_indexed_lookup_rows(max_rows)
+                    .try_build()
+                    .unwrap()
+                    .execute_reader(Box::new(RecordBatchIterator::new(
+                        batches.into_iter().map(Ok),
+                        schema,
+                    )))
+                    .await
+                    .unwrap()
+            }
+        };
+        let count = |ds: Arc<Dataset>, filter: &'static str| async move {
+            ds.scan()
+                .filter(filter)
+                .unwrap()
+                .count_rows()
+                .await
+                .unwrap()
+        };
+
+        // The same indexed key appears in two source batches, the matched rows must only be
+        // updated once
+        for use_index in [true, false] {
+            let (updated, stats) = upsert(
+                ds.clone(),
+                vec![source_batch(&[(5, 0)]), source_batch(&[(5, 1), (999, 0)])],
+                use_index,
+                DEFAULT_MAX_INDEXED_LOOKUP_RATIO,
+                DEFAULT_MAX_INDEXED_LOOKUP_ROWS,
+            )
+            .await;
+            assert_eq!(stats.used_index, use_index);
+            assert_eq!(stats.num_updated_rows, 2);
+            assert_eq!(stats.num_inserted_rows, 1);
+            assert_eq!(updated.count_rows(None).await.unwrap(), 1001);
+            assert_eq!(count(updated.clone(), "value = 9999").await, 3);
+            assert_eq!(count(updated, "a = 5").await, 2);
+        }
+
+        // A source that is a large part of the target falls back to a full scan, even when it
+        // is far below the absolute cap
+        let keys = (0..100)
+            .flat_map(|a| [(a, 0), (a, 1)])
+            .chain([(1000, 0)])
+            .collect::<Vec<_>>();
+        for (max_ratio, used_index) in [(DEFAULT_MAX_INDEXED_LOOKUP_RATIO, false), (0.5, true)] {
+            let (updated, stats) = upsert(
+                ds.clone(),
+                vec![source_batch(&keys)],
+                true,
+                max_ratio,
+                DEFAULT_MAX_INDEXED_LOOKUP_ROWS,
+            )
+            .await;
+            assert_eq!(stats.used_index, used_index);
+            assert_eq!(stats.num_updated_rows, 200);
+            assert_eq!(stats.num_inserted_rows, 1);
+            assert_eq!(updated.count_rows(None).await.unwrap(), 1001);
+            assert_eq!(count(updated, "value = 9999").await, 201);
+        }
+
+        // The absolute cap applies even when the ratio would allow the lookup
+        let (updated, stats) = upsert(ds.clone(), vec![source_batch(&keys)], true, 1.0, 100).await;
+        assert!(!stats.used_index);
+        assert_eq!(stats.num_updated_rows, 200);
+        assert_eq!(count(updated, "value = 9999").await, 201);
+    }
+
+    #[tokio::test]
+    async fn test_merge_insert_source_duplicates() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("key", DataType::UInt32, false),
+            Field::new("value", DataType::UInt32, false),
+            Field::new("ts", DataType::UInt32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(UInt32Array::from_iter_values(0..10)),
+                Arc::new(UInt32Array::from_iter_values(0..10)),
+                Arc::new(UInt32Array::from_iter_values(0..10)),
+            ],
+        )
+        .unwrap();
+        let batches = RecordBatchIterator::new([Ok(batch)], schema.clone());
+        let ds = Arc::new(Dataset::write(batches, test_uri, None).await.unwrap());
+
+        // Keys 1 and 20 appear twice, 20 is a new key
+        let source = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(UInt32Array::from(vec![1, 20, 1, 3, 20])),
+                Arc::new(UInt32Array::from(vec![100, 200, 101, 300, 201])),
+                Arc::new(UInt32Array::from(vec![5, 7, 6, 1, 2])),
+            ],
+        )
+        .unwrap();
+        let merge_source = |source: RecordBatch,
+                            behavior: Option<SourceDedupeBehavior>,
+                            sink: Option<Arc<VecSink>>| {
+            let ds = ds.clone();
+            let schema = schema.clone();
+            async move {
+                let mut ds = (*ds).clone();
+                ds.restore().await.unwrap();
+                let mut builder =
+                    MergeInsertBuilder::try_new(Arc::new(ds), vec!["key".to_string()]).unwrap();
+                builder
+                    .when_matched(WhenMatched::UpdateAll)
+                    .when_not_matched_by_source(WhenNotMatchedBySource::DeleteIf(
+                        Expr::Column(Column::from_name("key"))
+                            .gt_eq(Expr::Literal(ScalarValue::UInt32(Some(8)))),
+                    ));
+                if let Some(behavior) = behavior {
+                    builder.source_dedupe_behavior(behavior);
+                }
+                if let Some(sink) = sink {
+                    builder.old_rows_sink(sink);
+                }
+                builder
+                    .try_build()
+                    .unwrap()
+                    .execute_reader(Box::new(RecordBatchIterator::new([Ok(source)], schema)))
+                    .await
+            }
+        };
+        let merge = |behavior: Option<SourceDedupeBehavior>, sink: Option<Arc<VecSink>>| {
+            merge_source(source.clone(), behavior, sink)
+        };
+        let values = |ds: Arc<Dataset>| async move {
+            let batch = ds.scan().try_into_batch().await.unwrap();
+            let keys = batch["key"].as_primitive::<UInt32Type>().values().to_vec();
+            let values = batch["value"]
+                .as_primitive::<UInt32Type>()
+                .values()
+                .to_vec();
+            keys.into_iter()
+                .zip(values)
+                .filter(|(key, _)| [1, 3, 20].contains(key))
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        // Duplicates are an error by default
+        let err = merge(None, None).await.unwrap_err();
+        assert!(err.to_string().contains("duplicate"), "{}", err);
+
+        let sink = Arc::new(VecSink::default());
+        let (merged, stats) = merge(Some(SourceDedupeBehavior::FirstSeen), Some(sink.clone()))
+            .await
+            .unwrap();
+        // The old version of the updated rows 1 and 3, and the deleted rows 8 and 9
+        let old_rows = sink.0.lock().unwrap().clone();
+        let old_rows = concat_batches(&old_rows[0].schema(), &old_rows).unwrap();
+        assert_eq!(
+            old_rows["key"].as_primitive::<UInt32Type>().values(),
+            &[1, 3, 8, 9]
+        );
+        assert_eq!(
+            old_rows["value"].as_primitive::<UInt32Type>().values(),
+            &[1, 3, 8, 9]
+        );
+        assert_eq!(stats.num_inserted_rows, 1);
+        assert_eq!(stats.num_updated_rows, 2);
+        assert_eq!(stats.num_deleted_rows, 2);
+        assert_eq!(stats.num_deduplicated_rows, 2);
+        assert_eq!(stats.operation.num_rows_affected, 5);
+        // A deletion file for the single fragment and a new fragment for the new rows
+        assert_eq!(stats.operation.num_fragments_touched, 2);
+        assert_eq!(stats.operation.num_files_added, 2);
+        assert_eq!(stats.operation.num_files_removed, 0);
+        assert!(stats.operation.bytes_written > 0);
+        assert_eq!(merged.count_rows(None).await.unwrap(), 9);
+        assert_eq!(
+            values(merged).await,
+            BTreeMap::from([(1, 100), (3, 300), (20, 200)])
+        );
+
+        let (merged, _) = merge(Some(SourceDedupeBehavior::LastBy("ts".to_string())), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            values(merged).await,
+            BTreeMap::from([(1, 101), (3, 300), (20, 200)])
+        );
+
+        let sum = datafusion::logical_expr::sum(Expr::Column(Column::from_name("value")));
+        let (merged, _) = merge(
+            Some(SourceDedupeBehavior::Aggregate(HashMap::from([(
+                "value".to_string(),
+                sum,
+            )]))),
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            values(merged).await,
+            BTreeMap::from([(1, 201), (3, 300), (20, 401)])
+        );
+
+        // A combined value that does not fit the type of the column is an error
+        let overflowing = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(UInt32Array::from(vec![1, 1])),
+                Arc::new(UInt32Array::from(vec![u32::MAX, 1])),
+                Arc::new(UInt32Array::from(vec![0, 1])),
+            ],
+        )
+        .unwrap();
+        let sum = datafusion::logical_expr::sum(Expr::Column(Column::from_name("value")));
+        let err = merge_source(
+            overflowing,
+            Some(SourceDedupeBehavior::Aggregate(HashMap::from([(
+                "value".to_string(),
+                sum,
+            )]))),
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("do not fit"), "{}", err);
+
+        let err = merge(
+            Some(SourceDedupeBehavior::LastBy("missing".to_string())),
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_partial_merge_insert() {
+        for use_index in [false, true] {
+            let test_dir = tempdir().unwrap();
+            let test_uri = test_dir.path().to_str().unwrap();
+
+            let schema = Arc::new(Schema::new(vec![
+                Field::new("key", DataType::UInt32, false),
+                Field::new("value", DataType::UInt32, false),
+                Field::new("label", DataType::Utf8, true),
+            ]));
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(UInt32Array::from_iter_values(0..100)),
+                    Arc::new(UInt32Array::from_iter_values(100..200)),
+                    Arc::new(StringArray::from_iter_values((0..100).map(|_| "old"))),
+                ],
+            )
+            .unwrap();
+            let write_params = WriteParams {
+                max_rows_per_file: 50,
+                ..Default::default()
+            };
+            let batches = RecordBatchIterator::new([Ok(batch)], schema.clone());
+            let mut ds = Dataset::write(batches, test_uri, Some(write_params))
+                .await
+                .unwrap();
+            if use_index {
+                ds.create_index(
+                    &["key"],
+                    IndexType::Scalar,
+                    None,
+                    &ScalarIndexParams::default(),
+                    false,
+                )
+                .await
+                .unwrap();
+            }
+            let ds = Arc::new(ds);
+
+            let source_schema = Arc::new(Schema::new(vec![
+                Field::new("label", DataType::Utf8, true),
+                Field::new("key", DataType::UInt32, false),
+            ]));
+            let source = |keys: Vec<u32>, label: &str| {
+                let batch = RecordBatch::try_new(
+                    source_schema.clone(),
+                    vec![
+                        Arc::new(StringArray::from_iter_values(keys.iter().map(|_| label))),
+                        Arc::new(UInt32Array::from(keys)),
+                    ],
+                )
+                .unwrap();
+                Box::new(RecordBatchIterator::new([Ok(batch)], source_schema.clone()))
+            };
+            let builder = |ds: Arc<Dataset>| {
+                let mut builder = MergeInsertBuilder::try_new(ds, vec!["key".to_string()]).unwrap();
+                builder
+                    .when_matched(WhenMatched::UpdateAll)
+                    .when_not_matched(WhenNotMatched::DoNothing);
+                builder
+            };
+
+            // Only rows of the second fragment are updated, 200 does not exist
+            let (ds, _) = builder(ds.clone())
+                .try_build()
+                .unwrap()
+                .execute_reader(source(vec![60, 70, 200], "new"))
+                .await
+                .unwrap();
+
+            let fragments = ds.get_fragments().await.unwrap();
+            assert_eq!(fragments.len(), 2);
+            assert_eq!(fragments[0].metadata.files.len(), 1);
+            assert_eq!(fragments[1].metadata.files.len(), 2);
+            assert!(fragments.iter().all(|f| f.metadata.deletion_file.is_none()));
+            assert_ne!(ds.manifest.reader_feature_flags & FLAG_REPLACED_FIELDS, 0);
+
+            let batch = ds.scan().with_row_id().try_into_batch().await.unwrap();
+            assert_eq!(batch.num_rows(), 100);
+            assert_eq!(
+                batch["value"].as_primitive::<UInt32Type>().values(),
+                &(100..200).collect::<Vec<_>>()
+            );
+            let row_ids = batch[ROW_ID].as_primitive::<UInt64Type>();
+            assert_eq!(row_ids.value(60), (1 << 32) + 10);
+            let labels = batch["label"].as_string::<i32>();
+            for (i, label) in labels.iter().enumerate() {
+                let expected = if i == 60 || i == 70 { "new" } else { "old" };
+                assert_eq!(label, Some(expected));
+            }
+
+            // Updating all the rows of the partial file again replaces it
+            let (ds, _) = builder(ds.clone())
+                .try_build()
+                .unwrap()
+                .execute_reader(source((50..100).collect(), "newer"))
+                .await
+                .unwrap();
+            let fragments = ds.get_fragments().await.unwrap();
+            assert_eq!(fragments[1].metadata.files.len(), 2);
+            let batch = ds.scan().try_into_batch().await.unwrap();
+            let labels = batch["label"].as_string::<i32>();
+            assert_eq!(labels.value(40), "old");
+            assert_eq!(labels.value(60), "newer");
+
+            // A partial source cannot insert rows
+            let mut builder =
+                MergeInsertBuilder::try_new(ds.clone(), vec!["key".to_string()]).unwrap();
+            builder.when_matched(WhenMatched::UpdateAll);
+            let result = builder
+                .try_build()
+                .unwrap()
+                .execute_reader(source(vec![1], "new"))
+                .await;
+            assert!(matches!(result, Err(Error::InvalidInput { .. })));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_partial_merge_insert_indexed_column() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("key", DataType::UInt32, false),
+            Field::new("value", DataType::UInt32, false),
+            Field::new("label", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(UInt32Array::from_iter_values(0..100)),
+                Arc::new(UInt32Array::from_iter_values(100..200)),
+                Arc::new(StringArray::from_iter_values((0..100).map(|_| "old"))),
+            ],
+        )
+        .unwrap();
+        let batches = RecordBatchIterator::new([Ok(batch)], schema.clone());
+        let mut ds = Dataset::write(batches, test_uri, None).await.unwrap();
+        ds.create_index(
+            &["value"],
+            IndexType::Scalar,
+            None,
+            &ScalarIndexParams::default(),
+            false,
+        )
+        .await
+        .unwrap();
+
+        let source_schema = Arc::new(Schema::new(vec![
+            Field::new("key", DataType::UInt32, false),
+            Field::new("value", DataType::UInt32, false),
+        ]));
+        let source = RecordBatch::try_new(
+            source_schema.clone(),
+            vec![
+                Arc::new(UInt32Array::from(vec![1])),
+                Arc::new(UInt32Array::from(vec![0])),
+            ],
+        )
+        .unwrap();
+        let result = MergeInsertBuilder::try_new(Arc::new(ds), vec!["key".to_string()])
+            .unwrap()
+            .when_matched(WhenMatched::UpdateAll)
+            .when_not_matched(WhenNotMatched::DoNothing)
+            .try_build()
+            .unwrap()
+            .execute_reader(Box::new(RecordBatchIterator::new(
+                [Ok(source)],
+                source_schema,
+            )))
+            .await;
+        assert!(matches!(result, Err(Error::InvalidInput { .. })));
+    }
 }
@@ -0,0 +1,586 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Primary keys, see [`Dataset::set_primary_key`].
+//!
+//! The primary key is stored in the metadata of the schema, under [`PRIMARY_KEY_META_KEY`].
+//! When its uniqueness is enforced, every transaction that adds fragments is checked when it is
+//! committed: the keys of the new rows are checked for duplicates within the new rows and against
+//! the rows of the dataset, which are looked up with a scalar index on one of the key columns.
+//! This includes the rows committed concurrently, which makes a duplicate a commit conflict.
+//!
+//! [`PRIMARY_KEY_META_KEY`]: lance_core::datatypes::PRIMARY_KEY_META_KEY
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use arrow::compute::cast;
+use arrow::util::display::array_value_to_string;
+use arrow_array::{Array, ArrayRef, RecordBatch};
+use arrow_row::{OwnedRow, RowConverter, SortField};
+use arrow_schema::DataType;
+use datafusion::logical_expr::{col, lit, Expr};
+use datafusion::scalar::ScalarValue;
+use futures::TryStreamExt;
+use lance_core::datatypes::Schema;
+use lance_core::{Error, Result};
+use lance_index::DatasetIndexExt;
+use lance_table::format::{Fragment, Manifest};
+use snafu::{location, Location};
+
+use crate::dataset::fragment::FileFragment;
+use crate::dataset::transaction::{Operation, Transaction};
+use crate::Dataset;
+
+// Existing keys are looked up with this many values of the indexed column at a time
+const KEY_LOOKUP_CHUNK_SIZE: usize = 1024;
+
+/// Checks that `columns` can be the primary key of a dataset with `schema`
+pub fn validate_primary_key(schema: &Schema, columns: &[&str]) -> Result<()> {
+    let mut seen = HashSet::new();
+    for column in columns {
+        if !seen.insert(*column) {
+            return Err(Error::invalid_input(
+                format!("Column \"{}\" is repeated in the primary key", column),
+                location!(),
+            ));
+        }
+        let Some(field) = schema.fields.iter().find(|f| &f.name == column) else {
+            return Err(Error::invalid_input(
+                format!(
+                    "Primary key column \"{}\" is not a top-level column of the dataset",
+                    column
+                ),
+                location!(),
+            ));
+        };
+        if field.data_type().is_nested() {
+            return Err(Error::invalid_input(
+                format!(
+                    "Primary key column \"{}\" has the nested type {}",
+                    column,
+                    field.data_type()
+                ),
+                location!(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The primary keys of a set of rows
+struct Keys {
+    columns: Vec<String>,
+    converter: RowConverter,
+    // The types the key columns are converted with
+    types: Vec<DataType>,
+    keys: HashSet<OwnedRow>,
+}
+
+impl Keys {
+    fn try_new(schema: &Schema, columns: &[&str]) -> Result<Self> {
+        let types = columns
+            .iter()
+            .map(|c| {
+                schema.field(c).map(|f| f.data_type()).ok_or_else(|| {
+                    Error::invalid_input(
+                        format!("Primary key column \"{}\" does not exist in the dataset", c),
+                        location!(),
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let converter = RowConverter::new(types.iter().cloned().map(SortField::new).collect())?;
+        Ok(Self {
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            converter,
+            types,
+            keys: HashSet::new(),
+        })
+    }
+
+    /// The key columns of `batch`, cast to the types of the keys
+    fn key_columns(&self, batch: &RecordBatch) -> Result<Vec<ArrayRef>> {
+        self.columns
+            .iter()
+            .zip(&self.types)
+            .map(|(name, data_type)| {
+                let column = batch.column_by_name(name).ok_or_else(|| Error::Internal {
+                    message: format!("Primary key column \"{}\" was not read", name),
+                    location: location!(),
+                })?;
+                if column.null_count() > 0 {
+                    return Err(Error::invalid_input(
+                        format!("Primary key column \"{}\" cannot contain nulls", name),
+                        location!(),
+                    ));
+                }
+                if column.data_type() == data_type {
+                    Ok(column.clone())
+                } else {
+                    Ok(cast(column, data_type)?)
+                }
+            })
+            .collect()
+    }
+
+    fn display(&self, columns: &[ArrayRef], row: usize) -> String {
+        let values = self
+            .columns
+            .iter()
+            .zip(columns)
+            .map(|(name, column)| {
+                let value = array_value_to_string(column, row).unwrap_or_default();
+                format!("{}={}", name, value)
+            })
+            .collect::<Vec<_>>();
+        format!("({})", values.join(", "))
+    }
+
+    /// Adds the keys of `batch`, returning the first one that was already present
+    fn insert_batch(&mut self, batch: &RecordBatch) -> Result<Option<String>> {
+        let columns = self.key_columns(batch)?;
+        let rows = self.converter.convert_columns(&columns)?;
+        for (i, row) in rows.iter().enumerate() {
+            if !self.keys.insert(row.owned()) {
+                return Ok(Some(self.display(&columns, i)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the first key of `batch` that is present
+    fn find_batch(&self, batch: &RecordBatch) -> Result<Option<String>> {
+        let columns = self.key_columns(batch)?;
+        let rows = self.converter.convert_columns(&columns)?;
+        Ok(rows
+            .iter()
+            .position(|row| self.keys.contains(&row.owned()))
+            .map(|i| self.display(&columns, i)))
+    }
+}
+
+/// Reads the key columns of `fragments` of `dataset`
+async fn read_keys(
+    dataset: &Arc<Dataset>,
+    fragments: &[Fragment],
+    columns: &[&str],
+) -> Result<Vec<RecordBatch>> {
+    let mut batches = Vec::new();
+    for fragment in fragments {
+        let fragment = FileFragment::new(dataset.clone(), fragment.clone());
+        let mut scanner = fragment.scan();
+        scanner.project(columns)?;
+        batches.extend(
+            scanner
+                .try_into_stream()
+                .await?
+                .try_collect::<Vec<_>>()
+                .await?,
+        );
+    }
+    Ok(batches)
+}
+
+/// The primary key column with a scalar index, used to look up existing keys
+async fn indexed_key_column<'a>(dataset: &Dataset, columns: &[&'a str]) -> Result<&'a str> {
+    for column in columns {
+        if dataset
+            .load_scalar_index_for_column(column)
+            .await?
+            .is_some()
+        {
+            return Ok(column);
+        }
+    }
+    Err(Error::invalid_input(
+        format!(
+            "Enforcing the primary key ({}) requires a scalar index on one of its columns",
+            columns.join(", ")
+        ),
+        location!(),
+    ))
+}
+
+/// Checks that the rows of `dataset` have unique primary keys
+///
+/// Also checks that a key column has a scalar index, which enforcing the key needs.
+pub async fn check_unique(dataset: &Dataset, columns: &[&str]) -> Result<()> {
+    indexed_key_column(dataset, columns).await?;
+    let mut keys = Keys::try_new(dataset.schema(), columns)?;
+    let mut scanner = dataset.scan();
+    scanner.project(columns)?;
+    let mut stream = scanner.try_into_stream().await?;
+    while let Some(batch) = stream.try_next().await? {
+        if let Some(key) = keys.insert_batch(&batch)? {
+            return Err(Error::invalid_input(
+                format!("Duplicate primary key {} in the dataset", key),
+                location!(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the first key of `keys` found in `fragments` of `dataset`
+///
+/// The rows are looked up with the `values` of the indexed key column.
+async fn find_keys(
+    dataset: &Dataset,
+    fragments: Vec<Fragment>,
+    columns: &[&str],
+    indexed_column: &str,
+    values: &[ScalarValue],
+    keys: &Keys,
+) -> Result<Option<String>> {
+    if fragments.is_empty() {
+        return Ok(None);
+    }
+    for chunk in values.chunks(KEY_LOOKUP_CHUNK_SIZE) {
+        let filter: Expr =
+            col(indexed_column).in_list(chunk.iter().cloned().map(lit).collect(), false);
+        let mut scanner = dataset.scan();
+        scanner.with_fragments(fragments.clone());
+        scanner.project(columns)?;
+        scanner.filter_expr(filter);
+        let mut stream = scanner.try_into_stream().await?;
+        while let Some(batch) = stream.try_next().await? {
+            if let Some(key) = keys.find_batch(&batch)? {
+                return Ok(Some(key));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Checks that the fragments added by `transaction` do not create duplicate primary keys, if
+/// the dataset enforces its primary key
+///
+/// `dataset` is the latest version of the dataset and `manifest` the one built by applying the
+/// transaction to it.  This covers appends, and the rows inserted by updates and merges.  The
+/// new fragments are read with the schema of `manifest`, which differs from the one of the
+/// dataset when the transaction evolves it, and the existing rows with its deletions, so that
+/// the rows an update rewrites keep their keys.  A key that was added since the transaction
+/// read the dataset is a commit conflict.
+pub async fn check_added_keys(
+    dataset: &Dataset,
+    transaction: &Transaction,
+    manifest: &Manifest,
+) -> Result<()> {
+    if !manifest.schema.primary_key_enforced()
+        || !matches!(
+            transaction.operation,
+            Operation::Append { .. } | Operation::Update { .. } | Operation::Merge { .. }
+        )
+    {
+        return Ok(());
+    }
+    let existing_ids = dataset
+        .manifest
+        .fragments
+        .iter()
+        .map(|f| f.id)
+        .collect::<HashSet<_>>();
+    let (new_fragments, old_fragments): (Vec<_>, Vec<_>) = manifest
+        .fragments
+        .iter()
+        .cloned()
+        .partition(|f| !existing_ids.contains(&f.id));
+    if new_fragments.is_empty() {
+        return Ok(());
+    }
+    let columns = manifest.schema.primary_key();
+    let indexed_column = indexed_key_column(dataset, &columns).await?;
+
+    // The indices are read from the manifest file of the latest version
+    let mut manifest = manifest.clone();
+    manifest.version = dataset.manifest.version;
+    manifest.index_section = dataset.manifest.index_section;
+    let new_dataset = Arc::new(Dataset {
+        manifest: Arc::new(manifest),
+        ..dataset.clone()
+    });
+    let batches = read_keys(&new_dataset, &new_fragments, &columns).await?;
+
+    let mut keys = Keys::try_new(new_dataset.schema(), &columns)?;
+    for batch in &batches {
+        if let Some(key) = keys.insert_batch(batch)? {
+            return Err(Error::invalid_input(
+                format!("Duplicate primary key {} in the appended data", key),
+                location!(),
+            ));
+        }
+    }
+
+    let mut values = Vec::new();
+    let mut seen = HashSet::new();
+    for batch in &batches {
+        let column = batch.column_by_name(indexed_column).unwrap();
+        for i in 0..column.len() {
+            let value = ScalarValue::try_from_array(column, i)?;
+            if seen.insert(value.clone()) {
+                values.push(value);
+            }
+        }
+    }
+
+    // The fragments committed since the transaction read the dataset
+    let concurrent_ids = if dataset.manifest.version > transaction.read_version {
+        let read_dataset = dataset.checkout_version(transaction.read_version).await?;
+        let read_ids = read_dataset
+            .manifest
+            .fragments
+            .iter()
+            .map(|f| f.id)
+            .collect::<HashSet<_>>();
+        existing_ids
+            .difference(&read_ids)
+            .copied()
+            .collect::<HashSet<_>>()
+    } else {
+        HashSet::new()
+    };
+    let (concurrent_fragments, old_fragments): (Vec<_>, Vec<_>) = old_fragments
+        .into_iter()
+        .partition(|f| concurrent_ids.contains(&f.id));
+
+    let find = |fragments| {
+        find_keys(
+            &new_dataset,
+            fragments,
+            &columns,
+            indexed_column,
+            &values,
+            &keys,
+        )
+    };
+    if let Some(key) = find(old_fragments).await? {
+        return Err(Error::invalid_input(
+            format!("Primary key {} already exists in the dataset", key),
+            location!(),
+        ));
+    }
+    if let Some(key) = find(concurrent_fragments).await? {
+        return Err(Error::CommitConflict {
+            version: dataset.manifest.version,
+            source: format!(
+                "Primary key {} was also added by a concurrent transaction",
+                key
+            )
+            .into(),
+            location: location!(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{Int32Array, RecordBatchIterator};
+    use arrow_schema::{Field as ArrowField, Schema as ArrowSchema};
+    use lance_index::IndexType;
+    use tempfile::tempdir;
+
+    use crate::dataset::{
+        write_fragments, MergeInsertBuilder, WhenMatched, WriteMode, WriteParams,
+    };
+    use crate::index::scalar::ScalarIndexParams;
+
+    fn rows(
+        schema: &Arc<ArrowSchema>,
+        ids: Vec<i32>,
+        values: Vec<i32>,
+    ) -> Box<dyn arrow_array::RecordBatchReader + Send> {
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(ids)),
+                Arc::new(Int32Array::from(values)),
+            ],
+        )
+        .unwrap();
+        Box::new(RecordBatchIterator::new(vec![Ok(batch)], schema.clone()))
+    }
+
+    fn reader(
+        schema: &Arc<ArrowSchema>,
+        ids: Vec<i32>,
+    ) -> Box<dyn arrow_array::RecordBatchReader + Send> {
+        let values = ids.iter().map(|id| id * 10).collect();
+        rows(schema, ids, values)
+    }
+
+    #[tokio::test]
+    async fn test_primary_key() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("id", DataType::Int32, false),
+            ArrowField::new("value", DataType::Int32, false),
+        ]));
+        let mut dataset = Dataset::write(reader(&schema, vec![1, 2, 3]), test_uri, None)
+            .await
+            .unwrap();
+
+        let err = dataset.set_primary_key(&["missing"], false).await;
+        assert!(matches!(err, Err(Error::InvalidInput { .. })));
+
+        // Merge insert matches rows on the primary key by default
+        dataset.set_primary_key(&["id"], false).await.unwrap();
+        assert_eq!(dataset.schema().primary_key(), vec!["id"]);
+        let (merged, _) = MergeInsertBuilder::try_new(Arc::new(dataset.clone()), vec![])
+            .unwrap()
+            .when_matched(WhenMatched::UpdateAll)
+            .try_build()
+            .unwrap()
+            .execute_reader(reader(&schema, vec![3, 4]))
+            .await
+            .unwrap();
+        assert_eq!(merged.count_rows(None).await.unwrap(), 4);
+        let mut dataset = merged.as_ref().clone();
+
+        // Not enforced, duplicates can be appended
+        dataset
+            .append(reader(&schema, vec![1]), None)
+            .await
+            .unwrap();
+        let err = dataset.set_primary_key(&["id"], true).await.unwrap_err();
+        assert!(
+            err.to_string().contains("requires a scalar index"),
+            "{}",
+            err
+        );
+        dataset
+            .create_index(
+                &["id"],
+                IndexType::Scalar,
+                None,
+                &ScalarIndexParams::default(),
+                false,
+            )
+            .await
+            .unwrap();
+        let err = dataset.set_primary_key(&["id"], true).await.unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Duplicate primary key (id=1) in the dataset"),
+            "{}",
+            err
+        );
+        dataset.delete("id = 1").await.unwrap();
+        dataset.set_primary_key(&["id"], true).await.unwrap();
+        assert!(dataset.schema().primary_key_enforced());
+
+        let err = dataset
+            .append(reader(&schema, vec![5, 6, 5]), None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }));
+        assert!(
+            err.to_string()
+                .contains("Duplicate primary key (id=5) in the appended data"),
+            "{}",
+            err
+        );
+        let err = dataset
+            .append(reader(&schema, vec![5, 4]), None)
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Primary key (id=4) already exists in the dataset"),
+            "{}",
+            err
+        );
+        dataset
+            .append(reader(&schema, vec![5, 6]), None)
+            .await
+            .unwrap();
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 5);
+
+        // Concurrent appends of the same key conflict, others still commit
+        let mut other = dataset.clone();
+        let mut third = dataset.clone();
+        dataset
+            .append(reader(&schema, vec![7, 8]), None)
+            .await
+            .unwrap();
+        let err = other
+            .append(reader(&schema, vec![9, 8]), None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::CommitConflict { .. }), "{}", err);
+        assert!(err.to_string().contains("(id=8) was also added"), "{}", err);
+        third.append(reader(&schema, vec![10]), None).await.unwrap();
+        assert_eq!(third.count_rows(None).await.unwrap(), 8);
+
+        // Fragments committed directly are checked too
+        let fragments = write_fragments(
+            test_uri,
+            reader(&schema, vec![10]),
+            WriteParams {
+                mode: WriteMode::Append,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        let err = Dataset::commit(
+            test_uri,
+            Operation::Append { fragments },
+            Some(third.version().version),
+            None,
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Primary key (id=10) already exists in the dataset"),
+            "{}",
+            err
+        );
+
+        // So are the rows inserted by a merge insert, which are committed as an update
+        let insert = |dataset: &Dataset, on: &str, source| {
+            let dataset = Arc::new(dataset.clone());
+            let on = vec![on.to_string()];
+            async move {
+                MergeInsertBuilder::try_new(dataset, on)
+                    .unwrap()
+                    .try_build()
+                    .unwrap()
+                    .execute_reader(source)
+                    .await
+            }
+        };
+        let err = insert(&third, "value", rows(&schema, vec![10], vec![0]))
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Primary key (id=10) already exists in the dataset"),
+            "{}",
+            err
+        );
+        let (inserted, _) = insert(&third, "id", reader(&schema, vec![11]))
+            .await
+            .unwrap();
+        let err = insert(&third, "id", reader(&schema, vec![12, 11]))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::CommitConflict { .. }), "{}", err);
+        assert_eq!(inserted.count_rows(None).await.unwrap(), 9);
+        let mut third = inserted.as_ref().clone();
+
+        let err = third.drop_columns(&["id"]).await.unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }));
+
+        third.set_primary_key(&[], false).await.unwrap();
+        assert!(third.schema().primary_key().is_empty());
+        third.append(reader(&schema, vec![10]), None).await.unwrap();
+    }
+}
@@ -0,0 +1,247 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Schema evolution when appending data, see [`SchemaEvolution`].
+//!
+//! New columns are only added to the schema of the dataset.  The existing fragments do not
+//! have data files for them and so read them as nulls.  Columns that are widened are rewritten
+//! in every existing fragment, like [`Dataset::alter_columns`] does.
+//!
+//! Only the merge operation can change the schema of the dataset, so an append that changes
+//! it is committed as a merge.  That conflicts with any concurrent append, delete, update or
+//! other schema change, and the commit fails with a conflict instead of being rebased.
+
+use std::sync::Arc;
+
+use arrow::compute::CastOptions;
+use arrow_array::RecordBatch;
+use arrow_schema::{ArrowError, DataType, Field as ArrowField, Schema as ArrowSchema};
+use datafusion::error::DataFusionError;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::SendableRecordBatchStream;
+use futures::StreamExt;
+use lance_core::datatypes::{Field, Schema};
+use lance_core::{Error, Result};
+use lance_io::object_store::ObjectStore;
+use lance_table::format::Fragment;
+use snafu::{location, Location};
+
+use super::{write_fragments_internal, SchemaEvolution, WriteParams};
+use crate::dataset::transaction::{Operation, Transaction};
+use crate::Dataset;
+
+/// The changes to the schema of a dataset needed to append data to it
+#[derive(Debug, Clone)]
+pub struct SchemaChanges {
+    /// The schema of the dataset after the append
+    pub schema: Schema,
+    /// The `(old, new)` fields of the columns that are widened
+    cast_fields: Vec<(Field, Field)>,
+    /// True if columns are added to the dataset
+    has_new_columns: bool,
+}
+
+impl SchemaChanges {
+    fn changes_dataset_schema(&self) -> bool {
+        self.has_new_columns || !self.cast_fields.is_empty()
+    }
+}
+
+/// Returns true if every value of type `from` can be converted to type `to` without loss
+fn is_widening(from: &DataType, to: &DataType) -> bool {
+    use DataType::*;
+    matches!(
+        (from, to),
+        (Int8, Int16 | Int32 | Int64)
+            | (Int16, Int32 | Int64)
+            | (Int32, Int64)
+            | (UInt8, UInt16 | UInt32 | UInt64 | Int16 | Int32 | Int64)
+            | (UInt16, UInt32 | UInt64 | Int32 | Int64)
+            | (UInt32, UInt64 | Int64)
+            | (Float16, Float32 | Float64)
+            | (Float32, Float64)
+            | (Utf8, LargeUtf8)
+            | (Binary, LargeBinary)
+    )
+}
+
+/// Computes the changes to the schema of `dataset` needed to append data with schema `source`
+///
+/// Returns `None` if the policy is strict or the schemas have the same columns and types, in
+/// which case the data is appended as usual.
+pub fn evolve_schema(
+    dataset: &Dataset,
+    source: &Schema,
+    policy: SchemaEvolution,
+) -> Result<Option<SchemaChanges>> {
+    if policy == SchemaEvolution::Strict {
+        return Ok(None);
+    }
+
+    let mut schema = dataset.schema().clone();
+    let mut cast_fields = Vec::new();
+    let mut new_fields = Vec::new();
+    let mut needs_cast = false;
+    let mut next_field_id = dataset.manifest.max_field_id() + 1;
+
+    for source_field in &source.fields {
+        let Some(field) = dataset.schema().field(&source_field.name) else {
+            if !source_field.nullable {
+                return Err(Error::invalid_input(
+                    format!(
+                        "Cannot add column \"{}\" to the dataset because it is not nullable",
+                        source_field.name
+                    ),
+                    location!(),
+                ));
+            }
+            new_fields.push(ArrowField::from(source_field));
+            continue;
+        };
+
+        let (from, to) = (field.data_type(), source_field.data_type());
+        if from == to {
+            continue;
+        }
+        if policy == SchemaEvolution::WidenTypes {
+            if is_widening(&from, &to) {
+                let field_dest = schema.mut_field_by_id(field.id).unwrap();
+                let arrow_field = ArrowField::new(field.name.clone(), to, field.nullable);
+                *field_dest = Field::try_from(&arrow_field)?;
+                field_dest.set_id(field.parent_id, &mut next_field_id);
+                cast_fields.push((field.clone(), field_dest.clone()));
+                continue;
+            }
+            if is_widening(&to, &from) {
+                needs_cast = true;
+                continue;
+            }
+        }
+        return Err(Error::invalid_input(
+            format!(
+                "Cannot append column \"{}\" of type {} to the dataset, where its type is {}",
+                field.name, to, from
+            ),
+            location!(),
+        ));
+    }
+
+    if new_fields.is_empty() && cast_fields.is_empty() && !needs_cast {
+        return Ok(None);
+    }
+
+    for field in &dataset.schema().fields {
+        if source.field(&field.name).is_none() {
+            return Err(Error::invalid_input(
+                format!(
+                    "Column \"{}\" of the dataset is missing from the appended data",
+                    field.name
+                ),
+                location!(),
+            ));
+        }
+    }
+
+    let has_new_columns = !new_fields.is_empty();
+    if has_new_columns {
+        schema = schema.merge(&ArrowSchema::new(new_fields))?;
+        schema.set_field_id(Some(next_field_id - 1));
+    }
+    schema.validate()?;
+
+    Ok(Some(SchemaChanges {
+        schema,
+        cast_fields,
+        has_new_columns,
+    }))
+}
+
+/// Casts the columns of `batch` to the types of `schema`
+fn cast_to_schema(
+    batch: RecordBatch,
+    schema: &Arc<ArrowSchema>,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            let column = batch.column_by_name(field.name()).ok_or_else(|| {
+                ArrowError::SchemaError(format!("Column {} is missing", field.name()))
+            })?;
+            if column.data_type() == field.data_type() {
+                Ok(column.clone())
+            } else {
+                lance_arrow::cast::cast_with_options(
+                    column,
+                    field.data_type(),
+                    &CastOptions {
+                        safe: false,
+                        ..Default::default()
+                    },
+                )
+            }
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    RecordBatch::try_new(schema.clone(), columns)
+}
+
+/// Writes `data` to new fragments and returns the transaction that appends them to `dataset`
+/// and applies the schema `changes`
+pub async fn append_transaction(
+    dataset: &Dataset,
+    object_store: Arc<ObjectStore>,
+    changes: SchemaChanges,
+    data: SendableRecordBatchStream,
+    params: &WriteParams,
+) -> Result<Transaction> {
+    let arrow_schema = Arc::new(ArrowSchema::from(&changes.schema));
+    let stream_schema = arrow_schema.clone();
+    let data =
+        data.map(move |batch| cast_to_schema(batch?, &arrow_schema).map_err(DataFusionError::from));
+    let data = Box::pin(RecordBatchStreamAdapter::new(stream_schema, data));
+
+    // The data is written with the new schema, and so without the dataset to take the field
+    // ids from.
+    let new_fragments = write_fragments_internal(
+        None,
+        object_store,
+        &dataset.base,
+        &changes.schema,
+        data,
+        params.clone(),
+    )
+    .await?;
+
+    // Changing the schema needs a merge, which conflicts with concurrent appends
+    let operation = if changes.changes_dataset_schema() {
+        let mut fragments = if changes.cast_fields.is_empty() {
+            dataset.manifest.fragments.as_ref().clone()
+        } else {
+            dataset
+                .cast_columns_impl(changes.cast_fields, &changes.schema)
+                .await?
+        };
+        // Merge keeps the fragment ids it is given, so the new fragments need theirs here
+        let first_id = dataset
+            .manifest
+            .max_fragment_id()
+            .map(|id| id + 1)
+            .unwrap_or(0);
+        fragments.extend(
+            new_fragments
+                .into_iter()
+                .zip(first_id..)
+                .map(|(fragment, id)| Fragment { id, ..fragment }),
+        );
+        Operation::Merge {
+            fragments,
+            schema: changes.schema,
+        }
+    } else {
+        Operation::Append {
+            fragments: new_fragments,
+        }
+    };
+
+    Ok(Transaction::new(dataset.manifest.version, operation, None))
+}
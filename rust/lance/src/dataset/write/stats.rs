@@ -0,0 +1,177 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Statistics and old row output for operations that modify existing rows.
+//!
+//! Delete, update and merge insert report an [`OperationStats`] describing what they changed.
+//! They can also be given a [`RowSink`], which receives the rows that are deleted, or the old
+//! version of the rows that are updated, while the operation runs and before it is committed.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use arrow_array::{RecordBatch, UInt64Array};
+use async_trait::async_trait;
+use futures::{stream, StreamExt, TryStreamExt};
+use lance_core::{Result, ROW_ID_FIELD};
+use lance_table::format::Fragment;
+use lance_table::io::deletion::deletion_file_path;
+use object_store::path::Path;
+use roaring::RoaringTreemap;
+
+use crate::Dataset;
+
+/// Statistics about an operation that modified a dataset
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct OperationStats {
+    /// Number of rows deleted, updated or inserted
+    pub num_rows_affected: u64,
+    /// Number of fragments modified, removed or created
+    pub num_fragments_touched: u64,
+    /// Total size of the data and deletion files written
+    pub bytes_written: u64,
+    /// Number of data and deletion files written
+    pub num_files_added: u64,
+    /// Number of data and deletion files no longer referenced by the new version
+    pub num_files_removed: u64,
+}
+
+/// Receives rows removed by an operation while it runs
+///
+/// Each batch has the columns of the dataset followed by the `_rowid` of the row.  A delete
+/// sends the deleted rows, an update or merge insert also sends the old version of each updated
+/// row.  If the sink returns an error the operation fails and nothing is committed.
+#[async_trait]
+pub trait RowSink: Send + Sync {
+    async fn write_batch(&self, batch: RecordBatch) -> Result<()>;
+}
+
+impl std::fmt::Debug for dyn RowSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RowSink")
+    }
+}
+
+// Rows are taken from the dataset in chunks of this many rows
+const SINK_TAKE_CHUNK_SIZE: usize = 8192;
+
+impl OperationStats {
+    /// Computes the file statistics of a change to the fragments of `dataset`
+    ///
+    /// The number of rows affected is left at zero, it is only known by the operation.
+    pub(crate) async fn from_changes(
+        dataset: &Dataset,
+        removed_fragment_ids: &[u64],
+        updated_fragments: &[Fragment],
+        new_fragments: &[Fragment],
+    ) -> Result<Self> {
+        let old_fragments = dataset
+            .manifest
+            .fragments
+            .iter()
+            .map(|f| (f.id, f))
+            .collect::<HashMap<_, _>>();
+        let mut added = Vec::new();
+        let mut num_files_removed = 0;
+
+        for id in removed_fragment_ids {
+            if let Some(old) = old_fragments.get(id) {
+                num_files_removed += Self::fragment_files(dataset, old).len();
+            }
+        }
+        for fragment in updated_fragments {
+            let new_files = Self::fragment_files(dataset, fragment);
+            match old_fragments.get(&fragment.id) {
+                Some(old) => {
+                    let old_files = Self::fragment_files(dataset, old);
+                    num_files_removed += old_files.difference(&new_files).count();
+                    added.extend(new_files.difference(&old_files).cloned());
+                }
+                None => added.extend(new_files),
+            }
+        }
+        for fragment in new_fragments {
+            added.extend(Self::fragment_files(dataset, fragment));
+        }
+
+        let object_store = dataset.object_store.clone();
+        let bytes_written = stream::iter(added.clone())
+            .map(|path| {
+                let object_store = object_store.clone();
+                async move { object_store.size(&path).await }
+            })
+            .buffer_unordered(num_cpus::get())
+            .try_fold(0, |acc, size| async move { Ok(acc + size as u64) })
+            .await?;
+
+        Ok(Self {
+            num_rows_affected: 0,
+            num_fragments_touched: (removed_fragment_ids.len()
+                + updated_fragments.len()
+                + new_fragments.len()) as u64,
+            bytes_written,
+            num_files_added: added.len() as u64,
+            num_files_removed: num_files_removed as u64,
+        })
+    }
+
+    fn fragment_files(dataset: &Dataset, fragment: &Fragment) -> HashSet<Path> {
+        let mut files = fragment
+            .files
+            .iter()
+            .map(|f| dataset.data_dir().child(f.path.as_str()))
+            .collect::<HashSet<_>>();
+        if let Some(deletion_file) = &fragment.deletion_file {
+            files.insert(deletion_file_path(
+                &dataset.base,
+                fragment.id,
+                deletion_file,
+            ));
+        }
+        files
+    }
+}
+
+/// Takes the given rows of `dataset` and sends them, with their row ids, to `sink`
+pub async fn send_rows_to_sink(
+    dataset: &Dataset,
+    row_ids: &RoaringTreemap,
+    sink: &dyn RowSink,
+) -> Result<()> {
+    let row_ids = row_ids.iter().collect::<Vec<_>>();
+    for chunk in row_ids.chunks(SINK_TAKE_CHUNK_SIZE) {
+        let batch = dataset.take_rows(chunk, dataset.schema()).await?;
+        sink.write_batch(with_row_ids(batch, chunk)?).await?;
+    }
+    Ok(())
+}
+
+fn with_row_ids(batch: RecordBatch, row_ids: &[u64]) -> Result<RecordBatch> {
+    let mut fields = batch.schema().fields().to_vec();
+    fields.push(Arc::new(ROW_ID_FIELD.clone()));
+    let mut columns = batch.columns().to_vec();
+    columns.push(Arc::new(UInt64Array::from(row_ids.to_vec())));
+    Ok(RecordBatch::try_new(
+        Arc::new(arrow_schema::Schema::new(fields)),
+        columns,
+    )?)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use std::sync::Mutex;
+
+    /// A sink collecting the batches in memory
+    #[derive(Default)]
+    pub struct VecSink(pub Mutex<Vec<RecordBatch>>);
+
+    #[async_trait]
+    impl RowSink for VecSink {
+        async fn write_batch(&self, batch: RecordBatch) -> Result<()> {
+            self.0.lock().unwrap().push(batch);
+            Ok(())
+        }
+    }
+}
@@ -5,17 +5,19 @@ use std::collections::{BTreeMap, HashMap};
 use std::sync::{Arc, RwLock};
 
 use super::super::utils::make_rowid_capture_stream;
+use super::constraints::check_constraints;
+use super::stats::{OperationStats, RowSink};
 use super::write_fragments_internal;
 use arrow_array::RecordBatch;
-use arrow_schema::{ArrowError, DataType, Schema as ArrowSchema};
+use arrow_schema::{ArrowError, DataType, Schema as ArrowSchema, SchemaRef};
 use datafusion::common::DFSchema;
 use datafusion::error::{DataFusionError, Result as DFResult};
 use datafusion::logical_expr::ExprSchemable;
 use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
-use datafusion::physical_plan::PhysicalExpr;
+use datafusion::physical_plan::{PhysicalExpr, SendableRecordBatchStream};
 use datafusion::prelude::Expr;
 use datafusion::scalar::ScalarValue;
-use futures::StreamExt;
+use futures::{StreamExt, TryStreamExt};
 use lance_arrow::RecordBatchExt;
 use lance_core::error::{box_error, InvalidInputSnafu};
 use lance_datafusion::expr::safe_coerce_scalar;
@@ -24,7 +26,7 @@ use roaring::RoaringTreemap;
 use snafu::{location, Location, ResultExt};
 
 use crate::dataset::transaction::{Operation, Transaction};
-use crate::io::commit::commit_transaction;
+use crate::io::commit::{commit_transaction, CommitConfig};
 use crate::{io::exec::Planner, Dataset};
 use crate::{Error, Result};
 
@@ -41,7 +43,8 @@ use crate::{Error, Result};
 ///     .set("region_name", "New York")
 ///     .build()?
 ///     .execute()
-///     .await?;
+///     .await?
+///     .0;
 /// ```
 ///
 #[derive(Debug, Clone)]
@@ -52,6 +55,10 @@ pub struct UpdateBuilder {
     condition: Option<Expr>,
     /// The updates to apply to matching rows.
     updates: HashMap<String, Expr>,
+    /// How to commit the update.
+    commit_config: CommitConfig,
+    /// Receives the old version of the updated rows.
+    old_rows_sink: Option<Arc<dyn RowSink>>,
 }
 
 impl UpdateBuilder {
@@ -60,6 +67,8 @@ impl UpdateBuilder {
             dataset,
             condition: None,
             updates: HashMap::new(),
+            commit_config: CommitConfig::default(),
+            old_rows_sink: None,
         }
     }
 
@@ -155,6 +164,22 @@ impl UpdateBuilder {
         Ok(self)
     }
 
+    /// Set how the update is committed.
+    ///
+    /// With [`IsolationLevel::Snapshot`](crate::io::commit::IsolationLevel::Snapshot),
+    /// the update is rebased onto concurrent deletes and updates of the same fragments,
+    /// unless they modified the same rows.
+    pub fn commit_config(mut self, commit_config: CommitConfig) -> Self {
+        self.commit_config = commit_config;
+        self
+    }
+
+    /// Send the old version of each updated row to `sink` while the update runs.
+    pub fn old_rows_sink(mut self, sink: Arc<dyn RowSink>) -> Self {
+        self.old_rows_sink = Some(sink);
+        self
+    }
+
     // TODO: set write params
     // pub fn with_write_params(mut self, params: WriteParams) -> Self { ... }
 
@@ -178,6 +203,8 @@ impl UpdateBuilder {
             dataset: self.dataset,
             condition: self.condition,
             updates,
+            commit_config: self.commit_config,
+            old_rows_sink: self.old_rows_sink,
         })
     }
 }
@@ -189,10 +216,13 @@ pub struct UpdateJob {
     dataset: Arc<Dataset>,
     condition: Option<Expr>,
     updates: Arc<HashMap<String, Arc<dyn PhysicalExpr>>>,
+    commit_config: CommitConfig,
+    old_rows_sink: Option<Arc<dyn RowSink>>,
 }
 
 impl UpdateJob {
-    pub async fn execute(self) -> Result<Arc<Dataset>> {
+    /// Executes the update, returning the new dataset and statistics about the update.
+    pub async fn execute(self) -> Result<(Arc<Dataset>, OperationStats)> {
         let mut scanner = self.dataset.scan();
         scanner.with_row_id();
 
@@ -200,7 +230,20 @@ impl UpdateJob {
             scanner.filter_expr(expr.clone());
         }
 
-        let stream = scanner.try_into_stream().await?.into();
+        let mut stream: SendableRecordBatchStream = scanner.try_into_stream().await?.into();
+        if let Some(sink) = self.old_rows_sink.clone() {
+            let schema = stream.schema();
+            let with_sink = stream.and_then(move |batch| {
+                let sink = sink.clone();
+                async move {
+                    sink.write_batch(batch.clone())
+                        .await
+                        .map_err(|err| DataFusionError::External(Box::new(err)))?;
+                    Ok(batch)
+                }
+            });
+            stream = Box::pin(RecordBatchStreamAdapter::new(schema, with_sink));
+        }
 
         // We keep track of seen row ids so we can delete them from the existing
         // fragments.
@@ -217,11 +260,24 @@ impl UpdateJob {
             });
         }
 
+        // The updated values may break the NOT NULL constraints of the schema, they are
+        // checked with the other constraints before the rows are written.
+        let schema = Arc::new(ArrowSchema::new_with_metadata(
+            schema
+                .fields()
+                .iter()
+                .map(|field| field.as_ref().clone().with_nullable(true))
+                .collect::<Vec<_>>(),
+            schema.metadata().clone(),
+        ));
+
         let updates_ref = self.updates.clone();
+        let schema_ref = schema.clone();
         let stream = stream
             .map(move |batch| {
                 let updates = updates_ref.clone();
-                tokio::task::spawn_blocking(move || Self::apply_updates(batch?, updates))
+                let schema = schema_ref.clone();
+                tokio::task::spawn_blocking(move || Self::apply_updates(batch?, schema, updates))
             })
             .buffered(num_cpus::get())
             .map(|res| match res {
@@ -230,13 +286,14 @@ impl UpdateJob {
                 Err(e) => Err(DataFusionError::Execution(e.to_string())),
             });
         let stream = RecordBatchStreamAdapter::new(schema, stream);
+        let stream = check_constraints(Box::pin(stream), self.dataset.schema())?;
 
         let new_fragments = write_fragments_internal(
             Some(&self.dataset),
             self.dataset.object_store.clone(),
             &self.dataset.base,
             self.dataset.schema(),
-            Box::pin(stream),
+            stream,
             Default::default(),
         )
         .await?;
@@ -248,16 +305,28 @@ impl UpdateJob {
             .unwrap();
         let (old_fragments, removed_fragment_ids) = self.apply_deletions(&removed_row_ids).await?;
 
+        let mut stats = OperationStats::from_changes(
+            &self.dataset,
+            &removed_fragment_ids,
+            &old_fragments,
+            &new_fragments,
+        )
+        .await?;
+        stats.num_rows_affected = removed_row_ids.len();
+
         // Commit updated and new fragments
-        self.commit(removed_fragment_ids, old_fragments, new_fragments)
-            .await
+        let dataset = self
+            .commit(removed_fragment_ids, old_fragments, new_fragments)
+            .await?;
+        Ok((dataset, stats))
     }
 
     fn apply_updates(
         batch: RecordBatch,
+        schema: SchemaRef,
         updates: Arc<HashMap<String, Arc<dyn PhysicalExpr>>>,
     ) -> DFResult<RecordBatch> {
-        let mut batch = batch.clone();
+        let mut batch = RecordBatch::try_new(schema, batch.columns().to_vec())?;
         for (column, expr) in updates.iter() {
             let new_values = expr.evaluate(&batch)?.into_array(batch.num_rows())?;
             batch = batch.replace_column_by_name(column.as_str(), new_values)?;
@@ -283,7 +352,7 @@ impl UpdateJob {
         let mut updated_fragments = Vec::new();
         let mut removed_fragments = Vec::new();
 
-        let mut stream = futures::stream::iter(self.dataset.get_fragments())
+        let mut stream = futures::stream::iter(self.dataset.get_fragments().await?)
             .map(move |fragment| {
                 let bitmaps_ref = bitmaps.clone();
                 async move {
@@ -333,7 +402,7 @@ impl UpdateJob {
             self.dataset.commit_handler.as_ref(),
             &transaction,
             &Default::default(),
-            &Default::default(),
+            &self.commit_config,
         )
         .await?;
 
@@ -346,6 +415,7 @@ impl UpdateJob {
 
 #[cfg(test)]
 mod tests {
+    use crate::dataset::write::stats::tests::VecSink;
     use crate::dataset::WriteParams;
 
     use super::*;
@@ -353,7 +423,7 @@ mod tests {
     use arrow_array::{Int64Array, RecordBatchIterator, StringArray};
     use arrow_schema::{Field, Schema as ArrowSchema};
     use arrow_select::concat::concat_batches;
-    use futures::TryStreamExt;
+    use lance_core::ROW_ID;
     use tempfile::{tempdir, TempDir};
 
     /// Returns a dataset with 3 fragments, each with 10 rows.
@@ -433,7 +503,7 @@ mod tests {
     async fn test_update_all() {
         let (dataset, _test_dir) = make_test_dataset().await;
 
-        let dataset = UpdateBuilder::new(dataset)
+        let (dataset, stats) = UpdateBuilder::new(dataset)
             .set("name", "'bar' || cast(id as string)")
             .unwrap()
             .build()
@@ -441,6 +511,12 @@ mod tests {
             .execute()
             .await
             .unwrap();
+        assert_eq!(stats.num_rows_affected, 30);
+        // The 3 old fragments are removed, 1 new one is written
+        assert_eq!(stats.num_fragments_touched, 4);
+        assert_eq!(stats.num_files_removed, 3);
+        assert_eq!(stats.num_files_added, 1);
+        assert!(stats.bytes_written > 0);
 
         let actual_batches = dataset
             .scan()
@@ -465,25 +541,46 @@ mod tests {
 
         assert_eq!(actual_batch, expected);
 
-        assert_eq!(dataset.get_fragments().len(), 1);
+        assert_eq!(dataset.get_fragments().await.unwrap().len(), 1);
     }
 
     #[tokio::test]
     async fn test_update_conditional() {
         let (dataset, _test_dir) = make_test_dataset().await;
 
-        let original_fragments = dataset.get_fragments();
+        let original_fragments = dataset.get_fragments().await.unwrap();
 
-        let dataset = UpdateBuilder::new(dataset)
+        let sink = Arc::new(VecSink::default());
+        let (dataset, stats) = UpdateBuilder::new(dataset)
             .update_where("id >= 15")
             .unwrap()
             .set("name", "'bar' || cast(id as string)")
             .unwrap()
+            .old_rows_sink(sink.clone())
             .build()
             .unwrap()
             .execute()
             .await
             .unwrap();
+        assert_eq!(stats.num_rows_affected, 15);
+        // The second fragment gets a deletion file, the third is removed and a new one written
+        assert_eq!(stats.num_fragments_touched, 3);
+        assert_eq!(stats.num_files_added, 2);
+        assert_eq!(stats.num_files_removed, 1);
+
+        // The sink received the old version of the updated rows
+        let old_rows = sink.0.lock().unwrap().clone();
+        let old_rows = concat_batches(&old_rows[0].schema(), &old_rows).unwrap();
+        assert_eq!(old_rows.num_rows(), 15);
+        assert!(old_rows
+            .column_by_name("name")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap()
+            .iter()
+            .all(|name| name == Some("foo")));
+        assert!(old_rows.column_by_name(ROW_ID).is_some());
 
         let actual_batches = dataset
             .scan()
@@ -510,7 +607,7 @@ mod tests {
 
         assert_eq!(actual_batch, expected);
 
-        let fragments = dataset.get_fragments();
+        let fragments = dataset.get_fragments().await.unwrap();
         assert_eq!(fragments.len(), 3);
 
         // One fragment not touched (id = 0..10)
@@ -19,6 +19,7 @@ use lance_index::scalar::lance_format::LanceIndexStore;
 use lance_index::scalar::ScalarIndex;
 pub use lance_index::IndexParams;
 use lance_index::{pb, DatasetIndexExt, Index, IndexType, INDEX_FILE_NAME};
+use lance_io::object_writer::ChecksumRecorder;
 use lance_io::traits::Reader;
 use lance_io::utils::{
     read_last_block, read_message, read_message_from_buf, read_metadata_offset, read_version,
@@ -57,6 +58,32 @@ pub trait IndexBuilder {
     async fn build(&self) -> Result<()>;
 }
 
+/// A copy of `dataset` that records the checksums of the files written through it.
+///
+/// Indices are built with the returned dataset, so that the checksums of their
+/// files are computed while writing them, see [index_file_checksums].
+pub(crate) fn record_checksums(dataset: &Dataset) -> (Dataset, Arc<ChecksumRecorder>) {
+    let recorder = Arc::new(ChecksumRecorder::default());
+    let mut dataset = dataset.clone();
+    dataset.object_store = Arc::new(
+        dataset
+            .object_store
+            .with_checksum_recorder(recorder.clone()),
+    );
+    (dataset, recorder)
+}
+
+/// The checksums of the files written for the index `index_id`.
+///
+/// The keys are the file names relative to the index directory.
+pub(crate) fn index_file_checksums(
+    dataset: &Dataset,
+    recorder: &ChecksumRecorder,
+    index_id: &Uuid,
+) -> HashMap<String, u32> {
+    recorder.checksums_under(&dataset.indices_dir().child(index_id.to_string()))
+}
+
 pub(crate) async fn remap_index(
     dataset: &Dataset,
     index_id: &Uuid,
@@ -192,9 +219,10 @@ impl DatasetIndexExt for Dataset {
         }
 
         let index_id = Uuid::new_v4();
+        let (build_dataset, checksums) = record_checksums(self);
         match index_type {
             IndexType::Scalar => {
-                build_scalar_index(self, column, &index_id.to_string()).await?;
+                build_scalar_index(&build_dataset, column, &index_id.to_string()).await?;
             }
             IndexType::Vector => {
                 // Vector index params.
@@ -206,8 +234,14 @@ impl DatasetIndexExt for Dataset {
                         location: location!(),
                     })?;
 
-                build_vector_index(self, column, &index_name, &index_id.to_string(), vec_params)
-                    .await?;
+                build_vector_index(
+                    &build_dataset,
+                    column,
+                    &index_name,
+                    &index_id.to_string(),
+                    vec_params,
+                )
+                .await?;
             }
         }
 
@@ -216,7 +250,14 @@ impl DatasetIndexExt for Dataset {
             name: index_name,
             fields: vec![field.id],
             dataset_version: self.manifest.version,
-            fragment_bitmap: Some(self.get_fragments().iter().map(|f| f.id() as u32).collect()),
+            fragment_bitmap: Some(
+                self.get_fragments()
+                    .await?
+                    .iter()
+                    .map(|f| f.id() as u32)
+                    .collect(),
+            ),
+            file_checksums: index_file_checksums(self, &checksums, &index_id),
         };
         let transaction = Transaction::new(
             self.manifest.version,
@@ -285,7 +326,8 @@ impl DatasetIndexExt for Dataset {
 
     #[instrument(skip_all)]
     async fn optimize_indices(&mut self, options: &OptimizeOptions) -> Result<()> {
-        let dataset = Arc::new(self.clone());
+        let (dataset, checksums) = record_checksums(self);
+        let dataset = Arc::new(dataset);
         let indices = self.load_indices().await?;
 
         let name_to_indices = indices
@@ -312,6 +354,7 @@ impl DatasetIndexExt for Dataset {
                 fields: last_idx.fields.clone(),
                 dataset_version: self.manifest.version,
                 fragment_bitmap: Some(new_frag_ids),
+                file_checksums: index_file_checksums(self, &checksums, &new_id),
             };
             removed_indices.extend(removed.iter().map(|&idx| idx.clone()));
             if deltas.len() > removed.len() {
@@ -390,7 +433,7 @@ impl DatasetIndexExt for Dataset {
             })?;
         }
         let num_unindexed_fragments = unindexed_fragments.len();
-        let num_indexed_fragments = self.fragments().len() - num_unindexed_fragments;
+        let num_indexed_fragments = self.fragments().await?.len() - num_unindexed_fragments;
         let num_indexed_rows = self.count_rows(None).await? - num_unindexed_rows;
 
         let stats = json!({
@@ -557,6 +600,7 @@ impl DatasetIndexInternalExt for Dataset {
         }
         Ok(self
             .fragments()
+            .await?
             .iter()
             .filter(|f| !total_fragment_bitmap.contains(f.id as u32))
             .cloned()
@@ -765,6 +809,7 @@ mod tests {
         dataset
             .optimize_indices(&OptimizeOptions {
                 num_indices_to_merge: 0, // Just create index for delta
+                ..Default::default()
             })
             .await
             .unwrap();
@@ -781,6 +826,7 @@ mod tests {
         dataset
             .optimize_indices(&OptimizeOptions {
                 num_indices_to_merge: 2,
+                ..Default::default()
             })
             .await
             .unwrap();
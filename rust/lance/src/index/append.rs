@@ -13,6 +13,8 @@ use snafu::{location, Location};
 use uuid::Uuid;
 
 use super::vector::ivf::optimize_vector_indices;
+use super::vector::ivf::repartition::repartition_vector_indices;
+use super::vector::sparse::{optimize_sparse_indices, SparseIndex};
 use super::DatasetIndexInternalExt;
 use crate::dataset::scanner::ColumnOrdering;
 use crate::dataset::Dataset;
@@ -27,6 +29,8 @@ use crate::dataset::Dataset;
 /// - the UUID of the new index
 /// - merged indices,
 /// - Bitmap of the fragments that covered in the newly created index.
+///
+/// Returns `None` if the existing index is kept as is.
 pub async fn merge_indices<'a>(
     dataset: Arc<Dataset>,
     old_indices: &[&'a IndexMetadata],
@@ -77,7 +81,7 @@ pub async fn merge_indices<'a>(
         frag_bitmap.insert(frag.id as u32);
     });
 
-    let (new_uuid, indices_merged) = match indices[0].index_type() {
+    let merged = match indices[0].index_type() {
         IndexType::Scalar => {
             let index = dataset
                 .open_scalar_index(&column.name, &old_indices[0].uuid.to_string())
@@ -100,7 +104,7 @@ pub async fn merge_indices<'a>(
 
             index.update(new_data_stream.into(), &new_store).await?;
 
-            Ok((new_uuid, 1))
+            Ok(Some((new_uuid, 1)))
         }
         IndexType::Vector => {
             let new_data_stream = if unindexed.is_empty() {
@@ -114,18 +118,44 @@ pub async fn merge_indices<'a>(
                 Some(scanner.try_into_stream().await?)
             };
 
-            optimize_vector_indices(
-                &dataset.object_store,
-                &dataset.indices_dir(),
-                dataset.version().version,
-                new_data_stream,
-                &column.name,
-                &indices,
-                options,
-            )
-            .await
+            if indices[0].as_any().is::<SparseIndex>() {
+                optimize_sparse_indices(
+                    &dataset.object_store,
+                    &dataset.indices_dir(),
+                    new_data_stream,
+                    &column.name,
+                    &indices,
+                    options,
+                )
+                .await
+                .map(Some)
+            } else if let Some(repartition) = options.repartition.as_ref() {
+                repartition_vector_indices(
+                    dataset.as_ref(),
+                    new_data_stream,
+                    &column.name,
+                    &indices,
+                    repartition,
+                )
+                .await
+            } else {
+                optimize_vector_indices(
+                    &dataset.object_store,
+                    &dataset.indices_dir(),
+                    dataset.version().version,
+                    new_data_stream,
+                    &column.name,
+                    &indices,
+                    options,
+                )
+                .await
+                .map(Some)
+            }
         }
     }?;
+    let Some((new_uuid, indices_merged)) = merged else {
+        return Ok(None);
+    };
 
     Ok(Some((
         new_uuid,
@@ -355,6 +385,7 @@ mod tests {
         dataset
             .optimize_indices(&OptimizeOptions {
                 num_indices_to_merge: 0,
+                ..Default::default()
             })
             .await
             .unwrap();
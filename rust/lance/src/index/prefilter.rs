@@ -98,7 +98,7 @@ impl PreFilter {
         missing_frags: Vec<u32>,
         frags_with_deletion_files: Vec<u32>,
     ) -> Result<Arc<RowIdTreeMap>> {
-        let fragments = dataset.get_fragments();
+        let fragments = dataset.get_fragments().await?;
         let frag_map: Arc<HashMap<u32, &FileFragment>> = Arc::new(HashMap::from_iter(
             fragments.iter().map(|frag| (frag.id() as u32, frag)),
         ));
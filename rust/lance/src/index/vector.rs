@@ -10,6 +10,7 @@ use std::{any::Any, collections::HashMap};
 pub mod hnsw;
 pub mod ivf;
 pub mod pq;
+pub mod sparse;
 pub mod sq;
 mod traits;
 mod utils;
@@ -22,6 +23,7 @@ use lance_file::reader::FileReader;
 use lance_index::vector::hnsw::HNSW;
 use lance_index::vector::ivf::storage::IvfData;
 use lance_index::vector::pq::ProductQuantizerImpl;
+use lance_index::vector::sparse::{SparseIndexParams, SPARSE_INVERTED_INDEX_TYPE};
 use lance_index::vector::sq::builder::SQBuildParams;
 use lance_index::vector::sq::ScalarQuantizer;
 use lance_index::vector::{hnsw::builder::HnswBuildParams, ivf::IvfBuildParams, pq::PQBuildParams};
@@ -34,6 +36,7 @@ use tracing::instrument;
 use uuid::Uuid;
 
 use self::hnsw::{HNSWIndex, HNSWIndexOptions};
+use self::sparse::{build_sparse_index, SparseIndex};
 use self::{ivf::*, pq::PQIndex};
 
 use super::{pb, DatasetIndexInternalExt, IndexParams};
@@ -47,6 +50,7 @@ pub enum StageParams {
     Hnsw(HnswBuildParams),
     PQ(PQBuildParams),
     SQ(SQBuildParams),
+    Sparse(SparseIndexParams),
 }
 
 /// The parameters to build vector index.
@@ -64,7 +68,7 @@ impl VectorIndexParams {
     /// Parameters
     ///
     ///  - `num_partitions`: the number of IVF partitions.
-    ///  - `num_bits`: the number of bits to present the centroids used in PQ. Can only be `8` for now.
+    ///  - `num_bits`: the number of bits to present the centroids used in PQ. Can be `4` or `8`.
     ///  - `num_sub_vectors`: the number of sub vectors used in PQ.
     ///  - `metric_type`: how to compute distance, i.e., `L2` or `Cosine`.
     pub fn ivf_pq(
@@ -144,6 +148,16 @@ impl VectorIndexParams {
             metric_type,
         }
     }
+
+    /// Create index parameters for a sparse inverted index.
+    ///
+    /// Sparse indices only support the dot product.
+    pub fn sparse(params: SparseIndexParams) -> Self {
+        Self {
+            stages: vec![StageParams::Sparse(params)],
+            metric_type: MetricType::Dot,
+        }
+    }
 }
 
 impl IndexParams for VectorIndexParams {
@@ -188,7 +202,18 @@ pub(crate) async fn build_vector_index(
         });
     };
 
-    if is_ivf_pq(stages) {
+    if let [StageParams::Sparse(sparse_params)] = stages.as_slice() {
+        if params.metric_type != MetricType::Dot {
+            return Err(Error::Index {
+                message: format!(
+                    "Build Vector Index: sparse index only supports dot distance, got {}",
+                    params.metric_type
+                ),
+                location: location!(),
+            });
+        }
+        build_sparse_index(dataset, column, uuid, sparse_params).await?
+    } else if is_ivf_pq(stages) {
         // This is a IVF PQ index.
         let len = stages.len();
         let StageParams::Ivf(ivf_params) = &stages[len - 2] else {
@@ -288,6 +313,9 @@ pub(crate) async fn remap_vector_index(
         .open_vector_index(column, &old_uuid.to_string())
         .await?;
     old_index.check_can_remap()?;
+    if let Some(sparse_index) = old_index.as_any().downcast_ref::<SparseIndex>() {
+        return sparse::remap_sparse_index(dataset.as_ref(), sparse_index, new_uuid, mapping).await;
+    }
     let ivf_index: &IVFIndex =
         old_index
             .as_any()
@@ -485,6 +513,8 @@ pub(crate) async fn open_vector_index_v2(
             )?)
         }
 
+        SPARSE_INVERTED_INDEX_TYPE => Arc::new(SparseIndex::load(&reader).await?),
+
         index_type => {
             if let Some(ext) = dataset.session.vector_index_extensions.get(index_type) {
                 ext.load_index(dataset.clone(), column, uuid, reader)
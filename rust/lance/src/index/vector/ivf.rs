@@ -30,6 +30,7 @@ use lance_file::{
     writer::{FileWriter, FileWriterOptions},
 };
 use lance_index::vector::quantizer::{QuantizationMetadata, Quantizer};
+use lance_index::vector::utils::{is_vector_element, widen_to_float32};
 use lance_index::{
     optimize::OptimizeOptions,
     vector::{
@@ -88,7 +89,9 @@ use crate::{
 };
 
 mod builder;
+pub mod distributed;
 mod io;
+pub(crate) mod repartition;
 
 /// IVF Index.
 pub struct IVFIndex {
@@ -295,7 +298,7 @@ pub(crate) async fn optimize_vector_indices(
                 ivf,
                 None,
                 first_idx.ivf.num_partitions() as u32,
-                pq_index.pq.num_sub_vectors(),
+                pq_index.pq.code_length(),
                 10000,
                 2,
                 None,
@@ -451,6 +454,10 @@ impl VectorIndex for IVFIndex {
     #[instrument(level = "debug", skip_all, name = "IVFIndex::search")]
     async fn search(&self, query: &Query, pre_filter: Arc<PreFilter>) -> Result<RecordBatch> {
         let mut query = query.clone();
+        if query.key.data_type() != &self.ivf.centroids.value_type() {
+            // The query of an int8 / uint8 / bfloat16 column meets f32 centroids and codebook.
+            query.key = Arc::new(widen_to_float32(query.key.as_ref())?);
+        }
         if self.metric_type == MetricType::Cosine {
             let key = normalize_arrow(&query.key)?;
             query.key = key;
@@ -727,10 +734,10 @@ fn sanity_check<'a>(dataset: &'a Dataset, column: &str) -> Result<&'a Field> {
         });
     };
     if let DataType::FixedSizeList(elem_type, _) = field.data_type() {
-        if !elem_type.data_type().is_floating() {
+        if !is_vector_element(&elem_type) {
             return Err(Error::Index{
                 message:format!(
-                    "VectorIndex requires the column data type to be fixed size list of f16/f32/f64, got {}",
+                    "VectorIndex requires the column data type to be fixed size list of f16/bf16/f32/f64/int8/uint8, got {}",
                     elem_type.data_type()
                 ),
                 location: location!()
@@ -748,6 +755,34 @@ fn sanity_check<'a>(dataset: &'a Dataset, column: &str) -> Result<&'a Field> {
     Ok(field)
 }
 
+/// Vectors stored as int8, uint8 or bfloat16 are indexed with `f32` centroids and a
+/// PQ codebook trained on `f32` residuals, which only the L2 metric supports.
+fn sanity_check_stored_type(
+    field: &Field,
+    metric_type: MetricType,
+    index_type: &str,
+) -> Result<()> {
+    let DataType::FixedSizeList(elem_type, _) = field.data_type() else {
+        return Ok(());
+    };
+    if elem_type.data_type().is_floating() {
+        return Ok(());
+    }
+    if index_type == "IVF_PQ" && metric_type == MetricType::L2 {
+        return Ok(());
+    }
+    Err(Error::Index {
+        message: format!(
+            "Column {} of {} vectors only supports IVF_PQ with the L2 metric, got {} with {}",
+            field.name,
+            elem_type.data_type(),
+            index_type,
+            metric_type
+        ),
+        location: location!(),
+    })
+}
+
 fn sanity_check_ivf_params(ivf: &IvfBuildParams) -> Result<()> {
     if ivf.precomputed_partitons_file.is_some() && ivf.centroids.is_none() {
         return Err(Error::Index {
@@ -777,6 +812,12 @@ fn sanity_check_ivf_params(ivf: &IvfBuildParams) -> Result<()> {
 
 fn sanity_check_params(ivf: &IvfBuildParams, pq: &PQBuildParams) -> Result<()> {
     sanity_check_ivf_params(ivf)?;
+    if pq.num_bits != 4 && pq.num_bits != 8 {
+        return Err(Error::Index {
+            message: format!("PQ num_bits must be 4 or 8, got {}", pq.num_bits),
+            location: location!(),
+        });
+    }
     if ivf.precomputed_shuffle_buffers.is_some() && pq.codebook.is_none() {
         return Err(Error::Index {
             message: "precomputed_shuffle_buffers requires codebooks to be set".to_string(),
@@ -969,6 +1010,7 @@ pub async fn build_ivf_pq_index(
     ivf_params: &IvfBuildParams,
     pq_params: &PQBuildParams,
 ) -> Result<()> {
+    sanity_check_stored_type(sanity_check(dataset, column)?, metric_type, "IVF_PQ")?;
     let (ivf_model, pq) =
         build_ivf_model_and_pq(dataset, column, metric_type, ivf_params, pq_params).await?;
     let stream = scan_index_field_stream(dataset, column).await?;
@@ -1003,6 +1045,7 @@ pub async fn build_ivf_hnsw_pq_index(
     hnsw_params: &HnswBuildParams,
     pq_params: &PQBuildParams,
 ) -> Result<()> {
+    sanity_check_stored_type(sanity_check(dataset, column)?, metric_type, "IVF_HNSW_PQ")?;
     let (ivf_model, pq) =
         build_ivf_model_and_pq(dataset, column, metric_type, ivf_params, pq_params).await?;
     let stream = scan_index_field_stream(dataset, column).await?;
@@ -1038,6 +1081,7 @@ pub async fn build_ivf_hnsw_sq_index(
     hnsw_params: &HnswBuildParams,
     sq_params: &SQBuildParams,
 ) -> Result<()> {
+    sanity_check_stored_type(sanity_check(dataset, column)?, metric_type, "IVF_HNSW_SQ")?;
     let (ivf_model, sq) =
         build_ivf_model_and_sq(dataset, column, metric_type, ivf_params, sq_params).await?;
     let stream = scan_index_field_stream(dataset, column).await?;
@@ -1437,6 +1481,12 @@ async fn train_ivf_model(
         DataType::Float64 => {
             do_train_ivf_model::<Float64Type>(values.as_primitive(), dim, metric_type, params).await
         }
+        // Vectors stored as int8, uint8 or bfloat16 get f32 centroids. Only the training
+        // sample is widened here; the stored vectors are assigned in their own type.
+        DataType::Int8 | DataType::UInt8 | DataType::FixedSizeBinary(2) => {
+            let values = widen_to_float32(values.as_ref())?;
+            do_train_ivf_model::<Float32Type>(&values, dim, metric_type, params).await
+        }
         _ => Err(Error::Index {
             message: "Unsupported data type".to_string(),
             location: location!(),
@@ -1453,7 +1503,7 @@ mod tests {
     use std::ops::Range;
 
     use arrow_array::types::UInt64Type;
-    use arrow_array::{RecordBatchIterator, RecordBatchReader, UInt64Array};
+    use arrow_array::{Int8Array, RecordBatchIterator, RecordBatchReader, UInt64Array};
     use arrow_schema::Field;
     use itertools::Itertools;
     use lance_core::utils::address::RowAddress;
@@ -1843,6 +1893,7 @@ mod tests {
             fields: Vec::new(),
             name: INDEX_NAME.to_string(),
             fragment_bitmap: None,
+            file_checksums: Default::default(),
         };
 
         let prefilter = Arc::new(PreFilter::new(dataset.clone(), &[index_meta], None));
@@ -2075,6 +2126,40 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_create_ivf_pq_4bit() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let (mut dataset, vector_array) = generate_test_dataset(test_uri, 0.0..1.0).await;
+
+        let params = VectorIndexParams::ivf_pq(2, 4, 8, false, MetricType::L2, 50);
+        dataset
+            .create_index(&["vector"], IndexType::Vector, None, &params, false)
+            .await
+            .unwrap();
+
+        let stats: serde_json::Value =
+            serde_json::from_str(&dataset.index_statistics("vector_idx").await.unwrap()).unwrap();
+        assert_eq!(stats["indices"][0]["sub_index"]["nbits"], 4);
+
+        let sample_query = vector_array.value(10);
+        let query = sample_query.as_primitive::<Float32Type>();
+        let results = dataset
+            .scan()
+            .nearest("vector", query, 5)
+            .unwrap()
+            .nprobs(2)
+            .refine(10)
+            .with_row_id()
+            .try_into_batch()
+            .await
+            .unwrap();
+        assert_eq!(5, results.num_rows());
+        // The query vector itself is found after refine.
+        assert_eq!(results[ROW_ID].as_primitive::<UInt64Type>().value(0), 10);
+    }
+
     #[tokio::test]
     async fn test_create_ivf_pq_f16() {
         let test_dir = tempdir().unwrap();
@@ -2139,6 +2224,71 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_create_ivf_pq_int8() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        const DIM: usize = 16;
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "vector",
+            DataType::FixedSizeList(
+                Arc::new(Field::new("item", DataType::Int8, true)),
+                DIM as i32,
+            ),
+            true,
+        )]));
+        let values = generate_random_array_with_seed::<Float32Type>(1000 * DIM, [7; 32])
+            .values()
+            .iter()
+            .map(|v| (v * 255.0 - 128.0) as i8)
+            .collect::<Vec<_>>();
+        let fsl =
+            FixedSizeListArray::try_new_from_values(Int8Array::from(values), DIM as i32).unwrap();
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(fsl.clone())]).unwrap();
+        let batches = RecordBatchIterator::new(vec![batch].into_iter().map(Ok), schema.clone());
+        let mut dataset = Dataset::write(batches, test_uri, None).await.unwrap();
+
+        // Only L2 is supported over int8 vectors.
+        let params = VectorIndexParams::ivf_pq(2, 8, 4, false, MetricType::Cosine, 50);
+        assert!(dataset
+            .create_index(&["vector"], IndexType::Vector, None, &params, false)
+            .await
+            .is_err());
+
+        let params = VectorIndexParams::ivf_pq(2, 8, 4, false, MetricType::L2, 50);
+        dataset
+            .create_index(&["vector"], IndexType::Vector, None, &params, false)
+            .await
+            .unwrap();
+
+        let query = widen_to_float32(fsl.value(10).as_ref()).unwrap();
+        for use_index in [true, false] {
+            let results = dataset
+                .scan()
+                .nearest("vector", &query, 5)
+                .unwrap()
+                .nprobs(2)
+                .refine(10)
+                .use_index(use_index)
+                .with_row_id()
+                .try_into_batch()
+                .await
+                .unwrap();
+            assert_eq!(5, results.num_rows());
+            assert_eq!(results[ROW_ID].as_primitive::<UInt64Type>().value(0), 10);
+            assert_eq!(
+                results[DIST_COL].as_primitive::<Float32Type>().value(0),
+                0.0
+            );
+            // The stored vectors are returned in their own type.
+            assert_eq!(
+                results["vector"].as_fixed_size_list().value_type(),
+                DataType::Int8
+            );
+        }
+    }
+
     #[tokio::test]
     async fn test_create_ivf_pq_f16_with_codebook() {
         let test_dir = tempdir().unwrap();
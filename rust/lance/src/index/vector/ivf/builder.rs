@@ -75,7 +75,7 @@ pub(super) async fn build_partitions(
         ivf_model,
         precomputed_partitons,
         ivf.num_partitions() as u32,
-        pq.num_sub_vectors(),
+        pq.code_length(),
         shuffle_partition_batches,
         shuffle_partition_concurrency,
         precomputed_shuffle_buffers,
@@ -0,0 +1,507 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Distributed IVF_PQ index build.
+//!
+//! Building an IVF_PQ index over a large dataset is split into three phases
+//! that can run on different machines:
+//!
+//! 1. [IvfPqModel::train] trains IVF centroids and PQ codebooks on a sample of
+//!    the dataset. The model is serialized with [IvfPqModel::write] and shipped
+//!    to the workers.
+//! 2. Each worker calls [shuffle_fragments] on a disjoint subset of fragments. It
+//!    assigns the vectors to partitions, encodes them with PQ and writes a
+//!    partial partition file into a shared shuffle directory.
+//! 3. A single coordinator calls [merge_and_commit] with all the partial results.
+//!    It merges the partial files into one index and commits it with a single
+//!    `CreateIndex` transaction.
+//!
+//! The shuffle directory is accessed through the local file system, so it must
+//! be visible to the workers and the coordinator (e.g. a network file system).
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use arrow_schema::DataType;
+use lance_core::{Error, Result};
+use lance_index::{
+    pb,
+    vector::{
+        ivf::{new_ivf_with_pq, shuffler::write_unsorted_buffer, IvfBuildParams},
+        pq::{PQBuildParams, ProductQuantizer},
+    },
+    DatasetIndexExt,
+};
+use lance_io::object_store::ObjectStore;
+use lance_linalg::distance::MetricType;
+use lance_table::format::Index as IndexMetadata;
+use object_store::path::Path;
+use prost::Message;
+use roaring::RoaringBitmap;
+use serde::{Deserialize, Serialize};
+use snafu::{location, Location};
+use uuid::Uuid;
+
+use super::{
+    build_ivf_model_and_pq, sanity_check, sanity_check_stored_type, scan_index_field_stream,
+    write_ivf_pq_file, Ivf,
+};
+use crate::dataset::transaction::{Operation, Transaction};
+use crate::index::{index_file_checksums, record_checksums};
+use crate::io::commit::commit_transaction;
+use crate::Dataset;
+
+/// Trained IVF centroids and PQ codebooks, shared by all the phases of a
+/// distributed build.
+#[derive(Debug, Clone)]
+pub struct IvfPqModel {
+    column: String,
+    metric_type: MetricType,
+    ivf: Ivf,
+    pq: Arc<dyn ProductQuantizer>,
+}
+
+impl IvfPqModel {
+    /// Train IVF centroids and PQ codebooks on a sample of `column`.
+    pub async fn train(
+        dataset: &Dataset,
+        column: &str,
+        metric_type: MetricType,
+        ivf_params: &IvfBuildParams,
+        pq_params: &PQBuildParams,
+    ) -> Result<Self> {
+        sanity_check_stored_type(sanity_check(dataset, column)?, metric_type, "IVF_PQ")?;
+        if pq_params.use_opq {
+            return Err(Error::Index {
+                message: "Distributed IVF_PQ build does not support OPQ".to_string(),
+                location: location!(),
+            });
+        }
+        let (ivf, pq) =
+            build_ivf_model_and_pq(dataset, column, metric_type, ivf_params, pq_params).await?;
+        Ok(Self {
+            column: column.to_string(),
+            metric_type,
+            ivf,
+            pq,
+        })
+    }
+
+    /// The column this model was trained on.
+    pub fn column(&self) -> &str {
+        &self.column
+    }
+
+    pub fn metric_type(&self) -> MetricType {
+        self.metric_type
+    }
+
+    /// Number of IVF partitions.
+    pub fn num_partitions(&self) -> usize {
+        self.ivf.num_partitions()
+    }
+
+    /// Serialize the model to protobuf bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let stages = vec![
+            pb::VectorIndexStage {
+                stage: Some(pb::vector_index_stage::Stage::Ivf(pb::Ivf {
+                    centroids: vec![],
+                    offsets: vec![],
+                    lengths: vec![],
+                    centroids_tensor: Some(self.ivf.centroids.as_ref().try_into()?),
+                })),
+            },
+            pb::VectorIndexStage {
+                stage: Some(pb::vector_index_stage::Stage::Pq(
+                    self.pq.as_ref().try_into()?,
+                )),
+            },
+        ];
+        let proto = pb::Index {
+            name: String::new(),
+            columns: vec![self.column.clone()],
+            dataset_version: 0,
+            index_type: pb::IndexType::Vector.into(),
+            implementation: Some(pb::index::Implementation::VectorIndex(pb::VectorIndex {
+                spec_version: 1,
+                dimension: self.pq.dimension() as u32,
+                stages,
+                metric_type: pb::VectorMetricType::from(self.metric_type).into(),
+            })),
+        };
+        Ok(proto.encode_to_vec())
+    }
+
+    /// Deserialize a model written by [Self::to_bytes].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let invalid = |message: String| Error::Index {
+            message,
+            location: location!(),
+        };
+        let proto = pb::Index::decode(bytes)
+            .map_err(|e| invalid(format!("Failed to decode IVF_PQ model: {}", e)))?;
+        let Some(column) = proto.columns.first() else {
+            return Err(invalid("IVF_PQ model has no column".to_string()));
+        };
+        let Some(pb::index::Implementation::VectorIndex(vec_idx)) = &proto.implementation else {
+            return Err(invalid("IVF_PQ model is not a vector index".to_string()));
+        };
+        let metric_type: MetricType = pb::VectorMetricType::try_from(vec_idx.metric_type)?.into();
+
+        let mut ivf = None;
+        let mut pq = None;
+        for stage in &vec_idx.stages {
+            match &stage.stage {
+                Some(pb::vector_index_stage::Stage::Ivf(ivf_pb)) => {
+                    ivf = Some(Ivf::try_from(ivf_pb)?);
+                }
+                Some(pb::vector_index_stage::Stage::Pq(pq_pb)) => {
+                    pq = Some(lance_index::vector::pq::builder::from_proto(
+                        pq_pb,
+                        metric_type,
+                    )?);
+                }
+                _ => {
+                    return Err(invalid(format!(
+                        "Unexpected stage in IVF_PQ model: {:?}",
+                        stage
+                    )))
+                }
+            }
+        }
+        let (Some(ivf), Some(pq)) = (ivf, pq) else {
+            return Err(invalid(
+                "IVF_PQ model must contain both IVF and PQ stages".to_string(),
+            ));
+        };
+        Ok(Self {
+            column: column.clone(),
+            metric_type,
+            ivf,
+            pq,
+        })
+    }
+
+    /// Write the model to `path`.
+    pub async fn write(&self, object_store: &ObjectStore, path: &Path) -> Result<()> {
+        object_store.put(path, &self.to_bytes()?).await
+    }
+
+    /// Read a model written by [Self::write].
+    pub async fn read(object_store: &ObjectStore, path: &Path) -> Result<Self> {
+        let reader = object_store.open(path).await?;
+        let bytes = reader.get_range(0..reader.size().await?).await?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+/// The result of shuffling a subset of fragments on one worker.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartialIvfPqIndex {
+    /// Name of the partial partition file, relative to the shuffle directory.
+    pub buffer: String,
+
+    /// Fragments covered by this partial file.
+    pub fragment_ids: Vec<u32>,
+}
+
+/// Assign the vectors of `fragment_ids` to IVF partitions, encode them with PQ
+/// and write them to a new partial partition file under `shuffle_dir`.
+///
+/// Workers may run this concurrently on disjoint fragment subsets, sharing the
+/// same `shuffle_dir`.
+pub async fn shuffle_fragments(
+    dataset: &Dataset,
+    model: &IvfPqModel,
+    fragment_ids: &[u32],
+    shuffle_dir: &Path,
+) -> Result<PartialIvfPqIndex> {
+    let field = sanity_check(dataset, &model.column)?;
+    let DataType::FixedSizeList(_, dim) = field.data_type() else {
+        return Err(Error::Index {
+            message: format!(
+                "VectorIndex requires the column data type to be fixed size list, got {}",
+                field.data_type()
+            ),
+            location: location!(),
+        });
+    };
+    if dim as usize != model.ivf.dimension() {
+        return Err(Error::Index {
+            message: format!(
+                "IVF_PQ model dimension {} does not match column '{}' dimension {}",
+                model.ivf.dimension(),
+                model.column,
+                dim
+            ),
+            location: location!(),
+        });
+    }
+
+    let mut fragments = Vec::with_capacity(fragment_ids.len());
+    for id in fragment_ids {
+        let fragment = dataset
+            .get_fragment(*id as usize)
+            .await?
+            .ok_or_else(|| Error::Index {
+                message: format!("Fragment {} does not exist", id),
+                location: location!(),
+            })?;
+        fragments.push(fragment.metadata().clone());
+    }
+
+    let mut scanner = dataset.scan();
+    scanner.batch_readahead(num_cpus::get() * 2);
+    scanner.project(&[&model.column])?;
+    scanner.with_row_id();
+    scanner.with_fragments(fragments);
+    let stream = scanner.try_into_stream().await?;
+
+    let num_partitions = model.num_partitions() as u32;
+    let ivf = new_ivf_with_pq(
+        model.ivf.centroids.values(),
+        model.ivf.dimension(),
+        model.metric_type,
+        &model.column,
+        model.pq.clone(),
+        Some(0..num_partitions),
+    )?;
+
+    let buffer = format!("{}.lance", Uuid::new_v4());
+    write_unsorted_buffer(
+        stream,
+        &model.column,
+        ivf,
+        num_partitions,
+        model.pq.code_length(),
+        shuffle_dir.clone(),
+        &buffer,
+    )
+    .await?;
+
+    Ok(PartialIvfPqIndex {
+        buffer,
+        fragment_ids: fragment_ids.to_vec(),
+    })
+}
+
+/// Merge the partial partition files written by [shuffle_fragments] into one
+/// IVF_PQ index and commit it with a single `CreateIndex` transaction.
+///
+/// The index covers the union of the fragments of all `partials`, which must be
+/// disjoint. Fragments that are not covered are searched without the index.
+pub async fn merge_and_commit(
+    dataset: &mut Dataset,
+    model: &IvfPqModel,
+    name: Option<String>,
+    shuffle_dir: &Path,
+    partials: &[PartialIvfPqIndex],
+) -> Result<IndexMetadata> {
+    if partials.is_empty() {
+        return Err(Error::Index {
+            message: "No partial IVF_PQ results to merge".to_string(),
+            location: location!(),
+        });
+    }
+    let field = sanity_check(dataset, &model.column)?;
+    let field_id = field.id;
+
+    let mut fragment_bitmap = RoaringBitmap::new();
+    for partial in partials {
+        for id in &partial.fragment_ids {
+            if !fragment_bitmap.insert(*id) {
+                return Err(Error::Index {
+                    message: format!("Fragment {} is covered by more than one partial index", id),
+                    location: location!(),
+                });
+            }
+        }
+    }
+    let existing = dataset
+        .get_fragments()
+        .await?
+        .iter()
+        .map(|f| f.id() as u32)
+        .collect::<HashSet<_>>();
+    if let Some(missing) = fragment_bitmap.iter().find(|id| !existing.contains(id)) {
+        return Err(Error::Index {
+            message: format!("Fragment {} does not exist", missing),
+            location: location!(),
+        });
+    }
+
+    let index_name = name.unwrap_or(format!("{}_idx", model.column));
+    let indices = dataset.load_indices().await?;
+    if indices.iter().any(|i| i.name == index_name) {
+        return Err(Error::Index {
+            message: format!("Index name '{index_name}' already exists"),
+            location: location!(),
+        });
+    }
+
+    // The shuffle reads the partial files and ignores the data stream, which is
+    // only used to validate the schema.
+    let stream = scan_index_field_stream(dataset, &model.column).await?;
+    let buffers = partials.iter().map(|p| p.buffer.clone()).collect();
+    let index_id = Uuid::new_v4();
+    let default_params = IvfBuildParams::default();
+    let (build_dataset, checksums) = record_checksums(dataset);
+    write_ivf_pq_file(
+        &build_dataset,
+        &model.column,
+        &index_name,
+        &index_id.to_string(),
+        &[],
+        Ivf::new(model.ivf.centroids.clone()),
+        model.pq.clone(),
+        model.metric_type,
+        stream,
+        None,
+        default_params.shuffle_partition_batches,
+        default_params.shuffle_partition_concurrency,
+        Some((shuffle_dir.clone(), buffers)),
+    )
+    .await?;
+
+    let new_idx = IndexMetadata {
+        uuid: index_id,
+        name: index_name,
+        fields: vec![field_id],
+        dataset_version: dataset.manifest.version,
+        fragment_bitmap: Some(fragment_bitmap),
+        file_checksums: index_file_checksums(dataset, &checksums, &index_id),
+    };
+    let transaction = Transaction::new(
+        dataset.manifest.version,
+        Operation::CreateIndex {
+            new_indices: vec![new_idx.clone()],
+            removed_indices: vec![],
+        },
+        None,
+    );
+    let new_manifest = commit_transaction(
+        dataset,
+        dataset.object_store(),
+        dataset.commit_handler.as_ref(),
+        &transaction,
+        &Default::default(),
+        &Default::default(),
+    )
+    .await?;
+    dataset.manifest = Arc::new(new_manifest);
+
+    Ok(new_idx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{cast::AsArray, types::Float32Type, Array, RecordBatchIterator};
+    use lance_arrow::FixedSizeListArrayExt;
+    use lance_index::vector::DIST_COL;
+    use lance_testing::datagen::generate_random_array;
+    use tempfile::tempdir;
+
+    use crate::dataset::WriteParams;
+
+    #[tokio::test]
+    async fn test_distributed_ivf_pq_build() {
+        const DIM: usize = 32;
+        let test_dir = tempdir().unwrap();
+        let uri = test_dir.path().to_str().unwrap();
+
+        let vectors = arrow_array::FixedSizeListArray::try_new_from_values(
+            generate_random_array(1000 * DIM),
+            DIM as i32,
+        )
+        .unwrap();
+        let schema = Arc::new(arrow_schema::Schema::new(vec![arrow_schema::Field::new(
+            "vector",
+            vectors.data_type().clone(),
+            false,
+        )]));
+        let batch =
+            arrow_array::RecordBatch::try_new(schema.clone(), vec![Arc::new(vectors.clone())])
+                .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let params = WriteParams {
+            max_rows_per_file: 250,
+            ..Default::default()
+        };
+        let mut dataset = Dataset::write(reader, uri, Some(params)).await.unwrap();
+        assert_eq!(dataset.get_fragments().await.unwrap().len(), 4);
+
+        // Phase 1: train and ship the model.
+        let model = IvfPqModel::train(
+            &dataset,
+            "vector",
+            MetricType::L2,
+            &IvfBuildParams::new(4),
+            &PQBuildParams::new(4, 8),
+        )
+        .await
+        .unwrap();
+        let model_path = Path::from_absolute_path(test_dir.path().join("model.pb")).unwrap();
+        let store = ObjectStore::local();
+        model.write(&store, &model_path).await.unwrap();
+        let model = IvfPqModel::read(&store, &model_path).await.unwrap();
+        assert_eq!(model.column(), "vector");
+        assert_eq!(model.num_partitions(), 4);
+
+        // Phase 2: two workers shuffle disjoint fragments into a shared directory.
+        let shuffle_dir = tempdir().unwrap();
+        let shuffle_path = Path::from_filesystem_path(shuffle_dir.path()).unwrap();
+        let mut partials = vec![];
+        for ids in [[0_u32, 1], [2, 3]] {
+            let worker_ds = Dataset::open(uri).await.unwrap();
+            partials.push(
+                shuffle_fragments(&worker_ds, &model, &ids, &shuffle_path)
+                    .await
+                    .unwrap(),
+            );
+        }
+        assert_ne!(partials[0].buffer, partials[1].buffer);
+
+        // Overlapping fragments are rejected.
+        let overlapping = vec![partials[0].clone(), partials[0].clone()];
+        assert!(
+            merge_and_commit(&mut dataset, &model, None, &shuffle_path, &overlapping)
+                .await
+                .is_err()
+        );
+
+        // Phase 3: merge and commit once.
+        let version = dataset.version().version;
+        let index = merge_and_commit(&mut dataset, &model, None, &shuffle_path, &partials)
+            .await
+            .unwrap();
+        assert_eq!(dataset.version().version, version + 1);
+        assert_eq!(
+            index
+                .fragment_bitmap
+                .as_ref()
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![0, 1, 2, 3]
+        );
+        let indices = dataset.load_indices().await.unwrap();
+        assert_eq!(indices.len(), 1);
+        assert_eq!(indices[0].name, "vector_idx");
+
+        let query = vectors.value(123);
+        let results = dataset
+            .scan()
+            .nearest("vector", query.as_primitive::<Float32Type>(), 5)
+            .unwrap()
+            .nprobs(4)
+            .refine(10)
+            .try_into_batch()
+            .await
+            .unwrap();
+        let dists = results[DIST_COL].as_primitive::<Float32Type>();
+        assert_eq!(dists.value(0), 0.0);
+    }
+}
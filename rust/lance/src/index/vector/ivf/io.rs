@@ -199,7 +199,7 @@ pub(super) async fn write_pq_partitions(
                     let fsl = Arc::new(
                         FixedSizeListArray::try_new_from_values(
                             pq_code.as_ref().clone(),
-                            pq_index.pq.num_sub_vectors() as i32,
+                            pq_index.pq.code_length() as i32,
                         )
                         .unwrap(),
                     );
@@ -563,14 +563,14 @@ mod tests {
             .unwrap();
         let indices = ds.load_indices().await.unwrap();
         assert_eq!(indices.len(), 1);
-        assert_eq!(ds.get_fragments().len(), 1);
+        assert_eq!(ds.get_fragments().await.unwrap().len(), 1);
 
         let batches =
             RecordBatchIterator::new(vec![batch.clone()].into_iter().map(Ok), schema.clone());
         ds.append(batches, None).await.unwrap();
         let indices = ds.load_indices().await.unwrap();
         assert_eq!(indices.len(), 1);
-        assert_eq!(ds.get_fragments().len(), 2);
+        assert_eq!(ds.get_fragments().await.unwrap().len(), 2);
 
         let idx = ds
             .open_vector_index(&indices[0].name, &indices[0].uuid.to_string())
@@ -0,0 +1,644 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Rebalance the partitions of IVF_PQ indices.
+//!
+//! Appending data never re-trains the IVF centroids, so some partitions keep
+//! growing while others stay empty. Repartitioning splits the oversized
+//! partitions with k-means, removes the empty (or tiny) ones, and re-assigns
+//! only the rows of these partitions. The other partitions are copied as-is.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use arrow::compute::cast;
+use arrow_array::{
+    cast::AsArray, types::Float32Type, Array, ArrayRef, FixedSizeListArray, RecordBatch,
+    UInt32Array, UInt64Array,
+};
+use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
+use arrow_select::{concat::concat, concat::concat_batches, take::take};
+use futures::TryStreamExt;
+use lance_arrow::FixedSizeListArrayExt;
+use lance_core::{Error, Result, ROW_ID};
+use lance_file::format::MAGIC;
+use lance_index::{
+    optimize::RepartitionOptions,
+    pb,
+    vector::{
+        ivf::new_ivf_with_pq,
+        transform::{KeepFiniteVectors, Transformer},
+        utils::widen_to_float32,
+        PART_ID_COLUMN, PQ_CODE_COLUMN,
+    },
+    Index, INDEX_FILE_NAME,
+};
+use lance_io::{
+    encodings::plain::PlainEncoder,
+    stream::RecordBatchStream,
+    traits::{WriteExt, Writer},
+};
+use lance_linalg::{
+    distance::MetricType,
+    kernels::normalize_fsl,
+    kmeans::{KMeans, KMeansParams},
+};
+use log::info;
+use snafu::{location, Location};
+use uuid::Uuid;
+
+use super::{IVFIndex, Ivf, IvfPQIndexMetadata};
+use crate::{index::vector::pq::PQIndex, Dataset};
+
+/// How the partitions of the old index map to the new index.
+#[derive(Debug, PartialEq)]
+struct RepartitionPlan {
+    /// Old partitions copied as-is. The i-th kept partition becomes the new partition `i`.
+    kept: Vec<usize>,
+
+    /// Old partitions to split, and the number of partitions to split each into.
+    split: Vec<(usize, usize)>,
+
+    /// Non-empty old partitions whose rows are merged into the other partitions.
+    merged: Vec<usize>,
+}
+
+impl RepartitionPlan {
+    fn new(sizes: &[usize], options: &RepartitionOptions) -> Self {
+        let total = sizes.iter().sum::<usize>();
+        if total == 0 {
+            return Self {
+                kept: (0..sizes.len()).collect(),
+                split: vec![],
+                merged: vec![],
+            };
+        }
+        // The median is not skewed by the oversized partitions themselves. Fall back to
+        // the average when most partitions are empty.
+        let mut sorted = sizes.to_vec();
+        sorted.sort_unstable();
+        let reference = match sorted[sorted.len() / 2] {
+            0 => total as f32 / sizes.len() as f32,
+            median => median as f32,
+        };
+
+        let mut plan = Self {
+            kept: vec![],
+            split: vec![],
+            merged: vec![],
+        };
+        for (part_id, &size) in sizes.iter().enumerate() {
+            if size >= 2 && size as f32 > options.split_factor * reference {
+                let k = ((size as f32 / reference).ceil() as usize).clamp(2, size);
+                plan.split.push((part_id, k));
+            } else if size as f32 <= options.merge_factor * reference {
+                if size > 0 {
+                    plan.merged.push(part_id);
+                }
+            } else {
+                plan.kept.push(part_id);
+            }
+        }
+        plan
+    }
+
+    fn is_noop(&self) -> bool {
+        self.split.is_empty() && self.merged.is_empty()
+    }
+
+    /// Old partitions whose rows are re-assigned.
+    fn affected(&self) -> impl Iterator<Item = usize> + '_ {
+        self.split
+            .iter()
+            .map(|(p, _)| *p)
+            .chain(self.merged.iter().copied())
+    }
+}
+
+fn check_options(options: &RepartitionOptions) -> Result<()> {
+    if options.split_factor <= 1.0 {
+        return Err(Error::invalid_input(
+            format!(
+                "RepartitionOptions::split_factor must be greater than 1, got {}",
+                options.split_factor
+            ),
+            location!(),
+        ));
+    }
+    if !(0.0..1.0).contains(&options.merge_factor) {
+        return Err(Error::invalid_input(
+            format!(
+                "RepartitionOptions::merge_factor must be in [0, 1), got {}",
+                options.merge_factor
+            ),
+            location!(),
+        ));
+    }
+    Ok(())
+}
+
+/// Take the PQ codes and row ids at `indices` of a transformed batch.
+fn take_codes_and_row_ids(batch: &RecordBatch, indices: &[u32]) -> Result<(ArrayRef, ArrayRef)> {
+    let indices = UInt32Array::from(indices.to_vec());
+    let column = |name: &str| {
+        batch.column_by_name(name).ok_or_else(|| Error::Index {
+            message: format!("repartition: column {} not found", name),
+            location: location!(),
+        })
+    };
+    Ok((
+        take(column(PQ_CODE_COLUMN)?, &indices, None)?,
+        take(column(ROW_ID)?, &indices, None)?,
+    ))
+}
+
+/// Group the rows of a transformed batch by partition id.
+fn group_by_partition(batch: Option<&RecordBatch>, num_partitions: usize) -> Vec<Vec<u32>> {
+    let mut groups = vec![vec![]; num_partitions];
+    if let Some(batch) = batch {
+        let part_ids = batch[PART_ID_COLUMN].as_primitive::<arrow_array::types::UInt32Type>();
+        for (i, part_id) in part_ids.values().iter().enumerate() {
+            groups[*part_id as usize].push(i as u32);
+        }
+    }
+    groups
+}
+
+/// Convert vectors to float32 for k-means training.
+fn to_f32_vectors(vectors: &FixedSizeListArray) -> Result<FixedSizeListArray> {
+    let values = match vectors.value_type() {
+        DataType::Float32 => vectors.values().as_primitive::<Float32Type>().clone(),
+        DataType::Int8 | DataType::UInt8 | DataType::FixedSizeBinary(2) => {
+            widen_to_float32(vectors.values().as_ref())?
+        }
+        _ => cast(vectors.values(), &DataType::Float32)?
+            .as_primitive::<Float32Type>()
+            .clone(),
+    };
+    Ok(FixedSizeListArray::try_new_from_values(
+        values,
+        vectors.value_length(),
+    )?)
+}
+
+/// Train `k` centroids for the vectors of one partition.
+async fn split_partition(
+    vectors: &FixedSizeListArray,
+    k: usize,
+    metric_type: MetricType,
+    max_iters: u32,
+) -> Result<FixedSizeListArray> {
+    // Same as the initial training: cosine is trained as L2 over normalized vectors.
+    let (data, metric_type) = if metric_type == MetricType::Cosine {
+        (normalize_fsl(&to_f32_vectors(vectors)?)?, MetricType::L2)
+    } else {
+        (to_f32_vectors(vectors)?, metric_type)
+    };
+    let params = KMeansParams::<Float32Type> {
+        max_iters,
+        metric_type,
+        ..Default::default()
+    };
+    let kmeans = KMeans::<Float32Type>::new_with_params(&data, k, &params).await?;
+    Ok(FixedSizeListArray::try_new_from_values(
+        kmeans.centroids.as_ref().clone(),
+        data.value_length(),
+    )?)
+}
+
+/// Merge the unindexed data and all the `existing_indices` into one IVF_PQ index,
+/// splitting oversized partitions and removing empty ones.
+///
+/// Returns (new_uuid, num_indices_merged), or `None` if there is nothing to
+/// index and the plan keeps every partition as is, so the existing index can be kept.
+pub async fn repartition_vector_indices(
+    dataset: &Dataset,
+    unindexed: Option<impl RecordBatchStream + Unpin + 'static>,
+    vector_column: &str,
+    existing_indices: &[Arc<dyn Index>],
+    options: &RepartitionOptions,
+) -> Result<Option<(Uuid, usize)>> {
+    check_options(options)?;
+    if existing_indices.is_empty() {
+        return Err(Error::Index {
+            message: "repartitioning vector index: no existing index found".to_string(),
+            location: location!(),
+        });
+    }
+    let indices = existing_indices
+        .iter()
+        .map(|idx| {
+            idx.as_any().downcast_ref::<IVFIndex>().ok_or(Error::Index {
+                message: "repartitioning vector index: it is not a IVF index".to_string(),
+                location: location!(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let first_idx = indices[0];
+    let pq = first_idx
+        .sub_index
+        .as_any()
+        .downcast_ref::<PQIndex>()
+        .ok_or(Error::Index {
+            message: "repartitioning vector index: it is not a IVF_PQ index".to_string(),
+            location: location!(),
+        })?
+        .pq
+        .clone();
+    let metric_type = first_idx.metric_type;
+    let dim = first_idx.ivf.dimension();
+    let centroids = first_idx.ivf.centroids.clone();
+    let num_partitions = first_idx.ivf.num_partitions();
+
+    // Assign the unindexed rows to the current partitions.
+    let ivf = new_ivf_with_pq(
+        centroids.values(),
+        dim,
+        metric_type,
+        vector_column,
+        pq.clone(),
+        None,
+    )?;
+    let new_data = if let Some(mut stream) = unindexed {
+        let nan_filter = KeepFiniteVectors::new(vector_column);
+        let mut batches = vec![];
+        while let Some(batch) = stream.try_next().await? {
+            let batch = nan_filter.transform(&batch).await?;
+            batches.push(ivf.transform(&batch).await?);
+        }
+        match batches.first() {
+            Some(first) => Some(concat_batches(&first.schema(), &batches)?),
+            None => None,
+        }
+    } else {
+        None
+    };
+    let new_data_groups = group_by_partition(new_data.as_ref(), num_partitions);
+
+    let sizes = (0..num_partitions)
+        .map(|part_id| {
+            indices
+                .iter()
+                .map(|idx| idx.ivf.lengths[part_id] as usize)
+                .sum::<usize>()
+                + new_data_groups[part_id].len()
+        })
+        .collect::<Vec<_>>();
+    let plan = RepartitionPlan::new(&sizes, options);
+    if plan.is_noop() && new_data.is_none() && indices.len() == 1 {
+        info!(
+            "Repartitioning IVF index on {}: partitions are balanced, keeping the index",
+            vector_column
+        );
+        return Ok(None);
+    }
+    info!(
+        "Repartitioning IVF index on {}: split {:?}, merged {:?}",
+        vector_column, plan.split, plan.merged
+    );
+
+    // Collect the rows of the affected partitions. Rows of fragments that were
+    // removed since the index was built are dropped.
+    let fragment_ids = dataset
+        .get_fragments()
+        .await?
+        .iter()
+        .map(|f| f.id() as u64)
+        .collect::<HashSet<_>>();
+    let projection = dataset.schema().project(&[vector_column])?;
+    let mut affected_vectors: Vec<ArrayRef> = vec![];
+    let mut affected_row_ids: Vec<u64> = vec![];
+    let mut split_centroids: Vec<ArrayRef> = vec![];
+    for part_id in plan.affected().collect::<Vec<_>>() {
+        let mut row_ids = vec![];
+        for idx in indices.iter() {
+            let part = idx.load_partition(part_id, false).await?;
+            if let Some(ids) = part
+                .as_any()
+                .downcast_ref::<PQIndex>()
+                .and_then(|p| p.row_ids.as_ref())
+            {
+                row_ids.extend_from_slice(ids.values());
+            }
+        }
+        if let Some(new_data) = new_data.as_ref() {
+            let ids = new_data[ROW_ID].as_primitive::<arrow_array::types::UInt64Type>();
+            row_ids.extend(
+                new_data_groups[part_id]
+                    .iter()
+                    .map(|i| ids.value(*i as usize)),
+            );
+        }
+        row_ids.retain(|id| fragment_ids.contains(&(id >> 32)));
+        row_ids.sort_unstable();
+        if row_ids.is_empty() {
+            continue;
+        }
+
+        let batch = dataset.take_rows(&row_ids, &projection).await?;
+        let vectors = batch[vector_column].as_fixed_size_list();
+        if let Some((_, k)) = plan.split.iter().find(|(p, _)| *p == part_id) {
+            let k = (*k).min(vectors.len());
+            let new_centroids = split_partition(vectors, k, metric_type, options.max_iters).await?;
+            split_centroids.push(cast(&new_centroids, centroids.data_type())?);
+        }
+        affected_vectors.push(batch[vector_column].clone());
+        affected_row_ids.extend(row_ids);
+    }
+
+    // New centroids: the kept partitions first, then the split ones.
+    let kept_ids = UInt32Array::from_iter_values(plan.kept.iter().map(|p| *p as u32));
+    let mut new_centroids = vec![take(centroids.as_ref(), &kept_ids, None)?];
+    new_centroids.extend(split_centroids);
+    let new_centroids = concat(&new_centroids.iter().map(|c| c.as_ref()).collect::<Vec<_>>())?
+        .as_fixed_size_list()
+        .clone();
+    let new_num_partitions = new_centroids.len();
+    if new_num_partitions == 0 {
+        return Err(Error::Index {
+            message: "repartitioning vector index: no partition left".to_string(),
+            location: location!(),
+        });
+    }
+
+    // Re-assign and re-encode the rows of the affected partitions.
+    let reassigned = if affected_row_ids.is_empty() {
+        None
+    } else {
+        let vectors = concat(
+            &affected_vectors
+                .iter()
+                .map(|v| v.as_ref())
+                .collect::<Vec<_>>(),
+        )?;
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new(vector_column, vectors.data_type().clone(), true),
+            ArrowField::new(ROW_ID, DataType::UInt64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![vectors, Arc::new(UInt64Array::from(affected_row_ids))],
+        )?;
+        let ivf = new_ivf_with_pq(
+            new_centroids.values(),
+            dim,
+            metric_type,
+            vector_column,
+            pq.clone(),
+            None,
+        )?;
+        Some(ivf.transform(&batch).await?)
+    };
+    let reassigned_groups = group_by_partition(reassigned.as_ref(), new_num_partitions);
+
+    let new_uuid = Uuid::new_v4();
+    let index_file = dataset
+        .indices_dir()
+        .child(new_uuid.to_string())
+        .child(INDEX_FILE_NAME);
+    let mut writer = dataset.object_store().create(&index_file).await?;
+    let mut new_ivf = Ivf::new(Arc::new(new_centroids));
+    for (part_id, reassigned_rows) in reassigned_groups.iter().enumerate() {
+        let mut codes: Vec<ArrayRef> = vec![];
+        let mut row_ids: Vec<ArrayRef> = vec![];
+        if let Some(&old_part_id) = plan.kept.get(part_id) {
+            for idx in indices.iter() {
+                let part = idx.load_partition(old_part_id, false).await?;
+                let pq_index = part
+                    .as_any()
+                    .downcast_ref::<PQIndex>()
+                    .ok_or(Error::Index {
+                        message: "Invalid sub index".to_string(),
+                        location: location!(),
+                    })?;
+                if let Some(code) = pq_index.code.as_ref() {
+                    codes.push(Arc::new(FixedSizeListArray::try_new_from_values(
+                        code.as_ref().clone(),
+                        pq.code_length() as i32,
+                    )?));
+                    row_ids.push(pq_index.row_ids.as_ref().unwrap().clone());
+                }
+            }
+            if let Some(new_data) = new_data.as_ref() {
+                let (c, r) = take_codes_and_row_ids(new_data, &new_data_groups[old_part_id])?;
+                codes.push(c);
+                row_ids.push(r);
+            }
+        }
+        if let Some(reassigned) = reassigned.as_ref() {
+            let (c, r) = take_codes_and_row_ids(reassigned, reassigned_rows)?;
+            codes.push(c);
+            row_ids.push(r);
+        }
+
+        let total_records = row_ids.iter().map(|a| a.len()).sum::<usize>();
+        new_ivf.add_partition(writer.tell().await?, total_records as u32);
+        if total_records > 0 {
+            let codes = codes.iter().map(|a| a.as_ref()).collect::<Vec<_>>();
+            PlainEncoder::write(&mut writer, &codes).await?;
+            let row_ids = row_ids.iter().map(|a| a.as_ref()).collect::<Vec<_>>();
+            PlainEncoder::write(&mut writer, &row_ids).await?;
+        }
+    }
+    info!(
+        "Repartitioned IVF index on {}: {} -> {} partitions",
+        vector_column, num_partitions, new_num_partitions
+    );
+
+    let metadata = IvfPQIndexMetadata {
+        name: format!("_{}_idx", vector_column),
+        column: vector_column.to_string(),
+        dimension: dim as u32,
+        dataset_version: dataset.version().version,
+        metric_type,
+        ivf: new_ivf,
+        pq,
+        transforms: vec![],
+    };
+    let metadata = pb::Index::try_from(&metadata)?;
+    let pos = writer.write_protobuf(&metadata).await?;
+    writer.write_magics(pos, 0, 1, MAGIC).await?;
+    writer.shutdown().await?;
+
+    Ok(Some((new_uuid, existing_indices.len())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::RecordBatchIterator;
+    use lance_index::{
+        optimize::OptimizeOptions,
+        vector::{ivf::IvfBuildParams, pq::PQBuildParams, DIST_COL},
+        DatasetIndexExt, IndexType,
+    };
+    use lance_testing::datagen::generate_random_array;
+    use tempfile::tempdir;
+
+    use crate::index::vector::VectorIndexParams;
+
+    #[test]
+    fn test_repartition_plan() {
+        let options = RepartitionOptions::default();
+        // median = 5
+        let plan = RepartitionPlan::new(&[0, 5, 5, 45, 5], &options);
+        assert_eq!(
+            plan,
+            RepartitionPlan {
+                kept: vec![1, 2, 4],
+                split: vec![(3, 9)],
+                merged: vec![],
+            }
+        );
+
+        let options = RepartitionOptions {
+            merge_factor: 0.6,
+            ..Default::default()
+        };
+        // median = 10
+        let plan = RepartitionPlan::new(&[0, 5, 10, 45, 15], &options);
+        assert_eq!(plan.kept, vec![2, 4]);
+        assert_eq!(plan.split, vec![(3, 5)]);
+        assert_eq!(plan.merged, vec![1]);
+        assert_eq!(plan.affected().collect::<Vec<_>>(), vec![3, 1]);
+
+        assert!(RepartitionPlan::new(&[10, 10, 10], &options).is_noop());
+        assert!(RepartitionPlan::new(&[0, 0], &options).is_noop());
+
+        assert!(check_options(&RepartitionOptions {
+            split_factor: 1.0,
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_repartition_balanced_index_is_kept() {
+        const DIM: usize = 16;
+        let test_dir = tempdir().unwrap();
+        let uri = test_dir.path().to_str().unwrap();
+
+        let vectors =
+            FixedSizeListArray::try_new_from_values(generate_random_array(512 * DIM), DIM as i32)
+                .unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "vector",
+            vectors.data_type().clone(),
+            false,
+        )]));
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(vectors)]).unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let mut dataset = Dataset::write(reader, uri, None).await.unwrap();
+        let params = VectorIndexParams::with_ivf_pq_params(
+            MetricType::L2,
+            IvfBuildParams::new(1),
+            PQBuildParams::new(4, 8),
+        );
+        dataset
+            .create_index(&["vector"], IndexType::Vector, None, &params, false)
+            .await
+            .unwrap();
+        let old_uuid = dataset.load_indices().await.unwrap()[0].uuid;
+        let old_version = dataset.version().version;
+
+        // A single partition can neither be split nor merged with the default options.
+        dataset
+            .optimize_indices(&OptimizeOptions {
+                repartition: Some(RepartitionOptions::default()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let indices = dataset.load_indices().await.unwrap();
+        assert_eq!(indices.len(), 1);
+        assert_eq!(indices[0].uuid, old_uuid);
+        assert_eq!(dataset.version().version, old_version);
+    }
+
+    #[tokio::test]
+    async fn test_repartition_after_skewed_append() {
+        const DIM: usize = 16;
+        let test_dir = tempdir().unwrap();
+        let uri = test_dir.path().to_str().unwrap();
+
+        let vectors =
+            FixedSizeListArray::try_new_from_values(generate_random_array(512 * DIM), DIM as i32)
+                .unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "vector",
+            vectors.data_type().clone(),
+            false,
+        )]));
+        let write = |vectors: FixedSizeListArray| {
+            let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(vectors)]).unwrap();
+            RecordBatchIterator::new(vec![Ok(batch)], schema.clone())
+        };
+        let mut dataset = Dataset::write(write(vectors.clone()), uri, None)
+            .await
+            .unwrap();
+        let params = VectorIndexParams::with_ivf_pq_params(
+            MetricType::L2,
+            IvfBuildParams::new(4),
+            PQBuildParams::new(4, 8),
+        );
+        dataset
+            .create_index(&["vector"], IndexType::Vector, None, &params, false)
+            .await
+            .unwrap();
+
+        // Append many vectors close to the first one, so they all land in one partition.
+        let first = vectors.value(0);
+        let first = first.as_primitive::<Float32Type>().values();
+        let skewed = generate_random_array(2048 * DIM)
+            .values()
+            .iter()
+            .enumerate()
+            .map(|(i, v)| first[i % DIM] + v * 0.01)
+            .collect::<Vec<_>>();
+        let skewed = FixedSizeListArray::try_new_from_values(
+            arrow_array::Float32Array::from(skewed),
+            DIM as i32,
+        )
+        .unwrap();
+        dataset.append(write(skewed), None).await.unwrap();
+
+        dataset
+            .optimize_indices(&OptimizeOptions {
+                repartition: Some(RepartitionOptions::default()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let stats: serde_json::Value =
+            serde_json::from_str(&dataset.index_statistics("vector_idx").await.unwrap()).unwrap();
+        assert_eq!(stats["num_indices"], 1);
+        assert_eq!(stats["num_unindexed_rows"], 0);
+        let sizes = stats["indices"][0]["partitions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["size"].as_u64().unwrap())
+            .collect::<Vec<_>>();
+        assert!(sizes.len() > 4, "partition sizes: {:?}", sizes);
+        assert_eq!(sizes.iter().sum::<u64>(), 512 + 2048);
+        assert!(sizes.iter().all(|s| *s > 0), "partition sizes: {:?}", sizes);
+
+        let query = vectors.value(1);
+        let results = dataset
+            .scan()
+            .nearest("vector", query.as_primitive::<Float32Type>(), 5)
+            .unwrap()
+            .nprobs(sizes.len())
+            .refine(10)
+            .try_into_batch()
+            .await
+            .unwrap();
+        assert_eq!(
+            results[DIST_COL].as_primitive::<Float32Type>().value(0),
+            0.0
+        );
+    }
+}
@@ -86,7 +86,7 @@ impl PQIndex {
         pre_filter: &PreFilter,
         code: Arc<UInt8Array>,
         row_ids: Arc<UInt64Array>,
-        num_sub_vectors: i32,
+        code_length: i32,
     ) -> Result<(Arc<UInt8Array>, Arc<UInt64Array>)> {
         let indices_to_keep = pre_filter.filter_row_ids(row_ids.values());
         let indices_to_keep = UInt64Array::from(indices_to_keep);
@@ -94,8 +94,8 @@ impl PQIndex {
         let row_ids = take(row_ids.as_ref(), &indices_to_keep, None)?;
         let row_ids = Arc::new(as_primitive_array(&row_ids).clone());
 
-        let code = FixedSizeListArray::try_new_from_values(code.as_ref().clone(), num_sub_vectors)
-            .unwrap();
+        let code =
+            FixedSizeListArray::try_new_from_values(code.as_ref().clone(), code_length).unwrap();
         let code = take(&code, &indices_to_keep, None)?;
         let code = as_fixed_size_list_array(&code).values().clone();
         let code = Arc::new(as_primitive_array(&code).clone());
@@ -166,12 +166,12 @@ impl VectorIndex for PQIndex {
 
         let pq = self.pq.clone();
         let query = query.clone();
-        let num_sub_vectors = self.pq.num_sub_vectors() as i32;
+        let code_length = self.pq.code_length() as i32;
         spawn_cpu(move || {
             let (code, row_ids) = if pre_filter.is_empty() {
                 Ok((code, row_ids))
             } else {
-                Self::filter_arrays(pre_filter.as_ref(), code, row_ids, num_sub_vectors)
+                Self::filter_arrays(pre_filter.as_ref(), code, row_ids, code_length)
             }?;
 
             // Pre-compute distance table for each sub-vector.
@@ -208,7 +208,7 @@ impl VectorIndex for PQIndex {
         offset: usize,
         length: usize,
     ) -> Result<Box<dyn VectorIndex>> {
-        let pq_code_length = self.pq.num_sub_vectors() * length;
+        let pq_code_length = self.pq.code_length() * length;
         let pq_code = read_fixed_stride_array(
             reader.as_ref(),
             &DataType::UInt8,
@@ -246,7 +246,7 @@ impl VectorIndex for PQIndex {
             .as_ref()
             .unwrap()
             .values()
-            .chunks_exact(self.pq.num_sub_vectors());
+            .chunks_exact(self.pq.code_length());
         let row_ids = self.row_ids.as_ref().unwrap().values().iter();
         let remapped = row_ids
             .zip(code)
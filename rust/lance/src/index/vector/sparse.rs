@@ -0,0 +1,456 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Inverted index over sparse vector columns.
+
+use std::{any::Any, collections::HashMap, sync::Arc};
+
+use arrow_array::{Float32Array, RecordBatch, UInt64Array};
+use arrow_schema::{DataType, Field, Schema};
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use lance_arrow::sparse::{is_sparse_vector, SparseVectorArray};
+use lance_core::{Error, Result, ROW_ID};
+use lance_file::{
+    reader::FileReader,
+    writer::{FileWriter, FileWriterOptions},
+};
+use lance_index::{
+    optimize::OptimizeOptions,
+    vector::{
+        sparse::{
+            SparseIndexParams, SparseInvertedIndex, SPARSE_BLOCK_SIZE_KEY,
+            SPARSE_INVERTED_INDEX_TYPE,
+        },
+        Query, DIST_COL,
+    },
+    Index, IndexMetadata, IndexType, INDEX_FILE_NAME, INDEX_METADATA_SCHEMA_KEY,
+};
+use lance_io::{
+    object_store::ObjectStore, stream::RecordBatchStream, traits::Reader, ReadBatchParams,
+};
+use lance_linalg::distance::MetricType;
+use lance_table::io::manifest::ManifestDescribing;
+use object_store::path::Path;
+use roaring::RoaringBitmap;
+use serde_json::json;
+use snafu::{location, Location};
+use tracing::instrument;
+use uuid::Uuid;
+
+use super::VectorIndex;
+use crate::dataset::Dataset;
+use crate::index::prefilter::PreFilter;
+
+/// Sparse inverted index, scored by dot product.
+#[derive(Debug)]
+pub struct SparseIndex {
+    index: SparseInvertedIndex,
+}
+
+impl SparseIndex {
+    /// Load the index from an opened index file.
+    pub(crate) async fn load(reader: &FileReader) -> Result<Self> {
+        let block_size = reader
+            .schema()
+            .metadata
+            .get(SPARSE_BLOCK_SIZE_KEY)
+            .and_then(|s| s.parse::<usize>().ok())
+            .unwrap_or(SparseIndexParams::default().block_size);
+        let mut batches = Vec::with_capacity(reader.num_batches());
+        for i in 0..reader.num_batches() {
+            batches.push(
+                reader
+                    .read_batch(i as i32, ReadBatchParams::RangeFull, reader.schema(), None)
+                    .await?,
+            );
+        }
+        Ok(Self {
+            index: SparseInvertedIndex::try_from_batches(&batches, block_size)?,
+        })
+    }
+
+    pub(crate) fn inner(&self) -> &SparseInvertedIndex {
+        &self.index
+    }
+}
+
+#[async_trait]
+impl Index for SparseIndex {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_index(self: Arc<Self>) -> Arc<dyn Index> {
+        self
+    }
+
+    fn statistics(&self) -> Result<serde_json::Value> {
+        Ok(json!({
+            "index_type": SPARSE_INVERTED_INDEX_TYPE,
+            "distance_type": self.metric_type().to_string(),
+            "block_size": self.index.block_size(),
+            "num_dimensions": self.index.num_dimensions(),
+            "num_postings": self.index.num_postings(),
+        }))
+    }
+
+    fn index_type(&self) -> IndexType {
+        IndexType::Vector
+    }
+
+    async fn calculate_included_frags(&self) -> Result<RoaringBitmap> {
+        Ok(self
+            .index
+            .row_ids()
+            .map(|row_id| (row_id >> 32) as u32)
+            .collect())
+    }
+}
+
+#[async_trait]
+impl VectorIndex for SparseIndex {
+    #[instrument(level = "debug", skip_all, name = "SparseIndex::search")]
+    async fn search(&self, query: &Query, pre_filter: Arc<PreFilter>) -> Result<RecordBatch> {
+        let key = SparseVectorArray::try_from(query.key.as_ref())?;
+        if key.len() != 1 || key.is_null(0) {
+            return Err(Error::InvalidInput {
+                source: "Sparse query must contain exactly one vector".into(),
+                location: location!(),
+            });
+        }
+        let (indices, values) = key.value(0);
+
+        if !pre_filter.is_empty() {
+            pre_filter.wait_for_ready().await?;
+        }
+        let k = query.k * query.refine_factor.unwrap_or(1) as usize;
+        let results = self.index.search(indices, values, k, |row_id| {
+            pre_filter.is_empty() || pre_filter.check_one(row_id)
+        });
+
+        let row_ids = UInt64Array::from_iter_values(results.iter().map(|(row_id, _)| *row_id));
+        // Smaller distance is better, so use the negative dot product.
+        let distances = Float32Array::from_iter_values(results.iter().map(|(_, score)| -score));
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(DIST_COL, DataType::Float32, true),
+            Field::new(ROW_ID, DataType::UInt64, true),
+        ]));
+        Ok(RecordBatch::try_new(
+            schema,
+            vec![Arc::new(distances), Arc::new(row_ids)],
+        )?)
+    }
+
+    fn is_loadable(&self) -> bool {
+        false
+    }
+
+    fn use_residual(&self) -> bool {
+        false
+    }
+
+    fn check_can_remap(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn load(
+        &self,
+        _reader: Arc<dyn Reader>,
+        _offset: usize,
+        _length: usize,
+    ) -> Result<Box<dyn VectorIndex>> {
+        Err(Error::Index {
+            message: "Sparse index does not support loading as a sub-index".to_string(),
+            location: location!(),
+        })
+    }
+
+    fn remap(&mut self, mapping: &HashMap<u64, Option<u64>>) -> Result<()> {
+        self.index = self.index.remap(mapping);
+        Ok(())
+    }
+
+    fn metric_type(&self) -> MetricType {
+        MetricType::Dot
+    }
+}
+
+fn sanity_check(dataset: &Dataset, column: &str) -> Result<()> {
+    let Some(field) = dataset.schema().field(column) else {
+        return Err(Error::Index {
+            message: format!(
+                "Building index: column {} does not exist in dataset",
+                column
+            ),
+            location: location!(),
+        });
+    };
+    if !is_sparse_vector(&field.data_type()) {
+        return Err(Error::Index {
+            message: format!(
+                "Sparse index requires a sparse vector column (struct of indices and values), got {}",
+                field.data_type()
+            ),
+            location: location!(),
+        });
+    }
+    Ok(())
+}
+
+/// Feed a stream of `(column, _rowid)` batches into the index builder.
+async fn add_stream(
+    builder: &mut lance_index::vector::sparse::SparseIndexBuilder,
+    mut stream: impl RecordBatchStream + Unpin,
+    column: &str,
+) -> Result<()> {
+    while let Some(batch) = stream.try_next().await? {
+        let vectors = batch.column_by_name(column).ok_or_else(|| Error::Index {
+            message: format!("Building sparse index: column {} not found", column),
+            location: location!(),
+        })?;
+        let row_ids = batch[ROW_ID]
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .ok_or_else(|| Error::Index {
+                message: "Building sparse index: row id column has wrong type".to_string(),
+                location: location!(),
+            })?;
+        builder.add_batch(row_ids, vectors.as_ref())?;
+    }
+    Ok(())
+}
+
+/// Write a sparse index into `<index_dir>/<uuid>/index.idx`.
+async fn write_sparse_index_file(
+    object_store: &ObjectStore,
+    index_dir: &Path,
+    uuid: &str,
+    index: &SparseInvertedIndex,
+) -> Result<()> {
+    let path = index_dir.child(uuid).child(INDEX_FILE_NAME);
+    let writer = object_store.create(&path).await?;
+
+    let batch = index.to_batch()?;
+    let schema = lance_core::datatypes::Schema::try_from(batch.schema().as_ref())?;
+    let mut writer = FileWriter::<ManifestDescribing>::with_object_writer(
+        writer,
+        schema,
+        &FileWriterOptions::default(),
+    )?;
+    writer.add_metadata(
+        INDEX_METADATA_SCHEMA_KEY,
+        json!(IndexMetadata {
+            index_type: SPARSE_INVERTED_INDEX_TYPE.to_string(),
+            distance_type: MetricType::Dot.to_string(),
+        })
+        .to_string()
+        .as_str(),
+    );
+    if batch.num_rows() > 0 {
+        writer.write(&[batch]).await?;
+    }
+    writer.finish().await?;
+    Ok(())
+}
+
+/// Build a sparse inverted index over `column`.
+#[instrument(level = "debug", skip(dataset))]
+pub(crate) async fn build_sparse_index(
+    dataset: &Dataset,
+    column: &str,
+    uuid: &str,
+    params: &SparseIndexParams,
+) -> Result<()> {
+    sanity_check(dataset, column)?;
+
+    let mut scanner = dataset.scan();
+    scanner.project(&[column])?;
+    scanner.with_row_id();
+    let stream = scanner.try_into_stream().await?;
+
+    let mut builder = SparseInvertedIndex::builder(params);
+    add_stream(&mut builder, stream, column).await?;
+    let index = builder.build();
+
+    write_sparse_index_file(dataset.object_store(), &dataset.indices_dir(), uuid, &index).await
+}
+
+/// Write a copy of `index` with its row ids remapped.
+pub(crate) async fn remap_sparse_index(
+    dataset: &Dataset,
+    index: &SparseIndex,
+    new_uuid: &Uuid,
+    mapping: &HashMap<u64, Option<u64>>,
+) -> Result<()> {
+    let remapped = index.inner().remap(mapping);
+    write_sparse_index_file(
+        dataset.object_store(),
+        &dataset.indices_dir(),
+        &new_uuid.to_string(),
+        &remapped,
+    )
+    .await
+}
+
+/// Merge the unindexed data and the last [OptimizeOptions::num_indices_to_merge]
+/// deltas into a new sparse index.
+///
+/// Returns (new_uuid, num_indices_merged)
+pub(crate) async fn optimize_sparse_indices(
+    object_store: &ObjectStore,
+    index_dir: &Path,
+    unindexed: Option<impl RecordBatchStream + Unpin + 'static>,
+    column: &str,
+    existing_indices: &[Arc<dyn Index>],
+    options: &OptimizeOptions,
+) -> Result<(Uuid, usize)> {
+    let sparse_indices = existing_indices
+        .iter()
+        .map(|idx| {
+            idx.as_any()
+                .downcast_ref::<SparseIndex>()
+                .ok_or(Error::Index {
+                    message: "optimizing sparse index: it is not a sparse index".to_string(),
+                    location: location!(),
+                })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let Some(first) = sparse_indices.first() else {
+        return Err(Error::Index {
+            message: "optimizing sparse index: no existing index found".to_string(),
+            location: location!(),
+        });
+    };
+
+    let start_pos = sparse_indices
+        .len()
+        .saturating_sub(options.num_indices_to_merge);
+    let mut builder = SparseInvertedIndex::builder(&SparseIndexParams {
+        block_size: first.inner().block_size(),
+    });
+    for idx in &sparse_indices[start_pos..] {
+        builder.add_index(idx.inner());
+    }
+    if let Some(stream) = unindexed {
+        add_stream(&mut builder, stream, column).await?;
+    }
+    let index = builder.build();
+
+    let new_uuid = Uuid::new_v4();
+    write_sparse_index_file(object_store, index_dir, &new_uuid.to_string(), &index).await?;
+    Ok((new_uuid, sparse_indices.len() - start_pos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{Array, Int32Array, RecordBatchIterator, StructArray};
+    use lance_index::DatasetIndexExt;
+    use tempfile::tempdir;
+
+    use crate::index::vector::VectorIndexParams;
+
+    fn sparse_batch(ids: std::ops::Range<i32>) -> RecordBatch {
+        let vectors = ids
+            .clone()
+            .map(|i| {
+                let i = i as u32;
+                (
+                    vec![i % 7, 7 + i % 5, 20 + i % 3],
+                    vec![1.0, 0.5 + (i % 4) as f32, 2.0],
+                )
+            })
+            .collect::<Vec<_>>();
+        let vectors: StructArray = SparseVectorArray::from_pairs(
+            vectors
+                .iter()
+                .map(|(idx, val)| (idx.as_slice(), val.as_slice())),
+        )
+        .unwrap()
+        .into();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("vec", vectors.data_type().clone(), true),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from_iter_values(ids)),
+                Arc::new(vectors),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sparse_index_search() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let batch = sparse_batch(0..500);
+        let schema = batch.schema();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let mut dataset = Dataset::write(reader, test_uri, None).await.unwrap();
+
+        let query =
+            SparseVectorArray::from_pairs([(&[3_u32, 9][..], &[1.0_f32, 1.0][..])]).unwrap();
+        let (q_indices, q_values) = query.value(0);
+        let q_indices = q_indices.to_vec();
+        let q_values = q_values.to_vec();
+
+        let flat = dataset
+            .scan()
+            .nearest_sparse("vec", &q_indices, &q_values, 5)
+            .unwrap()
+            .try_into_batch()
+            .await
+            .unwrap();
+
+        dataset
+            .create_index(
+                &["vec"],
+                IndexType::Vector,
+                None,
+                &VectorIndexParams::sparse(SparseIndexParams::default()),
+                true,
+            )
+            .await
+            .unwrap();
+
+        let indexed = dataset
+            .scan()
+            .nearest_sparse("vec", &q_indices, &q_values, 5)
+            .unwrap()
+            .try_into_batch()
+            .await
+            .unwrap();
+        assert_eq!(indexed.num_rows(), 5);
+        assert_eq!(indexed[DIST_COL].as_ref(), flat[DIST_COL].as_ref());
+
+        // New data is searched with a flat scan and combined with the index results.
+        let reader = RecordBatchIterator::new(vec![Ok(sparse_batch(500..600))], schema);
+        dataset.append(reader, None).await.unwrap();
+        let results = dataset
+            .scan()
+            .nearest_sparse("vec", &q_indices, &q_values, 5)
+            .unwrap()
+            .try_into_batch()
+            .await
+            .unwrap();
+        assert_eq!(results.num_rows(), 5);
+
+        dataset
+            .optimize_indices(&lance_index::optimize::OptimizeOptions {
+                num_indices_to_merge: 1,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let stats: serde_json::Value =
+            serde_json::from_str(&dataset.index_statistics("vec_idx").await.unwrap()).unwrap();
+        assert_eq!(stats["num_unindexed_rows"], 0);
+        assert_eq!(stats["num_indices"], 1);
+        assert_eq!(stats["index_type"], SPARSE_INVERTED_INDEX_TYPE);
+    }
+}
@@ -26,8 +26,10 @@ use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use lance_table::format::{pb, DeletionFile, Fragment, Index, Manifest, WriterVersion};
-use lance_table::io::commit::{CommitConfig, CommitError, CommitHandler};
-use lance_table::io::deletion::read_deletion_file;
+pub use lance_table::io::commit::{CommitConfig, IsolationLevel};
+use lance_table::io::commit::{CommitError, CommitHandler};
+use lance_table::io::deletion::{merge_deletion_vectors, read_deletion_file, write_deletion_file};
+use lance_table::io::manifest::read_manifest_root;
 use snafu::{location, Location};
 
 use futures::future::Either;
@@ -40,7 +42,9 @@ use prost::Message;
 use super::ObjectStore;
 use crate::dataset::fragment::FileFragment;
 use crate::dataset::transaction::{Operation, Transaction};
-use crate::dataset::{write_manifest_file, ManifestWriteConfig};
+use crate::dataset::{
+    check_added_keys, write_manifest_file, ManifestWriteConfig, VersionIndexUpdate,
+};
 use crate::index::DatasetIndexInternalExt;
 use crate::Dataset;
 
@@ -78,11 +82,16 @@ async fn write_transaction_file(
     Ok(file_name)
 }
 
+/// Check whether `transaction` can be committed after `other_transaction`.
+///
+/// Returns true if the transactions conflict, but `transaction` can be rebased
+/// onto `other_transaction` under the given isolation level.
 fn check_transaction(
     transaction: &Transaction,
     other_version: u64,
     other_transaction: &Option<Transaction>,
-) -> Result<()> {
+    isolation_level: IsolationLevel,
+) -> Result<bool> {
     if other_transaction.is_none() {
         return Err(crate::Error::Internal {
             message: format!(
@@ -94,7 +103,11 @@ fn check_transaction(
         });
     }
 
-    if transaction.conflicts_with(other_transaction.as_ref().unwrap()) {
+    let other = other_transaction.as_ref().unwrap();
+    if transaction.conflicts_with(other) {
+        if isolation_level == IsolationLevel::Snapshot && transaction.rebasable_onto(other) {
+            return Ok(true);
+        }
         return Err(crate::Error::CommitConflict {
             version: other_version,
             source: format!(
@@ -108,7 +121,144 @@ fn check_transaction(
         });
     }
 
-    Ok(())
+    Ok(false)
+}
+
+/// Rebase a `Delete` or `Update` transaction onto the `latest` version of the dataset.
+///
+/// The fragments that were modified concurrently get a new deletion file with the
+/// rows deleted by both. Returns [Error::CommitConflict] if both deleted the same
+/// row, or if one of them removed a fragment that the other modified.
+async fn rebase_transaction(
+    transaction: &Transaction,
+    latest: &Dataset,
+    object_store: &ObjectStore,
+) -> Result<Transaction> {
+    let (updated_fragments, removed_fragment_ids) = match &transaction.operation {
+        Operation::Delete {
+            updated_fragments,
+            deleted_fragment_ids,
+            ..
+        } => (updated_fragments, deleted_fragment_ids),
+        Operation::Update {
+            updated_fragments,
+            removed_fragment_ids,
+            ..
+        } => (updated_fragments, removed_fragment_ids),
+        _ => return Ok(transaction.clone()),
+    };
+
+    let conflict = |message: String| crate::Error::CommitConflict {
+        version: latest.manifest.version,
+        source: message.into(),
+        location: location!(),
+    };
+    let base = latest.checkout_version(transaction.read_version).await?;
+    let base_fragments = base
+        .manifest
+        .fragments
+        .iter()
+        .map(|f| (f.id, f))
+        .collect::<HashMap<_, _>>();
+    let latest_fragments = latest
+        .manifest
+        .fragments
+        .iter()
+        .map(|f| (f.id, f))
+        .collect::<HashMap<_, _>>();
+    let is_unchanged = |id: &u64| match (base_fragments.get(id), latest_fragments.get(id)) {
+        (Some(base), Some(latest)) => base == latest,
+        _ => false,
+    };
+
+    let mut removed_fragment_ids = removed_fragment_ids.clone();
+    for id in removed_fragment_ids.iter() {
+        if !is_unchanged(id) {
+            return Err(conflict(format!(
+                "Fragment {} was removed by this transaction and modified by a concurrent one",
+                id
+            )));
+        }
+    }
+
+    let mut rebased_fragments = Vec::with_capacity(updated_fragments.len());
+    for ours in updated_fragments.iter() {
+        if is_unchanged(&ours.id) {
+            rebased_fragments.push(ours.clone());
+            continue;
+        }
+        let (Some(base_fragment), Some(theirs)) =
+            (base_fragments.get(&ours.id), latest_fragments.get(&ours.id))
+        else {
+            return Err(conflict(format!(
+                "Fragment {} was modified by this transaction and removed by a concurrent one",
+                ours.id
+            )));
+        };
+        if base_fragment.files != theirs.files {
+            return Err(conflict(format!(
+                "The data files of fragment {} were modified by a concurrent transaction",
+                ours.id
+            )));
+        }
+
+        let read = |fragment: &Fragment| {
+            let fragment = fragment.clone();
+            async move {
+                Ok::<_, crate::Error>(
+                    read_deletion_file(&latest.base, &fragment, object_store)
+                        .await?
+                        .unwrap_or_default(),
+                )
+            }
+        };
+        let merged = merge_deletion_vectors(
+            &read(base_fragment).await?,
+            &read(ours).await?,
+            &read(theirs).await?,
+        )
+        .map_err(|row| {
+            conflict(format!(
+                "Row {} of fragment {} was deleted by both this transaction and a concurrent one",
+                row, ours.id
+            ))
+        })?;
+
+        if theirs.physical_rows == Some(merged.len()) {
+            removed_fragment_ids.push(ours.id);
+            continue;
+        }
+        let mut rebased = (*theirs).clone();
+        // Our data files may have been updated in place
+        rebased.files = ours.files.clone();
+        rebased.deletion_file = write_deletion_file(
+            &latest.base,
+            ours.id,
+            latest.manifest.version,
+            &merged,
+            object_store,
+        )
+        .await?;
+        rebased_fragments.push(rebased);
+    }
+
+    let operation = match &transaction.operation {
+        Operation::Delete { predicate, .. } => Operation::Delete {
+            updated_fragments: rebased_fragments,
+            deleted_fragment_ids: removed_fragment_ids,
+            predicate: predicate.clone(),
+        },
+        Operation::Update { new_fragments, .. } => Operation::Update {
+            removed_fragment_ids,
+            updated_fragments: rebased_fragments,
+            new_fragments: new_fragments.clone(),
+        },
+        _ => unreachable!(),
+    };
+    Ok(Transaction {
+        operation,
+        ..transaction.clone()
+    })
 }
 
 pub(crate) async fn commit_new_dataset(
@@ -134,6 +284,10 @@ pub(crate) async fn commit_new_dataset(
             Some(indices.clone())
         },
         write_config,
+        Some(VersionIndexUpdate {
+            previous: None,
+            operation: transaction.operation.name(),
+        }),
     )
     .await?;
 
@@ -204,9 +358,9 @@ fn fix_schema(manifest: &mut Manifest) -> Result<()> {
     let mut seen_fields = HashSet::new();
     for fragment in manifest.fragments.iter() {
         for file in fragment.files.iter() {
-            for field_id in file.fields.iter() {
-                if !seen_fields.insert(*field_id) {
-                    fields_with_duplicate_ids.insert(*field_id);
+            for field_id in file.live_fields() {
+                if !seen_fields.insert(field_id) {
+                    fields_with_duplicate_ids.insert(field_id);
                 }
             }
         }
@@ -261,9 +415,8 @@ fn fix_schema(manifest: &mut Manifest) -> Result<()> {
         .collect::<HashSet<_>>();
     for fragment in fragments.iter_mut() {
         fragment.files.retain(|file| {
-            file.fields
-                .iter()
-                .any(|field_id| remaining_field_ids.contains(field_id))
+            file.live_fields()
+                .any(|field_id| remaining_field_ids.contains(&field_id))
         });
     }
 
@@ -372,10 +525,12 @@ pub(crate) async fn commit_transaction(
 ) -> Result<Manifest> {
     // Note: object_store has been configured with WriteParams, but dataset.object_store()
     // has not necessarily. So for anything involving writing, use `object_store`.
-    let transaction_file = write_transaction_file(object_store, &dataset.base, transaction).await?;
+    let mut transaction_file =
+        write_transaction_file(object_store, &dataset.base, transaction).await?;
 
     let mut dataset = dataset.clone();
-    // First, get all transactions since read_version
+    // First, get all transactions since read_version.  Only the transaction
+    // files are needed here, so the fragments are loaded once we have the latest.
     let mut other_transactions = Vec::new();
     let mut version = transaction.read_version;
     loop {
@@ -398,18 +553,36 @@ pub(crate) async fn commit_transaction(
             }
         }
     }
+    dataset.load_fragments().await?;
 
     let mut target_version = version;
 
     // If any of them conflict with the transaction, return an error
+    let mut needs_rebase = false;
     for (version_offset, other_transaction) in other_transactions.iter().enumerate() {
         let other_version = transaction.read_version + version_offset as u64 + 1;
-        check_transaction(transaction, other_version, other_transaction)?;
+        needs_rebase |= check_transaction(
+            transaction,
+            other_version,
+            other_transaction,
+            commit_config.isolation_level,
+        )?;
+    }
+
+    // Under snapshot isolation, the transaction is rebased onto the latest version.
+    // It is always rebased from the original transaction, so that the rows it deleted
+    // are not confused with the ones deleted concurrently.
+    let mut rebased = None;
+    if needs_rebase {
+        let txn = rebase_transaction(transaction, &dataset, object_store).await?;
+        transaction_file = write_transaction_file(object_store, &dataset.base, &txn).await?;
+        rebased = Some(txn);
     }
 
     for _ in 0..commit_config.num_retries {
+        let current = rebased.as_ref().unwrap_or(transaction);
         // Build an up-to-date manifest from the transaction and current manifest
-        let (mut manifest, mut indices) = match transaction.operation {
+        let (mut manifest, mut indices) = match current.operation {
             Operation::Restore { version } => {
                 Transaction::restore_old_manifest(
                     object_store,
@@ -421,7 +594,7 @@ pub(crate) async fn commit_transaction(
                 )
                 .await?
             }
-            _ => transaction.build_manifest(
+            _ => current.build_manifest(
                 Some(dataset.manifest.as_ref()),
                 dataset.load_indices().await?.as_ref().clone(),
                 &transaction_file,
@@ -431,6 +604,9 @@ pub(crate) async fn commit_transaction(
 
         manifest.version = target_version;
 
+        // The rows added by the transaction must not repeat a primary key of the dataset
+        check_added_keys(&dataset, transaction, &manifest).await?;
+
         let previous_writer_version = &dataset.manifest.writer_version;
         // The versions of Lance prior to when we started writing the writer version
         // sometimes wrote incorrect `Fragment.physical_rows` values, so we should
@@ -444,6 +620,17 @@ pub(crate) async fn commit_transaction(
 
         migrate_indices(&dataset, &mut indices).await?;
 
+        // The version index continues from the version this one replaces.  When
+        // restoring, that is not the checked out version.
+        let previous_manifest = if dataset.manifest.version + 1 == target_version {
+            None
+        } else {
+            let path = commit_handler
+                .resolve_version(&dataset.base, target_version - 1, &object_store.inner)
+                .await?;
+            Some(read_manifest_root(object_store, &path).await?)
+        };
+
         // Try to commit the manifest
         let result = write_manifest_file(
             object_store,
@@ -456,6 +643,10 @@ pub(crate) async fn commit_transaction(
                 Some(indices.clone())
             },
             write_config,
+            Some(VersionIndexUpdate {
+                previous: Some(previous_manifest.as_ref().unwrap_or(&dataset.manifest)),
+                operation: current.operation.name(),
+            }),
         )
         .await;
 
@@ -473,7 +664,17 @@ pub(crate) async fn commit_transaction(
                     } else {
                         None
                     };
-                check_transaction(transaction, target_version, &other_transaction)?;
+                if check_transaction(
+                    transaction,
+                    target_version,
+                    &other_transaction,
+                    commit_config.isolation_level,
+                )? {
+                    let txn = rebase_transaction(transaction, &dataset, object_store).await?;
+                    transaction_file =
+                        write_transaction_file(object_store, &dataset.base, &txn).await?;
+                    rebased = Some(txn);
+                }
                 target_version += 1;
             }
             Err(CommitError::OtherError(err)) => {
@@ -507,7 +708,8 @@ mod tests {
     use lance_linalg::distance::MetricType;
     use lance_table::format::DataFile;
     use lance_table::io::commit::{
-        CommitLease, CommitLock, RenameCommitHandler, UnsafeCommitHandler,
+        CommitLease, CommitLock, ConditionalPutCommitHandler, RenameCommitHandler,
+        UnsafeCommitHandler,
     };
     use lance_testing::datagen::generate_random_array;
 
@@ -517,6 +719,14 @@ mod tests {
     use crate::index::vector::VectorIndexParams;
 
     async fn test_commit_handler(handler: Arc<dyn CommitHandler>, should_succeed: bool) {
+        test_commit_handler_at(handler, "memory://test", should_succeed).await;
+    }
+
+    async fn test_commit_handler_at(
+        handler: Arc<dyn CommitHandler>,
+        uri: &str,
+        should_succeed: bool,
+    ) {
         // Create a dataset, passing handler as commit handler
         let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
             "x",
@@ -534,9 +744,7 @@ mod tests {
             commit_handler: Some(handler),
             ..Default::default()
         };
-        let dataset = Dataset::write(reader, "memory://test", Some(options))
-            .await
-            .unwrap();
+        let dataset = Dataset::write(reader, uri, Some(options)).await.unwrap();
 
         // Create 10 concurrent tasks to write into the table
         // Record how many succeed and how many fail
@@ -583,6 +791,71 @@ mod tests {
         test_commit_handler(handler, true).await;
     }
 
+    #[tokio::test]
+    async fn test_conditional_put_commit_handler() {
+        let handler = Arc::new(ConditionalPutCommitHandler);
+        test_commit_handler(handler.clone(), true).await;
+
+        let test_dir = tempfile::tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        test_commit_handler_at(handler, test_uri, true).await;
+    }
+
+    #[tokio::test]
+    async fn test_conditional_put_conflict() {
+        let object_store = ObjectStore::memory();
+        let base_path = Path::from("test");
+        let handler = ConditionalPutCommitHandler;
+
+        fn write_empty<'a>(
+            object_store: &'a dyn object_store::ObjectStore,
+            _manifest: &'a mut Manifest,
+            _indices: Option<Vec<Index>>,
+            path: &'a Path,
+        ) -> futures::future::BoxFuture<'a, Result<()>> {
+            Box::pin(async move {
+                object_store.put(path, "manifest".into()).await?;
+                Ok(())
+            })
+        }
+
+        let schema = Schema::try_from(&ArrowSchema::new(vec![ArrowField::new(
+            "x",
+            DataType::Int64,
+            false,
+        )]))
+        .unwrap();
+        let mut manifest = Manifest::new(schema, Arc::new(vec![]));
+        manifest.version = 2;
+        handler
+            .commit(
+                &mut manifest,
+                None,
+                &base_path,
+                object_store.inner.as_ref(),
+                write_empty,
+            )
+            .await
+            .unwrap();
+        let res = handler
+            .commit(
+                &mut manifest,
+                None,
+                &base_path,
+                object_store.inner.as_ref(),
+                write_empty,
+            )
+            .await;
+        assert!(matches!(res, Err(CommitError::CommitConflict)));
+        assert_eq!(
+            handler
+                .resolve_latest_version_id(&base_path, object_store.inner.as_ref())
+                .await
+                .unwrap(),
+            2
+        );
+    }
+
     #[tokio::test]
     async fn test_custom_commit() {
         #[derive(Debug)]
@@ -821,10 +1094,10 @@ mod tests {
 
             match write_mode {
                 WriteMode::Append => {
-                    assert_eq!(dataset.get_fragments().len(), 5);
+                    assert_eq!(dataset.get_fragments().await.unwrap().len(), 5);
                 }
                 WriteMode::Overwrite => {
-                    assert_eq!(dataset.get_fragments().len(), 1);
+                    assert_eq!(dataset.get_fragments().await.unwrap().len(), 1);
                 }
                 _ => unreachable!(),
             }
@@ -833,6 +1106,86 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_snapshot_isolation_deletes_and_updates() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..100))],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let base = Dataset::write(reader, test_uri, None).await.unwrap();
+        assert_eq!(base.get_fragments().await.unwrap().len(), 1);
+
+        let snapshot = CommitConfig {
+            isolation_level: IsolationLevel::Snapshot,
+            ..Default::default()
+        };
+
+        let mut dataset = base.clone();
+        dataset
+            .delete_with_config("i < 10", &snapshot)
+            .await
+            .unwrap();
+
+        // Deletes of other rows in the same fragment conflict under serializable isolation...
+        let mut dataset = base.clone();
+        assert!(matches!(
+            dataset.delete("i >= 90").await,
+            Err(Error::CommitConflict { .. })
+        ));
+        // ...and are rebased under snapshot isolation.
+        let mut dataset = base.clone();
+        dataset
+            .delete_with_config("i >= 90", &snapshot)
+            .await
+            .unwrap();
+
+        crate::dataset::UpdateBuilder::new(Arc::new(base.clone()))
+            .update_where("i = 50")
+            .unwrap()
+            .set("i", "500")
+            .unwrap()
+            .commit_config(snapshot.clone())
+            .build()
+            .unwrap()
+            .execute()
+            .await
+            .unwrap();
+
+        // Deleting a row that was already deleted concurrently is a true conflict.
+        let mut dataset = base.clone();
+        assert!(matches!(
+            dataset.delete_with_config("i = 5", &snapshot).await,
+            Err(Error::CommitConflict { .. })
+        ));
+
+        let dataset = Dataset::open(test_uri).await.unwrap();
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 80);
+        assert_eq!(
+            dataset
+                .count_rows(Some("i = 50 OR i = 5 OR i = 95".to_string()))
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            dataset
+                .count_rows(Some("i = 500".to_string()))
+                .await
+                .unwrap(),
+            1
+        );
+        dataset.validate().await.unwrap();
+    }
+
     #[test]
     fn test_fix_schema() {
         // Manifest has a fragment with no fields in use
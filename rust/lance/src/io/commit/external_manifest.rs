@@ -13,6 +13,7 @@ mod test {
     use lance_table::io::commit::external_manifest::{
         ExternalManifestCommitHandler, ExternalManifestStore,
     };
+    use lance_table::io::commit::file_external_manifest::FileExternalManifestStore;
     use lance_table::io::commit::{latest_manifest_path, manifest_path, CommitHandler};
     use lance_testing::datagen::{BatchGenerator, IncrementingInt32};
     use object_store::local::LocalFileSystem;
@@ -240,6 +241,61 @@ mod test {
         }
     }
 
+    #[tokio::test]
+    async fn test_file_external_store_concurrent_commits() {
+        let store_dir = tempfile::tempdir().unwrap();
+        // Each writer gets its own store instance, as separate processes would
+        let make_handler = || {
+            Arc::new(ExternalManifestCommitHandler {
+                external_manifest_store: Arc::new(
+                    FileExternalManifestStore::try_new(store_dir.path()).unwrap(),
+                ),
+            })
+        };
+
+        let mut data_gen =
+            BatchGenerator::new().col(Box::new(IncrementingInt32::new().named("x".to_owned())));
+        let dir = tempfile::tempdir().unwrap();
+        let ds_uri = dir.path().to_str().unwrap();
+
+        Dataset::write(
+            data_gen.batch(10),
+            ds_uri,
+            Some(write_params(make_handler())),
+        )
+        .await
+        .unwrap();
+
+        let write_futs = (0..5)
+            .map(|_| data_gen.batch(10))
+            .map(|data| {
+                let mut params = write_params(make_handler());
+                params.mode = WriteMode::Append;
+                Dataset::write(data, ds_uri, Some(params))
+            })
+            .collect::<Vec<_>>();
+        let res = join_all(write_futs).await;
+        let errors = res.into_iter().filter_map(|r| r.err()).collect::<Vec<_>>();
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let handler = make_handler();
+        let ds = DatasetBuilder::from_uri(ds_uri)
+            .with_read_params(read_params(handler.clone()))
+            .load()
+            .await
+            .unwrap();
+        assert_eq!(ds.version().version, 6);
+        assert_eq!(ds.count_rows(None).await.unwrap(), 60);
+        let (latest_version, latest_path) = handler
+            .external_manifest_store
+            .get_latest_version(ds.base.as_ref())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(latest_version, 6);
+        assert!(latest_path.ends_with(".manifest"));
+    }
+
     #[tokio::test]
     async fn test_out_of_sync_dataset_can_recover() {
         let sleepy_store = SleepyExternalManifestStore::new();
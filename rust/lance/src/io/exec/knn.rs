@@ -17,9 +17,10 @@ use datafusion::physical_plan::{
     RecordBatchStream as DFRecordBatchStream, SendableRecordBatchStream, Statistics,
 };
 use futures::{stream, FutureExt, Stream, StreamExt, TryStreamExt};
+use lance_arrow::sparse::is_sparse_vector;
 use lance_core::utils::mask::{RowIdMask, RowIdTreeMap};
 use lance_core::{ROW_ID, ROW_ID_FIELD};
-use lance_index::vector::{flat::flat_search, Query, DIST_COL};
+use lance_index::vector::{flat::flat_search, utils::is_vector_element, Query, DIST_COL};
 use lance_io::stream::RecordBatchStream;
 use lance_table::format::Index;
 use snafu::{location, Location};
@@ -165,11 +166,12 @@ impl KNNFlatExec {
                 location: location!(),
             })?;
         match field.data_type() {
-            DataType::FixedSizeList(list_field, _) if list_field.data_type().is_floating() => {}
+            DataType::FixedSizeList(list_field, _) if is_vector_element(list_field) => {}
+            data_type if is_sparse_vector(data_type) => {}
             _ => {
                 return Err(Error::IO {
                     message: format!(
-                        "KNNFlatExec node: query column {} is not a vector. Expect FixedSizeList<Float32>, got {}",
+                        "KNNFlatExec node: query column {} is not a vector. Expect FixedSizeList of floats, int8 or uint8, or a sparse vector, got {}",
                         query.column, field.data_type()
                     ),
                     location: location!(),
@@ -471,9 +473,11 @@ impl ExecutionPlan for KNNIndexExec {
 mod tests {
     use super::*;
 
-    use arrow_array::RecordBatchIterator;
+    use arrow_array::types::Float32Type;
     use arrow_array::{cast::as_primitive_array, FixedSizeListArray, Int32Array, StringArray};
+    use arrow_array::{Float32Array, RecordBatchIterator, UInt8Array};
     use arrow_schema::{Field as ArrowField, Schema as ArrowSchema};
+    use lance_arrow::bfloat16::{BFloat16Array, ARROW_EXT_NAME_KEY, BFLOAT16_EXT_NAME};
     use lance_linalg::distance::MetricType;
     use lance_testing::datagen::generate_random_array;
     use tempfile::tempdir;
@@ -569,6 +573,72 @@ mod tests {
         assert_eq!(expected, results[0]);
     }
 
+    #[tokio::test]
+    async fn knn_flat_search_native_types() {
+        let bf16_field = ArrowField::new("item", DataType::FixedSizeBinary(2), true).with_metadata(
+            [(
+                ARROW_EXT_NAME_KEY.to_string(),
+                BFLOAT16_EXT_NAME.to_string(),
+            )]
+            .into(),
+        );
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new(
+                "bf16",
+                DataType::FixedSizeList(Arc::new(bf16_field.clone()), 4),
+                true,
+            ),
+            ArrowField::new(
+                "u8",
+                DataType::FixedSizeList(
+                    Arc::new(ArrowField::new("item", DataType::UInt8, true)),
+                    4,
+                ),
+                true,
+            ),
+        ]));
+        let values = (0..400).map(|v| (v % 97) as f32).collect::<Vec<_>>();
+        let bf16_values =
+            BFloat16Array::from_iter_values(values.iter().map(|v| half::bf16::from_f32(*v)));
+        let u8_values = UInt8Array::from_iter_values(values.iter().map(|v| *v as u8));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(
+                    FixedSizeListArray::try_new(
+                        Arc::new(bf16_field),
+                        4,
+                        Arc::new(bf16_values.into_inner()),
+                        None,
+                    )
+                    .unwrap(),
+                ),
+                Arc::new(FixedSizeListArray::try_new_from_values(u8_values, 4).unwrap()),
+            ],
+        )
+        .unwrap();
+
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let dataset = Dataset::write(reader, test_uri, None).await.unwrap();
+
+        let query = Float32Array::from(values[12..16].to_vec());
+        for column in ["bf16", "u8"] {
+            let results = dataset
+                .scan()
+                .nearest(column, &query, 3)
+                .unwrap()
+                .try_into_batch()
+                .await
+                .unwrap();
+            assert_eq!(results.num_rows(), 3);
+            let dists = results[DIST_COL].as_primitive::<Float32Type>();
+            assert_eq!(dists.value(0), 0.0, "column {}", column);
+            assert!(dists.value(1) > 0.0);
+        }
+    }
+
     #[test]
     fn test_create_knn_flat() {
         let dim: usize = 128;
@@ -683,7 +683,7 @@ mod test {
 
         let dataset = Dataset::write(batches, test_uri, None).await.unwrap();
 
-        let fragments = dataset.fragments().clone();
+        let fragments = dataset.fragments().await.unwrap().clone();
         let projection = Arc::new(dataset.schema().clone());
 
         let predicate = col("i").eq(lit(42));
@@ -730,7 +730,7 @@ mod test {
 
         let dataset = Dataset::write(batches, test_uri, None).await.unwrap();
 
-        let fragments = dataset.fragments().clone();
+        let fragments = dataset.fragments().await.unwrap().clone();
         let projection = Arc::new(dataset.schema().clone());
 
         let predicate = col("s").eq(lit("x"));
@@ -795,7 +795,7 @@ mod test {
 
         let dataset = Arc::new(Dataset::write(batches, test_uri, None).await.unwrap());
 
-        let fragments = dataset.fragments().clone();
+        let fragments = dataset.fragments().await.unwrap().clone();
         // [x.b, y.a]
         let projection = Arc::new(dataset.schema().clone().project_by_ids(&[2, 4]));
 
@@ -883,7 +883,7 @@ mod test {
                 .unwrap(),
         );
 
-        let fragments = dataset.fragments().clone();
+        let fragments = dataset.fragments().await.unwrap().clone();
         assert_eq!(fragments.len(), 1);
         let fragment = fragments[0].clone();
 
@@ -1051,7 +1051,7 @@ mod test {
             };
             let scan = LancePushdownScanExec::try_new(
                 dataset.clone(),
-                dataset.fragments().clone(),
+                dataset.fragments().await.unwrap().clone(),
                 Arc::new(dataset.schema().clone()),
                 predicate.clone(),
                 config,
@@ -1117,7 +1117,7 @@ mod test {
     ) -> Result<RecordBatch> {
         let scan = LancePushdownScanExec::try_new(
             dataset.clone(),
-            dataset.fragments().clone(),
+            dataset.fragments().await.unwrap().clone(),
             Arc::new(dataset.schema().clone().project_by_ids(&projection_indices)),
             predicate,
             scan_config,
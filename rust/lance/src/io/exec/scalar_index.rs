@@ -1,7 +1,7 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use arrow_array::{RecordBatch, UInt64Array};
 use arrow_schema::{DataType, Field, Schema, SchemaRef};
@@ -25,7 +25,7 @@ use lance_index::{
     DatasetIndexExt,
 };
 use lance_table::format::Fragment;
-use roaring::RoaringBitmap;
+use roaring::{RoaringBitmap, RoaringTreemap};
 use snafu::{location, Location};
 use tracing::{debug_span, instrument};
 
@@ -147,6 +147,8 @@ lazy_static::lazy_static! {
 /// An execution node that translates index values into row addresses
 ///
 /// This can be combined with TakeExec to perform an "indexed take"
+///
+/// Each row address is emitted at most once, even if its value appears in several input batches
 #[derive(Debug)]
 pub struct MapIndexExec {
     dataset: Arc<Dataset>,
@@ -177,6 +179,7 @@ impl MapIndexExec {
         column_name: String,
         dataset: Arc<Dataset>,
         deletion_mask: Option<Arc<RowIdTreeMap>>,
+        seen: Arc<Mutex<RoaringTreemap>>,
         batch: RecordBatch,
     ) -> datafusion::error::Result<RecordBatch> {
         let index_vals = batch.column(0);
@@ -198,6 +201,8 @@ impl MapIndexExec {
             if let Some(deletion_mask) = deletion_mask {
                 allow_list.retain(|row_id| !deletion_mask.contains(*row_id));
             }
+            let mut seen = seen.lock().unwrap();
+            allow_list.retain(|row_id| seen.insert(*row_id));
             let allow_list = UInt64Array::from(allow_list);
             Ok(RecordBatch::try_new(
                 INDEX_LOOKUP_SCHEMA.clone(),
@@ -228,11 +233,13 @@ impl MapIndexExec {
         } else {
             None
         };
+        let seen = Arc::new(Mutex::new(RoaringTreemap::new()));
         Ok(input.and_then(move |res| {
             let column_name = column_name.clone();
             let dataset = dataset.clone();
             let deletion_mask = deletion_mask.clone();
-            Self::map_batch(column_name, dataset, deletion_mask, res)
+            let seen = seen.clone();
+            Self::map_batch(column_name, dataset, deletion_mask, seen, res)
         }))
     }
 }
@@ -357,7 +357,7 @@ mod tests {
         // With row id
         let input = Arc::new(LanceScanExec::new(
             dataset.clone(),
-            dataset.fragments().clone(),
+            dataset.fragments().await.unwrap().clone(),
             scan_schema,
             10,
             10,
@@ -390,7 +390,7 @@ mod tests {
 
         let input = Arc::new(LanceScanExec::new(
             dataset.clone(),
-            dataset.fragments().clone(),
+            dataset.fragments().await.unwrap().clone(),
             scan_schema,
             10,
             10,
@@ -423,7 +423,7 @@ mod tests {
         // No row ID
         let input = Arc::new(LanceScanExec::new(
             dataset.clone(),
-            dataset.fragments().clone(),
+            dataset.fragments().await.unwrap().clone(),
             scan_schema,
             10,
             10,
@@ -441,7 +441,7 @@ mod tests {
 
         let input = Arc::new(LanceScanExec::new(
             dataset.clone(),
-            dataset.fragments().clone(),
+            dataset.fragments().await.unwrap().clone(),
             Arc::new(dataset.schema().project(&["i"])?),
             10,
             10,
@@ -177,10 +177,13 @@ mod test {
             fragment_bitmap: Some(
                 dataset
                     .get_fragments()
+                    .await
+                    .unwrap()
                     .iter()
                     .map(|f| f.id() as u32)
                     .collect(),
             ),
+            file_checksums: Default::default(),
         };
 
         let transaction = Transaction::new(